@@ -0,0 +1,124 @@
+//! Standardized scenarios for `cargo bench`.
+//!
+//! Each benchmark runs the test client for a fixed number of frames against the
+//! compositor of the current session and reports the wall and CPU time per frame of
+//! the frame loop, as printed by `--loop-time`, so the process start and connection
+//! setup aren't part of it. Without `WAYLAND_DISPLAY` all benchmarks are skipped.
+
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const FRAMES: u64 = 300;
+const ITERATIONS: usize = 3;
+
+const BENCHES: &[(&str, &[&str])] = &[
+    ("continuous/fifo", &["--scenario", "continuous"]),
+    (
+        "continuous/no-fifo",
+        &["--scenario", "continuous", "--no-fifo"],
+    ),
+    ("baseline", &["--scenario", "baseline"]),
+    ("present-divisor/2", &["--present-divisor", "2"]),
+    ("commit-thread", &["--commit-thread"]),
+];
+
+/// Frame loop times of a run, per frame.
+struct Times {
+    wall: Duration,
+    cpu: Duration,
+}
+
+fn main() {
+    // cargo passes `--bench` and an optional name filter.
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+
+    if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        println!("WAYLAND_DISPLAY is not set, skipping all benchmarks");
+        return;
+    }
+
+    for (name, args) in BENCHES {
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !name.contains(filter.as_str()))
+        {
+            continue;
+        }
+
+        let mut per_frame = Vec::with_capacity(ITERATIONS);
+        for _ in 0..ITERATIONS {
+            match run(args) {
+                Ok(times) => per_frame.push(times),
+                Err(err) => {
+                    println!("{:<24} failed: {}", name, err);
+                    break;
+                }
+            }
+        }
+        if per_frame.len() != ITERATIONS {
+            continue;
+        }
+
+        let wall = per_frame.iter().map(|times| times.wall).collect::<Vec<_>>();
+        let cpu = per_frame.iter().map(|times| times.cpu).collect::<Vec<_>>();
+        println!("{:<24} time:   {}", name, range(wall));
+        println!("{:<24} cpu:    {}", "", range(cpu));
+    }
+}
+
+/// `[min mean max]` of `times`.
+fn range(mut times: Vec<Duration>) -> String {
+    times.sort();
+    let mean = times.iter().sum::<Duration>() / times.len() as u32;
+    format!(
+        "[{:.3} ms {:.3} ms {:.3} ms]",
+        ms(times[0]),
+        ms(mean),
+        ms(times[times.len() - 1])
+    )
+}
+
+fn run(args: &[&str]) -> Result<Times, String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_fifo_test"))
+        .args(args)
+        .args(["--quiet", "--loop-time", "--frames", &FRAMES.to_string()])
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(output.status.to_string());
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(parse)
+        .ok_or_else(|| "no frame loop time printed".to_string())
+}
+
+/// Parses `frame loop: frames=N wall=Ss cpu=Ss` into the times per frame.
+fn parse(line: &str) -> Option<Times> {
+    let fields = line.strip_prefix("frame loop: ")?;
+    let field = |name: &str| {
+        fields
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+    };
+    let frames = field("frames")?
+        .parse::<u32>()
+        .ok()
+        .filter(|frames| *frames > 0)?;
+    let seconds = |name: &str| {
+        field(name)?
+            .strip_suffix('s')?
+            .parse::<f64>()
+            .ok()
+            .map(Duration::from_secs_f64)
+    };
+    Some(Times {
+        wall: seconds("wall")? / frames,
+        cpu: seconds("cpu")? / frames,
+    })
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
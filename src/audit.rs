@@ -0,0 +1,245 @@
+//! Protocol-order linter for the requests and events of a single test surface.
+//!
+//! The stream is either recorded from the client itself (`--audit`) or replayed
+//! from a `WAYLAND_DEBUG=1` log of libwayland or the rust backend (`--audit-replay`).
+//! Replayed logs are assumed to contain a single xdg_surface and, optionally, its fifo.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, BufRead};
+
+use crate::protocol_log::Message;
+
+/// A request or event relevant to the xdg-shell and fifo ordering rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Configure(u32),
+    AckConfigure(u32),
+    Attach,
+    WaitBarrier,
+    SetBarrier,
+    Commit,
+    /// `wp_fifo_manager_v1.get_fifo` for the surface with the given object id.
+    GetFifo(u32),
+    DestroySurface,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Configure(serial) => write!(f, "configure({})", serial),
+            Event::AckConfigure(serial) => write!(f, "ack_configure({})", serial),
+            Event::Attach => write!(f, "attach"),
+            Event::WaitBarrier => write!(f, "wait_barrier"),
+            Event::SetBarrier => write!(f, "set_barrier"),
+            Event::Commit => write!(f, "commit"),
+            Event::GetFifo(surface) => write!(f, "get_fifo(wl_surface#{})", surface),
+            Event::DestroySurface => write!(f, "destroy"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Audit {
+    /// Events since the last commit.
+    current: Vec<Event>,
+    /// Sequences of all commits, consecutive identical ones merged with a count.
+    commits: Vec<(Vec<Event>, u64)>,
+    commit_count: u64,
+    pending_configures: Vec<u32>,
+    acked: bool,
+    fifo_surfaces: HashSet<u32>,
+    destroyed: bool,
+    violations: Vec<String>,
+}
+
+impl Audit {
+    pub fn record(&mut self, event: Event) {
+        let commit = self.commit_count + 1;
+        let mut violation = |message: String| {
+            self.violations
+                .push(format!("commit {}: {}: {}", commit, event, message))
+        };
+
+        if self.destroyed {
+            violation("request after the surface was destroyed".to_string());
+        }
+
+        match event {
+            Event::Configure(serial) => self.pending_configures.push(serial),
+            Event::AckConfigure(serial) => {
+                match self.pending_configures.iter().position(|s| *s == serial) {
+                    // Acking a configure implicitly drops all older ones.
+                    Some(index) => {
+                        self.pending_configures.drain(..=index);
+                    }
+                    None => violation("serial was never configured or already acked".to_string()),
+                }
+                self.acked = true;
+            }
+            Event::Attach if !self.acked => {
+                violation("buffer attached before the first ack_configure".to_string())
+            }
+            Event::WaitBarrier | Event::SetBarrier if self.fifo_surfaces.is_empty() => {
+                // Only detectable in replays, the client never uses a fifo it didn't get.
+                violation("fifo request without a fifo object".to_string())
+            }
+            Event::GetFifo(surface) if !self.fifo_surfaces.insert(surface) => {
+                violation("surface already has a fifo object".to_string())
+            }
+            Event::Commit if !self.pending_configures.is_empty() => violation(format!(
+                "latest configure {} was not acked",
+                self.pending_configures[self.pending_configures.len() - 1]
+            )),
+            Event::DestroySurface => self.destroyed = true,
+            _ => {}
+        }
+
+        self.current.push(event);
+        if event == Event::Commit {
+            self.commit_count += 1;
+            let sequence = std::mem::take(&mut self.current);
+            match self.commits.last_mut() {
+                Some((last, count)) if *last == sequence => *count += 1,
+                _ => self.commits.push((sequence, 1)),
+            }
+        }
+    }
+
+    /// Reads a `WAYLAND_DEBUG=1` log and records the relevant lines.
+    pub fn replay(reader: impl BufRead) -> io::Result<Audit> {
+        let mut audit = Audit::default();
+        for line in reader.lines() {
+            if let Some(event) = parse_debug_line(&line?) {
+                audit.record(event);
+            }
+        }
+        Ok(audit)
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        println!("{}protocol order:", prefix);
+        let mut first = 1;
+        for (sequence, count) in &self.commits {
+            let sequence = sequence
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" ");
+            if *count == 1 {
+                println!("{}  commit {}: {}", prefix, first, sequence);
+            } else {
+                println!(
+                    "{}  commits {}-{} ({}x): {}",
+                    prefix,
+                    first,
+                    first + count - 1,
+                    count,
+                    sequence
+                );
+            }
+            first += count;
+        }
+        if self.violations.is_empty() {
+            println!("{}  no ordering violations", prefix);
+        } else {
+            println!("{}  {} ordering violations:", prefix, self.violations.len());
+            for violation in &self.violations {
+                println!("{}    {}", prefix, violation);
+            }
+        }
+    }
+}
+
+/// Parses lines like `[1234.567] -> wl_surface#3.attach(wl_buffer#12, 0, 0)`.
+fn parse_debug_line(line: &str) -> Option<Event> {
+    let message = Message::parse(line)?;
+    let serial = || message.args.trim().parse::<u32>().ok();
+    match (message.interface, message.name) {
+        ("xdg_surface", "configure") => serial().map(Event::Configure),
+        ("xdg_surface", "ack_configure") => serial().map(Event::AckConfigure),
+        ("wl_surface", "attach") => Some(Event::Attach),
+        ("wl_surface", "commit") => Some(Event::Commit),
+        ("wl_surface", "destroy") => Some(Event::DestroySurface),
+        ("wp_fifo_v1", "wait_barrier") => Some(Event::WaitBarrier),
+        ("wp_fifo_v1", "set_barrier") => Some(Event::SetBarrier),
+        ("wp_fifo_manager_v1", "get_fifo") => {
+            let surface = message.args.split(',').nth(1)?.trim();
+            let (_, id) = surface
+                .split_once('#')
+                .or_else(|| surface.split_once('@'))?;
+            id.parse().ok().map(Event::GetFifo)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A libwayland and a rust backend line of every event.
+    const LINES: &[(&str, &str, Event)] = &[
+        (
+            "[1234567.890] xdg_surface#9.configure(42)",
+            "[1234567.890][rs] <- xdg_surface@9.configure, (42)",
+            Event::Configure(42),
+        ),
+        (
+            "[1234567.890]  -> xdg_surface#9.ack_configure(42)",
+            "[1234567.890][rs] -> xdg_surface@9.ack_configure(42)",
+            Event::AckConfigure(42),
+        ),
+        (
+            "[1234567.890]  -> wl_surface#3.attach(wl_buffer#14, 0, 0)",
+            "[1234567.890][rs] -> wl_surface@3.attach(wl_buffer@14, 0, 0)",
+            Event::Attach,
+        ),
+        (
+            "[1234567.890]  -> wp_fifo_v1#12.wait_barrier()",
+            "[1234567.890][rs] -> wp_fifo_v1@12.wait_barrier()",
+            Event::WaitBarrier,
+        ),
+        (
+            "[1234567.890]  -> wp_fifo_v1#12.set_barrier()",
+            "[1234567.890][rs] -> wp_fifo_v1@12.set_barrier()",
+            Event::SetBarrier,
+        ),
+        (
+            "[1234567.890]  -> wl_surface#3.commit()",
+            "[1234567.890][rs] -> wl_surface@3.commit()",
+            Event::Commit,
+        ),
+        (
+            "[1234567.890]  -> wp_fifo_manager_v1#5.get_fifo(new id wp_fifo_v1#12, wl_surface#3)",
+            "[1234567.890][rs] -> wp_fifo_manager_v1@5.get_fifo(wp_fifo_v1@12, wl_surface@3)",
+            Event::GetFifo(3),
+        ),
+        (
+            "[1234567.890]  -> wl_surface#3.destroy()",
+            "[1234567.890][rs] -> wl_surface@3.destroy()",
+            Event::DestroySurface,
+        ),
+    ];
+
+    #[test]
+    fn parses_libwayland_and_rust_backend_lines() {
+        for (libwayland, rust, event) in LINES {
+            assert_eq!(parse_debug_line(libwayland), Some(*event), "{}", libwayland);
+            assert_eq!(parse_debug_line(rust), Some(*event), "{}", rust);
+        }
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        assert_eq!(
+            parse_debug_line("[1234567.890]  -> wl_surface#3.frame(new id wl_callback#15)"),
+            None
+        );
+        assert_eq!(
+            parse_debug_line("[1234567.890][rs] <- wl_callback@15.done, (5000)"),
+            None
+        );
+        assert_eq!(parse_debug_line("not a protocol line"), None);
+    }
+}
@@ -0,0 +1,73 @@
+/// Optional GPU libraries, with the sonames they are loaded by. Nothing links
+/// against them, so one binary runs on systems without them.
+const LIBRARIES: &[(&str, &[&str])] = &[
+    ("egl", &["libEGL.so.1", "libEGL.so"]),
+    ("vulkan", &["libvulkan.so.1", "libvulkan.so"]),
+    ("gbm", &["libgbm.so.1", "libgbm.so"]),
+];
+
+/// Availability of one optional backend.
+pub struct Backend {
+    pub name: &'static str,
+    /// The soname or device found, or why the backend is disabled.
+    pub state: Result<String, String>,
+}
+
+/// Detects the optional buffer backends at runtime.
+///
+/// Rendering always uses wl_shm, the report tells which backends a system could
+/// offer and why the others are disabled.
+pub fn detect(dmabuf_version: Option<u32>) -> Vec<Backend> {
+    let mut backends = LIBRARIES
+        .iter()
+        .map(|&(name, sonames)| Backend {
+            name,
+            state: sonames
+                .iter()
+                .find(|soname| {
+                    // SAFETY: the libraries are only loaded, not called into, their
+                    // initializers are those of common system libraries.
+                    unsafe { libloading::Library::new(soname) }.is_ok()
+                })
+                .map(|soname| soname.to_string())
+                .ok_or_else(|| format!("{} not found", sonames[0])),
+        })
+        .collect::<Vec<_>>();
+
+    let render_node = std::fs::read_dir("/dev/dri").ok().and_then(|entries| {
+        entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("renderD"))
+            })
+    });
+    let gbm = backends
+        .iter()
+        .any(|backend| backend.name == "gbm" && backend.state.is_ok());
+    backends.push(Backend {
+        name: "dmabuf",
+        state: match (dmabuf_version, render_node, gbm) {
+            (None, _, _) => Err("the compositor has no zwp_linux_dmabuf_v1".into()),
+            (_, None, _) => Err("no render node in /dev/dri".into()),
+            (_, _, false) => Err("needs gbm".into()),
+            (Some(version), Some(node), true) => Ok(format!(
+                "zwp_linux_dmabuf_v1 v{} on {}",
+                version,
+                node.display()
+            )),
+        },
+    });
+    backends
+}
+
+pub fn print_report(backends: &[Backend], prefix: &str) {
+    for backend in backends {
+        match &backend.state {
+            Ok(found) => println!("{}  {:<18} available, {}", prefix, backend.name, found),
+            Err(reason) => println!("{}  {:<18} disabled, {}", prefix, backend.name, reason),
+        }
+    }
+    println!("{}  {:<18} in use", prefix, "shm");
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use crate::plugin::FramePlan;
+
+/// Unconsumed commits the compositor may be behind by before the submission slows
+/// down, far more than any fifo queue needs.
+const THRESHOLD: u64 = 30;
+/// Delay of the first backed off frame, doubled with every further one.
+const MIN_DELAY: Duration = Duration::from_millis(16);
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// Slows down the submission while the compositor doesn't consume the commits, so the
+/// tool doesn't queue up unboundedly on a compositor that is already struggling.
+/// Disabled with `--no-backoff`.
+///
+/// The compositor applies commits in order, so a commit counts as consumed once it or
+/// a later one is presented or discarded, or its buffer is released. Feedback is only
+/// requested when something else needs it, buffer releases are always there.
+#[derive(Default)]
+pub struct Backoff {
+    /// Committed frames not known to be consumed yet.
+    outstanding: BTreeSet<u64>,
+    delay: Option<Duration>,
+    episodes: u64,
+    /// Most commits outstanding at once.
+    worst: u64,
+}
+
+impl Backoff {
+    pub fn committed(&mut self, frame: u64) {
+        self.outstanding.insert(frame);
+        self.worst = self.worst.max(self.outstanding.len() as u64);
+    }
+
+    /// `frame` was consumed, and with it every commit before it.
+    pub fn consumed(&mut self, frame: u64, prefix: &str) {
+        self.outstanding = self.outstanding.split_off(&(frame + 1));
+        if self.delay.is_some() && self.outstanding.len() as u64 <= THRESHOLD / 2 {
+            self.delay = None;
+            eprintln!(
+                "{}backoff: the compositor consumes commits again, back to full rate",
+                prefix
+            );
+        }
+    }
+
+    /// Delays the next frame while too many commits are outstanding.
+    pub fn apply(&mut self, plan: &mut FramePlan, prefix: &str) {
+        if self.outstanding.len() as u64 <= THRESHOLD {
+            return;
+        }
+        let delay = match self.delay {
+            Some(delay) => (delay * 2).min(MAX_DELAY),
+            None => {
+                self.episodes += 1;
+                eprintln!(
+                    "{}backoff: {} commits neither presented nor released, the compositor isn't consuming them, slowing down (--no-backoff to disable)",
+                    prefix,
+                    self.outstanding.len()
+                );
+                MIN_DELAY
+            }
+        };
+        self.delay = Some(delay);
+        plan.delay = plan.delay.max(delay);
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if self.episodes == 0 {
+            return;
+        }
+        println!(
+            "{}backoff: slowed down {} times, up to {} commits were outstanding",
+            prefix, self.episodes, self.worst
+        );
+    }
+}
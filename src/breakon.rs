@@ -0,0 +1,170 @@
+use std::os::fd::AsRawFd;
+use std::str::FromStr;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::Connection;
+
+use crate::presentation::{self, Presented};
+
+/// Presentation anomalies `--break-on` can freeze on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Anomaly {
+    /// A refresh without a new frame although the next one was committed in time.
+    Miss,
+    /// Two frames presented at the same vblank.
+    Duplicate,
+    /// A frame presented before an earlier one, or its feedback arriving first.
+    OutOfOrder,
+}
+
+impl Anomaly {
+    pub fn name(self) -> &'static str {
+        match self {
+            Anomaly::Miss => "miss",
+            Anomaly::Duplicate => "duplicate",
+            Anomaly::OutOfOrder => "out-of-order",
+        }
+    }
+}
+
+/// Parsed from `anomaly=<type>`, `None` matches every type.
+#[derive(Clone, Copy, Debug)]
+pub struct BreakOn(Option<Anomaly>);
+
+impl FromStr for BreakOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some(("anomaly", "miss")) => Ok(BreakOn(Some(Anomaly::Miss))),
+            Some(("anomaly", "duplicate")) => Ok(BreakOn(Some(Anomaly::Duplicate))),
+            Some(("anomaly", "out-of-order")) => Ok(BreakOn(Some(Anomaly::OutOfOrder))),
+            Some(("anomaly", "any")) => Ok(BreakOn(None)),
+            _ => Err(format!(
+                "invalid break condition `{}`, expected anomaly=<miss|duplicate|out-of-order|any>",
+                s
+            )),
+        }
+    }
+}
+
+/// An anomaly found in the presentation feedback.
+pub struct Detected {
+    pub anomaly: Anomaly,
+    /// Refreshes a missed frame stayed on screen too long, zero for other anomalies.
+    pub missed: u64,
+    pub description: String,
+}
+
+struct Last {
+    frame: u64,
+    time: Duration,
+    seq: u64,
+    refresh: Duration,
+}
+
+/// Detects anomalies in the presentation feedback, `--break-on`.
+pub struct Breaker {
+    conditions: Vec<BreakOn>,
+    last: Option<Last>,
+}
+
+impl Breaker {
+    pub fn new(conditions: Vec<BreakOn>) -> Self {
+        Self {
+            conditions,
+            last: None,
+        }
+    }
+
+    /// Returns a description of the anomaly if `frame` triggers one of the conditions.
+    pub fn presented(&mut self, frame: u64, presented: &Presented) -> Option<String> {
+        let detected = self.detect(frame, presented)?;
+        self.conditions
+            .iter()
+            .any(|condition| condition.0.is_none_or(|wanted| wanted == detected.anomaly))
+            .then(|| format!("{}: {}", detected.anomaly.name(), detected.description))
+    }
+
+    /// Returns the anomaly `frame` shows, whatever the conditions.
+    pub fn detect(&mut self, frame: u64, presented: &Presented) -> Option<Detected> {
+        let refresh = if presented.refresh.is_zero() {
+            self.last
+                .as_ref()
+                .map_or(Duration::ZERO, |last| last.refresh)
+        } else {
+            presented.refresh
+        };
+        let last = self.last.replace(Last {
+            frame,
+            time: presented.time,
+            seq: presented.seq,
+            refresh,
+        })?;
+
+        let (anomaly, missed, description) = if frame < last.frame || presented.time < last.time {
+            (
+                Anomaly::OutOfOrder,
+                0,
+                format!(
+                    "frame {} presented at {:?} after frame {} at {:?}",
+                    frame, presented.time, last.frame, last.time
+                ),
+            )
+        } else if presented.time == last.time || (last.seq != 0 && presented.seq == last.seq) {
+            (
+                Anomaly::Duplicate,
+                0,
+                format!(
+                    "frames {} and {} presented at the same vblank",
+                    last.frame, frame
+                ),
+            )
+        } else {
+            let held = presentation::refresh_cycles(
+                (last.time, last.seq),
+                (presented.time, presented.seq),
+                refresh,
+            );
+            if frame != last.frame + 1 || held <= 1 {
+                return None;
+            }
+            (
+                Anomaly::Miss,
+                held - 1,
+                format!(
+                    "frame {} stayed on screen for {} refreshes",
+                    last.frame, held
+                ),
+            )
+        };
+
+        Some(Detected {
+            anomaly,
+            missed,
+            description,
+        })
+    }
+}
+
+/// Pid of the compositor on the other end of the connection.
+pub fn compositor_pid(conn: &Connection) -> Option<u32> {
+    let fd = conn.backend().poll_fd().as_raw_fd();
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: the fd is the open wayland socket and the buffer matches SO_PEERCRED.
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    (result == 0 && credentials.pid > 0).then_some(credentials.pid as u32)
+}
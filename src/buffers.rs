@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use smithay_client_toolkit::reexports::client::protocol::wl_shm;
+use smithay_client_toolkit::shm::slot::{Buffer, SlotPool};
+
+use crate::canvas::Canvas;
+use crate::plugin::FramePattern;
+
+/// Number of buffers cycled through by the test window, unless `--buffers` is given.
+pub const DEFAULT_COUNT: usize = 4;
+
+/// Creates `count` buffers for a `width`x`height` surface, static patterns are
+/// rendered into them right away.
+pub fn create(
+    pool: &mut SlotPool,
+    width: u32,
+    height: u32,
+    count: usize,
+    format: wl_shm::Format,
+    pattern: &mut dyn FramePattern,
+) -> Vec<Buffer> {
+    let buffers = (0..count)
+        .map(|_| {
+            pool.create_buffer(width as i32, height as i32, width as i32 * 4, format)
+                .expect("create buffer")
+                .0
+        })
+        .collect::<Vec<_>>();
+
+    if pattern.is_static() {
+        for buffer in &buffers {
+            let mut canvas = Canvas::new(pool.canvas(buffer).unwrap(), width, height);
+            pattern.render(&mut canvas, 0);
+        }
+    }
+
+    buffers
+}
+
+/// Initial size of the buffers, parsed from `<width>x<height>`.
+#[derive(Clone, Copy, Debug)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid size `{}`, expected <width>x<height>", s);
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+        let parse = |value: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (1..=8192).contains(value))
+                .ok_or_else(invalid)
+        };
+        Ok(Size {
+            width: parse(width)?,
+            height: parse(height)?,
+        })
+    }
+}
+
+/// How the shm pool is adapted when the buffer size changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PoolStrategy {
+    /// Create a new pool and wl_shm_pool for the new buffers
+    Recreate,
+    /// Keep the pool and grow it with wl_shm_pool.resize
+    Resize,
+}
+
+/// Progressive buffer growth, parsed from `step=<px>,every=<n>[,max=<px>]`.
+#[derive(Clone, Copy, Debug)]
+pub struct Growth {
+    pub step: u32,
+    pub every: u64,
+    pub max: u32,
+}
+
+impl FromStr for Growth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut step = None;
+        let mut every = None;
+        let mut max = 4096;
+
+        for part in s.split(',') {
+            let parse = |value: &str| {
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|value| *value > 0)
+                    .ok_or_else(|| format!("invalid value `{}` in `{}`", value, part))
+            };
+            match part.split_once('=') {
+                Some(("step", value)) => step = Some(parse(value)?),
+                Some(("every", value)) => every = Some(parse(value)? as u64),
+                Some(("max", value)) => max = parse(value)?,
+                _ => return Err(format!("unknown growth parameter `{}`", part)),
+            }
+        }
+
+        match (step, every) {
+            (Some(step), Some(every)) => Ok(Growth { step, every, max }),
+            _ => Err("expected step=<px>,every=<n>[,max=<px>]".to_string()),
+        }
+    }
+}
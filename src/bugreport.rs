@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::Args;
+
+/// Options only handled by the parent process, removed from the child's arguments.
+const OPTIONS: [&str; 2] = ["--bug-report", "--bug-report-messages"];
+
+/// Environment variables worth attaching, by prefix.
+const ENV_PREFIXES: [&str; 6] = [
+    "WAYLAND_",
+    "XDG_",
+    "DISPLAY",
+    "DESKTOP_SESSION",
+    "GDK_BACKEND",
+    "QT_QPA_PLATFORM",
+];
+
+/// Runs the test in a child process with `WAYLAND_DEBUG=1` and bundles its output,
+/// the protocol log tail, the environment and the HTML frame timeline of
+/// `--html-timeline` into a tar.gz at `path`, `--bug-report`.
+///
+/// The timeline is only written by plain runs, subcommands and repeated runs leave it
+/// out of the bundle.
+///
+/// Exits with the child's exit code if it failed.
+pub fn run(args: &Args, path: &Path, messages: usize) {
+    let started = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut child_args = child_args(std::env::args().skip(1));
+    // Written next to the bundle unless requested anyway, before any subcommand.
+    let timeline = args.html_timeline.clone().unwrap_or_else(|| {
+        let path = std::path::absolute(path).unwrap_or_else(|_| PathBuf::from(path));
+        let timeline = path.with_file_name(format!(
+            "fifo_test-bug-report-{}-timeline.html",
+            std::process::id()
+        ));
+        child_args.insert(0, format!("--html-timeline={}", timeline.display()));
+        timeline
+    });
+
+    let mut child = Command::new(std::env::current_exe().expect("Failed to find the executable"))
+        .args(&child_args)
+        .env("WAYLAND_DEBUG", "1")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn the test run");
+
+    // Forward the output while it is recorded, the protocol log goes to the bundle only.
+    let stdout = child.stdout.take().unwrap();
+    let stdout = std::thread::spawn(move || {
+        let mut log = String::new();
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            println!("{}", line);
+            log.push_str(&line);
+            log.push('\n');
+        }
+        log
+    });
+    let mut stderr_log = String::new();
+    let mut globals = String::new();
+    let mut protocol = VecDeque::with_capacity(messages);
+    for line in BufReader::new(child.stderr.take().unwrap())
+        .lines()
+        .map_while(Result::ok)
+    {
+        if !is_protocol_line(&line) {
+            eprintln!("{}", line);
+            stderr_log.push_str(&line);
+            stderr_log.push('\n');
+            continue;
+        }
+        if is_global(&line) {
+            globals.push_str(&line);
+            globals.push('\n');
+        }
+        if protocol.len() == messages {
+            protocol.pop_front();
+        }
+        if messages > 0 {
+            protocol.push_back(line);
+        }
+    }
+    let status = child.wait().expect("Failed to wait for the test run");
+    let stdout_log = stdout.join().unwrap_or_default();
+
+    let manifest = format!(
+        "fifo_test {}\nstarted: {} (unix time)\ncommand line: {}\nresult: {}\n\n{:#?}\n",
+        env!("CARGO_PKG_VERSION"),
+        started.as_secs(),
+        child_args.join(" "),
+        status,
+        args
+    );
+    let mut protocol = Vec::from(protocol).join("\n");
+    protocol.push('\n');
+
+    let mut files = vec![
+        ("manifest.txt", manifest),
+        ("stdout.txt", stdout_log),
+        ("stderr.txt", stderr_log),
+        ("globals.txt", globals),
+        ("protocol.txt", protocol),
+        ("environment.txt", environment()),
+    ];
+    if let Ok(page) = std::fs::read_to_string(&timeline) {
+        files.push(("timeline.html", page));
+    }
+    if args.html_timeline.is_none() {
+        let _ = std::fs::remove_file(&timeline);
+    }
+    match write_bundle(path, &files) {
+        Ok(()) => println!("bug report written to {}", path.display()),
+        Err(err) => eprintln!(
+            "failed to write the bug report to {}: {}",
+            path.display(),
+            err
+        ),
+    }
+
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+/// Arguments of this process without the bug report options.
+fn child_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut child_args = Vec::new();
+    let mut skip_value = false;
+    for arg in args {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if OPTIONS.contains(&arg.as_str()) {
+            skip_value = true;
+        } else if !OPTIONS
+            .iter()
+            .any(|option| arg.starts_with(&format!("{}=", option)))
+        {
+            child_args.push(arg);
+        }
+    }
+    child_args
+}
+
+/// Lines like `[1234.567] -> wl_surface#3.commit()`.
+pub fn is_protocol_line(line: &str) -> bool {
+    crate::protocol_log::Message::parse(line).is_some()
+}
+
+/// `wl_registry.global` events, announcing the compositor's globals.
+fn is_global(line: &str) -> bool {
+    crate::protocol_log::Message::parse(line)
+        .is_some_and(|message| message.interface == "wl_registry" && message.name == "global")
+}
+
+fn environment() -> String {
+    let mut environment = String::new();
+    let mut vars = std::env::vars()
+        .filter(|(name, _)| ENV_PREFIXES.iter().any(|prefix| name.starts_with(prefix)))
+        .collect::<Vec<_>>();
+    vars.sort();
+    for (name, value) in vars {
+        environment.push_str(&format!("{}={}\n", name, value));
+    }
+
+    if let Ok(output) = Command::new("uname").arg("-a").output() {
+        environment.push_str(&format!(
+            "\nuname: {}",
+            String::from_utf8_lossy(&output.stdout)
+        ));
+    }
+    if let Ok(os_release) = std::fs::read_to_string("/etc/os-release") {
+        environment.push_str(&format!("\n/etc/os-release:\n{}", os_release));
+    }
+    environment
+}
+
+/// Writes `files` into a directory next to `path` and packs it with `tar`.
+///
+/// The directory isn't created in the temporary directory, which may not be shared
+/// with the host in a sandbox.
+fn write_bundle(path: &Path, files: &[(&str, String)]) -> std::io::Result<()> {
+    let path = std::path::absolute(path).unwrap_or_else(|_| PathBuf::from(path));
+    let parent = path.parent().unwrap_or(Path::new("/"));
+    let name = format!("fifo_test-bug-report-{}", std::process::id());
+    let staging = parent.join(&name);
+    std::fs::create_dir_all(&staging)?;
+    for (file, contents) in files {
+        std::fs::File::create(staging.join(file))?.write_all(contents.as_bytes())?;
+    }
+
+    let status = Command::new("tar")
+        .arg("czf")
+        .arg(&path)
+        .arg("-C")
+        .arg(parent)
+        .arg(&name)
+        .status();
+    let _ = std::fs::remove_dir_all(&staging);
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(std::io::Error::other(format!("tar failed with {}", status))),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_globals_of_both_backends() {
+        assert!(is_global(
+            r#"[1234567.890] wl_registry#2.global(1, "wl_compositor", 6)"#
+        ));
+        assert!(is_global(
+            r#"[1234567.890][rs] <- wl_registry@2.global, (1, "wl_compositor", 6)"#
+        ));
+        assert!(!is_global(
+            "[1234567.890][rs] <- wl_registry@2.global_remove, (1)"
+        ));
+        assert!(!is_global(
+            r#"[1234567.890][rs] -> wl_registry@2.bind(1, "wl_compositor", 6, wl_compositor@3)"#
+        ));
+    }
+}
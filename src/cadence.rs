@@ -0,0 +1,73 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::clock::Clock;
+
+/// An interval further than this fraction away from the median counts as unstable.
+const TOLERANCE: f64 = 0.25;
+
+/// Intervals between commits that carry new content, for `--present-divisor`.
+pub struct Cadence {
+    divisor: u64,
+    last: Option<Duration>,
+    intervals: Vec<Duration>,
+    clock: Rc<dyn Clock>,
+}
+
+impl Cadence {
+    pub fn new(divisor: u64, clock: Rc<dyn Clock>) -> Self {
+        Self {
+            divisor,
+            last: None,
+            intervals: Vec::new(),
+            clock,
+        }
+    }
+
+    /// Whether the given rendered frame is committed with its content.
+    pub fn presents(&self, frame: u64) -> bool {
+        frame.is_multiple_of(self.divisor)
+    }
+
+    pub fn presented(&mut self) {
+        let now = self.clock.now();
+        if let Some(last) = self.last.replace(now) {
+            self.intervals.push(now - last);
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if self.intervals.is_empty() {
+            return;
+        }
+
+        let mut sorted = self.intervals.clone();
+        sorted.sort();
+        let median = sorted[sorted.len() / 2].as_secs_f64();
+        let count = sorted.len() as f64;
+        let mean = sorted.iter().map(Duration::as_secs_f64).sum::<f64>() / count;
+        let variance = sorted
+            .iter()
+            .map(|interval| (interval.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let unstable = sorted
+            .iter()
+            .filter(|interval| (interval.as_secs_f64() - median).abs() > median * TOLERANCE)
+            .count();
+
+        println!(
+            "{}cadence (1/{} frames presented): {} intervals, median {:.2}ms, mean {:.2}ms, stddev {:.2}ms, min {:.2}ms, max {:.2}ms, {} off by more than {}%",
+            prefix,
+            self.divisor,
+            sorted.len(),
+            median * 1000.0,
+            mean * 1000.0,
+            variance.sqrt() * 1000.0,
+            sorted[0].as_secs_f64() * 1000.0,
+            sorted[sorted.len() - 1].as_secs_f64() * 1000.0,
+            unstable,
+            (TOLERANCE * 100.0) as u32,
+        );
+    }
+}
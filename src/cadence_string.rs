@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use crate::observer::{FrameObserver, Log};
+use crate::presentation::{self, Presented};
+
+/// Refreshes per line of the report.
+const LINE_LENGTH: usize = 60;
+/// Longer repeats are written as `-{N}`, e.g. while paused.
+const LONG_REPEAT: u64 = LINE_LENGTH as u64;
+
+/// One character per refresh of the surface's output, `--cadence-string`: `V` when a
+/// new frame was presented at that refresh, `-` when the previous one was repeated.
+///
+/// Refreshes are counted from the retrace counters of the feedback, or from the
+/// presentation times and the refresh period if the compositor has no counters.
+#[derive(Default)]
+pub struct CadenceString {
+    last: Option<(Duration, u64, Duration)>,
+    tokens: Vec<String>,
+    refreshes: u64,
+    presented: u64,
+    /// Frames presented at the same refresh as the one before.
+    duplicates: u64,
+    longest_repeat: u64,
+}
+
+impl FrameObserver for CadenceString {
+    fn presented(&mut self, _: u64, presented: Option<&Presented>, _: &str, _: &Log) {
+        let Some(presented) = presented else {
+            return;
+        };
+        let refresh = match self.last {
+            Some((_, _, refresh)) if presented.refresh.is_zero() => refresh,
+            _ => presented.refresh,
+        };
+        let Some((time, seq, _)) = self.last.replace((presented.time, presented.seq, refresh))
+        else {
+            self.tokens.push("V".to_string());
+            self.refreshes += 1;
+            self.presented += 1;
+            return;
+        };
+
+        let held =
+            presentation::refresh_cycles((time, seq), (presented.time, presented.seq), refresh);
+        if held == 0 {
+            self.duplicates += 1;
+            return;
+        }
+
+        let repeats = held - 1;
+        self.longest_repeat = self.longest_repeat.max(repeats);
+        if repeats > LONG_REPEAT {
+            self.tokens.push(format!("-{{{}}}", repeats));
+        } else {
+            self.tokens.extend((0..repeats).map(|_| "-".to_string()));
+        }
+        self.tokens.push("V".to_string());
+        self.refreshes += held;
+        self.presented += 1;
+    }
+
+    fn print_report(&self, prefix: &str) {
+        println!(
+            "{}cadence string: {} refreshes, {} new frames, {} repeated, longest repeat {}, {} presented at the same refresh",
+            prefix,
+            self.refreshes,
+            self.presented,
+            self.refreshes - self.presented,
+            self.longest_repeat,
+            self.duplicates
+        );
+        for line in self.tokens.chunks(LINE_LENGTH) {
+            println!("{}  {}", prefix, line.concat());
+        }
+    }
+}
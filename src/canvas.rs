@@ -0,0 +1,55 @@
+/// Mutable view of an ARGB8888 buffer for the overlay renderers.
+pub struct Canvas<'a> {
+    data: &'a mut [u8],
+    width: u32,
+    height: u32,
+}
+
+impl<'a> Canvas<'a> {
+    /// Wraps a tightly packed (`stride == width * 4`) canvas.
+    pub fn new(data: &'a mut [u8], width: u32, height: u32) -> Self {
+        assert!(data.len() >= (width * height * 4) as usize);
+        Self {
+            data,
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..(self.width * self.height * 4) as usize]
+    }
+
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: u32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let offset = ((y * self.width + x) * 4) as usize;
+        self.data[offset..offset + 4].copy_from_slice(&color.to_le_bytes());
+    }
+
+    /// Fills a rectangle, clipped to the canvas.
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: u32) {
+        let x0 = x.clamp(0, self.width as i32) as u32;
+        let y0 = y.clamp(0, self.height as i32) as u32;
+        let x1 = (x + width as i32).clamp(0, self.width as i32) as u32;
+        let y1 = (y + height as i32).clamp(0, self.height as i32) as u32;
+
+        let bytes = color.to_le_bytes();
+        for y in y0..y1 {
+            let row =
+                &mut self.data[((y * self.width + x0) * 4) as usize..][..((x1 - x0) * 4) as usize];
+            for pixel in row.chunks_exact_mut(4) {
+                pixel.copy_from_slice(&bytes);
+            }
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! Self-verification by capturing our own toplevel with ext-image-copy-capture.
+//!
+//! The toplevel is looked up by title and app id in the ext-foreign-toplevel-list and
+//! captured continuously into a shm buffer. Every capture is identified by its frame
+//! id stamp and compared by [`crate::golden`] against the frame rendered with that id.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::{
+    backend::ObjectId, delegate_noop, event_created_child, globals::GlobalList, protocol::wl_shm,
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+use smithay_client_toolkit::reexports::protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1, ext_foreign_toplevel_list_v1,
+};
+use smithay_client_toolkit::reexports::protocols::ext::image_capture_source::v1::client::{
+    ext_foreign_toplevel_image_capture_source_manager_v1, ext_image_capture_source_v1,
+};
+use smithay_client_toolkit::reexports::protocols::ext::image_copy_capture::v1::client::{
+    ext_image_copy_capture_frame_v1, ext_image_copy_capture_manager_v1,
+    ext_image_copy_capture_session_v1,
+};
+use smithay_client_toolkit::shm::{
+    slot::{Buffer, SlotPool},
+    Shm,
+};
+
+use crate::{clock, golden::Golden, SimpleWindow};
+
+#[derive(Default)]
+struct Toplevel {
+    title: String,
+    app_id: String,
+}
+
+#[derive(Default)]
+struct Constraints {
+    size: Option<(u32, u32)>,
+    formats: Vec<wl_shm::Format>,
+}
+
+pub struct Capture {
+    title: String,
+    app_id: String,
+    _list: ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1,
+    source_manager:
+        ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+    copy_manager: ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1,
+    toplevels: HashMap<ObjectId, Toplevel>,
+    found: bool,
+    session: Option<ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1>,
+    constraints: Constraints,
+    pool: SlotPool,
+    buffer: Option<(Buffer, u32, u32)>,
+    capturing: bool,
+    /// Presentation time of the capture in progress, if the compositor sends it.
+    presented: Option<Duration>,
+    golden: Golden,
+    /// Captures the compositor failed.
+    failed: u64,
+}
+
+impl Capture {
+    /// Binds the capture globals, `None` with a warning if any of them is missing.
+    pub fn bind(
+        globals: &GlobalList,
+        qh: &QueueHandle<SimpleWindow>,
+        shm: &Shm,
+        title: String,
+        app_id: String,
+    ) -> Option<Capture> {
+        let bound = (|| {
+            Some((
+                globals.bind(qh, 1..=1, ()).ok()?,
+                globals.bind(qh, 1..=1, ()).ok()?,
+                globals.bind(qh, 1..=1, ()).ok()?,
+            ))
+        })();
+        let Some((list, source_manager, copy_manager)) = bound else {
+            eprintln!(
+                "capture verification requested, but ext-foreign-toplevel-list or ext-image-copy-capture is unavailable"
+            );
+            return None;
+        };
+
+        Some(Capture {
+            title,
+            app_id,
+            _list: list,
+            source_manager,
+            copy_manager,
+            toplevels: HashMap::new(),
+            found: false,
+            session: None,
+            constraints: Constraints::default(),
+            pool: SlotPool::new(4096, shm).expect("Failed to create capture pool"),
+            buffer: None,
+            capturing: false,
+            presented: None,
+            golden: Golden::default(),
+            failed: 0,
+        })
+    }
+
+    /// Remembers the content of a frame about to be committed.
+    pub fn rendered(&mut self, frame: u32, width: u32, height: u32, data: &[u8]) {
+        self.golden.rendered(frame, width, height, data);
+    }
+
+    /// Captures that differed from the rendered frame.
+    pub fn mismatched(&self) -> u64 {
+        self.golden.mismatched()
+    }
+
+    fn toplevel_done(
+        &mut self,
+        handle: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+        qh: &QueueHandle<SimpleWindow>,
+    ) {
+        if self.session.is_some() {
+            return;
+        }
+        let Some(toplevel) = self.toplevels.get(&handle.id()) else {
+            return;
+        };
+        if toplevel.title != self.title || toplevel.app_id != self.app_id {
+            return;
+        }
+
+        self.found = true;
+        let source = self.source_manager.create_source(handle, qh, ());
+        self.session = Some(self.copy_manager.create_session(
+            &source,
+            ext_image_copy_capture_manager_v1::Options::empty(),
+            qh,
+            (),
+        ));
+        source.destroy();
+    }
+
+    /// Applies the buffer constraints sent with a session `done`.
+    fn constraints_done(&mut self, qh: &QueueHandle<SimpleWindow>) {
+        let Some((width, height)) = self.constraints.size else {
+            return;
+        };
+        let Some(format) = [wl_shm::Format::Xrgb8888, wl_shm::Format::Argb8888]
+            .into_iter()
+            .find(|format| self.constraints.formats.contains(format))
+        else {
+            eprintln!("capture: the compositor offers no 32-bit shm format");
+            return;
+        };
+
+        if !matches!(self.buffer, Some((_, w, h)) if (w, h) == (width, height)) {
+            let (buffer, _) = self
+                .pool
+                .create_buffer(width as i32, height as i32, width as i32 * 4, format)
+                .expect("create capture buffer");
+            self.buffer = Some((buffer, width, height));
+        }
+        self.capture_next(qh);
+    }
+
+    fn capture_next(&mut self, qh: &QueueHandle<SimpleWindow>) {
+        let (Some(session), Some((buffer, width, height))) =
+            (self.session.as_ref(), self.buffer.as_ref())
+        else {
+            return;
+        };
+        if self.capturing {
+            return;
+        }
+
+        let frame = session.create_frame(qh, ());
+        frame.attach_buffer(buffer.wl_buffer());
+        frame.damage_buffer(0, 0, *width as i32, *height as i32);
+        frame.capture();
+        self.capturing = true;
+    }
+
+    /// Compares a finished capture, returns the frame id and presentation time of
+    /// matching ones.
+    fn ready(&mut self, log_prefix: &str) -> Option<(u32, Duration)> {
+        self.capturing = false;
+        let presented = self.presented.take().unwrap_or_else(clock::monotonic);
+        let (buffer, width, height) = self.buffer.as_ref()?;
+        let (width, height) = (*width, *height);
+        let data = self.pool.canvas(buffer)?;
+        let frame = self.golden.captured(data, width, height, log_prefix)?;
+        Some((frame, presented))
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if !self.found {
+            println!(
+                "{}capture: toplevel `{}` not found in the toplevel list",
+                prefix, self.title
+            );
+            return;
+        }
+        self.golden.print_report(prefix, "capture");
+        if self.failed > 0 {
+            println!("{}capture: {} captures failed", prefix, self.failed);
+        }
+    }
+}
+
+impl Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, ()> for SimpleWindow {
+    fn event(
+        state: &mut Self,
+        _proxy: &ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event {
+            if let Some(capture) = state.capture.as_mut() {
+                capture.toplevels.insert(toplevel.id(), Toplevel::default());
+            }
+        }
+    }
+
+    event_created_child!(SimpleWindow, ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1, ()> for SimpleWindow {
+    fn event(
+        state: &mut Self,
+        proxy: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let Some(capture) = state.capture.as_mut() else {
+            return;
+        };
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => {
+                if let Some(toplevel) = capture.toplevels.get_mut(&proxy.id()) {
+                    toplevel.title = title;
+                }
+            }
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                if let Some(toplevel) = capture.toplevels.get_mut(&proxy.id()) {
+                    toplevel.app_id = app_id;
+                }
+            }
+            ext_foreign_toplevel_handle_v1::Event::Done => capture.toplevel_done(proxy, qh),
+            ext_foreign_toplevel_handle_v1::Event::Closed => {
+                capture.toplevels.remove(&proxy.id());
+                proxy.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1, ()>
+    for SimpleWindow
+{
+    fn event(
+        state: &mut Self,
+        _proxy: &ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+        event: ext_image_copy_capture_session_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let Some(capture) = state.capture.as_mut() else {
+            return;
+        };
+        match event {
+            ext_image_copy_capture_session_v1::Event::BufferSize { width, height } => {
+                capture.constraints.size = Some((width, height));
+            }
+            ext_image_copy_capture_session_v1::Event::ShmFormat {
+                format: WEnum::Value(format),
+            } => capture.constraints.formats.push(format),
+            ext_image_copy_capture_session_v1::Event::Done => capture.constraints_done(qh),
+            ext_image_copy_capture_session_v1::Event::Stopped => {
+                eprintln!("{}capture session stopped", state.log_prefix);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1, ()> for SimpleWindow {
+    fn event(
+        state: &mut Self,
+        proxy: &ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+        event: ext_image_copy_capture_frame_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let Some(capture) = state.capture.as_mut() else {
+            return;
+        };
+        match event {
+            ext_image_copy_capture_frame_v1::Event::PresentationTime {
+                tv_sec_hi,
+                tv_sec_lo,
+                tv_nsec,
+            } => {
+                let secs = (u64::from(tv_sec_hi) << 32) | u64::from(tv_sec_lo);
+                capture.presented = Some(Duration::new(secs, tv_nsec));
+            }
+            ext_image_copy_capture_frame_v1::Event::Ready => {
+                proxy.destroy();
+                let matched = capture.ready(&state.log_prefix);
+                capture.capture_next(qh);
+                if let (Some(grid), Some((frame, presented))) =
+                    (state.damage_grid.as_mut(), matched)
+                {
+                    grid.captured(frame as u64, presented);
+                }
+            }
+            ext_image_copy_capture_frame_v1::Event::Failed { reason } => {
+                proxy.destroy();
+                capture.capturing = false;
+                capture.failed += 1;
+                match reason {
+                    // New constraints follow with the next session done.
+                    WEnum::Value(
+                        ext_image_copy_capture_frame_v1::FailureReason::BufferConstraints,
+                    ) => {}
+                    WEnum::Value(ext_image_copy_capture_frame_v1::FailureReason::Stopped) => {
+                        capture.session = None;
+                    }
+                    _ => capture.capture_next(qh),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(SimpleWindow: ignore ext_image_capture_source_v1::ExtImageCaptureSourceV1);
+delegate_noop!(SimpleWindow: ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1);
+delegate_noop!(SimpleWindow: ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1);
@@ -0,0 +1,82 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Source of the current `CLOCK_MONOTONIC` time for the pacing, statistics and
+/// watchdogs, so their logic runs on simulated time as well.
+pub trait Clock {
+    fn now(&self) -> Duration;
+
+    /// Time since `earlier`, zero if the clock is behind it.
+    fn since(&self, earlier: Duration) -> Duration {
+        self.now().saturating_sub(earlier)
+    }
+}
+
+/// The real clock, [`monotonic`].
+pub struct Monotonic;
+
+impl Clock for Monotonic {
+    fn now(&self) -> Duration {
+        monotonic()
+    }
+}
+
+/// A clock that only moves when told to, for simulations and replays. Clones share
+/// the time, so the clock handed out keeps following the one fast-forwarded.
+#[derive(Clone, Default)]
+pub struct Simulated(Rc<Cell<Duration>>);
+
+impl Simulated {
+    /// Fast-forwards the clock to `time`, it never goes back.
+    pub fn advance_to(&self, time: Duration) {
+        self.0.set(self.0.get().max(time));
+    }
+}
+
+impl Clock for Simulated {
+    fn now(&self) -> Duration {
+        self.0.get()
+    }
+}
+
+/// Current `CLOCK_MONOTONIC` time, the clock compositors usually report presentation
+/// timestamps in.
+pub fn monotonic() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: ts is a valid timespec to write to.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// CPU time used by all threads of the process so far, `CLOCK_PROCESS_CPUTIME_ID`.
+pub fn process_cpu() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: ts is a valid timespec to write to.
+    unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Log timestamp of the current time relative to `start`, the run start, so logs of
+/// several tools can be merged on the `CLOCK_MONOTONIC` timeline.
+pub fn stamp(start: Duration) -> String {
+    format!("[+{:.6}]", monotonic().saturating_sub(start).as_secs_f64())
+}
+
+/// `CLOCK_REALTIME` minus `CLOCK_MONOTONIC`, to map wall clock timestamps, e.g. in
+/// compositor logs, onto the presentation clock.
+pub fn realtime_offset() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: ts is a valid timespec to write to.
+    unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32).saturating_sub(monotonic())
+}
@@ -0,0 +1,109 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::{
+    protocol::{wl_buffer::WlBuffer, wl_surface::WlSurface},
+    Connection,
+};
+use smithay_client_toolkit::reexports::protocols::wp::commit_timing::v1::client::wp_commit_timer_v1::WpCommitTimerV1;
+use smithay_client_toolkit::reexports::protocols::wp::fifo::v1::client::wp_fifo_v1::WpFifoV1;
+
+/// Damage rectangle, `x, y, width, height` in surface coordinates.
+pub type Rect = (i32, i32, i32, i32);
+
+/// A request on the surface that belongs to the commit it is queued with, like a frame
+/// callback or presentation feedback.
+pub type Request = Box<dyn FnOnce(&WlSurface) + Send>;
+
+struct Job {
+    /// Issued right before the commit, so they can't end up in the previous one
+    /// still queued.
+    requests: Vec<Request>,
+    /// Buffer and damage, `None` for a commit that only carries the barrier.
+    content: Option<(WlBuffer, Vec<Rect>)>,
+    barrier: bool,
+    /// Earliest presentation time in `CLOCK_MONOTONIC`, set with commit-timing.
+    timestamp: Option<Duration>,
+}
+
+/// Issues attach, damage, barrier and commit requests from a secondary thread, like a
+/// toolkit presenting from its render thread.
+///
+/// Buffer preparation and all event handling stay on the main thread.
+pub struct CommitThread {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl CommitThread {
+    pub fn spawn(
+        conn: Connection,
+        surface: WlSurface,
+        fifo: Option<WpFifoV1>,
+        timer: Option<WpCommitTimerV1>,
+    ) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+
+        std::thread::Builder::new()
+            .name("commit-thread".into())
+            .spawn(move || {
+                for job in receiver {
+                    for request in job.requests {
+                        request(&surface);
+                    }
+                    if let Some((buffer, damage)) = job.content.as_ref() {
+                        for &(x, y, width, height) in damage {
+                            surface.damage(x, y, width, height);
+                        }
+                        surface.attach(Some(buffer), 0, 0);
+                    }
+                    if let Some(fifo) = fifo.as_ref().filter(|_| job.barrier) {
+                        fifo.wait_barrier();
+                        fifo.set_barrier();
+                    }
+                    if let (Some(timer), Some(timestamp)) = (timer.as_ref(), job.timestamp) {
+                        set_timestamp(timer, timestamp);
+                    }
+                    surface.commit();
+                    if conn.flush().is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn commit thread");
+
+        Self { jobs }
+    }
+
+    /// Queues a commit of `buffer`, which must already be marked active.
+    pub fn commit(
+        &self,
+        buffer: WlBuffer,
+        damage: Vec<Rect>,
+        barrier: bool,
+        timestamp: Option<Duration>,
+        requests: Vec<Request>,
+    ) {
+        let _ = self.jobs.send(Job {
+            requests,
+            content: Some((buffer, damage)),
+            barrier,
+            timestamp,
+        });
+    }
+
+    /// Queues a commit without new content, only setting and waiting for a barrier.
+    pub fn commit_barrier(&self, requests: Vec<Request>) {
+        let _ = self.jobs.send(Job {
+            requests,
+            content: None,
+            barrier: true,
+            timestamp: None,
+        });
+    }
+}
+
+/// Sets the earliest presentation time of the next commit.
+pub fn set_timestamp(timer: &WpCommitTimerV1, time: Duration) {
+    let secs = time.as_secs();
+    timer.set_timestamp((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+}
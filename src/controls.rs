@@ -0,0 +1,104 @@
+use crate::{relock, SimpleWindow};
+
+/// Runtime controls shared by all input methods.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// Stop committing continuously and commit a single frame per step.
+    Step,
+    /// Go back to committing continuously.
+    Resume,
+    /// Enable or disable the use of the fifo barrier.
+    ToggleFifo,
+    /// Show or hide the help overlay listing the controls.
+    ToggleHelp,
+    /// Mark an output mode change made outside the tool, for `--mode-relock`.
+    MarkModeChange,
+    /// Mark a gamma or color change, for `--gamma-watch`.
+    MarkGammaChange,
+}
+
+/// Text of the help overlay.
+pub const HELP: &str = "\
+controls      key  tap        gamepad
+step          s    1 finger   south
+resume        r    3 fingers  start
+toggle fifo   f    2 fingers  west
+move window   m
+mode changed  o
+gamma changed g
+this help     ? F1";
+
+impl SimpleWindow {
+    pub fn control(&mut self, control: Control) {
+        match control {
+            Control::Step => {
+                if self.frozen {
+                    println!("single-step mode");
+                    self.frozen = false;
+                    self.paused = true;
+                } else if self.paused {
+                    self.draw();
+                } else {
+                    // The draw already in flight becomes the first step.
+                    println!("single-step mode");
+                    self.paused = true;
+                }
+            }
+            Control::Resume => {
+                if self.paused || self.frozen {
+                    println!("continuous mode");
+                    self.frozen = false;
+                    self.resume();
+                }
+            }
+            Control::ToggleFifo => {
+                if self.fifo.is_none() {
+                    println!("fifo unavailable, cannot toggle");
+                    return;
+                }
+                self.fifo_enabled = !self.fifo_enabled;
+                println!(
+                    "fifo {}",
+                    if self.fifo_enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                );
+            }
+            Control::ToggleHelp => {
+                self.help = !self.help;
+                println!("help {}", if self.help { "shown" } else { "hidden" });
+                if !self.help
+                    && (self.pattern.is_static()
+                        || self.partial.is_some()
+                        || self.damage_grid.is_some())
+                {
+                    // The overlay was drawn into the pre-rendered or partially
+                    // redrawn buffers, or needs its area damaged.
+                    self.resize(self.width, self.height);
+                }
+            }
+            Control::MarkModeChange => {
+                let Some(relock) = self.relock.as_mut() else {
+                    println!("mode changes are only marked with --mode-relock");
+                    return;
+                };
+                relock.mark(
+                    relock::Source::Manual,
+                    self.frame,
+                    self.clock.now(),
+                    None,
+                    &self.log_prefix,
+                );
+            }
+            Control::MarkGammaChange => {
+                let Some(gamma) = self.gamma.as_mut() else {
+                    println!("gamma changes are only marked with --gamma-watch");
+                    return;
+                };
+                gamma.mark(self.frame, self.clock.now(), &self.log_prefix);
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Latency map over a grid of damage regions, `--damage-grid COLUMNSxROWS`.
+//!
+//! Every frame flashes one random cell of the grid and only damages it and the cell
+//! flashed before, so compositors with damage dependent scheduling, e.g. per plane or
+//! per region of the output, show up as cells with a different latency. Next to the
+//! presentation feedback, `--verify-capture` measures when the flash actually showed
+//! up in a capture of the toplevel.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::canvas::Canvas;
+use crate::commit_thread::Rect;
+use crate::rng::Rng;
+
+/// Commits remembered for captures, older captures aren't mapped.
+const HISTORY: u64 = 64;
+const UNLIT: u32 = 0xFF10_1010;
+const FLASH: u32 = 0xFFFF_FFFF;
+
+/// Grid size, parsed from `<columns>x<rows>`.
+#[derive(Clone, Copy, Debug)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl FromStr for Grid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (1..=64).contains(value))
+                .ok_or_else(|| format!("invalid cell count `{}` in `{}`, 1 to 64", value, s))
+        };
+        let (columns, rows) = s
+            .split_once('x')
+            .ok_or_else(|| format!("expected `<columns>x<rows>`, got `{}`", s))?;
+        Ok(Self {
+            columns: parse(columns)?,
+            rows: parse(rows)?,
+        })
+    }
+}
+
+pub struct DamageGrid {
+    grid: Grid,
+    rng: Rng,
+    /// Cell of the frame rendered last.
+    cell: usize,
+    /// Cell rectangle of the last commit.
+    committed: Option<Rect>,
+    damage: Vec<Rect>,
+    /// Cell of frames until their feedback.
+    pending: HashMap<u64, usize>,
+    /// Cell and commit time of recent frames until their first capture.
+    captures: HashMap<u64, (usize, Duration)>,
+    /// Commit to present latencies per cell.
+    presented: Vec<Vec<Duration>>,
+    /// Commit to capture presentation latencies per cell.
+    captured: Vec<Vec<Duration>>,
+}
+
+impl DamageGrid {
+    pub fn new(grid: Grid, seed: u64) -> Self {
+        let cells = (grid.columns * grid.rows) as usize;
+        Self {
+            grid,
+            rng: Rng::new(seed),
+            cell: 0,
+            committed: None,
+            damage: Vec::new(),
+            pending: HashMap::new(),
+            captures: HashMap::new(),
+            presented: vec![Vec::new(); cells],
+            captured: vec![Vec::new(); cells],
+        }
+    }
+
+    fn rect(&self, cell: usize, width: u32, height: u32) -> Rect {
+        let (column, row) = (
+            cell as u32 % self.grid.columns,
+            cell as u32 / self.grid.columns,
+        );
+        let x0 = width * column / self.grid.columns;
+        let x1 = width * (column + 1) / self.grid.columns;
+        let y0 = height * row / self.grid.rows;
+        let y1 = height * (row + 1) / self.grid.rows;
+        (x0 as i32, y0 as i32, (x1 - x0) as i32, (y1 - y0) as i32)
+    }
+
+    /// Draws the next frame with a new random cell flashing.
+    pub fn render(&mut self, canvas: &mut Canvas) {
+        let (width, height) = (canvas.width(), canvas.height());
+        self.cell = (self.rng.next_u64() % self.presented.len() as u64) as usize;
+        let rect = self.rect(self.cell, width, height);
+        canvas.fill_rect(0, 0, width, height, UNLIT);
+        canvas.fill_rect(rect.0, rect.1, rect.2 as u32, rect.3 as u32, FLASH);
+        self.damage = match self.committed {
+            Some(committed) => vec![committed, rect],
+            None => vec![(0, 0, width as i32, height as i32)],
+        };
+    }
+
+    /// The flashing and the previously flashed cell.
+    pub fn damage(&self) -> Vec<Rect> {
+        self.damage.clone()
+    }
+
+    /// Forgets the last commit, e.g. after the buffers were recreated.
+    pub fn invalidate(&mut self) {
+        self.committed = None;
+    }
+
+    pub fn committed(&mut self, frame: u64, time: Duration) {
+        self.committed = self.damage.last().copied();
+        self.pending.insert(frame, self.cell);
+        self.captures.insert(frame, (self.cell, time));
+        self.captures
+            .retain(|committed, _| *committed + HISTORY > frame);
+    }
+
+    pub fn presented(&mut self, frame: u64, latency: Option<Duration>) {
+        if let (Some(cell), Some(latency)) = (self.pending.remove(&frame), latency) {
+            self.presented[cell].push(latency);
+        }
+    }
+
+    /// A capture first showing `frame` was presented at `time`.
+    pub fn captured(&mut self, frame: u64, time: Duration) {
+        if let Some((cell, committed)) = self.captures.remove(&frame) {
+            self.captured[cell].push(time.saturating_sub(committed));
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        println!(
+            "{}damage grid: {}x{} cells, one flashing per frame",
+            prefix, self.grid.columns, self.grid.rows
+        );
+        let mean = |latencies: &[Duration]| {
+            (!latencies.is_empty()).then(|| {
+                (latencies.iter().sum::<Duration>() / latencies.len() as u32).as_secs_f64() * 1000.0
+            })
+        };
+        for (name, cells) in [
+            ("commit to present", &self.presented),
+            ("commit to capture", &self.captured),
+        ] {
+            let means = cells.iter().map(|cell| mean(cell)).collect::<Vec<_>>();
+            let known = means.iter().flatten().copied().collect::<Vec<_>>();
+            if known.is_empty() {
+                continue;
+            }
+            let min = known.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = known.iter().copied().fold(0.0, f64::max);
+            println!(
+                "{}  {} mean per cell in ms, {:.3}ms to {:.3}ms, spread {:.3}ms:",
+                prefix,
+                name,
+                min,
+                max,
+                max - min
+            );
+            for row in means.chunks(self.grid.columns as usize) {
+                let row = row
+                    .iter()
+                    .map(|mean| match mean {
+                        Some(mean) => format!("{:>8.3}", mean),
+                        None => format!("{:>8}", "-"),
+                    })
+                    .collect::<String>();
+                println!("{}  {}", prefix, row);
+            }
+        }
+    }
+}
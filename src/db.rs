@@ -0,0 +1,241 @@
+//! SQLite storage for run manifests and per-frame data, `--db`.
+//!
+//! Frames are collected in memory and written in one transaction once the window is
+//! closed, so the database doesn't add I/O to the measured frames.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+
+use crate::plugin::Annotation;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    started INTEGER NOT NULL,
+    command_line TEXT NOT NULL,
+    compositor TEXT,
+    connection INTEGER,
+    pattern TEXT NOT NULL,
+    scenario TEXT NOT NULL,
+    fifo INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS frames (
+    run INTEGER NOT NULL REFERENCES runs(id),
+    frame INTEGER NOT NULL,
+    committed_ns INTEGER NOT NULL,
+    interval_ns INTEGER,
+    barrier INTEGER NOT NULL,
+    content INTEGER NOT NULL,
+    waited_for_buffer INTEGER NOT NULL,
+    PRIMARY KEY (run, frame)
+);
+-- CLOCK_MONOTONIC process start, frames.committed_ns - start_ns is run-relative.
+CREATE TABLE IF NOT EXISTS clocks (
+    run INTEGER PRIMARY KEY REFERENCES runs(id),
+    start_ns INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS protocol_logs (
+    run INTEGER PRIMARY KEY REFERENCES runs(id),
+    path TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS annotations (
+    run INTEGER NOT NULL REFERENCES runs(id),
+    frame INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT
+);
+";
+
+/// Describes one run, i.e. one window.
+pub struct Manifest {
+    pub started: SystemTime,
+    /// `CLOCK_MONOTONIC` process start, the origin of the log timestamps.
+    pub start: Duration,
+    pub command_line: String,
+    pub compositor: Option<String>,
+    pub connection: Option<u32>,
+    pub pattern: String,
+    pub scenario: String,
+    pub fifo: bool,
+    /// File the protocol log was captured to, `--capture-protocol`.
+    pub protocol_log: Option<PathBuf>,
+}
+
+pub struct Frame {
+    pub frame: u64,
+    /// `CLOCK_MONOTONIC` time of the commit.
+    pub committed: Duration,
+    /// Time since the previous frame was drawn.
+    pub interval: Option<Duration>,
+    pub barrier: bool,
+    /// Whether the commit carried new content, see `--present-divisor`.
+    pub content: bool,
+    pub waited_for_buffer: bool,
+}
+
+pub struct Recorder {
+    manifest: Manifest,
+    frames: Vec<Frame>,
+    annotations: Vec<(u64, Annotation)>,
+}
+
+impl Manifest {
+    /// Manifest of a run with the current command line and environment.
+    pub fn current(
+        start: Duration,
+        connection: Option<u32>,
+        pattern: &str,
+        scenario: &str,
+        fifo: bool,
+        protocol_log: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            started: SystemTime::now(),
+            start,
+            command_line: std::env::args().collect::<Vec<_>>().join(" "),
+            compositor: std::env::var("XDG_CURRENT_DESKTOP").ok(),
+            connection,
+            pattern: pattern.to_string(),
+            scenario: scenario.to_string(),
+            fifo,
+            protocol_log: protocol_log.map(|path| std::path::absolute(&path).unwrap_or(path)),
+        }
+    }
+}
+
+impl Recorder {
+    pub fn new(manifest: Manifest) -> Self {
+        Self {
+            manifest,
+            frames: Vec::new(),
+            annotations: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        self.frames.push(frame);
+    }
+
+    pub fn annotate(&mut self, frame: u64, annotation: Annotation) {
+        self.annotations.push((frame, annotation));
+    }
+
+    /// Appends the run to the database at `path`, creating it if needed.
+    pub fn store(&self, path: &Path) -> rusqlite::Result<()> {
+        let mut db = open(path)?;
+        let tx = db.transaction()?;
+
+        let manifest = &self.manifest;
+        let started = manifest
+            .started
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        tx.execute(
+            "INSERT INTO runs (started, command_line, compositor, connection, pattern, scenario, fifo)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                started,
+                manifest.command_line,
+                manifest.compositor,
+                manifest.connection,
+                manifest.pattern,
+                manifest.scenario,
+                manifest.fifo,
+            ],
+        )?;
+        let run = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO clocks (run, start_ns) VALUES (?1, ?2)",
+            params![run, manifest.start.as_nanos() as i64],
+        )?;
+        if let Some(path) = manifest.protocol_log.as_ref() {
+            tx.execute(
+                "INSERT INTO protocol_logs (run, path) VALUES (?1, ?2)",
+                params![run, path.to_string_lossy()],
+            )?;
+        }
+
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO frames (run, frame, committed_ns, interval_ns, barrier, content, waited_for_buffer)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for frame in &self.frames {
+                insert.execute(params![
+                    run,
+                    frame.frame as i64,
+                    frame.committed.as_nanos() as i64,
+                    frame.interval.map(|interval| interval.as_nanos() as i64),
+                    frame.barrier,
+                    frame.content,
+                    frame.waited_for_buffer,
+                ])?;
+            }
+
+            let mut insert = tx.prepare(
+                "INSERT INTO annotations (run, frame, key, value) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (frame, annotation) in &self.annotations {
+                let (key, value) = match annotation {
+                    Annotation::Label(label) => (label, None),
+                    Annotation::Value(key, value) => (key, Some(value)),
+                };
+                insert.execute(params![run, *frame as i64, key, value])?;
+            }
+        }
+
+        tx.commit()
+    }
+}
+
+fn open(path: &Path) -> rusqlite::Result<Connection> {
+    let db = Connection::open(path)?;
+    // Several connections may finish at the same time.
+    db.busy_timeout(Duration::from_secs(10))?;
+    db.execute_batch(SCHEMA)?;
+    Ok(db)
+}
+
+/// Prints one line per stored run, with its frame interval statistics.
+pub fn print_report(path: &Path) -> rusqlite::Result<()> {
+    let db = open(path)?;
+    let mut query = db.prepare(
+        "SELECT runs.id, runs.started, runs.compositor, runs.pattern, runs.scenario, runs.fifo,
+                COUNT(frames.frame), AVG(frames.interval_ns), MIN(frames.interval_ns),
+                MAX(frames.interval_ns), TOTAL(frames.waited_for_buffer), protocol_logs.path
+         FROM runs LEFT JOIN frames ON frames.run = runs.id
+         LEFT JOIN protocol_logs ON protocol_logs.run = runs.id
+         GROUP BY runs.id ORDER BY runs.id",
+    )?;
+    let mut rows = query.query([])?;
+
+    let ms = |ns: Option<f64>| match ns {
+        Some(ns) => format!("{:.2}ms", ns / 1_000_000.0),
+        None => "-".to_string(),
+    };
+    while let Some(row) = rows.next()? {
+        let compositor: Option<String> = row.get(2)?;
+        let fifo: bool = row.get(5)?;
+        println!(
+            "run {} (started {}, {}): {}/{} {}, {} frames, interval avg {} min {} max {}, {} waited for a buffer",
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            compositor.as_deref().unwrap_or("unknown compositor"),
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            if fifo { "fifo" } else { "no fifo" },
+            row.get::<_, i64>(6)?,
+            ms(row.get(7)?),
+            ms(row.get::<_, Option<i64>>(8)?.map(|ns| ns as f64)),
+            ms(row.get::<_, Option<i64>>(9)?.map(|ns| ns as f64)),
+            row.get::<_, f64>(10)? as u64,
+        );
+        if let Some(path) = row.get::<_, Option<String>>(11)? {
+            println!("  protocol log {}", path);
+        }
+    }
+    Ok(())
+}
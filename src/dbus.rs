@@ -0,0 +1,596 @@
+//! A minimal D-Bus client on the session bus, enough to drive the desktop portal.
+//!
+//! `gdbus call` opens a connection per call, but portal sessions are closed as soon
+//! as the connection that created them goes away, so the portal needs a connection
+//! of its own. Only what the portal needs is here: the EXTERNAL authentication,
+//! method calls and waiting for signals, in little endian and without file
+//! descriptor passing.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixStream};
+use std::path::PathBuf;
+
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+const NO_REPLY_EXPECTED: u8 = 0x1;
+
+/// A D-Bus value, with the signatures of arrays kept so empty ones can be written.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Byte(u8),
+    Bool(bool),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    Double(f64),
+    /// Index into the file descriptors of the message, which aren't received.
+    Fd(u32),
+    Str(String),
+    Path(String),
+    Signature(String),
+    /// Element signature and elements.
+    Array(String, Vec<Value>),
+    Struct(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+    Variant(Box<Value>),
+}
+
+impl Value {
+    /// An `a{sv}` dictionary.
+    pub fn dict(entries: Vec<(&str, Value)>) -> Value {
+        Value::Array(
+            "{sv}".to_string(),
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    Value::DictEntry(
+                        Box::new(Value::Str(key.to_string())),
+                        Box::new(Value::Variant(Box::new(value))),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    pub fn signature(&self) -> String {
+        match self {
+            Value::Byte(_) => "y".to_string(),
+            Value::Bool(_) => "b".to_string(),
+            Value::I16(_) => "n".to_string(),
+            Value::U16(_) => "q".to_string(),
+            Value::I32(_) => "i".to_string(),
+            Value::U32(_) => "u".to_string(),
+            Value::I64(_) => "x".to_string(),
+            Value::U64(_) => "t".to_string(),
+            Value::Double(_) => "d".to_string(),
+            Value::Fd(_) => "h".to_string(),
+            Value::Str(_) => "s".to_string(),
+            Value::Path(_) => "o".to_string(),
+            Value::Signature(_) => "g".to_string(),
+            Value::Array(element, _) => format!("a{}", element),
+            Value::Struct(fields) => format!(
+                "({})",
+                fields.iter().map(Value::signature).collect::<String>()
+            ),
+            Value::DictEntry(key, value) => {
+                format!("{{{}{}}}", key.signature(), value.signature())
+            }
+            Value::Variant(_) => "v".to_string(),
+        }
+    }
+
+    /// The value of `key` in an `a{sv}` dictionary, without its variant.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        let Value::Array(_, entries) = self else {
+            return None;
+        };
+        entries.iter().find_map(|entry| match entry {
+            Value::DictEntry(name, value) if name.as_str() == Some(key) => Some(value.inner()),
+            _ => None,
+        })
+    }
+
+    /// The value inside any variants.
+    pub fn inner(&self) -> &Value {
+        match self {
+            Value::Variant(value) => value.inner(),
+            value => value,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.inner() {
+            Value::Str(value) | Value::Path(value) | Value::Signature(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self.inner() {
+            Value::U32(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Alignment of the first complete type of `signature`.
+fn alignment(signature: &str) -> usize {
+    match signature.as_bytes().first() {
+        Some(b'n' | b'q') => 2,
+        Some(b'b' | b'i' | b'u' | b'h' | b's' | b'o' | b'a') => 4,
+        Some(b'x' | b't' | b'd' | b'(' | b'{') => 8,
+        _ => 1,
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Splits the first complete type off `signature`.
+fn split(signature: &str) -> io::Result<(&str, &str)> {
+    let end = match signature.as_bytes().first() {
+        None => return Err(invalid("empty signature")),
+        Some(b'a') => 1 + split(&signature[1..])?.0.len(),
+        Some(open @ (b'(' | b'{')) => {
+            let close = if *open == b'(' { b')' } else { b'}' };
+            let mut depth = 0;
+            signature
+                .bytes()
+                .position(|byte| {
+                    if byte == *open {
+                        depth += 1;
+                    } else if byte == close {
+                        depth -= 1;
+                    }
+                    depth == 0
+                })
+                .ok_or_else(|| invalid(format!("unbalanced signature `{}`", signature)))?
+                + 1
+        }
+        Some(_) => 1,
+    };
+    Ok(signature.split_at(end))
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn align(&mut self, alignment: usize) {
+        while !self.buf.len().is_multiple_of(alignment) {
+            self.buf.push(0);
+        }
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.align(4);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn fixed<const N: usize>(&mut self, bytes: [u8; N]) {
+        self.align(N);
+        self.buf.extend_from_slice(&bytes);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn signature(&mut self, value: &str) {
+        self.buf.push(value.len() as u8);
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Byte(value) => self.buf.push(*value),
+            Value::Bool(value) => self.u32(u32::from(*value)),
+            Value::I16(value) => self.fixed(value.to_le_bytes()),
+            Value::U16(value) => self.fixed(value.to_le_bytes()),
+            Value::I32(value) => self.fixed(value.to_le_bytes()),
+            Value::U32(value) | Value::Fd(value) => self.u32(*value),
+            Value::I64(value) => self.fixed(value.to_le_bytes()),
+            Value::U64(value) => self.fixed(value.to_le_bytes()),
+            Value::Double(value) => self.fixed(value.to_le_bytes()),
+            Value::Str(value) | Value::Path(value) => self.string(value),
+            Value::Signature(value) => self.signature(value),
+            Value::Array(element, values) => {
+                self.u32(0);
+                let length_at = self.buf.len() - 4;
+                // The padding to the first element doesn't count into the length.
+                self.align(alignment(element));
+                let start = self.buf.len();
+                for value in values {
+                    self.value(value);
+                }
+                let length = (self.buf.len() - start) as u32;
+                self.buf[length_at..length_at + 4].copy_from_slice(&length.to_le_bytes());
+            }
+            Value::Struct(fields) => {
+                self.align(8);
+                for field in fields {
+                    self.value(field);
+                }
+            }
+            Value::DictEntry(key, value) => {
+                self.align(8);
+                self.value(key);
+                self.value(value);
+            }
+            Value::Variant(value) => {
+                self.signature(&value.signature());
+                self.value(value);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn align(&mut self, alignment: usize) -> io::Result<()> {
+        self.pos = self.pos.next_multiple_of(alignment);
+        if self.pos > self.data.len() {
+            return Err(invalid("message too short"));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, length: usize) -> io::Result<&[u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + length)
+            .ok_or_else(|| invalid("message too short"))?;
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn fixed<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        self.align(N)?;
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.fixed().map(u32::from_le_bytes)
+    }
+
+    fn text(&mut self, length: usize) -> io::Result<String> {
+        let text = String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| invalid("string isn't UTF-8"))?;
+        self.take(1)?;
+        Ok(text)
+    }
+
+    fn signature(&mut self) -> io::Result<String> {
+        let length = self.take(1)?[0] as usize;
+        self.text(length)
+    }
+
+    /// Reads a value of the complete type `signature`.
+    fn value(&mut self, signature: &str) -> io::Result<Value> {
+        Ok(match signature.as_bytes()[0] {
+            b'y' => Value::Byte(self.take(1)?[0]),
+            b'b' => Value::Bool(self.u32()? != 0),
+            b'n' => Value::I16(i16::from_le_bytes(self.fixed()?)),
+            b'q' => Value::U16(u16::from_le_bytes(self.fixed()?)),
+            b'i' => Value::I32(i32::from_le_bytes(self.fixed()?)),
+            b'u' => Value::U32(self.u32()?),
+            b'h' => Value::Fd(self.u32()?),
+            b'x' => Value::I64(i64::from_le_bytes(self.fixed()?)),
+            b't' => Value::U64(u64::from_le_bytes(self.fixed()?)),
+            b'd' => Value::Double(f64::from_le_bytes(self.fixed()?)),
+            b's' => {
+                let length = self.u32()? as usize;
+                Value::Str(self.text(length)?)
+            }
+            b'o' => {
+                let length = self.u32()? as usize;
+                Value::Path(self.text(length)?)
+            }
+            b'g' => Value::Signature(self.signature()?),
+            b'v' => {
+                let signature = self.signature()?;
+                Value::Variant(Box::new(self.value(&signature)?))
+            }
+            b'a' => {
+                let element = &signature[1..];
+                let length = self.u32()? as usize;
+                self.align(alignment(element))?;
+                let end = self.pos + length;
+                let mut values = Vec::new();
+                while self.pos < end {
+                    values.push(self.value(element)?);
+                }
+                Value::Array(element.to_string(), values)
+            }
+            b'(' | b'{' => {
+                self.align(8)?;
+                let mut fields = Vec::new();
+                let mut rest = &signature[1..signature.len() - 1];
+                while !rest.is_empty() {
+                    let (field, tail) = split(rest)?;
+                    fields.push(self.value(field)?);
+                    rest = tail;
+                }
+                if signature.starts_with('{') {
+                    let [key, value] = <[Value; 2]>::try_from(fields)
+                        .map_err(|_| invalid("dict entry without key and value"))?;
+                    Value::DictEntry(Box::new(key), Box::new(value))
+                } else {
+                    Value::Struct(fields)
+                }
+            }
+            other => return Err(invalid(format!("unknown type `{}`", other as char))),
+        })
+    }
+
+    /// Reads the values of every complete type in `signature`.
+    fn values(&mut self, mut signature: &str) -> io::Result<Vec<Value>> {
+        let mut values = Vec::new();
+        while !signature.is_empty() {
+            let (value, rest) = split(signature)?;
+            values.push(self.value(value)?);
+            signature = rest;
+        }
+        Ok(values)
+    }
+}
+
+pub struct Message {
+    kind: u8,
+    reply_serial: Option<u32>,
+    pub path: Option<String>,
+    pub member: Option<String>,
+    error_name: Option<String>,
+    pub body: Vec<Value>,
+}
+
+/// Opens the socket of the session bus, from `DBUS_SESSION_BUS_ADDRESS` or the
+/// default `$XDG_RUNTIME_DIR/bus`.
+pub fn session_bus() -> io::Result<UnixStream> {
+    let Some(address) = std::env::var("DBUS_SESSION_BUS_ADDRESS").ok() else {
+        let runtime = std::env::var_os("XDG_RUNTIME_DIR").ok_or_else(|| {
+            io::Error::other("neither DBUS_SESSION_BUS_ADDRESS nor XDG_RUNTIME_DIR is set")
+        })?;
+        return UnixStream::connect(PathBuf::from(runtime).join("bus"));
+    };
+    for address in address.split(';') {
+        let Some(options) = address.strip_prefix("unix:") else {
+            continue;
+        };
+        for option in options.split(',') {
+            match option.split_once('=') {
+                Some(("path", path)) => return UnixStream::connect(path),
+                Some(("abstract", name)) => {
+                    return UnixStream::connect_addr(&SocketAddr::from_abstract_name(name)?)
+                }
+                _ => {}
+            }
+        }
+    }
+    Err(io::Error::other(format!(
+        "no supported address in DBUS_SESSION_BUS_ADDRESS `{}`",
+        address
+    )))
+}
+
+pub struct Connection {
+    stream: UnixStream,
+    serial: u32,
+    /// Signals received while waiting for a reply.
+    signals: VecDeque<Message>,
+    /// Name the bus assigned to the connection, e.g. `:1.42`.
+    pub unique_name: String,
+}
+
+impl Connection {
+    /// Authenticates on `stream` and registers with the bus.
+    pub fn open(mut stream: UnixStream) -> io::Result<Self> {
+        let uid = unsafe { libc::getuid() }.to_string();
+        let hex = uid
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        stream.write_all(format!("\0AUTH EXTERNAL {}\r\n", hex).as_bytes())?;
+        let mut line = Vec::new();
+        while !line.ends_with(b"\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte)?;
+            line.push(byte[0]);
+        }
+        if !line.starts_with(b"OK ") {
+            return Err(io::Error::other(format!(
+                "authentication rejected: {}",
+                String::from_utf8_lossy(&line).trim()
+            )));
+        }
+        stream.write_all(b"BEGIN\r\n")?;
+
+        let mut connection = Self {
+            stream,
+            serial: 0,
+            signals: VecDeque::new(),
+            unique_name: String::new(),
+        };
+        let reply = connection.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "Hello",
+            &[],
+        )?;
+        connection.unique_name = reply
+            .first()
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("Hello without unique name"))?
+            .to_string();
+        Ok(connection)
+    }
+
+    /// Sends a method call, returns its serial.
+    pub fn send(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        args: &[Value],
+        no_reply: bool,
+    ) -> io::Result<u32> {
+        self.serial += 1;
+        let mut body = Writer::default();
+        for arg in args {
+            body.value(arg);
+        }
+        let signature = args.iter().map(Value::signature).collect::<String>();
+        let field = |code: u8, value: Value| {
+            Value::Struct(vec![Value::Byte(code), Value::Variant(Box::new(value))])
+        };
+        let mut fields = vec![
+            field(1, Value::Path(path.to_string())),
+            field(2, Value::Str(interface.to_string())),
+            field(3, Value::Str(member.to_string())),
+            field(6, Value::Str(destination.to_string())),
+        ];
+        if !signature.is_empty() {
+            fields.push(field(8, Value::Signature(signature)));
+        }
+
+        let mut message = Writer::default();
+        message.buf.extend_from_slice(&[
+            b'l',
+            METHOD_CALL,
+            if no_reply { NO_REPLY_EXPECTED } else { 0 },
+            1,
+        ]);
+        message.u32(body.buf.len() as u32);
+        message.u32(self.serial);
+        message.value(&Value::Array("(yv)".to_string(), fields));
+        message.align(8);
+        message.buf.extend_from_slice(&body.buf);
+        self.stream.write_all(&message.buf)?;
+        Ok(self.serial)
+    }
+
+    /// Calls a method and waits for its reply.
+    pub fn call(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        args: &[Value],
+    ) -> io::Result<Vec<Value>> {
+        let serial = self.send(destination, path, interface, member, args, false)?;
+        loop {
+            let message = self.read()?;
+            match message.kind {
+                SIGNAL => self.signals.push_back(message),
+                METHOD_RETURN if message.reply_serial == Some(serial) => return Ok(message.body),
+                ERROR if message.reply_serial == Some(serial) => {
+                    return Err(io::Error::other(format!(
+                        "{} failed: {}{}",
+                        member,
+                        message.error_name.unwrap_or_default(),
+                        message
+                            .body
+                            .first()
+                            .and_then(Value::as_str)
+                            .map(|text| format!(", {}", text))
+                            .unwrap_or_default()
+                    )))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Waits for the signal `member` from the object at `path`.
+    pub fn signal(&mut self, path: &str, member: &str) -> io::Result<Message> {
+        let matches = |message: &Message| {
+            message.path.as_deref() == Some(path) && message.member.as_deref() == Some(member)
+        };
+        if let Some(index) = self.signals.iter().position(matches) {
+            return Ok(self.signals.remove(index).unwrap());
+        }
+        loop {
+            let message = self.read()?;
+            if message.kind == SIGNAL && matches(&message) {
+                return Ok(message);
+            }
+        }
+    }
+
+    fn read(&mut self) -> io::Result<Message> {
+        let mut data = vec![0; 16];
+        self.stream.read_exact(&mut data)?;
+        if data[0] != b'l' {
+            return Err(invalid("big endian messages aren't supported"));
+        }
+        let body_length = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        let fields_length = u32::from_le_bytes(data[12..16].try_into().unwrap()) as usize;
+        let body_start = (16 + fields_length).next_multiple_of(8);
+        data.resize(body_start + body_length, 0);
+        self.stream.read_exact(&mut data[16..])?;
+
+        let mut reader = Reader {
+            data: &data[..body_start],
+            pos: 12,
+        };
+        let Value::Array(_, fields) = reader.value("a(yv)")? else {
+            unreachable!("read as an array");
+        };
+        let mut message = Message {
+            kind: data[1],
+            reply_serial: None,
+            path: None,
+            member: None,
+            error_name: None,
+            body: Vec::new(),
+        };
+        let mut signature = String::new();
+        for field in fields {
+            let Value::Struct(field) = field else {
+                continue;
+            };
+            let (Some(Value::Byte(code)), Some(value)) = (field.first(), field.get(1)) else {
+                continue;
+            };
+            let text = value.as_str().map(ToString::to_string);
+            match code {
+                1 => message.path = text,
+                3 => message.member = text,
+                4 => message.error_name = text,
+                5 => message.reply_serial = value.as_u32(),
+                8 => signature = text.unwrap_or_default(),
+                _ => {}
+            }
+        }
+        message.body = Reader {
+            data: &data[body_start..],
+            pos: 0,
+        }
+        .values(&signature)?;
+        Ok(message)
+    }
+
+    pub fn stream(&self) -> &UnixStream {
+        &self.stream
+    }
+}
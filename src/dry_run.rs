@@ -0,0 +1,262 @@
+//! Scenario validation without committing anything, `--dry-run`.
+//!
+//! The scenario is planned frame by frame on a simulated clock, as if on a 60Hz
+//! output presenting every frame, and its timeline is printed in steps of frames
+//! planned alike. The protocols the scenario needs are checked against the
+//! compositor, which is only asked for its globals, or against a capability file
+//! written by an earlier dry run, so nothing has to connect at all.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::wp_presentation_feedback::Kind;
+
+use crate::clock::{self, Clock, Simulated};
+use crate::planner::{Decision, Next, Planner, Surface};
+use crate::plugin::{Damage, Registry, Stats};
+use crate::presentation::Presented;
+use crate::{probe, session, Args};
+
+const REFRESH: Duration = Duration::from_nanos(16_666_667);
+/// Frames planned without `--frames`.
+const DEFAULT_FRAMES: u64 = 600;
+/// Steps printed in full, the rest is only counted.
+const MAX_STEPS: usize = 40;
+
+/// Frames planned alike, in a row.
+struct Step {
+    first: u64,
+    last: u64,
+    /// Simulated time of the first frame since the start.
+    start: Duration,
+    decision: Decision,
+    damage: Damage,
+    /// Annotations and pattern switches of the first frame.
+    notes: Vec<String>,
+}
+
+impl Step {
+    fn describe(&self) -> String {
+        let decision = &self.decision;
+        let mut parts = Vec::new();
+        if decision.barrier {
+            parts.push("barrier".to_string());
+        }
+        if decision.frame_callback {
+            parts.push("frame callback".to_string());
+        }
+        if parts.is_empty() {
+            parts.push("unthrottled".to_string());
+        }
+        match self.damage {
+            Damage::Full => {}
+            Damage::Inset(inset) => parts.push(format!("damage inset {}px", inset)),
+        }
+        let delay = match decision.next {
+            Next::Presented => Duration::ZERO,
+            Next::FrameCallback(delay) | Next::Timer(delay) => delay,
+        };
+        if !delay.is_zero() {
+            parts.push(format!("delay {:.3}ms", delay.as_secs_f64() * 1000.0));
+        }
+        if decision.present_at.is_some() {
+            parts.push("presentation time".to_string());
+        }
+        parts.join(", ")
+    }
+
+    /// Whether the next frame continues this step.
+    fn continues(&self, decision: &Decision, damage: Damage) -> bool {
+        // The targets of consecutive frames differ, only their presence counts.
+        let key = |decision: &Decision| Decision {
+            present_at: decision.present_at.map(|_| Duration::ZERO),
+            ..*decision
+        };
+        key(&self.decision) == key(decision) && self.damage == damage
+    }
+}
+
+/// Validates the scenario of `args` and prints its timeline, exits with 1 if it fails.
+pub fn run(args: &Args, registry: &Registry) {
+    let versions = match args.capabilities.as_ref().filter(|path| path.exists()) {
+        Some(path) => {
+            let versions = probe::load(path).unwrap_or_else(|err| {
+                eprintln!("dry run: failed to read {}: {}", path.display(), err);
+                std::process::exit(1)
+            });
+            println!("dry run: protocols from {}", path.display());
+            versions
+        }
+        None => {
+            let versions = probe::versions(&session::connect());
+            println!("dry run: protocols from the compositor, nothing is committed");
+            if let Some(path) = args.capabilities.as_ref() {
+                match probe::save(path, &versions) {
+                    Ok(()) => println!("dry run: capabilities written to {}", path.display()),
+                    Err(err) => {
+                        eprintln!("dry run: failed to write {}: {}", path.display(), err)
+                    }
+                }
+            }
+            versions
+        }
+    };
+    let version = |interface: &str| probe::version(&versions, interface);
+    let fifo = version("wp_fifo_manager_v1");
+    let presentation = version("wp_presentation").is_some();
+
+    let scenario = registry
+        .scenario(&args.scenario)
+        .expect("scenario was validated");
+    let missing = probe::degradations(scenario.as_ref(), &versions);
+    let fifo = fifo.is_some_and(|version| version >= scenario.min_fifo_version()) && !args.no_fifo;
+    let clock = Simulated::default();
+    // Scenarios derive presentation times from the real clock, so simulate from now.
+    clock.advance_to(clock::monotonic());
+    let start = clock.now();
+    let mut planner = Planner::new(args, scenario, None, Rc::new(clock.clone()));
+    let surface = Surface {
+        fifo,
+        present: true,
+        frame_callbacks: false,
+        draw_on_presented: false,
+        feedback: planner.wants_feedback() && presentation,
+    };
+
+    let frames = args.frames.unwrap_or(DEFAULT_FRAMES);
+    println!(
+        "dry run: scenario `{}`, up to {} frames on a simulated {:.0}Hz output",
+        args.scenario,
+        frames,
+        1.0 / REFRESH.as_secs_f64()
+    );
+    if missing.is_empty() {
+        println!("dry run: the compositor supports everything the scenario uses");
+    } else {
+        println!("dry run: degraded, {}", missing.join(", "));
+    }
+
+    let mut stats = Stats::default();
+    let mut steps: Vec<Step> = Vec::new();
+    let mut errors = Vec::new();
+    let mut ended = None;
+    for frame in 1..=frames {
+        let Some(plan) = planner.plan(frame, &stats, "dry run: ") else {
+            ended = Some(frame);
+            break;
+        };
+        let mut notes = planner
+            .scenario
+            .take_annotations()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        if let Some(pattern) = planner.scenario.switch_pattern() {
+            if !registry.has_pattern(&pattern) {
+                errors.push(format!(
+                    "frame {} switches to the unknown pattern `{}`",
+                    frame, pattern
+                ));
+            }
+            notes.push(format!("pattern {}", pattern));
+        }
+        let decision = Decision::new(&plan, &surface);
+        match steps.last_mut() {
+            Some(step) if notes.is_empty() && step.continues(&decision, plan.damage) => {
+                step.last = frame;
+            }
+            _ => steps.push(Step {
+                first: frame,
+                last: frame,
+                start: clock.since(start),
+                decision,
+                damage: plan.damage,
+                notes,
+            }),
+        }
+        simulate(&mut planner, &clock, frame, &decision, &mut stats);
+    }
+
+    println!("dry run: timeline");
+    for step in steps.iter().take(MAX_STEPS) {
+        let frames = if step.first == step.last {
+            format!("frame {}", step.first)
+        } else {
+            format!("frames {}-{}", step.first, step.last)
+        };
+        println!(
+            "  {:>11}  {:<16} {}",
+            format!("+{:.3}s", step.start.as_secs_f64()),
+            frames,
+            step.describe()
+        );
+        for note in &step.notes {
+            println!("  {:>11}  {:<16} {}", "", "", note);
+        }
+    }
+    if steps.len() > MAX_STEPS {
+        println!("  ... {} more steps", steps.len() - MAX_STEPS);
+    }
+    let duration = clock.since(start).as_secs_f64();
+    match ended {
+        Some(frame) => match planner.scenario.failure() {
+            Some(failure) => errors.push(format!(
+                "the scenario failed at frame {}: {}",
+                frame, failure
+            )),
+            None => println!(
+                "dry run: the scenario ends after {} frames, {:.3}s",
+                frame - 1,
+                duration
+            ),
+        },
+        None => println!(
+            "dry run: the scenario still runs after {} frames, {:.3}s",
+            frames, duration
+        ),
+    }
+    for error in &errors {
+        println!("dry run: error: {}", error);
+    }
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Advances the clock to the next draw and feeds the planner the presentation of the
+/// frame one refresh after its commit.
+fn simulate(
+    planner: &mut Planner,
+    clock: &Simulated,
+    frame: u64,
+    decision: &Decision,
+    stats: &mut Stats,
+) {
+    let committed = clock.now();
+    stats.frames += 1;
+    planner.committed(frame);
+    if decision.feedback {
+        let presented = Presented {
+            time: committed + REFRESH,
+            committed,
+            refresh: REFRESH,
+            seq: stats.frames,
+            flags: Kind::Vsync,
+            output: None,
+        };
+        planner.feedback(frame, Some(&presented), "dry run: ");
+        stats.presented += 1;
+        stats.latency = Some(REFRESH);
+    }
+    let wait = match decision.next {
+        Next::Presented => REFRESH,
+        Next::FrameCallback(delay) => REFRESH + delay,
+        // The barrier holds the next commit until this one is latched.
+        Next::Timer(delay) if decision.barrier => delay.max(REFRESH),
+        Next::Timer(delay) => delay,
+    };
+    // Every draw takes some time, so unthrottled scenarios still move forward.
+    let wait = wait.max(Duration::from_micros(100));
+    clock.advance_to(committed + wait);
+    stats.interval = Some(wait);
+}
@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+
+const POWERCAP: &str = "/sys/class/powercap";
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+enum Counter {
+    /// RAPL package domain, energy_uj in µJ wrapping at max_energy_range_uj.
+    Rapl { range: u64 },
+    /// Battery energy_now in µWh, decreasing while discharging.
+    Battery,
+}
+
+struct Source {
+    path: PathBuf,
+    counter: Counter,
+    start: u64,
+}
+
+/// Energy used by the whole system during a run, from RAPL or the battery, `--energy`.
+pub struct Meter {
+    name: &'static str,
+    sources: Vec<Source>,
+}
+
+fn read(path: &Path) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// The top level RAPL domains, i.e. the packages.
+fn rapl() -> Vec<Source> {
+    let mut sources = std::fs::read_dir(POWERCAP)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with("intel-rapl:") && name.matches(':').count() == 1
+                })
+        })
+        .filter_map(|dir| {
+            let path = dir.join("energy_uj");
+            Some(Source {
+                start: read(&path)?,
+                counter: Counter::Rapl {
+                    range: read(&dir.join("max_energy_range_uj"))?,
+                },
+                path,
+            })
+        })
+        .collect::<Vec<_>>();
+    sources.sort_by(|a, b| a.path.cmp(&b.path));
+    sources
+}
+
+/// Batteries that are discharging, while charging the counter says nothing about the
+/// load.
+fn batteries() -> Vec<Source> {
+    std::fs::read_dir(POWER_SUPPLY)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|dir| {
+            std::fs::read_to_string(dir.join("status"))
+                .is_ok_and(|status| status.trim() == "Discharging")
+        })
+        .filter_map(|dir| {
+            let path = dir.join("energy_now");
+            Some(Source {
+                start: read(&path)?,
+                counter: Counter::Battery,
+                path,
+            })
+        })
+        .collect()
+}
+
+impl Meter {
+    /// Starts measuring, `None` without a readable counter.
+    pub fn start() -> Option<Self> {
+        let rapl = rapl();
+        if !rapl.is_empty() {
+            return Some(Self {
+                name: "rapl",
+                sources: rapl,
+            });
+        }
+        if Path::new(POWERCAP).join("intel-rapl:0").exists() {
+            eprintln!("energy: the RAPL counters are only readable by root, trying the battery");
+        }
+        let batteries = batteries();
+        if !batteries.is_empty() {
+            return Some(Self {
+                name: "battery",
+                sources: batteries,
+            });
+        }
+        eprintln!("energy: neither readable RAPL counters nor a discharging battery found");
+        None
+    }
+
+    /// Energy in joules since the start.
+    pub fn finish(&self) -> Energy {
+        let joules = self
+            .sources
+            .iter()
+            .filter_map(|source| {
+                let now = read(&source.path)?;
+                Some(match source.counter {
+                    Counter::Rapl { range } => {
+                        let used = if now >= source.start {
+                            now - source.start
+                        } else {
+                            range - source.start + now
+                        };
+                        used as f64 / 1_000_000.0
+                    }
+                    Counter::Battery => {
+                        source.start.saturating_sub(now) as f64 * 3600.0 / 1_000_000.0
+                    }
+                })
+            })
+            .sum();
+        Energy {
+            joules,
+            source: self.name,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Energy {
+    pub joules: f64,
+    pub source: &'static str,
+}
+
+impl Energy {
+    /// Millijoules per frame.
+    pub fn per_frame(&self, frames: u64) -> Option<f64> {
+        (frames > 0).then(|| self.joules * 1000.0 / frames as f64)
+    }
+}
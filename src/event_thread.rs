@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::reexports::client::{
+    delegate_noop, protocol::wl_callback, Connection, Dispatch, QueueHandle,
+};
+use smithay_client_toolkit::reexports::protocols::wp::fifo::v1::client::{
+    wp_fifo_manager_v1, wp_fifo_v1,
+};
+
+use crate::clock;
+
+/// A second event queue, dispatched on its own thread.
+///
+/// The fifo objects and the frame callbacks of the test surface are created on this
+/// queue, like a client with a dedicated render thread would do, while the xdg-shell
+/// and buffer handling stays on the main queue.
+pub struct EventThread {
+    qh: QueueHandle<ThreadState>,
+}
+
+/// Frame number and commit time of a frame callback.
+pub struct FrameData {
+    frame: u64,
+    committed: Instant,
+}
+
+pub struct ThreadState {
+    /// Log every nth frame callback, zero disables logging.
+    log_every: u64,
+    /// Run start the log timestamps are relative to.
+    start: Duration,
+}
+
+impl EventThread {
+    pub fn spawn(conn: &Connection, log_every: u64, start: Duration) -> Self {
+        let mut event_queue = conn.new_event_queue();
+        let qh = event_queue.handle();
+
+        std::thread::Builder::new()
+            .name("event-thread".into())
+            .spawn(move || {
+                let mut state = ThreadState { log_every, start };
+                while event_queue.blocking_dispatch(&mut state).is_ok() {}
+            })
+            .expect("failed to spawn event thread");
+
+        Self { qh }
+    }
+
+    pub fn handle(&self) -> &QueueHandle<ThreadState> {
+        &self.qh
+    }
+}
+
+impl FrameData {
+    pub fn new(frame: u64) -> Self {
+        Self {
+            frame,
+            committed: Instant::now(),
+        }
+    }
+}
+
+impl Dispatch<wl_callback::WlCallback, FrameData> for ThreadState {
+    fn event(
+        state: &mut Self,
+        _proxy: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        data: &FrameData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            if state.log_every == 0 || !data.frame.is_multiple_of(state.log_every) {
+                return;
+            }
+            println!(
+                "{} Frame {} done on event thread, after: {:?}",
+                clock::stamp(state.start),
+                data.frame,
+                data.committed.elapsed()
+            );
+        }
+    }
+}
+
+delegate_noop!(ThreadState: ignore wp_fifo_manager_v1::WpFifoManagerV1);
+delegate_noop!(ThreadState: ignore wp_fifo_v1::WpFifoV1);
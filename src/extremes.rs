@@ -0,0 +1,240 @@
+use smithay_client_toolkit::reexports::client::backend::protocol::Argument;
+use smithay_client_toolkit::reexports::client::backend::{self, ObjectId};
+use smithay_client_toolkit::reexports::client::globals::{registry_queue_init, GlobalListContents};
+use smithay_client_toolkit::reexports::client::protocol::{wl_compositor, wl_registry, wl_surface};
+use smithay_client_toolkit::reexports::client::{
+    delegate_noop, Connection, Dispatch, Proxy, QueueHandle,
+};
+use smithay_client_toolkit::reexports::protocols::wp::viewporter::client::{
+    wp_viewport, wp_viewporter,
+};
+
+use crate::session;
+
+/// wl_surface.set_buffer_transform, sent raw as the typed request can't carry
+/// values outside the enum.
+const SET_BUFFER_TRANSFORM: u16 = 7;
+
+/// A protocol error, interface and code.
+type Error = (&'static str, u32);
+
+const INVALID_SCALE: Error = ("wl_surface", 0);
+const INVALID_TRANSFORM: Error = ("wl_surface", 1);
+const BAD_VALUE: Error = ("wp_viewport", 0);
+
+struct Case {
+    name: &'static str,
+    /// The protocol error the compositor must raise, `None` if the request is valid.
+    expected: Option<Error>,
+    /// Lowest wl_surface version defining the request or error.
+    surface_version: u32,
+    /// Sent on a fresh surface right before its commit.
+    request: Request,
+}
+
+enum Request {
+    Surface(fn(&wl_surface::WlSurface)),
+    Raw(u16, i32),
+    Viewport(fn(&wp_viewport::WpViewport)),
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "damage-max-offset",
+        expected: None,
+        surface_version: 1,
+        request: Request::Surface(|surface| surface.damage(i32::MAX, i32::MAX, 1, 1)),
+    },
+    Case {
+        name: "damage-overflowing-size",
+        expected: None,
+        surface_version: 1,
+        request: Request::Surface(|surface| surface.damage(1, 1, i32::MAX, i32::MAX)),
+    },
+    Case {
+        name: "damage-negative-size",
+        expected: None,
+        surface_version: 1,
+        request: Request::Surface(|surface| surface.damage(0, 0, -10, i32::MIN)),
+    },
+    Case {
+        name: "damage-buffer-min-offset",
+        expected: None,
+        surface_version: 4,
+        request: Request::Surface(|surface| {
+            surface.damage_buffer(i32::MIN, i32::MIN, i32::MAX, i32::MAX)
+        }),
+    },
+    Case {
+        name: "scale-zero",
+        expected: Some(INVALID_SCALE),
+        surface_version: 3,
+        request: Request::Surface(|surface| surface.set_buffer_scale(0)),
+    },
+    Case {
+        name: "scale-min",
+        expected: Some(INVALID_SCALE),
+        surface_version: 3,
+        request: Request::Surface(|surface| surface.set_buffer_scale(i32::MIN)),
+    },
+    Case {
+        name: "transform-out-of-range",
+        expected: Some(INVALID_TRANSFORM),
+        surface_version: 2,
+        request: Request::Raw(SET_BUFFER_TRANSFORM, 8),
+    },
+    Case {
+        name: "transform-negative",
+        expected: Some(INVALID_TRANSFORM),
+        surface_version: 2,
+        request: Request::Raw(SET_BUFFER_TRANSFORM, i32::MIN),
+    },
+    Case {
+        name: "viewport-destination-max",
+        expected: None,
+        surface_version: 1,
+        request: Request::Viewport(|viewport| viewport.set_destination(i32::MAX, i32::MAX)),
+    },
+    Case {
+        name: "viewport-destination-negative",
+        expected: Some(BAD_VALUE),
+        surface_version: 1,
+        request: Request::Viewport(|viewport| viewport.set_destination(-2, i32::MIN)),
+    },
+    Case {
+        name: "viewport-source-negative",
+        expected: Some(BAD_VALUE),
+        surface_version: 1,
+        request: Request::Viewport(|viewport| viewport.set_source(-2.0, 0.0, 16.0, 16.0)),
+    },
+    Case {
+        name: "viewport-source-empty",
+        expected: Some(BAD_VALUE),
+        surface_version: 1,
+        request: Request::Viewport(|viewport| viewport.set_source(0.0, 0.0, 0.0, 0.0)),
+    },
+];
+
+enum Verdict {
+    Pass,
+    Fail,
+    Skip(&'static str),
+}
+
+struct Extremes;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Extremes {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(Extremes: wl_compositor::WlCompositor);
+delegate_noop!(Extremes: ignore wl_surface::WlSurface);
+delegate_noop!(Extremes: wp_viewporter::WpViewporter);
+delegate_noop!(Extremes: wp_viewport::WpViewport);
+
+fn describe(error: Option<Error>) -> String {
+    error.map_or("no error".to_string(), |(interface, code)| {
+        format!("{} error {}", interface, code)
+    })
+}
+
+/// Runs `case` on its own connection, a protocol error ends the connection.
+fn run_case(case: &Case) -> (Verdict, String) {
+    let conn = session::connect();
+    let (globals, mut queue) = registry_queue_init::<Extremes>(&conn).unwrap();
+    let qh = queue.handle();
+    let compositor: wl_compositor::WlCompositor = globals
+        .bind(&qh, 1..=6, ())
+        .expect("wl_compositor not available");
+    if compositor.version() < case.surface_version {
+        return (Verdict::Skip("wl_compositor too old"), String::new());
+    }
+    let surface = compositor.create_surface(&qh, ());
+
+    match case.request {
+        Request::Surface(request) => request(&surface),
+        Request::Raw(opcode, value) => {
+            let message = backend::protocol::Message::<ObjectId, std::os::fd::RawFd> {
+                sender_id: surface.id(),
+                opcode,
+                args: [Argument::Int(value)].into_iter().collect(),
+            };
+            let _ = conn.backend().send_request(message, None, None);
+        }
+        Request::Viewport(request) => {
+            let Ok(viewporter) = globals.bind::<wp_viewporter::WpViewporter, _, _>(&qh, 1..=1, ())
+            else {
+                return (Verdict::Skip("no wp_viewporter"), String::new());
+            };
+            request(&viewporter.get_viewport(&surface, &qh, ()));
+        }
+    }
+    surface.commit();
+
+    let (got, message) = match queue.roundtrip(&mut Extremes) {
+        Ok(_) => (None, String::new()),
+        Err(_) => match conn.protocol_error() {
+            Some(error) => (
+                Some((error.object_interface, error.code)),
+                format!(": {}", error.message),
+            ),
+            None => return (Verdict::Fail, "connection lost".to_string()),
+        },
+    };
+    let verdict = match (case.expected, got.as_ref()) {
+        (None, None) => Verdict::Pass,
+        (Some((interface, code)), Some((got_interface, got_code)))
+            if interface == got_interface && code == *got_code =>
+        {
+            Verdict::Pass
+        }
+        _ => Verdict::Fail,
+    };
+    let got = got.map_or("no error".to_string(), |(interface, code)| {
+        format!("{} error {}", interface, code)
+    });
+    (verdict, format!("{}{}", got, message))
+}
+
+/// Sends requests with extreme values, each on its own connection, and checks the
+/// compositor rejects the invalid ones with the right protocol error and accepts the
+/// others, the `extremes` subcommand.
+pub fn run() {
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for case in CASES {
+        let (verdict, got) = run_case(case);
+        let verdict = match verdict {
+            Verdict::Pass => {
+                passed += 1;
+                "ok".to_string()
+            }
+            Verdict::Fail => {
+                failed += 1;
+                "FAILED".to_string()
+            }
+            Verdict::Skip(reason) => {
+                skipped += 1;
+                format!("skipped, {}", reason)
+            }
+        };
+        println!(
+            "extremes: {:<30} expected {:<20} got {:<40} {}",
+            case.name,
+            describe(case.expected),
+            got,
+            verdict
+        );
+    }
+    println!(
+        "extremes: {} passed, {} failed, {} skipped",
+        passed, failed, skipped
+    );
+}
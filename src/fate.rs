@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What happened to a committed frame, according to its presentation feedback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fate {
+    Presented,
+    /// Discarded while a newer frame was already committed, so it was replaced in the
+    /// compositor's queue.
+    Replaced,
+    /// Discarded without a newer frame, i.e. because the surface was unmapped.
+    DiscardedOnUnmap,
+    /// No feedback received before the report.
+    Pending,
+}
+
+impl fmt::Display for Fate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Fate::Presented => "presented",
+            Fate::Replaced => "replaced",
+            Fate::DiscardedOnUnmap => "discarded-on-unmap",
+            Fate::Pending => "pending",
+        })
+    }
+}
+
+struct Committed {
+    barrier: bool,
+    fate: Fate,
+}
+
+/// Per-frame fate classification, `--fate`.
+#[derive(Default)]
+pub struct Fates {
+    frames: BTreeMap<u64, Committed>,
+    last_committed: u64,
+    unmapped: bool,
+}
+
+impl Fates {
+    pub fn committed(&mut self, frame: u64, barrier: bool) {
+        self.last_committed = frame;
+        self.frames.insert(
+            frame,
+            Committed {
+                barrier,
+                fate: Fate::Pending,
+            },
+        );
+    }
+
+    /// Marks the surface as unmapped, frames discarded from now on weren't replaced.
+    pub fn unmapped(&mut self) {
+        self.unmapped = true;
+    }
+
+    /// Records the feedback of `frame`, `presented` is false if it was discarded.
+    pub fn feedback(&mut self, frame: u64, presented: bool) {
+        let replaced = frame < self.last_committed && !self.unmapped;
+        let Some(committed) = self.frames.get_mut(&frame) else {
+            return;
+        };
+        committed.fate = if presented {
+            Fate::Presented
+        } else if replaced {
+            Fate::Replaced
+        } else {
+            Fate::DiscardedOnUnmap
+        };
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        println!("{}frame fates:", prefix);
+        for fate in [
+            Fate::Presented,
+            Fate::Replaced,
+            Fate::DiscardedOnUnmap,
+            Fate::Pending,
+        ] {
+            let frames = self
+                .frames
+                .iter()
+                .filter(|(_, committed)| committed.fate == fate)
+                .collect::<Vec<_>>();
+            if frames.is_empty() {
+                continue;
+            }
+            let with_barrier = frames
+                .iter()
+                .filter(|(_, committed)| committed.barrier)
+                .count();
+            println!(
+                "{}  {}: {} ({} with a barrier)",
+                prefix,
+                fate,
+                frames.len(),
+                with_barrier
+            );
+            if fate != Fate::Presented {
+                let list = frames
+                    .iter()
+                    .map(|(frame, committed)| {
+                        if committed.barrier {
+                            format!("{}*", frame)
+                        } else {
+                            frame.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}    frames (* = barrier): {}", prefix, list.join(" "));
+            }
+        }
+    }
+}
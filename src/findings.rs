@@ -0,0 +1,230 @@
+//! Presentation anomalies grouped by signature, `--findings`.
+//!
+//! A long run can show thousands of misses and duplicates that mostly share a few
+//! causes. Anomalies are detected like `--break-on` does and grouped by their type
+//! and, for misses, by the refreshes missed. Simple heuristics then describe each
+//! group: anomalies recurring at a fixed period, mostly following a wait for a free
+//! buffer, or coming in bursts. The report leads with the largest groups.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use crate::breakon::{Anomaly, Breaker};
+use crate::observer::{Committed, FrameObserver, Log};
+use crate::presentation::Presented;
+
+/// Findings listed in full, the rest is only counted.
+const MAX_FINDINGS: usize = 5;
+/// Intervals between anomalies needed to call a group periodic.
+const MIN_INTERVALS: usize = 3;
+/// Relative deviation from the median interval that still counts as the period.
+const PERIOD_TOLERANCE: f64 = 0.1;
+/// Share of the intervals that has to match the period.
+const PERIODIC_SHARE: f64 = 0.8;
+/// A buffer stall this many frames before an anomaly counts as preceding it.
+const STALL_FRAMES: u64 = 2;
+/// Share of anomalies following a stall that makes a group correlated with stalls,
+/// if also twice the share of all frames.
+const STALL_SHARE: f64 = 0.5;
+/// Anomalies closer than this are in the same burst.
+const BURST_GAP: Duration = Duration::from_millis(250);
+
+/// Misses of this many refreshes and more are grouped together.
+const MISSED_BUCKETS: u64 = 3;
+
+struct Event {
+    frame: u64,
+    /// Presentation time in `CLOCK_MONOTONIC`.
+    time: Duration,
+}
+
+/// Anomalies of one type, and for misses of as many refreshes missed.
+struct Group {
+    anomaly: Anomaly,
+    missed: u64,
+    events: Vec<Event>,
+}
+
+impl Group {
+    fn signature(&self) -> String {
+        match (self.anomaly, self.missed) {
+            (Anomaly::Miss, 1) => "missed 1 refresh".to_string(),
+            (Anomaly::Miss, missed) if missed >= MISSED_BUCKETS => {
+                format!("missed {} or more refreshes", missed)
+            }
+            (Anomaly::Miss, missed) => format!("missed {} refreshes", missed),
+            (anomaly, _) => anomaly.name().to_string(),
+        }
+    }
+}
+
+pub struct Findings {
+    breaker: Breaker,
+    groups: BTreeMap<(Anomaly, u64), Group>,
+    committed: u64,
+    /// Frames drawn after waiting for a free buffer.
+    stalled: BTreeSet<u64>,
+}
+
+impl Default for Findings {
+    fn default() -> Self {
+        Self {
+            breaker: Breaker::new(Vec::new()),
+            groups: BTreeMap::new(),
+            committed: 0,
+            stalled: BTreeSet::new(),
+        }
+    }
+}
+
+impl FrameObserver for Findings {
+    fn committed(&mut self, committed: &Committed) {
+        self.committed += 1;
+        if committed.waited_for_buffer {
+            self.stalled.insert(committed.frame);
+        }
+    }
+
+    fn presented(&mut self, frame: u64, presented: Option<&Presented>, _: &str, _: &Log) {
+        let Some(presented) = presented else {
+            return;
+        };
+        let Some(detected) = self.breaker.detect(frame, presented) else {
+            return;
+        };
+        let missed = detected.missed.min(MISSED_BUCKETS);
+        self.groups
+            .entry((detected.anomaly, missed))
+            .or_insert_with(|| Group {
+                anomaly: detected.anomaly,
+                missed,
+                events: Vec::new(),
+            })
+            .events
+            .push(Event {
+                frame,
+                time: presented.time,
+            });
+    }
+
+    fn print_report(&self, prefix: &str) {
+        let mut groups = self.groups.values().collect::<Vec<_>>();
+        if groups.is_empty() {
+            println!("{}findings: no presentation anomalies", prefix);
+            return;
+        }
+        groups.sort_by_key(|group| std::cmp::Reverse(group.events.len()));
+        let total = groups.iter().map(|group| group.events.len()).sum::<usize>();
+        println!(
+            "{}findings: {} anomalies in {} groups",
+            prefix,
+            total,
+            groups.len()
+        );
+        for (index, group) in groups.iter().take(MAX_FINDINGS).enumerate() {
+            let events = &group.events;
+            println!(
+                "{}  {}. {}, {} times, {}, frames {}-{}",
+                prefix,
+                index + 1,
+                group.signature(),
+                events.len(),
+                self.describe(events).join(", "),
+                events[0].frame,
+                events[events.len() - 1].frame
+            );
+        }
+        if groups.len() > MAX_FINDINGS {
+            let rest = groups[MAX_FINDINGS..]
+                .iter()
+                .map(|group| group.events.len())
+                .sum::<usize>();
+            println!(
+                "{}  ... {} more groups with {} anomalies",
+                prefix,
+                groups.len() - MAX_FINDINGS,
+                rest
+            );
+        }
+    }
+}
+
+impl Findings {
+    /// Describes a group of anomalies with whatever the heuristics find.
+    fn describe(&self, events: &[Event]) -> Vec<String> {
+        let mut traits = Vec::new();
+
+        let mut intervals = events
+            .windows(2)
+            .map(|pair| pair[1].time.saturating_sub(pair[0].time))
+            .collect::<Vec<_>>();
+        intervals.sort();
+        if let Some(period) = intervals
+            .get(intervals.len() / 2)
+            .filter(|period| intervals.len() >= MIN_INTERVALS && **period > BURST_GAP)
+        {
+            let matching = intervals
+                .iter()
+                .filter(|interval| {
+                    (interval.as_secs_f64() / period.as_secs_f64() - 1.0).abs() < PERIOD_TOLERANCE
+                })
+                .count();
+            if matching as f64 >= intervals.len() as f64 * PERIODIC_SHARE {
+                traits.push(format!(
+                    "periodic every {:.2}s ({} of {} intervals)",
+                    period.as_secs_f64(),
+                    matching,
+                    intervals.len()
+                ));
+            }
+        }
+
+        let stalled = events
+            .iter()
+            .filter(|event| {
+                self.stalled
+                    .range(event.frame.saturating_sub(STALL_FRAMES)..=event.frame)
+                    .next()
+                    .is_some()
+            })
+            .count();
+        let share = stalled as f64 / events.len() as f64;
+        let base = (self.stalled.len() as f64 * (STALL_FRAMES + 1) as f64
+            / self.committed.max(1) as f64)
+            .min(1.0);
+        if share >= STALL_SHARE && share > base * 2.0 {
+            traits.push(format!(
+                "correlated with buffer stalls ({:.0}% follow one, against {:.0}% of all frames)",
+                share * 100.0,
+                base * 100.0
+            ));
+        }
+
+        if traits.is_empty() {
+            let mut bursts = Vec::new();
+            let mut last: Option<Duration> = None;
+            for event in events {
+                match bursts.last_mut() {
+                    Some(size)
+                        if last
+                            .is_some_and(|last| event.time.saturating_sub(last) <= BURST_GAP) =>
+                    {
+                        *size += 1
+                    }
+                    _ => bursts.push(1),
+                }
+                last = Some(event.time);
+            }
+            if bursts.len() * 3 <= events.len() {
+                traits.push(format!(
+                    "in {} bursts of up to {}",
+                    bursts.len(),
+                    bursts.iter().max().unwrap_or(&0)
+                ));
+            } else {
+                traits.push("scattered".to_string());
+            }
+        }
+        traits
+    }
+}
@@ -0,0 +1,160 @@
+//! The kind flags of the presentation feedback.
+//!
+//! Every presented frame carries how it was presented: synced to the vertical
+//! retrace, timestamped by the hardware clock, completion signalled by the hardware,
+//! and scanned out without a copy. A compositor switching between direct scanout and
+//! composition, or falling back from the hardware clock, changes the flags, and
+//! pacing shifts mid-run often line up with such a change. The flags of every frame
+//! are counted, changes are logged and annotated, and the report lists the runs of
+//! frames presented alike with their latency.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::wp_presentation_feedback::Kind;
+
+use crate::plugin::Annotation;
+use crate::presentation::Presented;
+
+/// Changes logged while running, the rest is only counted.
+const MAX_LOGGED: u64 = 20;
+/// Runs listed in the report, the rest is only counted.
+const MAX_RUNS: usize = 20;
+
+const NAMES: [(Kind, &str); 4] = [
+    (Kind::Vsync, "vsync"),
+    (Kind::HwClock, "hw_clock"),
+    (Kind::HwCompletion, "hw_completion"),
+    (Kind::ZeroCopy, "zero_copy"),
+];
+
+/// The set flags of `kind`, comma separated.
+pub fn describe(kind: Kind) -> String {
+    let names = NAMES
+        .iter()
+        .filter(|(flag, _)| kind.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        "no flags".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Frames in a row presented with the same flags.
+struct Run {
+    kind: Kind,
+    first: u64,
+    last: u64,
+    frames: u32,
+    latency: Duration,
+}
+
+#[derive(Default)]
+pub struct Flags {
+    presented: u64,
+    /// Frames presented with each flag, in the order of [`NAMES`].
+    counts: [u64; 4],
+    /// Frames presented with each combination of flags, by its bits.
+    combinations: BTreeMap<u32, u64>,
+    runs: Vec<Run>,
+    annotation: Option<Annotation>,
+}
+
+impl Flags {
+    pub fn presented(&mut self, frame: u64, presented: &Presented, log: bool, prefix: &str) {
+        let kind = presented.flags;
+        let latency = presented.time.saturating_sub(presented.committed);
+        self.presented += 1;
+        for (count, (flag, _)) in self.counts.iter_mut().zip(NAMES) {
+            if kind.contains(flag) {
+                *count += 1;
+            }
+        }
+        *self.combinations.entry(kind.bits()).or_default() += 1;
+
+        match self.runs.last_mut() {
+            Some(run) if run.kind == kind => {
+                run.last = frame;
+                run.frames += 1;
+                run.latency += latency;
+                return;
+            }
+            Some(run) => {
+                let label = format!(
+                    "presentation flags changed from {} to {}",
+                    describe(run.kind),
+                    describe(kind)
+                );
+                let changes = self.runs.len() as u64;
+                if log && changes <= MAX_LOGGED {
+                    println!("{}Frame {} {}", prefix, frame, label);
+                    if changes == MAX_LOGGED {
+                        println!("{}further flag changes are only counted", prefix);
+                    }
+                }
+                self.annotation = Some(Annotation::Label(label));
+            }
+            None => {}
+        }
+        self.runs.push(Run {
+            kind,
+            first: frame,
+            last: frame,
+            frames: 1,
+            latency,
+        });
+    }
+
+    /// A change of the flags to log with the next frame.
+    pub fn take_annotation(&mut self) -> Option<Annotation> {
+        self.annotation.take()
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if self.presented == 0 {
+            return;
+        }
+        let percent = |count: u64| count as f64 * 100.0 / self.presented as f64;
+        let counts = NAMES
+            .iter()
+            .zip(self.counts)
+            .map(|((_, name), count)| format!("{} {} ({:.1}%)", name, count, percent(count)))
+            .collect::<Vec<_>>();
+        println!(
+            "{}presentation flags: {} frames presented, {}, {} changes",
+            prefix,
+            self.presented,
+            counts.join(", "),
+            self.runs.len() - 1
+        );
+        if self.combinations.len() > 1 {
+            for (bits, count) in &self.combinations {
+                println!(
+                    "{}  {:<40} {} frames ({:.1}%)",
+                    prefix,
+                    describe(Kind::from_bits_truncate(*bits)),
+                    count,
+                    percent(*count)
+                );
+            }
+        }
+        if self.runs.len() < 2 {
+            return;
+        }
+        for run in self.runs.iter().take(MAX_RUNS) {
+            println!(
+                "{}  frames {}-{}: {}, mean commit to present {:.3}ms",
+                prefix,
+                run.first,
+                run.last,
+                describe(run.kind),
+                (run.latency / run.frames).as_secs_f64() * 1000.0
+            );
+        }
+        if self.runs.len() > MAX_RUNS {
+            println!("{}  ... {} more runs", prefix, self.runs.len() - MAX_RUNS);
+        }
+    }
+}
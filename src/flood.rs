@@ -0,0 +1,259 @@
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::reexports::client::globals::{registry_queue_init, GlobalListContents};
+use smithay_client_toolkit::reexports::client::protocol::{wl_compositor, wl_registry, wl_surface};
+use smithay_client_toolkit::reexports::client::{
+    delegate_noop, Connection, Dispatch, EventQueue, QueueHandle,
+};
+use smithay_client_toolkit::reexports::protocols::wp::fifo::v1::client::{
+    wp_fifo_manager_v1, wp_fifo_v1,
+};
+
+use crate::commit_thread::Rect;
+use crate::{breakon, session};
+
+/// Options of the `barrier-flood` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+pub struct BarrierFlood {
+    /// set_barrier/wait_barrier pairs to send without a commit
+    #[arg(long, default_value_t = 50_000)]
+    requests: u32,
+
+    /// Pairs sent between two roundtrips timing the compositor
+    #[arg(long, default_value_t = 5_000)]
+    batch: u32,
+}
+
+/// Cost of a per-request roundtrip above which the compositor counts as slowing down,
+/// relative to the first batch.
+const GROWTH_LIMIT: f64 = 2.0;
+const IDLE_ROUNDTRIPS: u32 = 10;
+
+struct Flood;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Flood {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(Flood: wl_compositor::WlCompositor);
+delegate_noop!(Flood: ignore wl_surface::WlSurface);
+delegate_noop!(Flood: wp_fifo_manager_v1::WpFifoManagerV1);
+delegate_noop!(Flood: wp_fifo_v1::WpFifoV1);
+
+/// Mean time of a roundtrip without pending requests.
+fn idle_roundtrip(queue: &mut EventQueue<Flood>) -> Result<Duration, String> {
+    let start = Instant::now();
+    for _ in 0..IDLE_ROUNDTRIPS {
+        queue.roundtrip(&mut Flood).map_err(|err| err.to_string())?;
+    }
+    Ok(start.elapsed() / IDLE_ROUNDTRIPS)
+}
+
+/// Resident memory of `pid` in KiB, only readable for compositors of the same user.
+fn rss(pid: Option<u32>) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid?)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Sends barrier requests without ever committing them and reports how the
+/// compositor's roundtrip time and memory react, the `barrier-flood` subcommand.
+///
+/// Both requests only update pending state, so a compositor that queues them instead
+/// slows down or grows with every batch.
+pub fn run(flood: &BarrierFlood) {
+    let conn = session::connect();
+    let (globals, mut queue) = registry_queue_init::<Flood>(&conn).unwrap();
+    let qh = queue.handle();
+    let compositor: wl_compositor::WlCompositor = globals
+        .bind(&qh, 1..=6, ())
+        .expect("wl_compositor not available");
+    let Ok(manager) = globals.bind::<wp_fifo_manager_v1::WpFifoManagerV1, _, _>(&qh, 1..=1, ())
+    else {
+        eprintln!("barrier-flood: wp_fifo_manager_v1 is unavailable");
+        return;
+    };
+    let surface = compositor.create_surface(&qh, ());
+    let fifo = manager.get_fifo(&surface, &qh, ());
+
+    let pid = breakon::compositor_pid(&conn);
+    let rss_before = rss(pid);
+    let idle_before = idle_roundtrip(&mut queue).expect("initial roundtrip failed");
+    println!(
+        "barrier-flood: idle roundtrip {:?}, {} pairs in batches of {}",
+        idle_before, flood.requests, flood.batch
+    );
+
+    let batch = flood.batch.max(1);
+    let mut costs = Vec::new();
+    let mut sent = 0;
+    while sent < flood.requests {
+        let count = batch.min(flood.requests - sent);
+        let start = Instant::now();
+        for _ in 0..count {
+            fifo.set_barrier();
+            fifo.wait_barrier();
+        }
+        if let Err(err) = queue.roundtrip(&mut Flood) {
+            println!(
+                "barrier-flood: the compositor dropped the connection after {} pairs: {}",
+                sent + count,
+                err
+            );
+            return;
+        }
+        sent += count;
+        let cost = start.elapsed() / count;
+        println!(
+            "barrier-flood: {:>7} pairs  {:>8.0} ns per pair  rss {}",
+            sent,
+            cost.as_nanos(),
+            rss(pid).map_or("-".to_string(), |rss| format!("{} KiB", rss))
+        );
+        costs.push(cost);
+    }
+
+    surface.commit();
+    match queue
+        .roundtrip(&mut Flood)
+        .map_err(|err| err.to_string())
+        .and_then(|_| idle_roundtrip(&mut queue))
+    {
+        Ok(idle_after) => {
+            println!(
+                "barrier-flood: commit accepted, idle roundtrip {:?}",
+                idle_after
+            )
+        }
+        Err(err) => {
+            println!(
+                "barrier-flood: the compositor dropped the connection on commit: {}",
+                err
+            );
+            return;
+        }
+    }
+
+    let (Some(first), Some(last)) = (costs.first(), costs.last()) else {
+        return;
+    };
+    let growth = last.as_secs_f64() / first.as_secs_f64().max(f64::EPSILON);
+    println!(
+        "barrier-flood: per-pair cost changed {:.2}x from the first to the last batch",
+        growth
+    );
+    if let (Some(before), Some(after)) = (rss_before, rss(pid)) {
+        println!(
+            "barrier-flood: compositor rss {} KiB -> {} KiB ({:+} KiB)",
+            before,
+            after,
+            after as i64 - before as i64
+        );
+    }
+    if growth > GROWTH_LIMIT {
+        println!("barrier-flood: verdict: requests get slower, pending barriers may be queued");
+    } else {
+        println!("barrier-flood: verdict: constant cost, pending barriers are not queued");
+    }
+}
+
+/// Frames in a row with and without the damage flood, alternating so both halves see
+/// the same conditions.
+const DAMAGE_BLOCK: u64 = 60;
+
+/// Many tiny damage rectangles per commit like a terminal emulator redrawing single
+/// cells, `--damage-flood`.
+pub struct DamageFlood {
+    count: u32,
+    flooded: Vec<Duration>,
+    plain: Vec<Duration>,
+}
+
+impl DamageFlood {
+    pub fn new(count: u32) -> Self {
+        Self {
+            count,
+            flooded: Vec::new(),
+            plain: Vec::new(),
+        }
+    }
+
+    /// Whether `frame` carries the flood, every second block starting with the second.
+    pub fn floods(&self, frame: u64) -> bool {
+        (frame.saturating_sub(1) / DAMAGE_BLOCK) % 2 == 1
+    }
+
+    /// The rectangles, spread on a grid over the surface.
+    pub fn rects(&self, width: u32, height: u32) -> Vec<Rect> {
+        let columns = (self.count as f64).sqrt().ceil().max(1.0) as u32;
+        let rows = self.count.div_ceil(columns);
+        let step_x = (width / columns).max(1);
+        let step_y = (height / rows.max(1)).max(1);
+        (0..self.count)
+            .map(|index| {
+                let x = (index % columns) * step_x;
+                let y = (index / columns) * step_y;
+                (x as i32, y as i32, 1, 1)
+            })
+            .collect()
+    }
+
+    pub fn presented(&mut self, frame: u64, latency: Duration) {
+        if self.floods(frame) {
+            self.flooded.push(latency);
+        } else {
+            self.plain.push(latency);
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let stats = |latencies: &[Duration]| {
+            let mut sorted = latencies.to_vec();
+            sorted.sort();
+            let mean = (!sorted.is_empty())
+                .then(|| sorted.iter().sum::<Duration>() / sorted.len() as u32)?;
+            let p99 = sorted[(sorted.len() * 99 / 100).min(sorted.len() - 1)];
+            Some((mean, p99))
+        };
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        println!(
+            "{}damage flood: {} rectangles per commit, blocks of {} frames",
+            prefix, self.count, DAMAGE_BLOCK
+        );
+        for (name, latencies) in [("without", &self.plain), ("with", &self.flooded)] {
+            match stats(latencies) {
+                Some((mean, p99)) => println!(
+                    "{}  {:<7} flood: {:>5} frames, commit to present mean {:.3}ms, p99 {:.3}ms",
+                    prefix,
+                    name,
+                    latencies.len(),
+                    ms(mean),
+                    ms(p99)
+                ),
+                None => println!("{}  {:<7} flood: no presented frames", prefix, name),
+            }
+        }
+        if let (Some((plain, _)), Some((flooded, _))) = (stats(&self.plain), stats(&self.flooded)) {
+            println!(
+                "{}  the flood adds {:+.3}ms to the mean latency",
+                prefix,
+                ms(flooded) - ms(plain)
+            );
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Machine readable frame identifier stamped into every buffer.
+//!
+//! The block is an 8x8 grid of `CELL`x`CELL` pixel cells in the top-right corner of the
+//! buffer. Cells are read row by row, most significant bit first: the first 32 cells
+//! hold the frame id, the remaining 32 the CRC-32 (IEEE) of its little-endian bytes. A
+//! set bit is white, a cleared bit black.
+
+use crate::canvas::Canvas;
+
+/// Size of one bit cell in pixels.
+pub const CELL: u32 = 4;
+/// Number of cells per row and column.
+pub const GRID: u32 = 8;
+/// Size of the whole block in pixels.
+pub const SIZE: u32 = CELL * GRID;
+
+const ONE: u32 = 0xFFFF_FFFF;
+const ZERO: u32 = 0xFF00_0000;
+
+pub fn stamp(canvas: &mut Canvas, frame: u32) {
+    let x0 = canvas.width().saturating_sub(SIZE) as i32;
+    let bits = encode(frame);
+
+    for index in 0..GRID * GRID {
+        let bit = bits >> (63 - index) & 1;
+        let x = x0 + ((index % GRID) * CELL) as i32;
+        let y = ((index / GRID) * CELL) as i32;
+        canvas.fill_rect(x, y, CELL, CELL, if bit == 1 { ONE } else { ZERO });
+    }
+}
+
+fn encode(frame: u32) -> u64 {
+    (frame as u64) << 32 | crc32(&frame.to_le_bytes()) as u64
+}
+
+/// Bitwise CRC-32 (IEEE 802.3), as used by zlib and PNG.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Reads the block back from a tightly packed ARGB/XRGB8888 image, `None` if it is
+/// missing or its CRC doesn't match.
+pub fn read(data: &[u8], width: u32, height: u32) -> Option<u32> {
+    if width < SIZE || height < SIZE || data.len() < (width * height * 4) as usize {
+        return None;
+    }
+
+    let x0 = width - SIZE;
+    let mut bits = 0u64;
+    for index in 0..GRID * GRID {
+        // Sample the center of the cell, the green channel is enough to tell white
+        // from black.
+        let x = x0 + (index % GRID) * CELL + CELL / 2;
+        let y = (index / GRID) * CELL + CELL / 2;
+        let green = data[((y * width + x) * 4 + 1) as usize];
+        bits = bits << 1 | (green >= 0x80) as u64;
+    }
+
+    let frame = (bits >> 32) as u32;
+    (encode(frame) == bits).then_some(frame)
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use gilrs::{Button, EventType, Gilrs};
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay_client_toolkit::reexports::calloop::LoopHandle;
+
+use crate::controls::Control;
+use crate::SimpleWindow;
+
+/// gilrs has no pollable fd, so events are fetched periodically.
+const POLL_INTERVAL: Duration = Duration::from_millis(8);
+
+/// Starts polling connected gamepads, mapping buttons to runtime controls.
+pub fn init(loop_handle: &LoopHandle<'static, SimpleWindow>) {
+    let mut gilrs = match Gilrs::new() {
+        Ok(gilrs) => gilrs,
+        Err(err) => {
+            eprintln!("gamepad input unavailable: {}", err);
+            return;
+        }
+    };
+
+    loop_handle
+        .insert_source(Timer::immediate(), move |_, _, window| {
+            while let Some(event) = gilrs.next_event() {
+                if let EventType::ButtonPressed(button, _) = event.event {
+                    if let Some(control) = control(button) {
+                        window.control(control);
+                    }
+                }
+            }
+            TimeoutAction::ToDuration(POLL_INTERVAL)
+        })
+        .unwrap();
+}
+
+fn control(button: Button) -> Option<Control> {
+    match button {
+        Button::South => Some(Control::Step),
+        Button::West => Some(Control::ToggleFifo),
+        Button::Start => Some(Control::Resume),
+        _ => None,
+    }
+}
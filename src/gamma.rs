@@ -0,0 +1,284 @@
+//! Gamma and color changes during the run, `--gamma-watch`.
+//!
+//! A new gamma table, e.g. a night light fading in, makes the compositor recompose
+//! the whole output, which commonly costs a frame that has nothing to do with fifo.
+//! Every [`PROBE_INTERVAL`] the tool asks for the wlr-gamma-control of the surface's
+//! output and gives it back right away without setting a table. The request fails
+//! while another client, like a night light tool, holds the gamma table, so a client
+//! taking or releasing it is seen as a change. Changes the probe can't see, a tool
+//! fading the table it holds or a night light built into the compositor, are marked
+//! by hand with the `g` key or the `gamma` command of `--repl`.
+//!
+//! The report splits the hitches of the run, presentations of consecutive frames
+//! more than one and a half refresh apart, into those around a change, those right
+//! after a probe, as giving the control back may make the compositor reapply the
+//! table, and the rest, which gamma changes don't explain.
+
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::{
+    delegate_noop, globals::GlobalList, protocol::wl_output, Connection, Dispatch, Proxy,
+    QueueHandle,
+};
+use smithay_client_toolkit::reexports::protocols_wlr::gamma_control::v1::client::{
+    zwlr_gamma_control_manager_v1, zwlr_gamma_control_v1,
+};
+
+use crate::plugin::Annotation;
+use crate::presentation::Presented;
+use crate::SimpleWindow;
+
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+/// Hitches this close to a change marked by hand are around it.
+const NEAR: Duration = Duration::from_millis(500);
+/// Hitches this soon after a probe gave the control back are right after it.
+const AFTER_PROBE: Duration = Duration::from_millis(100);
+/// Interval between presentations of consecutive frames, in refresh cycles, that
+/// counts as a hitch.
+const HITCH: f64 = 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// Another client took or released the gamma control.
+    Probe,
+    /// Marked by hand.
+    Manual,
+}
+
+struct Change {
+    source: Source,
+    /// Last frame committed before the change was seen.
+    frame: u64,
+    /// Window the change happened in.
+    from: Duration,
+    to: Duration,
+    label: String,
+}
+
+impl Change {
+    fn around(&self, time: Duration) -> bool {
+        time >= self.from && time <= self.to + NEAR
+    }
+}
+
+struct Probe {
+    control: zwlr_gamma_control_v1::ZwlrGammaControlV1,
+    output: wl_output::WlOutput,
+    name: String,
+}
+
+#[derive(Default)]
+pub struct Gamma {
+    manager: Option<zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1>,
+    /// The probe waiting for the compositor's answer.
+    probe: Option<Probe>,
+    /// Probed output and whether another client held its gamma table.
+    held: Option<(wl_output::WlOutput, bool)>,
+    /// When each answered probe gave the control back.
+    probes: Vec<Duration>,
+    changes: Vec<Change>,
+    annotation: Option<Annotation>,
+    /// Frame and presentation time of the latest presented frame.
+    last: Option<(u64, Duration)>,
+    presented: u64,
+    /// Presentation times of the hitches.
+    hitches: Vec<Duration>,
+}
+
+impl Gamma {
+    pub fn bind(globals: &GlobalList, qh: &QueueHandle<SimpleWindow>) -> Self {
+        let manager = globals.bind(qh, 1..=1, ()).ok();
+        if manager.is_none() {
+            eprintln!(
+                "gamma watch requested, but zwlr_gamma_control_manager_v1 is unavailable, only changes marked by hand are seen"
+            );
+        }
+        Self {
+            manager,
+            ..Self::default()
+        }
+    }
+
+    /// Asks for the gamma control of `output`, unless the previous probe is still
+    /// waiting for its answer.
+    pub fn probe(
+        &mut self,
+        output: Option<&wl_output::WlOutput>,
+        name: String,
+        qh: &QueueHandle<SimpleWindow>,
+    ) {
+        let (Some(manager), Some(output)) = (self.manager.as_ref(), output) else {
+            return;
+        };
+        if self.probe.is_some() {
+            return;
+        }
+        self.probe = Some(Probe {
+            control: manager.get_gamma_control(output, qh, ()),
+            output: output.clone(),
+            name,
+        });
+    }
+
+    /// The compositor answered the probe, `held` if it refused the control.
+    fn answered(&mut self, held: bool, frame: u64, now: Duration, prefix: &str) {
+        let Some(probe) = self.probe.take() else {
+            return;
+        };
+        probe.control.destroy();
+        let previous = self.held.replace((probe.output.clone(), held));
+        let from = self.probes.last().copied().unwrap_or(now);
+        self.probes.push(now);
+        match previous {
+            Some((output, previous)) if output == probe.output => {
+                if previous == held {
+                    return;
+                }
+                let label = if held {
+                    format!("gamma: another client took the gamma table of {}", probe.name)
+                } else {
+                    format!("gamma: the gamma table of {} was released", probe.name)
+                };
+                self.change(Source::Probe, frame, from, now, label, prefix);
+            }
+            _ if held => println!(
+                "{}gamma: another client, e.g. a night light tool, holds the gamma table of {} or it has none",
+                prefix, probe.name
+            ),
+            _ => {}
+        }
+    }
+
+    /// Marks a gamma or color change made some other way after `frame`.
+    pub fn mark(&mut self, frame: u64, now: Duration, prefix: &str) {
+        self.change(
+            Source::Manual,
+            frame,
+            now.saturating_sub(NEAR),
+            now,
+            "gamma: change marked by hand".to_string(),
+            prefix,
+        );
+    }
+
+    fn change(
+        &mut self,
+        source: Source,
+        frame: u64,
+        from: Duration,
+        to: Duration,
+        label: String,
+        prefix: &str,
+    ) {
+        println!("{}{} after frame {}", prefix, label, frame);
+        self.annotation = Some(Annotation::Label(label.clone()));
+        self.changes.push(Change {
+            source,
+            frame,
+            from,
+            to,
+            label,
+        });
+    }
+
+    /// A change to log with the next frame.
+    pub fn take_annotation(&mut self) -> Option<Annotation> {
+        self.annotation.take()
+    }
+
+    pub fn presented(&mut self, frame: u64, presented: &Presented) {
+        self.presented += 1;
+        let last = self.last.replace((frame, presented.time));
+        let Some((last_frame, last_time)) = last else {
+            return;
+        };
+        if frame != last_frame + 1 || presented.refresh.is_zero() {
+            return;
+        }
+        let interval = presented.time.saturating_sub(last_time);
+        if interval.as_secs_f64() > presented.refresh.as_secs_f64() * HITCH {
+            self.hitches.push(presented.time);
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let manual = self
+            .changes
+            .iter()
+            .filter(|change| change.source == Source::Manual)
+            .count();
+        println!(
+            "{}gamma: {} changes, {} seen by {} probes, {} marked by hand",
+            prefix,
+            self.changes.len(),
+            self.changes.len() - manual,
+            self.probes.len(),
+            manual
+        );
+        for change in &self.changes {
+            let hitches = self
+                .hitches
+                .iter()
+                .filter(|time| change.around(**time))
+                .count();
+            println!(
+                "{}  after frame {}: {}, {} hitches around it",
+                prefix,
+                change.frame,
+                change.label.trim_start_matches("gamma: "),
+                hitches
+            );
+        }
+        let (mut around, mut probe, mut elsewhere) = (0, 0, 0);
+        for time in &self.hitches {
+            if self.changes.iter().any(|change| change.around(*time)) {
+                around += 1;
+            } else if self
+                .probes
+                .iter()
+                .any(|probe| *time >= *probe && *time <= *probe + AFTER_PROBE)
+            {
+                probe += 1;
+            } else {
+                elsewhere += 1;
+            }
+        }
+        println!(
+            "{}gamma: {} hitches in {} presentations, {} around a change, {} right after a probe, {} elsewhere not explained by gamma changes",
+            prefix,
+            self.hitches.len(),
+            self.presented,
+            around,
+            probe,
+            elsewhere
+        );
+    }
+}
+
+impl Dispatch<zwlr_gamma_control_v1::ZwlrGammaControlV1, ()> for SimpleWindow {
+    fn event(
+        state: &mut Self,
+        control: &zwlr_gamma_control_v1::ZwlrGammaControlV1,
+        event: zwlr_gamma_control_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(gamma) = state.gamma.as_mut().filter(|gamma| {
+            gamma
+                .probe
+                .as_ref()
+                .is_some_and(|probe| probe.control.id() == control.id())
+        }) else {
+            return;
+        };
+        let held = match event {
+            zwlr_gamma_control_v1::Event::GammaSize { .. } => false,
+            zwlr_gamma_control_v1::Event::Failed => true,
+            _ => return,
+        };
+        gamma.answered(held, state.frame, state.clock.now(), &state.log_prefix);
+    }
+}
+
+delegate_noop!(SimpleWindow: zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1);
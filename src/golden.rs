@@ -0,0 +1,138 @@
+//! Golden-frame verification of `--verify-frames` and `--verify-capture`.
+//!
+//! Every frame committed carries its `--frame-id` block, and the hash of its color
+//! channels is kept for the recent frames. A captured frame is identified by its block
+//! and compared against the frame rendered with that id, ignoring alpha. A capture
+//! that differs, e.g. a stale buffer presented for a newer frame, fails the run.
+
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hasher};
+
+use crate::frame_id;
+
+/// Number of rendered frames kept to compare captures against.
+const HISTORY: usize = 64;
+/// Differing frames listed in the report, the rest is only counted.
+const MAX_LISTED: usize = 20;
+
+#[derive(Default)]
+struct Stats {
+    captures: u64,
+    matched: u64,
+    /// Frame ids of the captures that differed from the rendered frame.
+    mismatched: Vec<u32>,
+    unidentified: u64,
+    /// Identified, but too old or of another size to be compared.
+    uncompared: u64,
+    backwards: u64,
+}
+
+#[derive(Default)]
+pub struct Golden {
+    /// Frame id, size and content hash of the recently rendered frames.
+    rendered: VecDeque<(u32, (u32, u32), u64)>,
+    last_frame: Option<u32>,
+    stats: Stats,
+}
+
+impl Golden {
+    /// Remembers the content of a frame about to be committed.
+    pub fn rendered(&mut self, frame: u32, width: u32, height: u32, data: &[u8]) {
+        if self.rendered.len() == HISTORY {
+            self.rendered.pop_front();
+        }
+        self.rendered
+            .push_back((frame, (width, height), rgb_hash(data, width, height)));
+    }
+
+    /// Compares a captured XRGB8888 image, returns the frame id of matching ones.
+    pub fn captured(
+        &mut self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        log_prefix: &str,
+    ) -> Option<u32> {
+        self.stats.captures += 1;
+        let Some(frame) = frame_id::read(data, width, height) else {
+            self.stats.unidentified += 1;
+            return None;
+        };
+        if self
+            .last_frame
+            .replace(frame)
+            .is_some_and(|last| frame < last)
+        {
+            self.stats.backwards += 1;
+        }
+
+        match self
+            .rendered
+            .iter()
+            .find(|(rendered, _, _)| *rendered == frame)
+        {
+            Some((_, size, hash)) if *size == (width, height) => {
+                if *hash == rgb_hash(data, width, height) {
+                    self.stats.matched += 1;
+                    return Some(frame);
+                }
+                self.stats.mismatched.push(frame);
+                eprintln!(
+                    "{}capture of frame {} differs from the rendered frame",
+                    log_prefix, frame
+                );
+            }
+            _ => self.stats.uncompared += 1,
+        }
+        None
+    }
+
+    /// Captures that differed from the rendered frame.
+    pub fn mismatched(&self) -> u64 {
+        self.stats.mismatched.len() as u64
+    }
+
+    /// Prints the comparison counts, the lines starting with `name`.
+    pub fn print_report(&self, prefix: &str, name: &str) {
+        let stats = &self.stats;
+        println!(
+            "{}{}: {} captures, {} matched, {} differed, {} without frame id, {} not comparable, {} went backwards",
+            prefix,
+            name,
+            stats.captures,
+            stats.matched,
+            stats.mismatched.len(),
+            stats.unidentified,
+            stats.uncompared,
+            stats.backwards
+        );
+        if !stats.mismatched.is_empty() {
+            let frames = stats
+                .mismatched
+                .iter()
+                .take(MAX_LISTED)
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            println!(
+                "{}{}: differing frames {}{}",
+                prefix,
+                name,
+                frames.join(", "),
+                if stats.mismatched.len() > MAX_LISTED {
+                    format!(" and {} more", stats.mismatched.len() - MAX_LISTED)
+                } else {
+                    String::new()
+                }
+            );
+        }
+    }
+}
+
+/// Hash of the color channels of a tightly packed 32-bit image.
+fn rgb_hash(data: &[u8], width: u32, height: u32) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for pixel in data[..(width * height * 4) as usize].chunks_exact(4) {
+        hasher.write_u32(u32::from_le_bytes(pixel.try_into().unwrap()) & 0x00FF_FFFF);
+    }
+    hasher.finish()
+}
@@ -0,0 +1,260 @@
+//! HDR10 tagging of the test surface with wp-color-management, `--hdr`.
+//!
+//! HDR surfaces often take different plane and composition paths, so their latch
+//! timing may differ from SDR. The surface alternates between blocks tagged with a
+//! PQ / BT.2020 image description carrying HDR10 mastering metadata and untagged
+//! blocks, and the latency from commit to presentation of both is compared.
+//!
+//! The buffer content stays 8 bit SDR, it only looks off while tagged.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::{
+    delegate_noop, globals::GlobalList, protocol::wl_surface, Connection, Dispatch, QueueHandle,
+    WEnum,
+};
+use wayland_protocols::wp::color_management::v1::client::{
+    wp_color_management_surface_v1, wp_color_manager_v1, wp_image_description_creator_params_v1,
+    wp_image_description_v1,
+};
+
+use crate::plugin::Annotation;
+use crate::SimpleWindow;
+
+/// Frames per tagged or untagged block.
+const BLOCK: u64 = 120;
+
+/// What the compositor advertised, complete with the `done` event.
+#[derive(Default)]
+struct Support {
+    done: bool,
+    parametric: bool,
+    pq: bool,
+    bt2020: bool,
+    perceptual: bool,
+    /// Mastering primaries and luminances, max CLL and max FALL.
+    metadata: bool,
+}
+
+pub struct Hdr {
+    surface: wp_color_management_surface_v1::WpColorManagementSurfaceV1,
+    support: Support,
+    description: Option<wp_image_description_v1::WpImageDescriptionV1>,
+    ready: bool,
+    /// Why the surface can't be tagged.
+    failed: Option<String>,
+    /// First frame of the first block, once the description is ready.
+    since: Option<u64>,
+    tagged: bool,
+    /// Whether a committed frame was tagged, until its feedback.
+    frames: HashMap<u64, bool>,
+    /// Latencies of untagged and tagged frames.
+    latencies: [Vec<Duration>; 2],
+}
+
+impl Hdr {
+    pub fn bind(
+        globals: &GlobalList,
+        qh: &QueueHandle<SimpleWindow>,
+        surface: &wl_surface::WlSurface,
+    ) -> Option<Self> {
+        let Ok(manager) =
+            globals.bind::<wp_color_manager_v1::WpColorManagerV1, _, _>(qh, 1..=1, ())
+        else {
+            eprintln!("hdr requested, but wp_color_manager_v1 is unavailable");
+            return None;
+        };
+        Some(Self {
+            surface: manager.get_surface(surface, qh, ()),
+            support: Support::default(),
+            description: None,
+            ready: false,
+            failed: None,
+            since: None,
+            tagged: false,
+            frames: HashMap::new(),
+            latencies: Default::default(),
+        })
+    }
+
+    /// Creates the image description once the compositor's support is known.
+    fn create(
+        &mut self,
+        manager: &wp_color_manager_v1::WpColorManagerV1,
+        qh: &QueueHandle<SimpleWindow>,
+    ) {
+        let support = &self.support;
+        let missing = [
+            (support.parametric, "parametric image descriptions"),
+            (support.pq, "the PQ transfer function"),
+            (support.bt2020, "BT.2020 primaries"),
+            (support.perceptual, "the perceptual render intent"),
+        ]
+        .into_iter()
+        .filter(|(supported, _)| !supported)
+        .map(|(_, name)| name)
+        .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            self.fail(format!("no support for {}", missing.join(", ")));
+            return;
+        }
+
+        let params = manager.create_parametric_creator(qh, ());
+        params.set_tf_named(wp_color_manager_v1::TransferFunction::St2084Pq);
+        params.set_primaries_named(wp_color_manager_v1::Primaries::Bt2020);
+        if support.metadata {
+            // HDR10 static metadata of a 1000 nit mastering display with DCI-P3
+            // primaries and a D65 white point, in units of 1/1000000.
+            params.set_mastering_display_primaries(
+                680_000, 320_000, 265_000, 690_000, 150_000, 60_000, 312_700, 329_000,
+            );
+            // The minimum in units of 0.0001 cd/m².
+            params.set_mastering_luminance(50, 1000);
+            params.set_max_cll(1000);
+            params.set_max_fall(400);
+        }
+        self.description = Some(params.create(qh, ()));
+    }
+
+    fn fail(&mut self, reason: String) {
+        eprintln!("hdr: can't tag the surface, {}", reason);
+        self.failed = Some(reason);
+    }
+
+    /// Switches between the blocks before the commit of `frame`.
+    pub fn committing(&mut self, frame: u64) -> Option<Annotation> {
+        let description = self.description.as_ref().filter(|_| self.ready)?;
+        let since = *self.since.get_or_insert(frame);
+        let tagged = ((frame - since) / BLOCK).is_multiple_of(2);
+        self.frames.insert(frame, tagged);
+        if tagged == self.tagged {
+            return None;
+        }
+        self.tagged = tagged;
+        if tagged {
+            self.surface
+                .set_image_description(description, wp_color_manager_v1::RenderIntent::Perceptual);
+        } else {
+            self.surface.unset_image_description();
+        }
+        Some(Annotation::Label(
+            if tagged {
+                "hdr: tagged"
+            } else {
+                "hdr: untagged"
+            }
+            .to_string(),
+        ))
+    }
+
+    pub fn presented(&mut self, frame: u64, latency: Option<Duration>) {
+        if let (Some(tagged), Some(latency)) = (self.frames.remove(&frame), latency) {
+            self.latencies[usize::from(tagged)].push(latency);
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if let Some(reason) = self.failed.as_ref() {
+            println!("{}hdr: not tested, {}", prefix, reason);
+            return;
+        }
+        if self.since.is_none() {
+            println!("{}hdr: the image description never got ready", prefix);
+            return;
+        }
+        println!(
+            "{}hdr: PQ / BT.2020{}, alternating blocks of {} frames",
+            prefix,
+            if self.support.metadata {
+                " with HDR10 metadata"
+            } else {
+                ", no metadata support"
+            },
+            BLOCK
+        );
+        for (name, latencies) in ["sdr", "hdr"].iter().zip(&self.latencies) {
+            if latencies.is_empty() {
+                println!("{}  {}: no frames presented", prefix, name);
+                continue;
+            }
+            let mut sorted = latencies.clone();
+            sorted.sort();
+            println!(
+                "{}  {}: {} frames, commit to present mean {:.3}ms, median {:.3}ms, max {:.3}ms",
+                prefix,
+                name,
+                sorted.len(),
+                (sorted.iter().sum::<Duration>() / sorted.len() as u32).as_secs_f64() * 1000.0,
+                sorted[sorted.len() / 2].as_secs_f64() * 1000.0,
+                sorted[sorted.len() - 1].as_secs_f64() * 1000.0
+            );
+        }
+    }
+}
+
+impl Dispatch<wp_color_manager_v1::WpColorManagerV1, ()> for SimpleWindow {
+    fn event(
+        state: &mut Self,
+        manager: &wp_color_manager_v1::WpColorManagerV1,
+        event: wp_color_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let Some(hdr) = state.hdr.as_mut().filter(|hdr| !hdr.support.done) else {
+            return;
+        };
+        let support = &mut hdr.support;
+        match event {
+            wp_color_manager_v1::Event::SupportedIntent {
+                render_intent: WEnum::Value(wp_color_manager_v1::RenderIntent::Perceptual),
+            } => support.perceptual = true,
+            wp_color_manager_v1::Event::SupportedFeature {
+                feature: WEnum::Value(feature),
+            } => match feature {
+                wp_color_manager_v1::Feature::Parametric => support.parametric = true,
+                wp_color_manager_v1::Feature::SetMasteringDisplayPrimaries => {
+                    support.metadata = true
+                }
+                _ => {}
+            },
+            wp_color_manager_v1::Event::SupportedTfNamed {
+                tf: WEnum::Value(wp_color_manager_v1::TransferFunction::St2084Pq),
+            } => support.pq = true,
+            wp_color_manager_v1::Event::SupportedPrimariesNamed {
+                primaries: WEnum::Value(wp_color_manager_v1::Primaries::Bt2020),
+            } => support.bt2020 = true,
+            wp_color_manager_v1::Event::Done => {
+                support.done = true;
+                hdr.create(manager, qh);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wp_image_description_v1::WpImageDescriptionV1, ()> for SimpleWindow {
+    fn event(
+        state: &mut Self,
+        _: &wp_image_description_v1::WpImageDescriptionV1,
+        event: wp_image_description_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(hdr) = state.hdr.as_mut() else {
+            return;
+        };
+        match event {
+            wp_image_description_v1::Event::Ready { .. } => hdr.ready = true,
+            wp_image_description_v1::Event::Failed { msg, .. } => {
+                hdr.fail(format!("the image description failed: {}", msg))
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(SimpleWindow: ignore wp_color_management_surface_v1::WpColorManagementSurfaceV1);
+delegate_noop!(SimpleWindow: wp_image_description_creator_params_v1::WpImageDescriptionCreatorParamsV1);
@@ -0,0 +1,107 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Captured result of a single `--exec-before`/`--exec-after` hook.
+pub struct HookOutput {
+    pub stage: &'static str,
+    pub command: String,
+    pub status: Option<ExitStatus>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs every command through `sh -c`, capturing its output for the run report.
+///
+/// A failing hook is recorded but does not abort the run.
+pub fn run_all(stage: &'static str, commands: &[String]) -> Vec<HookOutput> {
+    commands
+        .iter()
+        .map(|command| {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(command);
+            capture(stage, command.clone(), cmd)
+        })
+        .collect()
+}
+
+/// An unlinked temporary file the output of a command goes to.
+fn scratch() -> std::io::Result<File> {
+    static COUNT: AtomicU32 = AtomicU32::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "fifo_test-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    std::fs::remove_file(&path)?;
+    Ok(file)
+}
+
+/// What was written to `file` so far.
+fn written(mut file: File) -> std::io::Result<String> {
+    let mut data = Vec::new();
+    file.rewind()?;
+    file.read_to_end(&mut data)?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Runs `cmd` until it exits and records its output under `label`.
+///
+/// The output goes to files rather than pipes, so a background process the command
+/// starts, like a screen recorder, doesn't keep the run waiting for the pipes to
+/// close. Only the output written until the command exits is recorded.
+pub fn capture(stage: &'static str, label: String, mut cmd: Command) -> HookOutput {
+    let mut run = || -> std::io::Result<_> {
+        let stdout = scratch()?;
+        let stderr = scratch()?;
+        let status = cmd
+            .stdin(Stdio::null())
+            .stdout(stdout.try_clone()?)
+            .stderr(stderr.try_clone()?)
+            .spawn()?
+            .wait()?;
+        Ok((status, written(stdout)?, written(stderr)?))
+    };
+    match run() {
+        Ok((status, stdout, stderr)) => HookOutput {
+            stage,
+            command: label,
+            status: Some(status),
+            stdout,
+            stderr,
+        },
+        Err(err) => HookOutput {
+            stage,
+            command: label,
+            status: None,
+            stdout: String::new(),
+            stderr: format!("failed to spawn: {}", err),
+        },
+    }
+}
+
+pub fn print_report(outputs: &[HookOutput]) {
+    if outputs.is_empty() {
+        return;
+    }
+
+    println!("hooks:");
+    for output in outputs {
+        match output.status {
+            Some(status) => println!("  [{}] `{}` ({})", output.stage, output.command, status),
+            None => println!("  [{}] `{}` (not run)", output.stage, output.command),
+        }
+        for line in output.stdout.lines() {
+            println!("    stdout: {}", line);
+        }
+        for line in output.stderr.lines() {
+            println!("    stderr: {}", line);
+        }
+    }
+}
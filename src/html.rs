@@ -0,0 +1,243 @@
+//! Standalone HTML report, `report html`.
+//!
+//! The page embeds the frame times of a `--pipeline` run as JSON and draws them with
+//! a few lines of script, without anything to load, so it can be attached to a bug
+//! report and opened anywhere. Every chart zooms with the mouse wheel, pans by
+//! dragging and resets on a double click, all charts share the time axis.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{pipeline, plugin, report, sandbox, Args};
+
+/// Options of `report html`.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Html {
+    /// Frames the test commits
+    #[arg(long, default_value_t = 600)]
+    frames: u64,
+
+    /// File to write the page to
+    #[arg(long, value_name = "PATH", default_value = "fifo_test.html")]
+    output: PathBuf,
+}
+
+/// Runs a test with the pipeline recorded and writes the page.
+pub fn run(html: &Html, args: &Args, registry: &Arc<plugin::Registry>, start: Duration) {
+    let mut run_args = args.clone();
+    run_args.connections = 1;
+    run_args.frames = Some(html.frames);
+    run_args.quiet = true;
+    run_args.pipeline = true;
+    let Some(outcome) = crate::run_connections(&run_args, registry, start, None).pop() else {
+        return;
+    };
+    let path = sandbox::resolve(args.output_dir.as_deref(), &html.output);
+    write(&path, &outcome);
+}
+
+/// Writes the page of `outcome` to `path`, as `report html` and `--html-timeline` do.
+pub fn write(path: &Path, outcome: &crate::Outcome) {
+    let Some(pipeline) = outcome.pipeline.as_ref() else {
+        return;
+    };
+
+    let summary = [
+        ("tool", report::string(env!("CARGO_PKG_VERSION"))),
+        (
+            "compositor",
+            std::env::var("XDG_CURRENT_DESKTOP")
+                .ok()
+                .as_deref()
+                .map_or("null".to_string(), report::string),
+        ),
+        (
+            "command",
+            report::string(&std::env::args().collect::<Vec<_>>().join(" ")),
+        ),
+        ("frames", outcome.stats.frames.to_string()),
+        ("presented", outcome.stats.presented.to_string()),
+        ("discarded", outcome.stats.discarded.to_string()),
+        (
+            "interval",
+            outcome
+                .mean_interval
+                .map_or("null".to_string(), |interval| ms(interval).to_string()),
+        ),
+    ]
+    .iter()
+    .map(|(key, value)| format!("{}: {}", report::string(key), value))
+    .collect::<Vec<_>>();
+
+    let page = TEMPLATE
+        .replace(
+            "/*SUMMARY*/",
+            &format!("{{ {} }}", summary.join(", ")).replace("</", "<\\/"),
+        )
+        .replace("/*FRAMES*/", &frames(pipeline));
+    std::fs::write(path, page).expect("Failed to write the HTML report");
+    println!("report: HTML report written to {}", path.display());
+}
+
+fn ms(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1e6).round() / 1e3
+}
+
+/// `[frame, commit, callback, presented, released, discarded, buffer]` rows in ms
+/// since the first commit.
+fn frames(pipeline: &pipeline::Pipeline) -> String {
+    let origin = pipeline
+        .rows()
+        .next()
+        .map_or(Duration::ZERO, |(_, row)| row.committed);
+    let time = |time: Option<Duration>| {
+        time.map_or("null".to_string(), |time| {
+            ms(time.saturating_sub(origin)).to_string()
+        })
+    };
+    let rows = pipeline
+        .rows()
+        .map(|(frame, row)| {
+            format!(
+                "[{},{},{},{},{},{},{}]",
+                frame,
+                time(Some(row.committed)),
+                time(row.callback),
+                time(row.presented),
+                time(row.released),
+                u8::from(row.discarded),
+                row.buffer
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", rows.join(",\n"))
+}
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>fifo_test report</title>
+<style>
+body { font: 14px sans-serif; margin: 1em 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+td { padding: 2px 12px 2px 0; }
+h2 { font-size: 16px; margin: 1.2em 0 0.3em; }
+canvas { width: 100%; height: 220px; border: 1px solid #ccc; cursor: grab; }
+#tip { position: fixed; background: #fff; border: 1px solid #888; padding: 2px 6px; pointer-events: none; display: none; }
+.hint { color: #666; }
+</style>
+</head>
+<body>
+<h1>fifo_test report</h1>
+<table id="summary"></table>
+<p class="hint">Wheel to zoom, drag to pan, double click to reset. Red marks discarded frames.</p>
+<h2>Frame interval, commit to commit (ms)</h2>
+<canvas id="interval"></canvas>
+<h2>Latency, commit to presentation (ms)</h2>
+<canvas id="latency"></canvas>
+<h2>Buffer held, commit to release (ms)</h2>
+<canvas id="release"></canvas>
+<div id="tip"></div>
+<script>
+const summary = /*SUMMARY*/;
+const frames = /*FRAMES*/;
+
+const table = document.getElementById("summary");
+for (const [key, value] of Object.entries(summary)) {
+  const row = table.insertRow();
+  row.insertCell().textContent = key;
+  row.insertCell().textContent = value === null ? "-" : key === "interval" ? value + " ms" : value;
+}
+
+// Points are [time, value, frame, discarded], time in ms since the first commit.
+const series = {
+  interval: frames.slice(1).map((f, i) => [f[1], f[1] - frames[i][1], f[0], false]),
+  latency: frames.filter(f => f[3] !== null || f[5] === 1)
+    .map(f => [f[1], f[3] === null ? 0 : f[3] - f[1], f[0], f[5] === 1]),
+  release: frames.filter(f => f[4] !== null).map(f => [f[1], f[4] - f[1], f[0], false]),
+};
+const end = frames.length ? frames[frames.length - 1][1] : 1;
+let view = [0, end || 1];
+const charts = [];
+
+function draw(chart) {
+  const { canvas, points } = chart;
+  const ratio = window.devicePixelRatio || 1;
+  canvas.width = canvas.clientWidth * ratio;
+  canvas.height = canvas.clientHeight * ratio;
+  const ctx = canvas.getContext("2d");
+  ctx.scale(ratio, ratio);
+  const width = canvas.clientWidth, height = canvas.clientHeight, left = 50, bottom = 20;
+  const visible = points.filter(p => p[0] >= view[0] && p[0] <= view[1]);
+  const max = visible.reduce((max, p) => Math.max(max, p[1]), 1) * 1.1;
+  const x = t => left + (t - view[0]) / (view[1] - view[0]) * (width - left);
+  const y = v => (height - bottom) * (1 - v / max);
+
+  ctx.fillStyle = "#666";
+  ctx.strokeStyle = "#eee";
+  ctx.font = "11px sans-serif";
+  for (let i = 0; i <= 4; i++) {
+    const v = max * i / 4;
+    ctx.beginPath(); ctx.moveTo(left, y(v)); ctx.lineTo(width, y(v)); ctx.stroke();
+    ctx.fillText(v.toFixed(1), 4, y(v) + 4);
+  }
+  for (let i = 0; i <= 8; i++) {
+    const t = view[0] + (view[1] - view[0]) * i / 8;
+    ctx.fillText((t / 1000).toFixed(2) + "s", x(t) - 14, height - 5);
+  }
+
+  ctx.strokeStyle = "#2a6fdb";
+  ctx.beginPath();
+  visible.filter(p => !p[3]).forEach((p, i) => i ? ctx.lineTo(x(p[0]), y(p[1])) : ctx.moveTo(x(p[0]), y(p[1])));
+  ctx.stroke();
+  ctx.fillStyle = "#d33";
+  for (const p of visible.filter(p => p[3])) ctx.fillRect(x(p[0]) - 1, y(0) - 8, 3, 8);
+}
+
+function redraw() { charts.forEach(draw); }
+
+const tip = document.getElementById("tip");
+for (const [id, points] of Object.entries(series)) {
+  const chart = { canvas: document.getElementById(id), points };
+  charts.push(chart);
+  const time = event => {
+    const rect = chart.canvas.getBoundingClientRect();
+    const fraction = Math.max(0, (event.clientX - rect.left - 50) / (rect.width - 50));
+    return view[0] + fraction * (view[1] - view[0]);
+  };
+  chart.canvas.addEventListener("wheel", event => {
+    event.preventDefault();
+    const t = time(event), scale = event.deltaY > 0 ? 1.25 : 0.8;
+    view = [t - (t - view[0]) * scale, t + (view[1] - t) * scale];
+    redraw();
+  });
+  let drag = null;
+  chart.canvas.addEventListener("mousedown", event => { drag = [event.clientX, view]; });
+  window.addEventListener("mouseup", () => { drag = null; });
+  chart.canvas.addEventListener("mousemove", event => {
+    if (drag) {
+      const rect = chart.canvas.getBoundingClientRect();
+      const shift = (drag[0] - event.clientX) / (rect.width - 50) * (drag[1][1] - drag[1][0]);
+      view = [drag[1][0] + shift, drag[1][1] + shift];
+      redraw();
+      return;
+    }
+    const t = time(event);
+    const nearest = points.reduce((best, p) => !best || Math.abs(p[0] - t) < Math.abs(best[0] - t) ? p : best, null);
+    if (!nearest) return;
+    tip.style.display = "block";
+    tip.style.left = event.clientX + 12 + "px";
+    tip.style.top = event.clientY + 12 + "px";
+    tip.textContent = "frame " + nearest[2] + ": " + (nearest[3] ? "discarded" : nearest[1].toFixed(3) + " ms");
+  });
+  chart.canvas.addEventListener("mouseleave", () => { tip.style.display = "none"; });
+  chart.canvas.addEventListener("dblclick", () => { view = [0, end || 1]; redraw(); });
+}
+window.addEventListener("resize", redraw);
+redraw();
+</script>
+</body>
+</html>
+"##;
@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::canvas::Canvas;
+use crate::text;
+
+/// Height of the HUD strip at the bottom of the buffer.
+pub const HEIGHT: u32 = 32;
+
+/// Intervals at or above this are drawn as a full-height bar.
+const GRAPH_MAX: Duration = Duration::from_millis(50);
+/// Reference line drawn at a 60Hz frame interval.
+const GRAPH_REFERENCE: Duration = Duration::from_nanos(16_666_667);
+
+const BACKGROUND: u32 = 0xFF20_2020;
+const BAR: u32 = 0xFFE0_E0E0;
+const REFERENCE: u32 = 0xFF40_80FF;
+const LABEL: u32 = 0xFF00_0000;
+
+/// Commit-to-present latency covered by one histogram bin.
+const LATENCY_BIN: Duration = Duration::from_millis(2);
+/// Number of histogram bins, the last one also counts anything later.
+const LATENCY_BINS: usize = 25;
+const LATENCY_BIN_WIDTH: u32 = 4;
+const LATENCY_GRAPH_HEIGHT: u32 = 48;
+const LATENCY_TEXT: u32 = 0xFFE0_E0E0;
+
+/// What the client was doing for the frame shown in the HUD.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// No buffer was free, the frame had to wait for a release.
+    WaitingForBuffer,
+    /// The frame was committed with a fifo barrier.
+    Barrier,
+    /// The frame was committed without any throttling.
+    Unthrottled,
+}
+
+impl State {
+    fn color(self) -> u32 {
+        match self {
+            State::WaitingForBuffer => 0xFFFF_0000,
+            State::Barrier => 0xFF00_FF00,
+            State::Unthrottled => 0xFFFF_FF00,
+        }
+    }
+}
+
+/// Commit-to-present latencies of the whole run.
+struct Latencies {
+    bins: [u64; LATENCY_BINS],
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+pub struct Hud {
+    intervals: VecDeque<Duration>,
+    latencies: Option<Latencies>,
+}
+
+impl Hud {
+    pub fn new(latency: bool) -> Self {
+        Self {
+            intervals: VecDeque::new(),
+            latencies: latency.then_some(Latencies {
+                bins: [0; LATENCY_BINS],
+                count: 0,
+                sum: Duration::ZERO,
+                max: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// Whether the HUD shows the latency histogram, which needs presentation feedback.
+    pub fn wants_latency(&self) -> bool {
+        self.latencies.is_some()
+    }
+
+    pub fn push_interval(&mut self, interval: Duration) {
+        self.intervals.push_back(interval);
+    }
+
+    pub fn push_latency(&mut self, latency: Duration) {
+        let Some(latencies) = self.latencies.as_mut() else {
+            return;
+        };
+        let bin = (latency.as_nanos() / LATENCY_BIN.as_nanos()) as usize;
+        latencies.bins[bin.min(LATENCY_BINS - 1)] += 1;
+        latencies.count += 1;
+        latencies.sum += latency;
+        latencies.max = latencies.max.max(latency);
+    }
+
+    /// Renders the strip into the bottom rows of the canvas.
+    pub fn render(&mut self, canvas: &mut Canvas, state: State) {
+        let strip_height = HEIGHT.min(canvas.height());
+        let top = (canvas.height() - strip_height) as i32;
+        let indicator = strip_height;
+        let graph_width = canvas.width().saturating_sub(indicator + 1) as usize;
+
+        // Each interval is one pixel column, oldest on the left.
+        while self.intervals.len() > graph_width {
+            self.intervals.pop_front();
+        }
+        let graph_start = canvas.width() - self.intervals.len() as u32;
+
+        canvas.fill_rect(0, top, indicator, strip_height, state.color());
+        canvas.fill_rect(
+            indicator as i32,
+            top,
+            canvas.width() - indicator,
+            strip_height,
+            BACKGROUND,
+        );
+
+        let reference = bar_height(GRAPH_REFERENCE, strip_height);
+        canvas.fill_rect(
+            indicator as i32,
+            top + (strip_height - reference) as i32,
+            canvas.width() - indicator,
+            1,
+            REFERENCE,
+        );
+
+        for (index, interval) in self.intervals.iter().enumerate() {
+            let bar = bar_height(*interval, strip_height);
+            canvas.fill_rect(
+                (graph_start + index as u32) as i32,
+                top + (strip_height - bar) as i32,
+                1,
+                bar,
+                BAR,
+            );
+        }
+
+        if let Some(last) = self.intervals.back() {
+            let label = format!("{:.1}", last.as_secs_f64() * 1000.0);
+            let x = (indicator as i32 - text::width(&label, 1) as i32) / 2;
+            let y = top + (strip_height as i32 - text::GLYPH_HEIGHT as i32) / 2;
+            text::draw(canvas, x.max(0), y, &label, LABEL, 1);
+        }
+
+        if let Some(latencies) = self.latencies.as_ref() {
+            render_latencies(canvas, latencies, top);
+        }
+    }
+}
+
+/// Renders the latency histogram right above the strip in the bottom-right corner.
+fn render_latencies(canvas: &mut Canvas, latencies: &Latencies, bottom: i32) {
+    let label = match latencies.count {
+        0 => "latency: -".to_string(),
+        count => format!(
+            "latency avg {:.1} max {:.1}",
+            (latencies.sum / count as u32).as_secs_f64() * 1000.0,
+            latencies.max.as_secs_f64() * 1000.0
+        ),
+    };
+    let graph_width = LATENCY_BINS as u32 * LATENCY_BIN_WIDTH;
+    let width = graph_width.max(text::width(&label, 1)) + 4;
+    let height = LATENCY_GRAPH_HEIGHT + text::LINE_HEIGHT + 4;
+    let left = canvas.width() as i32 - width as i32;
+    let top = bottom - height as i32;
+
+    canvas.fill_rect(left, top, width, height, BACKGROUND);
+    text::draw(canvas, left + 2, top + 2, &label, LATENCY_TEXT, 1);
+
+    let graph_bottom = bottom - 2;
+    let most = latencies.bins.iter().copied().max().unwrap_or(0).max(1);
+    for (index, count) in latencies.bins.iter().enumerate() {
+        let bar = (*count as f64 / most as f64 * LATENCY_GRAPH_HEIGHT as f64).round() as u32;
+        canvas.fill_rect(
+            left + 2 + (index as u32 * LATENCY_BIN_WIDTH) as i32,
+            graph_bottom - bar as i32,
+            LATENCY_BIN_WIDTH - 1,
+            bar,
+            BAR,
+        );
+    }
+
+    // Mark the 60Hz frame interval on the latency axis.
+    let reference =
+        (GRAPH_REFERENCE.as_nanos() * LATENCY_BIN_WIDTH as u128 / LATENCY_BIN.as_nanos()) as i32;
+    canvas.fill_rect(
+        left + 2 + reference,
+        graph_bottom - LATENCY_GRAPH_HEIGHT as i32,
+        1,
+        LATENCY_GRAPH_HEIGHT,
+        REFERENCE,
+    );
+}
+
+fn bar_height(interval: Duration, strip_height: u32) -> u32 {
+    let ratio = interval.as_secs_f64() / GRAPH_MAX.as_secs_f64();
+    (ratio.min(1.0) * strip_height as f64).round() as u32
+}
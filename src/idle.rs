@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use crate::plugin::FramePlan;
+
+/// Stays idle for a while before every commit and measures how long the compositor
+/// takes to present it, `--idle-burst`.
+///
+/// Every frame is a single commit with a fifo barrier after `idle` without commits,
+/// so the latencies show how fast the compositor wakes up for a fifo client.
+pub struct IdleBurst {
+    idle: Duration,
+    latencies: Vec<Duration>,
+    refresh: Duration,
+    discarded: u64,
+}
+
+impl IdleBurst {
+    pub fn new(idle: Duration) -> Self {
+        Self {
+            idle,
+            latencies: Vec::new(),
+            refresh: Duration::ZERO,
+            discarded: 0,
+        }
+    }
+
+    /// Overrides the plan with a barrier commit followed by the idle period.
+    pub fn apply(&self, plan: &mut FramePlan) {
+        plan.barrier = true;
+        plan.frame_callback = false;
+        plan.delay = self.idle;
+    }
+
+    pub fn presented(&mut self, latency: Duration, refresh: Duration) {
+        self.latencies.push(latency);
+        if !refresh.is_zero() {
+            self.refresh = refresh;
+        }
+    }
+
+    pub fn discarded(&mut self) {
+        self.discarded += 1;
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let Some(max) = sorted.last().copied() else {
+            println!(
+                "{}idle burst: no frame presented after {}ms idle",
+                prefix,
+                self.idle.as_millis()
+            );
+            return;
+        };
+        let percentile = |p: usize| sorted[(sorted.len() * p / 100).min(sorted.len() - 1)];
+        let mean = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+        println!(
+            "{}idle burst: {} wakeups after {}ms idle, {} discarded, commit to present min {:.3}ms, median {:.3}ms, p99 {:.3}ms, max {:.3}ms",
+            prefix,
+            sorted.len(),
+            self.idle.as_millis(),
+            self.discarded,
+            ms(sorted[0]),
+            ms(percentile(50)),
+            ms(percentile(99)),
+            ms(max)
+        );
+        if !self.refresh.is_zero() {
+            println!(
+                "{}  mean {:.2} refresh cycles of {:.3}ms, {} wakeups took longer than two",
+                prefix,
+                mean.as_secs_f64() / self.refresh.as_secs_f64(),
+                ms(self.refresh),
+                sorted
+                    .iter()
+                    .filter(|latency| **latency > self.refresh * 2)
+                    .count()
+            );
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+use smithay_client_toolkit::reexports::client::Connection;
+
+/// Faults `--inject` triggers once a barrier is pending, for compositors to prove
+/// they clean up the fifo state of clients that disappear.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Inject {
+    /// Shut the socket down while the process keeps running
+    Disconnect,
+    /// Exit the process without destroying any object
+    Exit,
+    /// Run the test in a child process and kill it with SIGKILL
+    Kill,
+}
+
+/// Set in the child process of [`Inject::Kill`].
+const CHILD_ENV: &str = "FIFO_TEST_INJECT_CHILD";
+/// Printed by the child process once the barrier is pending.
+const MARKER: &str = "inject: barrier pending";
+
+/// Whether this process runs the test itself, false for the parent of [`Inject::Kill`].
+pub fn runs_test(inject: Option<Inject>) -> bool {
+    inject != Some(Inject::Kill) || std::env::var_os(CHILD_ENV).is_some()
+}
+
+/// Fires `inject` after `frame` was committed with a barrier.
+///
+/// Returns after [`Inject::Disconnect`] only, the window must not touch the
+/// connection anymore.
+pub fn fire(inject: Inject, conn: &Connection, frame: u64, prefix: &str) {
+    let _ = conn.flush();
+    match inject {
+        Inject::Disconnect => {
+            println!("{}inject: disconnecting after frame {}", prefix, frame);
+            // SAFETY: shutting down the open wayland socket, the fd stays valid.
+            unsafe {
+                libc::shutdown(conn.backend().poll_fd().as_raw_fd(), libc::SHUT_RDWR);
+            }
+        }
+        Inject::Exit => {
+            println!("{}inject: exiting after frame {}", prefix, frame);
+            let _ = std::io::stdout().flush();
+            std::process::exit(0);
+        }
+        Inject::Kill => {
+            println!("{}{} after frame {}", prefix, MARKER, frame);
+            let _ = std::io::stdout().flush();
+            // Keep going until the parent kills us.
+        }
+    }
+}
+
+/// Runs the test with the same arguments in a child process and kills it with
+/// SIGKILL as soon as it reports a pending barrier, `--inject=kill`.
+pub fn run_parent() {
+    let mut child = Command::new(std::env::current_exe().expect("Failed to find the executable"))
+        .args(std::env::args_os().skip(1))
+        .env(CHILD_ENV, "1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn the test run");
+
+    let mut killed = false;
+    for line in BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map_while(Result::ok)
+    {
+        println!("{}", line);
+        if !killed && line.contains(MARKER) {
+            // SAFETY: plain kill(2) of our own child, which wasn't reaped yet.
+            unsafe {
+                libc::kill(child.id() as libc::pid_t, libc::SIGKILL);
+            }
+            killed = true;
+            println!("inject: killed child {} with SIGKILL", child.id());
+        }
+    }
+    let status = child.wait().expect("Failed to wait for the test run");
+    if !killed {
+        eprintln!(
+            "inject: the child never committed a barrier at the injection frame, {}",
+            status
+        );
+    }
+}
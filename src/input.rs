@@ -0,0 +1,298 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::{
+    protocol::{wl_keyboard::WlKeyboard, wl_seat, wl_surface::WlSurface, wl_touch::WlTouch},
+    Connection, QueueHandle,
+};
+use smithay_client_toolkit::seat::keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers};
+use smithay_client_toolkit::seat::{touch::TouchHandler, Capability, SeatHandler, SeatState};
+use smithay_client_toolkit::shell::WaylandSurface;
+
+use crate::controls::Control;
+use crate::SimpleWindow;
+
+/// Touches lasting longer than this are not considered taps.
+const TAP_TIMEOUT_MS: u32 = 300;
+
+/// Draw intervals compared before and after an interactive move starts.
+const MOVE_FRAMES: usize = 120;
+
+/// Compares the draw intervals around interactive moves started with `m`, as
+/// compositors may switch to synchronous composition while moving.
+///
+/// The client isn't told when the move ends, the frames after the start are assumed
+/// to be moved.
+#[derive(Default)]
+pub struct MoveProbe {
+    before: VecDeque<Duration>,
+    /// Frame the move started after and the intervals since.
+    after: Option<(u64, Vec<Duration>)>,
+}
+
+impl MoveProbe {
+    fn started(&mut self, frame: u64) {
+        self.after = Some((frame, Vec::new()));
+    }
+
+    pub fn interval(&mut self, interval: Duration, prefix: &str) {
+        let Some((frame, after)) = self.after.as_mut() else {
+            if self.before.len() == MOVE_FRAMES {
+                self.before.pop_front();
+            }
+            self.before.push_back(interval);
+            return;
+        };
+        after.push(interval);
+        if after.len() < MOVE_FRAMES {
+            return;
+        }
+
+        println!(
+            "{}move: mean interval {:?} in the {} frames before frame {}, {:?} in the {} after, worst {:?}",
+            prefix,
+            mean(self.before.iter()),
+            self.before.len(),
+            frame,
+            mean(after.iter()),
+            after.len(),
+            after.iter().max()
+        );
+        self.before = after.drain(..).collect();
+        self.after = None;
+    }
+}
+
+fn mean<'a>(intervals: impl ExactSizeIterator<Item = &'a Duration>) -> Option<Duration> {
+    let count = intervals.len() as u32;
+    (count > 0).then(|| intervals.sum::<Duration>() / count)
+}
+
+/// Recognizes one, two and three finger taps on the test surface.
+#[derive(Default)]
+pub struct TapDetector {
+    active: HashSet<i32>,
+    fingers: usize,
+    start: u32,
+}
+
+impl TapDetector {
+    fn down(&mut self, time: u32, id: i32) {
+        if self.active.is_empty() {
+            self.fingers = 0;
+            self.start = time;
+        }
+        self.active.insert(id);
+        self.fingers = self.fingers.max(self.active.len());
+    }
+
+    /// Returns the control for a completed tap once the last finger is lifted.
+    fn up(&mut self, time: u32, id: i32) -> Option<Control> {
+        if !self.active.remove(&id) || !self.active.is_empty() {
+            return None;
+        }
+        if time.wrapping_sub(self.start) > TAP_TIMEOUT_MS {
+            return None;
+        }
+        match self.fingers {
+            1 => Some(Control::Step),
+            2 => Some(Control::ToggleFifo),
+            3 => Some(Control::Resume),
+            _ => None,
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.active.clear();
+    }
+}
+
+impl SeatHandler for SimpleWindow {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+
+    fn new_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Touch && self.touch.is_none() {
+            self.touch = self.seat_state.get_touch(qh, &seat).ok();
+        }
+        if capability == Capability::Keyboard && self.keyboard.is_none() {
+            self.keyboard = self.seat_state.get_keyboard(qh, &seat, None).ok();
+            self.keyboard_seat = Some(seat);
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Touch {
+            if let Some(touch) = self.touch.take() {
+                touch.release();
+            }
+        }
+        if capability == Capability::Keyboard {
+            if let Some(keyboard) = self.keyboard.take() {
+                keyboard.release();
+            }
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl TouchHandler for SimpleWindow {
+    fn down(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _serial: u32,
+        time: u32,
+        surface: WlSurface,
+        id: i32,
+        _position: (f64, f64),
+    ) {
+        if &surface == self.window.wl_surface() {
+            self.taps.down(time, id);
+        }
+    }
+
+    fn up(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _serial: u32,
+        time: u32,
+        id: i32,
+    ) {
+        if let Some(control) = self.taps.up(time, id) {
+            self.control(control);
+        }
+    }
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _time: u32,
+        _id: i32,
+        _position: (f64, f64),
+    ) {
+    }
+
+    fn shape(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _id: i32,
+        _major: f64,
+        _minor: f64,
+    ) {
+    }
+
+    fn orientation(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _id: i32,
+        _orientation: f64,
+    ) {
+    }
+
+    fn cancel(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _touch: &WlTouch) {
+        self.taps.cancel();
+    }
+}
+
+impl KeyboardHandler for SimpleWindow {
+    fn enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _surface: &WlSurface,
+        _serial: u32,
+        _raw: &[u32],
+        _keysyms: &[Keysym],
+    ) {
+    }
+
+    fn leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _surface: &WlSurface,
+        _serial: u32,
+    ) {
+    }
+
+    fn press_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        serial: u32,
+        event: KeyEvent,
+    ) {
+        if event.keysym == Keysym::m {
+            // Compositors may only honour moves started with a pointer button serial.
+            if let Some(seat) = self.keyboard_seat.as_ref() {
+                println!(
+                    "{}starting an interactive move after frame {}",
+                    self.log_prefix, self.frame
+                );
+                self.window.move_(seat, serial);
+                self.move_probe.started(self.frame);
+            }
+            return;
+        }
+        let control = match event.keysym {
+            Keysym::s => Control::Step,
+            Keysym::r => Control::Resume,
+            Keysym::f => Control::ToggleFifo,
+            Keysym::question | Keysym::F1 => Control::ToggleHelp,
+            Keysym::o => Control::MarkModeChange,
+            Keysym::g => Control::MarkGammaChange,
+            _ => return,
+        };
+        self.control(control);
+    }
+
+    fn release_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        _event: KeyEvent,
+    ) {
+    }
+
+    fn update_modifiers(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        _modifiers: Modifiers,
+        _layout: u32,
+    ) {
+    }
+}
@@ -0,0 +1,57 @@
+use std::hash::{DefaultHasher, Hasher};
+
+/// Checks that buffers come back from the compositor unmodified, `--verify-release`.
+///
+/// The contents of a buffer are hashed right before it is committed and again once
+/// it was released, any difference means someone else wrote into our shm.
+#[derive(Default)]
+pub struct Integrity {
+    /// Frame and content hash of every buffer held by the compositor.
+    pending: Vec<Option<(u64, u64)>>,
+    verified: u64,
+    corrupted: u64,
+}
+
+pub fn checksum(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(data);
+    hasher.finish()
+}
+
+impl Integrity {
+    pub fn new(buffers: usize) -> Self {
+        Self {
+            pending: vec![None; buffers],
+            ..Self::default()
+        }
+    }
+
+    pub fn committed(&mut self, index: usize, frame: u64, data: &[u8]) {
+        self.pending[index] = Some((frame, checksum(data)));
+    }
+
+    /// Verifies buffer `index`, which must have been released.
+    ///
+    /// Returns the frame the buffer was committed with if its contents changed.
+    pub fn released(&mut self, index: usize, data: &[u8]) -> Option<u64> {
+        let (frame, expected) = self.pending[index].take()?;
+        self.verified += 1;
+        if checksum(data) == expected {
+            return None;
+        }
+        self.corrupted += 1;
+        Some(frame)
+    }
+
+    /// Forgets all pending checks, for when the buffers are recreated.
+    pub fn reset(&mut self) {
+        self.pending.fill(None);
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        println!(
+            "{}buffer integrity: {} released buffers verified, {} modified",
+            prefix, self.verified, self.corrupted
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::hooks::{self, HookOutput};
+
+/// Compositor IPC used to place the test window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// Detect the compositor from the environment
+    Auto,
+    /// swaymsg
+    Sway,
+    /// hyprctl
+    Hyprland,
+    /// riverctl, only mode switches as it can't target the test window
+    River,
+}
+
+impl Backend {
+    /// The backend itself, or the detected one for `Auto`.
+    pub fn resolve(self) -> Option<Backend> {
+        match self {
+            Backend::Auto => Backend::detect(),
+            backend => Some(backend),
+        }
+    }
+
+    /// Whether `action` can be carried out, riverctl only acts on the focused view and
+    /// can't target the test window.
+    pub fn supports(self, action: &Action) -> bool {
+        self != Backend::River || matches!(action, Action::Mode(_))
+    }
+
+    fn detect() -> Option<Backend> {
+        if std::env::var_os("SWAYSOCK").is_some() {
+            return Some(Backend::Sway);
+        }
+        if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            return Some(Backend::Hyprland);
+        }
+        if std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| {
+            desktop
+                .split(':')
+                .any(|desktop| desktop.eq_ignore_ascii_case("river"))
+        }) {
+            return Some(Backend::River);
+        }
+        None
+    }
+}
+
+/// An output mode, parsed from `<width>x<height>@<hz>`.
+#[derive(Clone, Copy, Debug)]
+pub struct OutputMode {
+    pub width: u32,
+    pub height: u32,
+    pub hz: f64,
+}
+
+impl OutputMode {
+    pub fn refresh(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.hz)
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid mode `{}`, expected <width>x<height>@<hz>", s);
+        let (size, hz) = s.split_once('@').ok_or_else(invalid)?;
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let hz = hz
+            .trim_end_matches("Hz")
+            .parse::<f64>()
+            .ok()
+            .filter(|hz| hz.is_finite() && *hz > 0.0)
+            .ok_or_else(invalid)?;
+        Ok(OutputMode {
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+            hz,
+        })
+    }
+}
+
+/// A window-management action, parsed from `output=<name>`, `workspace=<name>`,
+/// `floating`, `fullscreen` or `mode=<width>x<height>@<hz>`, the latter switching the
+/// mode of the output the window is on.
+#[derive(Clone, Debug)]
+pub enum Action {
+    Output(String),
+    Workspace(String),
+    Floating,
+    Fullscreen,
+    Mode(OutputMode),
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some(("output", name)) if !name.is_empty() => Ok(Action::Output(name.to_string())),
+            Some(("workspace", name)) if !name.is_empty() => {
+                Ok(Action::Workspace(name.to_string()))
+            }
+            None if s == "floating" => Ok(Action::Floating),
+            None if s == "fullscreen" => Ok(Action::Fullscreen),
+            Some(("mode", mode)) => Ok(Action::Mode(mode.parse()?)),
+            _ => Err(format!(
+                "invalid action `{}`, expected output=<name>, workspace=<name>, floating, fullscreen or mode=<width>x<height>@<hz>",
+                s
+            )),
+        }
+    }
+}
+
+/// An action applied once the given frame has been committed, parsed from
+/// `<frame>:<action>`.
+#[derive(Clone, Debug)]
+pub struct Move {
+    pub frame: u64,
+    pub action: Action,
+}
+
+impl FromStr for Move {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (frame, action) = s
+            .split_once(':')
+            .ok_or_else(|| format!("invalid move `{}`, expected <frame>:<action>", s))?;
+        let frame = frame
+            .parse()
+            .map_err(|err| format!("invalid frame `{}`: {}", frame, err))?;
+        Ok(Move {
+            frame,
+            action: action.parse()?,
+        })
+    }
+}
+
+pub struct Placement {
+    backend: Backend,
+    pid: u32,
+    initial: Vec<Action>,
+    moves: Vec<Move>,
+    /// The mode switched to last, until taken.
+    switched: Option<OutputMode>,
+    /// Commands for the worker, which runs them off the frame loop in order.
+    commands: Option<mpsc::Sender<Vec<String>>>,
+    worker: Option<JoinHandle<Vec<HookOutput>>>,
+}
+
+impl Placement {
+    pub fn new(backend: Backend, initial: Vec<Action>, moves: Vec<Move>) -> Option<Placement> {
+        if initial.is_empty() && moves.is_empty() {
+            return None;
+        }
+
+        let Some(backend) = backend.resolve() else {
+            eprintln!("window placement requested, but no supported compositor IPC found");
+            return None;
+        };
+
+        let (commands, receiver) = mpsc::channel::<Vec<String>>();
+        let worker = std::thread::Builder::new()
+            .name("ipc".into())
+            .spawn(move || {
+                receiver
+                    .into_iter()
+                    .map(|argv| {
+                        let mut cmd = Command::new(&argv[0]);
+                        cmd.args(&argv[1..]);
+                        hooks::capture("ipc", argv.join(" "), cmd)
+                    })
+                    .collect()
+            })
+            .expect("failed to spawn the ipc thread");
+
+        Some(Placement {
+            backend,
+            pid: std::process::id(),
+            initial,
+            moves,
+            switched: None,
+            commands: Some(commands),
+            worker: Some(worker),
+        })
+    }
+
+    /// Applies the initial placement, to be called once the window entered `output`.
+    /// Later calls do nothing.
+    pub fn place(&mut self, output: Option<&str>) {
+        for action in std::mem::take(&mut self.initial) {
+            self.apply(&action, output);
+        }
+    }
+
+    /// Applies all moves scheduled for `frame`, the window is on `output`.
+    pub fn frame_committed(&mut self, frame: u64, output: Option<&str>) {
+        let (due, pending) = std::mem::take(&mut self.moves)
+            .into_iter()
+            .partition::<Vec<_>, _>(|mv| mv.frame <= frame);
+        self.moves = pending;
+        for mv in due {
+            self.apply(&mv.action, output);
+        }
+    }
+
+    /// Waits for the commands still running and returns the output of all of them.
+    pub fn finish(mut self) -> Vec<HookOutput> {
+        drop(self.commands.take());
+        self.worker
+            .take()
+            .and_then(|worker| worker.join().ok())
+            .unwrap_or_default()
+    }
+
+    /// The mode a move switched the output to since the last call.
+    pub fn take_mode_switch(&mut self) -> Option<OutputMode> {
+        self.switched.take()
+    }
+
+    /// Queues the commands of `action`, the worker runs them without holding up the
+    /// frame loop.
+    fn apply(&mut self, action: &Action, output: Option<&str>) {
+        if let Action::Mode(mode) = action {
+            if output.is_none() {
+                eprintln!("ipc: the window isn't on a known output, can't switch its mode");
+                return;
+            }
+            self.switched = Some(*mode);
+        }
+        for argv in self.commands(action, output.unwrap_or_default()) {
+            if let Some(commands) = self.commands.as_ref() {
+                let _ = commands.send(argv);
+            }
+        }
+    }
+
+    fn commands(&self, action: &Action, output: &str) -> Vec<Vec<String>> {
+        let pid = self.pid;
+        let argv = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        match self.backend {
+            Backend::Sway => {
+                let command = match action {
+                    Action::Output(name) => format!("move container to output {}", name),
+                    Action::Workspace(name) => format!("move container to workspace {}", name),
+                    Action::Floating => "floating enable".to_string(),
+                    Action::Fullscreen => "fullscreen enable".to_string(),
+                    Action::Mode(mode) => {
+                        return vec![argv(&[
+                            "swaymsg",
+                            &format!(
+                                "output {} mode {}x{}@{}Hz",
+                                output, mode.width, mode.height, mode.hz
+                            ),
+                        ])]
+                    }
+                };
+                vec![argv(&["swaymsg", &format!("[pid={}] {}", pid, command)])]
+            }
+            Backend::Hyprland => {
+                let window = format!("pid:{}", pid);
+                match action {
+                    Action::Output(name) => vec![
+                        argv(&["hyprctl", "dispatch", "focuswindow", &window]),
+                        argv(&[
+                            "hyprctl",
+                            "dispatch",
+                            "movewindow",
+                            &format!("mon:{}", name),
+                        ]),
+                    ],
+                    Action::Workspace(name) => vec![argv(&[
+                        "hyprctl",
+                        "dispatch",
+                        "movetoworkspacesilent",
+                        &format!("{},{}", name, window),
+                    ])],
+                    Action::Floating => {
+                        vec![argv(&["hyprctl", "dispatch", "setfloating", &window])]
+                    }
+                    Action::Fullscreen => vec![
+                        argv(&["hyprctl", "dispatch", "focuswindow", &window]),
+                        argv(&["hyprctl", "dispatch", "fullscreen", "0"]),
+                    ],
+                    // A monitor rule needs a position and scale as well.
+                    Action::Mode(mode) => vec![argv(&[
+                        "hyprctl",
+                        "keyword",
+                        "monitor",
+                        &format!(
+                            "{},{}x{}@{},auto,1",
+                            output, mode.width, mode.height, mode.hz
+                        ),
+                    ])],
+                }
+            }
+            Backend::River => match action {
+                Action::Output(_)
+                | Action::Workspace(_)
+                | Action::Floating
+                | Action::Fullscreen => {
+                    unreachable!("rejected by crate::check_placement")
+                }
+                // River has no output configuration of its own.
+                Action::Mode(mode) => vec![argv(&[
+                    "wlr-randr",
+                    "--output",
+                    output,
+                    "--mode",
+                    &format!("{}x{}@{}Hz", mode.width, mode.height, mode.hz),
+                ])],
+            },
+            Backend::Auto => unreachable!("backend is resolved in Placement::new"),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+/// Measurement of `--kiosk` mode, restarted whenever the compositor configures a
+/// new size.
+#[derive(Default)]
+pub struct Kiosk {
+    size: (u32, u32),
+    restarts: u32,
+    frames: u64,
+    stalls: u64,
+    interval_sum: Duration,
+    interval_count: u32,
+}
+
+impl Kiosk {
+    /// Handles a configured size, returns whether the measurement was restarted.
+    pub fn configured(&mut self, width: u32, height: u32) -> bool {
+        if self.size == (width, height) {
+            return false;
+        }
+        if self.size != (0, 0) {
+            self.restarts += 1;
+        }
+        *self = Kiosk {
+            size: (width, height),
+            restarts: self.restarts,
+            ..Default::default()
+        };
+        true
+    }
+
+    pub fn frame(&mut self, interval: Option<Duration>, stalled: bool) {
+        self.frames += 1;
+        if stalled {
+            self.stalls += 1;
+        }
+        if let Some(interval) = interval {
+            self.interval_sum += interval;
+            self.interval_count += 1;
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str, fifo: bool) {
+        println!(
+            "{}kiosk: fifo {}",
+            prefix,
+            if fifo { "supported" } else { "unsupported" }
+        );
+        println!(
+            "{}kiosk: {}x{}, {} restarts",
+            prefix, self.size.0, self.size.1, self.restarts
+        );
+        match (self.interval_count > 0).then(|| self.interval_sum / self.interval_count) {
+            Some(mean) => println!(
+                "{}kiosk: {} frames, mean interval {:.2}ms ({:.1} fps), {} stalls",
+                prefix,
+                self.frames,
+                mean.as_secs_f64() * 1000.0,
+                1.0 / mean.as_secs_f64(),
+                self.stalls
+            ),
+            None => println!("{}kiosk: {} frames", prefix, self.frames),
+        }
+    }
+}
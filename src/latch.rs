@@ -0,0 +1,291 @@
+use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::reexports::client::globals::{registry_queue_init, GlobalListContents};
+use smithay_client_toolkit::reexports::client::protocol::{
+    wl_buffer, wl_compositor, wl_output, wl_registry, wl_shm, wl_shm_pool, wl_surface,
+};
+use smithay_client_toolkit::reexports::client::{
+    delegate_noop, Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+use smithay_client_toolkit::reexports::protocols::wp::fifo::v1::client::{
+    wp_fifo_manager_v1, wp_fifo_v1,
+};
+use smithay_client_toolkit::reexports::protocols::wp::viewporter::client::{
+    wp_viewport, wp_viewporter,
+};
+use smithay_client_toolkit::reexports::protocols::xdg::shell::client::{
+    xdg_surface, xdg_toplevel, xdg_wm_base,
+};
+
+use crate::session;
+
+/// How long after the queued commit the error may still arrive, a few refresh cycles.
+const LATCH_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// When the compositor must raise the error of a case.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum When {
+    /// When the commit is received, even if it waits for a barrier.
+    Commit,
+    /// When the queued state is applied, after the barrier cleared.
+    Apply,
+}
+
+impl When {
+    fn name(self) -> &'static str {
+        match self {
+            When::Commit => "at commit",
+            When::Apply => "at latch",
+        }
+    }
+}
+
+struct Case {
+    name: &'static str,
+    /// Interface, code and timing of the expected protocol error.
+    expected: (&'static str, u32, When),
+    buffer: (i32, i32),
+    /// Called with the queued commit, after the buffer was attached.
+    state: fn(&wl_surface::WlSurface, &wp_viewport::WpViewport),
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "viewport-out-of-buffer",
+        expected: ("wp_viewport", 2, When::Apply),
+        buffer: (64, 64),
+        state: |_, viewport| viewport.set_source(0.0, 0.0, 128.0, 128.0),
+    },
+    Case {
+        name: "transform-moves-viewport-out",
+        expected: ("wp_viewport", 2, When::Apply),
+        buffer: (64, 32),
+        state: |surface, viewport| {
+            // Fits the buffer, but not the buffer rotated to 32x64.
+            viewport.set_source(0.0, 0.0, 64.0, 32.0);
+            surface.set_buffer_transform(wl_output::Transform::_90);
+        },
+    },
+    Case {
+        name: "viewport-fractional-size",
+        expected: ("wp_viewport", 1, When::Apply),
+        buffer: (64, 64),
+        state: |_, viewport| viewport.set_source(0.0, 0.0, 31.5, 31.5),
+    },
+    Case {
+        name: "scale-odd-buffer",
+        expected: ("wl_surface", 2, When::Commit),
+        buffer: (63, 63),
+        state: |surface, _| surface.set_buffer_scale(2),
+    },
+];
+
+#[derive(Default)]
+struct Latch {
+    configured: bool,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Latch {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<xdg_wm_base::XdgWmBase, ()> for Latch {
+    fn event(
+        _: &mut Self,
+        wm_base: &xdg_wm_base::XdgWmBase,
+        event: xdg_wm_base::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<xdg_surface::XdgSurface, ()> for Latch {
+    fn event(
+        state: &mut Self,
+        xdg_surface: &xdg_surface::XdgSurface,
+        event: xdg_surface::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            xdg_surface.ack_configure(serial);
+            state.configured = true;
+        }
+    }
+}
+
+delegate_noop!(Latch: wl_compositor::WlCompositor);
+delegate_noop!(Latch: ignore wl_surface::WlSurface);
+delegate_noop!(Latch: ignore wl_shm::WlShm);
+delegate_noop!(Latch: wl_shm_pool::WlShmPool);
+delegate_noop!(Latch: ignore wl_buffer::WlBuffer);
+delegate_noop!(Latch: ignore xdg_toplevel::XdgToplevel);
+delegate_noop!(Latch: wp_fifo_manager_v1::WpFifoManagerV1);
+delegate_noop!(Latch: wp_fifo_v1::WpFifoV1);
+delegate_noop!(Latch: wp_viewporter::WpViewporter);
+delegate_noop!(Latch: wp_viewport::WpViewport);
+
+/// A black XRGB8888 buffer, in a zero-filled memfd.
+fn create_buffer(
+    shm: &wl_shm::WlShm,
+    qh: &QueueHandle<Latch>,
+    (width, height): (i32, i32),
+) -> wl_buffer::WlBuffer {
+    let size = width * height * 4;
+    // SAFETY: plain memfd_create and ftruncate calls, the fd is owned right away.
+    let fd = unsafe {
+        let fd = libc::memfd_create(c"fifo_test-latch".as_ptr(), libc::MFD_CLOEXEC);
+        assert!(fd >= 0, "memfd_create failed");
+        assert_eq!(
+            libc::ftruncate(fd, size as libc::off_t),
+            0,
+            "ftruncate failed"
+        );
+        OwnedFd::from_raw_fd(fd)
+    };
+    let pool = shm.create_pool(fd.as_fd(), size, qh, ());
+    let buffer = pool.create_buffer(
+        0,
+        width,
+        height,
+        width * 4,
+        wl_shm::Format::Xrgb8888,
+        qh,
+        (),
+    );
+    pool.destroy();
+    buffer
+}
+
+/// Waits up to `timeout` for a protocol error, `None` if the connection stays alive.
+fn wait_for_error(
+    conn: &Connection,
+    queue: &mut EventQueue<Latch>,
+    state: &mut Latch,
+    timeout: Duration,
+) -> Option<(String, u32)> {
+    let start = Instant::now();
+    loop {
+        if queue.roundtrip(state).is_err() {
+            return conn
+                .protocol_error()
+                .map(|error| (error.object_interface, error.code));
+        }
+        if start.elapsed() >= timeout {
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// Runs `case` on a fresh toplevel, returns what happened.
+fn run_case(case: &Case) -> Result<Option<(String, u32, When)>, &'static str> {
+    let conn = session::connect();
+    let (globals, mut queue) = registry_queue_init::<Latch>(&conn).unwrap();
+    let qh = queue.handle();
+    let compositor: wl_compositor::WlCompositor = globals
+        .bind(&qh, 1..=6, ())
+        .expect("wl_compositor not available");
+    let shm: wl_shm::WlShm = globals.bind(&qh, 1..=1, ()).expect("wl_shm not available");
+    let wm_base: xdg_wm_base::XdgWmBase = globals
+        .bind(&qh, 1..=1, ())
+        .expect("xdg shell is not available");
+    let manager: wp_fifo_manager_v1::WpFifoManagerV1 = globals
+        .bind(&qh, 1..=crate::FIFO_VERSION, ())
+        .map_err(|_| "no fifo")?;
+    let viewporter: wp_viewporter::WpViewporter = globals
+        .bind(&qh, 1..=1, ())
+        .map_err(|_| "no wp_viewporter")?;
+    if compositor.version() < 3 {
+        return Err("wl_compositor too old");
+    }
+
+    let surface = compositor.create_surface(&qh, ());
+    let fifo = manager.get_fifo(&surface, &qh, ());
+    let viewport = viewporter.get_viewport(&surface, &qh, ());
+    let xdg_surface = wm_base.get_xdg_surface(&surface, &qh, ());
+    let toplevel = xdg_surface.get_toplevel(&qh, ());
+    toplevel.set_title("fifo_test latch".into());
+    surface.commit();
+    let mut state = Latch::default();
+    while !state.configured {
+        queue
+            .blocking_dispatch(&mut state)
+            .map_err(|_| "connection lost before the first configure")?;
+    }
+
+    // A valid frame that sets the barrier, then the inconsistent one waiting for it.
+    surface.attach(Some(&create_buffer(&shm, &qh, (64, 64))), 0, 0);
+    surface.damage_buffer(0, 0, i32::MAX, i32::MAX);
+    fifo.set_barrier();
+    surface.commit();
+    surface.attach(Some(&create_buffer(&shm, &qh, case.buffer)), 0, 0);
+    surface.damage_buffer(0, 0, i32::MAX, i32::MAX);
+    (case.state)(&surface, &viewport);
+    fifo.wait_barrier();
+    surface.commit();
+
+    // A single roundtrip is much shorter than a refresh cycle, an error arriving with
+    // it was raised when the commit was received.
+    if let Some((interface, code)) = wait_for_error(&conn, &mut queue, &mut state, Duration::ZERO) {
+        return Ok(Some((interface, code, When::Commit)));
+    }
+    Ok(wait_for_error(&conn, &mut queue, &mut state, LATCH_TIMEOUT)
+        .map(|(interface, code)| (interface, code, When::Apply)))
+}
+
+/// Queues commits with transform, viewport and buffer size state that disagree and
+/// checks the compositor raises the right error at the time the specs require, at
+/// commit or when the queued state is applied, the `latch` subcommand.
+pub fn run() {
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for case in CASES {
+        let (interface, code, when) = case.expected;
+        let expected = format!("{} error {} {}", interface, code, when.name());
+        let (got, verdict) = match run_case(case) {
+            Err(reason) => {
+                skipped += 1;
+                (String::new(), format!("skipped, {}", reason))
+            }
+            Ok(got) => {
+                let pass = got
+                    .as_ref()
+                    .is_some_and(|(got_interface, got_code, got_when)| {
+                        got_interface == interface && *got_code == code && *got_when == when
+                    });
+                if pass {
+                    passed += 1;
+                } else {
+                    failed += 1;
+                }
+                let got = got.map_or("no error".to_string(), |(interface, code, when)| {
+                    format!("{} error {} {}", interface, code, when.name())
+                });
+                (got, if pass { "ok" } else { "FAILED" }.to_string())
+            }
+        };
+        println!(
+            "latch: {:<30} expected {:<30} got {:<30} {}",
+            case.name, expected, got, verdict
+        );
+    }
+    println!(
+        "latch: {} passed, {} failed, {} skipped",
+        passed, failed, skipped
+    );
+}
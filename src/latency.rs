@@ -0,0 +1,139 @@
+//! Commit to present latency of every frame, `--latency`.
+//!
+//! Every presented frame is logged with its latency, refresh, retrace counter and
+//! presentation flags, and every [`SUMMARY_INTERVAL`] of presentations a summary of
+//! the latest window is printed. Frames committed with a fifo barrier and without
+//! are kept apart, so toggling the fifo with the `f` key compares both in one run.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::flags;
+use crate::observer::{Committed, FrameObserver, Log};
+use crate::presentation::Presented;
+
+/// Presentation time covered by one rolling summary.
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(1);
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn name(barrier: bool) -> &'static str {
+    if barrier {
+        "barrier"
+    } else {
+        "no barrier"
+    }
+}
+
+/// Mean, median and maximum of `latencies`, sorted in place.
+fn summarize(latencies: &mut [Duration]) -> String {
+    latencies.sort();
+    format!(
+        "{} frames, mean {:.3}ms, median {:.3}ms, max {:.3}ms",
+        latencies.len(),
+        ms(latencies.iter().sum::<Duration>() / latencies.len() as u32),
+        ms(latencies[latencies.len() / 2]),
+        ms(latencies[latencies.len() - 1])
+    )
+}
+
+#[derive(Default)]
+pub struct Latency {
+    /// Whether a committed frame had a barrier, until its feedback.
+    frames: BTreeMap<u64, bool>,
+    /// Start of the current summary window, in presentation time.
+    window: Option<Duration>,
+    /// Latencies of the current window, without and with barrier.
+    recent: [Vec<Duration>; 2],
+    /// Latencies of the whole run, without and with barrier.
+    latencies: [Vec<Duration>; 2],
+    discarded: [u64; 2],
+}
+
+impl FrameObserver for Latency {
+    fn committed(&mut self, committed: &Committed) {
+        if committed.present {
+            self.frames.insert(committed.frame, committed.barrier);
+        }
+    }
+
+    fn presented(&mut self, frame: u64, presented: Option<&Presented>, _: &str, log: &Log) {
+        let Some(barrier) = self.frames.remove(&frame) else {
+            return;
+        };
+        // Feedback arrives in commit order, earlier frames never get any.
+        self.frames = self.frames.split_off(&frame);
+        let Some(presented) = presented else {
+            self.discarded[usize::from(barrier)] += 1;
+            if log.every != 0 {
+                println!(
+                    "{}Frame {} discarded ({})",
+                    log.prefix,
+                    frame,
+                    name(barrier)
+                );
+            }
+            return;
+        };
+        let latency = presented.time.saturating_sub(presented.committed);
+        if log.every != 0 {
+            println!(
+                "{}Frame {} presented after {:.3}ms ({}), refresh {:.3}ms, seq {}, {}",
+                log.prefix,
+                frame,
+                ms(latency),
+                name(barrier),
+                ms(presented.refresh),
+                presented.seq,
+                flags::describe(presented.flags)
+            );
+        }
+        self.latencies[usize::from(barrier)].push(latency);
+        self.recent[usize::from(barrier)].push(latency);
+
+        let start = *self.window.get_or_insert(presented.time);
+        if presented.time.saturating_sub(start) < SUMMARY_INTERVAL {
+            return;
+        }
+        self.window = Some(presented.time);
+        let summary = self
+            .recent
+            .iter_mut()
+            .zip([false, true])
+            .filter(|(latencies, _)| !latencies.is_empty())
+            .map(|(latencies, barrier)| format!("{}: {}", name(barrier), summarize(latencies)))
+            .collect::<Vec<_>>();
+        println!(
+            "{}latency over the last {:.3}s, {}",
+            log.prefix,
+            presented.time.saturating_sub(start).as_secs_f64(),
+            summary.join("; ")
+        );
+        self.recent = Default::default();
+    }
+
+    fn print_report(&self, prefix: &str) {
+        println!("{}latency: commit to present", prefix);
+        for (barrier, latencies) in [false, true].into_iter().zip(&self.latencies) {
+            let discarded = self.discarded[usize::from(barrier)];
+            if latencies.is_empty() {
+                println!(
+                    "{}  {:<10}  no frames presented, {} discarded",
+                    prefix,
+                    name(barrier),
+                    discarded
+                );
+                continue;
+            }
+            println!(
+                "{}  {:<10}  {}, {} discarded",
+                prefix,
+                name(barrier),
+                summarize(&mut latencies.clone()),
+                discarded
+            );
+        }
+    }
+}
@@ -0,0 +1,2719 @@
+//! Test client for the `wp_fifo_v1` protocol.
+//!
+//! The binary is a thin wrapper around [`main_with`]; custom frame patterns and
+//! scenarios can be added by calling it with an extended [`plugin::Registry`].
+
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::{CommandFactory, Parser, Subcommand};
+
+mod audit;
+mod backends;
+mod backoff;
+mod breakon;
+mod buffers;
+mod bugreport;
+mod cadence;
+mod cadence_string;
+pub mod canvas;
+mod capture;
+mod clock;
+mod commit_thread;
+mod controls;
+mod damage_grid;
+#[cfg(feature = "sqlite")]
+mod db;
+mod dbus;
+mod dry_run;
+mod energy;
+mod event_thread;
+mod extremes;
+mod fate;
+mod findings;
+mod flags;
+mod flood;
+mod frame_id;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod gamma;
+mod golden;
+mod hdr;
+mod hooks;
+mod html;
+mod hud;
+mod idle;
+mod inject;
+mod input;
+mod integrity;
+mod ipc;
+mod kiosk;
+mod latch;
+mod latency;
+mod matrix;
+mod memory;
+mod metrics;
+mod notify;
+mod observer;
+mod pacing;
+mod partial;
+mod patterns;
+mod pipeline;
+mod planner;
+pub mod plugin;
+mod predict;
+mod presentation;
+mod probe;
+mod profile;
+mod promote;
+mod protocol_log;
+mod pulldown;
+mod qr;
+mod relock;
+mod repl;
+mod report;
+mod resize;
+mod rng;
+mod sandbox;
+mod scale;
+mod scanout;
+mod scenarios;
+mod scheduler;
+mod screencast;
+#[cfg(feature = "scripting")]
+mod script;
+mod session;
+mod signals;
+mod skew;
+mod spanning;
+mod spike;
+mod split;
+mod startup;
+mod statedump;
+mod suspend;
+mod sweep;
+mod target;
+pub mod text;
+mod thermal;
+mod timeline;
+mod trace;
+mod trigger;
+mod trim;
+mod verdict;
+
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay_client_toolkit::reexports::calloop::{EventLoop, Idle, LoopHandle, RegistrationToken};
+use smithay_client_toolkit::reexports::calloop_wayland_source::WaylandSource;
+use smithay_client_toolkit::reexports::client::delegate_noop;
+use smithay_client_toolkit::reexports::client::{
+    globals::registry_queue_init,
+    protocol::{wl_keyboard, wl_output, wl_seat, wl_shm, wl_surface, wl_touch},
+    Connection, Proxy, QueueHandle,
+};
+use smithay_client_toolkit::reexports::csd_frame::WindowState;
+use smithay_client_toolkit::reexports::protocols::wp::commit_timing::v1::client::{
+    wp_commit_timer_v1, wp_commit_timing_manager_v1,
+};
+use smithay_client_toolkit::reexports::protocols::wp::fifo::v1::client::{
+    wp_fifo_manager_v1, wp_fifo_v1,
+};
+use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::wp_presentation;
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_keyboard, delegate_output, delegate_registry, delegate_seat,
+    delegate_shm, delegate_touch, delegate_xdg_shell, delegate_xdg_window,
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::SeatState,
+    shell::{
+        xdg::{
+            window::{Window, WindowConfigure, WindowDecorations, WindowHandler},
+            XdgShell, XdgSurface,
+        },
+        WaylandSurface,
+    },
+    shm::{
+        slot::{Buffer, SlotPool},
+        Shm, ShmHandler,
+    },
+};
+
+/// Highest wp_fifo_manager_v1 version implemented here.
+pub(crate) const FIFO_VERSION: u32 = 1;
+
+#[derive(Parser, Clone, Debug)] // requires `derive` feature
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// List the available patterns and scenarios and exit
+    #[arg(long, default_value_t = false)]
+    list: bool,
+
+    /// Content rendered into every frame
+    #[arg(long, default_value = "gradient")]
+    pattern: String,
+
+    /// Sequence of commits to run
+    #[arg(long, default_value = "continuous")]
+    scenario: String,
+
+    /// Load a Rhai scenario script, registered under its file name without extension (repeatable)
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "PATH")]
+    script: Vec<std::path::PathBuf>,
+
+    /// Load patterns and scenarios from a plugin shared object built with the same compiler and fifo_test version (repeatable)
+    #[arg(long, value_name = "PATH")]
+    plugin: Vec<std::path::PathBuf>,
+
+    /// Exit after this many frames
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    frames: Option<u64>,
+
+    /// Print the wall and CPU time spent in the frame loop, without the process start and connection setup
+    #[arg(long, default_value_t = false)]
+    loop_time: bool,
+
+    /// Print the progress, with an ETA if --frames is given, every this many seconds
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    progress: Option<u64>,
+
+    /// Directory relative output paths (--db, --bug-report, --capture-protocol, report files) are written to, defaults to the data directory inside Flatpak or a container
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<std::path::PathBuf>,
+
+    /// Wayland socket to connect to, instead of the inherited WAYLAND_DISPLAY
+    #[arg(long, value_name = "NAME")]
+    socket: Option<String>,
+
+    /// Runtime directory the socket is looked up in, instead of the inherited XDG_RUNTIME_DIR
+    #[arg(long, value_name = "DIR")]
+    runtime_dir: Option<std::path::PathBuf>,
+
+    /// Drop the variables of the outer session (WAYLAND_DISPLAY, DISPLAY, compositor IPC sockets), requires --socket
+    #[arg(long, default_value_t = false, requires = "socket")]
+    clean_env: bool,
+
+    /// Disable usage of wp_fifo_v1
+    #[arg(long, default_value_t = false)]
+    no_fifo: bool,
+
+    /// Keep submitting at full rate when the compositor stops presenting commits and releasing buffers, e.g. for deliberate flood tests
+    #[arg(long, default_value_t = false)]
+    no_backoff: bool,
+
+    /// Shell command to run before the test window is created (repeatable)
+    #[arg(long, value_name = "CMD")]
+    exec_before: Vec<String>,
+
+    /// Shell command to run after the test window was closed (repeatable)
+    #[arg(long, value_name = "CMD")]
+    exec_after: Vec<String>,
+
+    /// Compositor IPC used for --place and --move
+    #[arg(long, value_enum, default_value_t = ipc::Backend::Auto)]
+    ipc: ipc::Backend,
+
+    /// Place the window once mapped: output=<name>, workspace=<name>, floating, fullscreen or mode=<W>x<H>@<HZ> of its output (repeatable)
+    #[arg(long, value_name = "ACTION")]
+    place: Vec<ipc::Action>,
+
+    /// Apply a placement action after the given frame: <frame>:<action> (repeatable)
+    #[arg(long = "move", value_name = "FRAME:ACTION")]
+    moves: Vec<ipc::Move>,
+
+    /// Record the test window through the ScreenCast portal to this WebM file, with gst-launch-1.0
+    #[arg(long, value_name = "PATH", conflicts_with = "connections")]
+    screencast: Option<std::path::PathBuf>,
+
+    /// Capture the test window through the ScreenCast portal, compare every captured frame with the rendered one and fail the run if any differs
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "connections",
+        requires = "frame_id"
+    )]
+    verify_frames: bool,
+
+    /// Render a HUD strip with the fifo state and recent frame intervals
+    #[arg(long, default_value_t = false)]
+    hud: bool,
+
+    /// Add a histogram of the commit-to-present latency to the HUD
+    #[arg(long, default_value_t = false, requires = "hud")]
+    hud_latency: bool,
+
+    /// Draw the frame number in the top-left corner of every frame
+    #[arg(long, default_value_t = false)]
+    frame_counter: bool,
+
+    /// Stamp the frame id and its CRC-32 as a bit block into the top-right corner
+    #[arg(long, default_value_t = false)]
+    frame_id: bool,
+
+    /// Render a QR code with the frame id and the monotonic render timestamp
+    #[arg(long, default_value_t = false)]
+    qr: bool,
+
+    /// Toggle RTS/DTR of this serial port at commit time while flashing the window white/black, `-` prints a trigger line instead
+    #[arg(long, value_name = "TTY")]
+    trigger_serial: Option<std::path::PathBuf>,
+
+    /// Dispatch the fifo objects and frame callbacks on a dedicated queue and thread
+    #[arg(long, default_value_t = false)]
+    event_thread: bool,
+
+    /// Number of independent Wayland connections, each with its own window and fifo
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    connections: u32,
+
+    /// Render the pattern this many times per frame to scale the CPU cost of a frame
+    #[arg(long, value_name = "PASSES", value_parser = clap::value_parser!(u32).range(1..))]
+    fill_cost: Option<u32>,
+
+    /// Kiosk compositor mode: no decorations, fullscreen at the configured size, restart the measurement on size changes and print a short report
+    #[arg(long, default_value_t = false)]
+    kiosk: bool,
+
+    /// Detect a suspended toplevel that gets no frame callbacks or presentation feedback, and log it or pause
+    #[arg(long, value_enum, value_name = "ACTION")]
+    on_suspend: Option<suspend::OnSuspend>,
+
+    /// Time without frame signals after which a suspended toplevel counts as suspended, in ms
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    suspend_threshold: u64,
+
+    /// Mimic the commit pattern of a toolkit, overriding the scenario's throttling and --scheduler
+    #[arg(long, value_enum)]
+    profile: Option<profile::Profile>,
+
+    /// How to wait out the delay before a frame: timer, busy or blocking
+    #[arg(long, value_enum, default_value_t)]
+    scheduler: scheduler::Scheduler,
+
+    /// Override the throttling per connection, cycling through the list: fifo, frame-callback or unthrottled
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "PACING")]
+    mix: Vec<pacing::Pacing>,
+
+    /// Skew the commit-timing timestamps like a bad client clock: offset=<ms>,drift=<ppm>,jitter=<ms>[,seed=<n>]
+    #[arg(long, value_name = "offset=<ms>,drift=<ppm>,jitter=<ms>")]
+    clock_skew: Option<skew::Skew>,
+
+    /// Inject periodic render-time spikes: every=<n>,cost=<ms>
+    #[arg(long, value_name = "every=<n>,cost=<ms>")]
+    spike: Option<spike::Spike>,
+
+    /// Buffers the test window cycles through
+    #[arg(long, value_name = "COUNT", default_value_t = buffers::DEFAULT_COUNT, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=16))]
+    buffers: usize,
+
+    /// Initial size of the buffers and minimum size of the window
+    #[arg(long, value_name = "WIDTHxHEIGHT", default_value = "256x256")]
+    size: buffers::Size,
+
+    /// Grow the buffers every n frames, reallocating the pool: step=<px>,every=<n>[,max=<px>]
+    #[arg(long, value_name = "step=<px>,every=<n>")]
+    grow: Option<buffers::Growth>,
+
+    /// How the shm pool is adapted when the buffer size changes
+    #[arg(long, value_enum, default_value_t = buffers::PoolStrategy::Recreate)]
+    pool_strategy: buffers::PoolStrategy,
+
+    /// Lock the buffer memory with mlock
+    #[arg(long, default_value_t = false, conflicts_with = "madvise_every")]
+    mlock: bool,
+
+    /// Every n frames, madvise(MADV_DONTNEED) the buffers not held by the compositor
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    madvise_every: Option<u64>,
+
+    /// Render every frame but only commit the content of every nth, the others only commit their barrier
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    present_divisor: Option<u64>,
+
+    /// Issue attach, damage, barrier and commit from a secondary thread
+    #[arg(long, default_value_t = false)]
+    commit_thread: bool,
+
+    /// Record the configure, attach, barrier and commit order and report ordering violations
+    #[arg(long, default_value_t = false)]
+    audit: bool,
+
+    /// Fault to inject once the --inject-at frame was committed with a barrier
+    #[arg(long, value_enum, value_name = "FAULT")]
+    inject: Option<inject::Inject>,
+
+    /// First frame a fault is injected after, the next one with a barrier if it has none
+    #[arg(long, default_value_t = 120, value_name = "FRAME", requires = "inject")]
+    inject_at: u64,
+
+    /// After every configure but the first, ack the previous serial again so the latest ack is stale, a negative test of the compositor
+    #[arg(long, default_value_t = false)]
+    ack_stale_serial: bool,
+
+    /// Lint the protocol order of a WAYLAND_DEBUG=1 log instead of running the test
+    #[arg(long, value_name = "LOG")]
+    audit_replay: Option<std::path::PathBuf>,
+
+    /// Store the run manifest and per-frame data in this SQLite database
+    #[cfg(feature = "sqlite")]
+    #[arg(long, value_name = "PATH")]
+    db: Option<std::path::PathBuf>,
+
+    /// Print a summary of the runs stored in --db and exit
+    #[cfg(feature = "sqlite")]
+    #[arg(long, default_value_t = false, requires = "db")]
+    db_report: bool,
+
+    /// Request presentation feedback for every frame and classify it as presented, replaced or discarded on unmap
+    #[arg(long, default_value_t = false)]
+    fate: bool,
+
+    /// Capture the own toplevel with ext-image-copy-capture, compare every capture with the rendered frame of its frame id and fail the run if any differs
+    #[arg(long, default_value_t = false, requires = "frame_id")]
+    verify_capture: bool,
+
+    /// Hash every buffer before it is committed and verify it is unchanged once released
+    #[arg(long, default_value_t = false)]
+    verify_release: bool,
+
+    /// Run the test this many times and print a table comparing the runs
+    #[arg(long, default_value_t = 1, value_name = "COUNT", value_parser = clap::value_parser!(u32).range(1..))]
+    repeat: u32,
+
+    /// Also run the test with these options added, e.g. --compare=--no-fifo, and print a table comparing the runs (repeatable)
+    #[arg(long, value_name = "OPTIONS", allow_hyphen_values = true)]
+    compare: Vec<String>,
+
+    /// Pin the window to a new size every this many frames, oscillating between 256 and 384 pixels, and report frames presented with content of a stale size
+    #[arg(long, value_name = "FRAMES")]
+    resize_stress: Option<u64>,
+
+    /// Print a paragraph on whether the compositor queues barrier commits, latches one per refresh and clears the queue on unmap
+    #[arg(long, default_value_t = false)]
+    verdict: bool,
+
+    /// Submit frames at this rate, `auto` follows the refresh rate of the surface's output, `refresh/2` or `refresh*2` a fraction or multiple of it
+    #[arg(long, alias = "target", value_name = "FPS")]
+    target_fps: Option<target::TargetFps>,
+
+    /// Measure the energy the system uses during the run with RAPL or the battery and report it per frame
+    #[arg(long, default_value_t = false)]
+    energy: bool,
+
+    /// Stay idle this long before every commit, each with a barrier, and report the time to presentation after waking up, in ms
+    #[arg(long, value_name = "MS")]
+    idle_burst: Option<u64>,
+
+    /// Add this many 1x1 damage rectangles to every commit of alternating 60 frame blocks and compare the commit-to-present latency
+    #[arg(long, value_name = "COUNT")]
+    damage_flood: Option<u32>,
+
+    /// Record commit, frame callback, presentation and buffer release times of every frame and print a pipeline diagram
+    #[arg(long, default_value_t = false)]
+    pipeline: bool,
+
+    /// Print one character per refresh over the whole run, V for a new frame and - for a repeated one
+    #[arg(long, default_value_t = false)]
+    cadence_string: bool,
+
+    /// Set the surface up to make direct scanout likely or impossible
+    #[arg(long, value_enum)]
+    promote: Option<promote::Promote>,
+
+    /// Classify every frame as scanned out directly or composited from the zero-copy flag or its latency and compare the pacing of both
+    #[arg(long, default_value_t = false)]
+    detect_scanout: bool,
+
+    /// Replace the pattern with a bouncing square, only repainting what changed since the reused buffer was drawn and only damaging the square's old and new position
+    #[arg(long, default_value_t = false, conflicts_with_all = ["hud", "qr", "trigger_serial", "fill_cost"])]
+    partial_redraw: bool,
+
+    /// Flash one random cell of a grid per frame, only damaging it and the previous one, and map the latency per cell
+    #[arg(long, value_name = "COLUMNSxROWS", conflicts_with_all = ["partial_redraw", "hud", "qr", "trigger_serial", "fill_cost"])]
+    damage_grid: Option<damage_grid::Grid>,
+
+    /// Sample CPU and GPU frequencies and throttle indicators every second and separate the pacing of throttled seconds in the report
+    #[arg(long, default_value_t = false)]
+    thermal: bool,
+
+    /// Mark output mode changes, from the output, a `--move <frame>:mode=WxH@HZ` or the `o` key, and report when the pacing locks on the new refresh again
+    #[arg(long, default_value_t = false)]
+    mode_relock: bool,
+
+    /// Probe the gamma table of the output with wlr-gamma-control every 2s, mark other gamma or color changes with the `g` key, and report the hitches around them apart
+    #[arg(long, default_value_t = false)]
+    gamma_watch: bool,
+
+    /// Print the commit-to-present latency of every presented frame and a summary every second, frames with and without fifo barrier apart
+    #[arg(long, default_value_t = false)]
+    latency: bool,
+
+    /// Alternate between blocks of frames tagged as HDR10 with wp-color-management and untagged ones and compare their latency
+    #[arg(long, default_value_t = false)]
+    hdr: bool,
+
+    /// Predict the presentation time of every frame from its output's previous presentation and report the error
+    #[arg(long, default_value_t = false)]
+    predict: bool,
+
+    /// Commit every frame this many microseconds later after vblank than the previous one and map the latch deadline
+    #[arg(long, value_name = "MICROS")]
+    phase_sweep: Option<u64>,
+
+    /// Serve Prometheus metrics (frame rate, stalls, interval and latency quantiles) on this address
+    #[arg(long, value_name = "ADDR")]
+    metrics: Option<std::net::SocketAddr>,
+
+    /// Freeze, i.e. stop committing but keep dispatching, on the first presentation anomaly: anomaly=<miss|duplicate|out-of-order|any> (repeatable)
+    #[arg(long, value_name = "CONDITION")]
+    break_on: Vec<breakon::BreakOn>,
+
+    /// Send a desktop notification on presentation anomalies, at most one a minute: anomaly=<miss|duplicate|out-of-order|any>, defaults to any
+    #[arg(long, value_name = "CONDITION", num_args = 0..=1, default_missing_value = "anomaly=any")]
+    notify: Option<breakon::BreakOn>,
+
+    /// Group the presentation anomalies by signature, e.g. periodic or following buffer stalls, and lead the report with the largest groups
+    #[arg(long, default_value_t = false)]
+    findings: bool,
+
+    /// Pause after the first frame and read commands like `attach`, `barrier` and `commit` from stdin
+    #[arg(long, default_value_t = false)]
+    repl: bool,
+
+    /// Run with WAYLAND_DEBUG and collect the output, protocol log, environment and HTML frame timeline into this tar.gz
+    #[arg(long, value_name = "PATH")]
+    bug_report: Option<std::path::PathBuf>,
+
+    /// Number of protocol messages kept in the --bug-report bundle
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1000,
+        requires = "bug_report"
+    )]
+    bug_report_messages: usize,
+
+    /// Write the protocol log of every connection, as with WAYLAND_DEBUG, to this file
+    #[arg(long, value_name = "PATH")]
+    capture_protocol: Option<std::path::PathBuf>,
+
+    /// Validate the scenario and print its planned timeline, checked against the protocols of the compositor or of --capabilities, without committing anything
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Capability file for --dry-run, written from the compositor's protocols if it doesn't exist, read instead of connecting if it does
+    #[arg(long, value_name = "PATH", requires = "dry_run")]
+    capabilities: Option<std::path::PathBuf>,
+
+    /// Record every event the frame planning depends on with its timestamp to this file, to be replayed with the replay subcommand
+    #[arg(long, value_name = "PATH", conflicts_with = "connections")]
+    record_trace: Option<std::path::PathBuf>,
+
+    /// Write the frame timeline of the run as a standalone HTML page like `report html` to this file, of the first connection and without subcommands or repeats
+    #[arg(long, value_name = "PATH")]
+    html_timeline: Option<std::path::PathBuf>,
+
+    /// Don't print per-frame log lines
+    #[arg(long, short, default_value_t = false)]
+    quiet: bool,
+
+    /// Only print the per-frame log line for every nth frame
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    log_every: u64,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum Command {
+    /// Spawn more and more fifo surfaces until the pacing degrades
+    ScaleTest(scale::ScaleTest),
+    /// Run every combination of a parameter grid and compare them
+    Matrix(matrix::Matrix),
+    /// Print the protocols the compositor supports and which scenarios can run on it
+    Probe,
+    /// Send barrier requests without commits and watch the compositor slow down or grow
+    BarrierFlood(flood::BarrierFlood),
+    /// Send requests with extreme values and check the compositor's protocol errors
+    Extremes,
+    /// Queue commits with disagreeing transform, viewport and buffer size and check when the compositor rejects them
+    Latch,
+    /// Show the same animation paced with fifo and with frame callbacks side by side in one window
+    Split(split::Split),
+    /// Run the test with every scheduler and compare pacing and CPU usage
+    SchedulerTradeoff(scheduler::Tradeoff),
+    /// Produce reports to share, e.g. a compatibility record
+    Report(report::Report),
+    /// Replay a trace of --record-trace against the frame planning, without a compositor, and report where it plans differently
+    Replay(trace::Replay),
+}
+
+/// Result of a single test window.
+struct Outcome {
+    hook_outputs: Vec<hooks::HookOutput>,
+    /// Mean time between two drawn frames.
+    mean_interval: Option<Duration>,
+    /// Standard deviation of the time between two drawn frames.
+    interval_deviation: Option<Duration>,
+    lateness: scheduler::Lateness,
+    /// Captures that differed from the rendered frames.
+    mismatched: u64,
+    stats: plugin::Stats,
+    /// Energy of `--energy`, measured system wide, so only the first window of a run
+    /// carries it.
+    energy: Option<energy::Energy>,
+    /// Frame times of `--pipeline`.
+    pipeline: Option<pipeline::Pipeline>,
+    /// Fifo checks of `--verdict`.
+    verdict: Option<Vec<(verdict::Check, verdict::Status)>>,
+}
+
+/// Resolves the relative output paths of `args` in its output directory.
+fn resolve_outputs(args: &mut Args) {
+    let output_dir = args.output_dir.clone();
+    for path in [
+        &mut args.bug_report,
+        &mut args.capture_protocol,
+        &mut args.record_trace,
+        &mut args.capabilities,
+        &mut args.screencast,
+        &mut args.html_timeline,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *path = sandbox::resolve(output_dir.as_deref(), path);
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = args.db.as_mut() {
+        *path = sandbox::resolve(output_dir.as_deref(), path);
+    }
+}
+
+/// Rejects placement actions the compositor IPC of `args` can't carry out.
+fn check_placement(args: &Args) {
+    let mut actions = args
+        .place
+        .iter()
+        .chain(args.moves.iter().map(|mv| &mv.action));
+    if let Some(backend) = args.ipc.resolve() {
+        if !actions.all(|action| backend.supports(action)) {
+            Args::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "riverctl only acts on the focused view and can't target the test window, only mode switches are supported on river",
+                )
+                .exit();
+        }
+    }
+}
+
+/// Parses the command line and runs the test with the patterns and scenarios from
+/// `registry`.
+pub fn main_with(mut registry: plugin::Registry) {
+    let start = clock::monotonic();
+    let mut args = Args::parse();
+    args.output_dir = sandbox::output_dir(args.output_dir.take());
+    resolve_outputs(&mut args);
+
+    if let Some(path) = args.bug_report.as_ref() {
+        bugreport::run(&args, path, args.bug_report_messages);
+        return;
+    }
+    if !inject::runs_test(args.inject) {
+        inject::run_parent();
+        return;
+    }
+    for path in &args.plugin {
+        // SAFETY: loading a plugin was explicitly requested on the command line, and
+        // --plugin documents that it has to be built with the same compiler and version
+        // of this crate, as the entry point uses the Rust ABI.
+        if let Err(err) = unsafe { registry.load(path) } {
+            Args::command()
+                .error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("failed to load plugin `{}`: {}", path.display(), err),
+                )
+                .exit();
+        }
+    }
+
+    #[cfg(feature = "scripting")]
+    for path in &args.script {
+        if let Err(err) = script::register(&mut registry, path) {
+            Args::command()
+                .error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("failed to load script `{}`: {}", path.display(), err),
+                )
+                .exit();
+        }
+    }
+    let registry = Arc::new(registry);
+
+    if args.list {
+        registry.print_list();
+        return;
+    }
+    #[cfg(feature = "sqlite")]
+    if args.db_report {
+        let path = args.db.as_ref().expect("--db-report requires --db");
+        db::print_report(path).expect("Failed to query the results database");
+        return;
+    }
+    if let Some(path) = args.audit_replay.as_ref() {
+        let file = std::fs::File::open(path).expect("Failed to open the log to replay");
+        audit::Audit::replay(std::io::BufReader::new(file))
+            .expect("Failed to read the log to replay")
+            .print_report("");
+        return;
+    }
+    if !registry.has_pattern(&args.pattern) {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::InvalidValue,
+                format!("unknown pattern `{}`, see --list", args.pattern),
+            )
+            .exit();
+    }
+    if !registry.has_scenario(&args.scenario) {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::InvalidValue,
+                format!("unknown scenario `{}`, see --list", args.scenario),
+            )
+            .exit();
+    }
+    check_placement(&args);
+
+    session::prepare(
+        args.clean_env,
+        args.socket.as_deref(),
+        args.runtime_dir.as_deref(),
+    );
+    // Sets WAYLAND_DEBUG and spawns the first thread, so only after the environment
+    // is prepared.
+    let protocol_log = args.capture_protocol.as_ref().map(|path| {
+        protocol_log::ProtocolLog::start(path).expect("Failed to capture the protocol log")
+    });
+    if args.dry_run {
+        dry_run::run(&args, &registry);
+        finish_protocol_log(protocol_log, &args);
+        return;
+    }
+
+    let metrics = args.metrics.map(|addr| {
+        metrics::Metrics::serve(addr).unwrap_or_else(|err| {
+            Args::command()
+                .error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("failed to serve metrics on {}: {}", addr, err),
+                )
+                .exit()
+        })
+    });
+
+    let mut hook_outputs = hooks::run_all("before", &args.exec_before);
+    let mut mismatched = 0;
+
+    match args.command.as_ref() {
+        Some(Command::ScaleTest(test)) => scale::run(test, &args, &registry, start),
+        Some(Command::Matrix(matrix)) => {
+            let (outputs, differed) = matrix::run(matrix, &args, &registry, start);
+            hook_outputs.extend(outputs);
+            mismatched += differed;
+        }
+        Some(Command::Probe) => probe::run(&registry),
+        Some(Command::BarrierFlood(flood)) => flood::run(flood),
+        Some(Command::Extremes) => extremes::run(),
+        Some(Command::Latch) => latch::run(),
+        Some(Command::Split(split)) => split::run(split),
+        Some(Command::SchedulerTradeoff(tradeoff)) => {
+            scheduler::run(tradeoff, &args, &registry, start)
+        }
+        Some(Command::Report(report)) => report::run(report, &args, &registry, start),
+        Some(Command::Replay(replay)) => trace::run(replay, &registry),
+        None if args.repeat > 1 || !args.compare.is_empty() => {
+            let (outputs, differed) =
+                matrix::run_repeated(&args, &registry, start, metrics.as_deref());
+            hook_outputs.extend(outputs);
+            mismatched += differed;
+        }
+        None => {
+            let outcomes = run_connections(&args, &registry, start, metrics.as_deref());
+            if let (Some(path), Some(outcome)) = (args.html_timeline.as_ref(), outcomes.first()) {
+                html::write(path, outcome);
+            }
+            for outcome in outcomes {
+                hook_outputs.extend(outcome.hook_outputs);
+                mismatched += outcome.mismatched;
+            }
+        }
+    }
+
+    hook_outputs.extend(hooks::run_all("after", &args.exec_after));
+    hooks::print_report(&hook_outputs);
+
+    finish_protocol_log(protocol_log, &args);
+
+    if mismatched > 0 {
+        eprintln!(
+            "frame verification failed: {} captures differed from the rendered frames",
+            mismatched
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Restores stderr and reports the messages `--capture-protocol` wrote.
+fn finish_protocol_log(protocol_log: Option<protocol_log::ProtocolLog>, args: &Args) {
+    if let (Some(protocol_log), Some(path)) = (protocol_log, args.capture_protocol.as_ref()) {
+        let messages = protocol_log.finish();
+        println!(
+            "protocol log: {} messages written to {}",
+            messages,
+            path.display()
+        );
+    }
+}
+
+/// Runs `args.connections` test windows, each on its own connection and thread.
+fn run_connections(
+    args: &Args,
+    registry: &Arc<plugin::Registry>,
+    start: Duration,
+    metrics: Option<&metrics::Metrics>,
+) -> Vec<Outcome> {
+    let meter = args.energy.then(energy::Meter::start).flatten();
+    let mut outcomes = if args.connections == 1 {
+        vec![run(args, registry, start, metrics, None)]
+    } else {
+        run_threads(args, registry, start, metrics)
+    };
+
+    if let Some(meter) = meter {
+        let energy = meter.finish();
+        let frames = outcomes.iter().map(|outcome| outcome.stats.frames).sum();
+        println!(
+            "energy: {:.3}J from {}, {}",
+            energy.joules,
+            energy.source,
+            energy
+                .per_frame(frames)
+                .map_or("no frames".to_string(), |per_frame| format!(
+                    "{:.3}mJ per frame",
+                    per_frame
+                ))
+        );
+        if let Some(outcome) = outcomes.first_mut() {
+            outcome.energy = Some(energy);
+        }
+    }
+    outcomes
+}
+
+fn run_threads(
+    args: &Args,
+    registry: &Arc<plugin::Registry>,
+    start: Duration,
+    metrics: Option<&metrics::Metrics>,
+) -> Vec<Outcome> {
+    std::thread::scope(|scope| {
+        let runs = (0..args.connections)
+            .map(|index| {
+                std::thread::Builder::new()
+                    .name(format!("connection-{}", index + 1))
+                    .spawn_scoped(scope, move || {
+                        run(args, registry, start, metrics, Some(index + 1))
+                    })
+                    .expect("failed to spawn connection thread")
+            })
+            .collect::<Vec<_>>();
+        runs.into_iter()
+            .map(|run| run.join().expect("connection thread panicked"))
+            .collect()
+    })
+}
+
+/// Runs one test window on its own connection until it is closed.
+///
+/// `start` is the process start time the first frame is measured against,
+/// `connection` numbers the window when several connections are used.
+fn run(
+    args: &Args,
+    registry: &Arc<plugin::Registry>,
+    start: Duration,
+    metrics: Option<&metrics::Metrics>,
+    connection: Option<u32>,
+) -> Outcome {
+    let conn = session::connect();
+    let (globals, event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    let mut event_loop: EventLoop<SimpleWindow> =
+        EventLoop::try_new().expect("Failed to initialize the event loop!");
+    let loop_handle = event_loop.handle();
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(loop_handle)
+        .unwrap();
+    let compositor = CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
+    let xdg_shell = XdgShell::bind(&globals, &qh).expect("xdg shell is not available");
+    let shm = Shm::bind(&globals, &qh).expect("wl shm is not available.");
+    let presentation: Option<wp_presentation::WpPresentation> = globals.bind(&qh, 1..=1, ()).ok();
+    if args.phase_sweep.is_some() && presentation.is_none() {
+        eprintln!("phase sweep requested, but wp_presentation is unavailable");
+    }
+    let surface = compositor.create_surface(&qh);
+    let decorations = if args.kiosk {
+        WindowDecorations::None
+    } else {
+        WindowDecorations::RequestServer
+    };
+    let window = xdg_shell.create_window(surface, decorations, &qh);
+
+    // Zero disables the per-frame log lines.
+    let log_every = if args.quiet { 0 } else { args.log_every };
+
+    let event_thread = args
+        .event_thread
+        .then(|| event_thread::EventThread::spawn(&conn, log_every, start));
+
+    let scenario = registry
+        .scenario(&args.scenario)
+        .expect("scenario was validated");
+    let fifo_manager: Option<wp_fifo_manager_v1::WpFifoManagerV1> = if !args.no_fifo {
+        let advertised = globals.contents().with_list(|list| {
+            list.iter()
+                .find(|global| {
+                    global.interface == wp_fifo_manager_v1::WpFifoManagerV1::interface().name
+                })
+                .map(|global| global.version)
+        });
+        match advertised {
+            None => {
+                eprintln!("fifo requested, but unavailable");
+                None
+            }
+            Some(version) if version < scenario.min_fifo_version() => {
+                eprintln!(
+                    "scenario `{}` needs wp_fifo_manager_v1 version {}, the compositor has version {}, running without fifo",
+                    args.scenario,
+                    scenario.min_fifo_version(),
+                    version
+                );
+                None
+            }
+            Some(version) => {
+                if version > FIFO_VERSION {
+                    println!(
+                        "compositor advertises wp_fifo_manager_v1 version {}, using version {}",
+                        version, FIFO_VERSION
+                    );
+                }
+                match event_thread.as_ref() {
+                    Some(thread) => globals.bind(thread.handle(), 1..=FIFO_VERSION, ()).ok(),
+                    None => globals.bind(&qh, 1..=FIFO_VERSION, ()).ok(),
+                }
+            }
+        }
+    } else {
+        None
+    };
+    let fifo_version = fifo_manager.as_ref().map(Proxy::version);
+    let fifo = fifo_manager
+        .as_ref()
+        .map(|fifo_manager| match event_thread.as_ref() {
+            Some(thread) => fifo_manager.get_fifo(window.wl_surface(), thread.handle(), ()),
+            None => fifo_manager.get_fifo(window.wl_surface(), &qh, ()),
+        });
+    let mut audit = args.audit.then(audit::Audit::default);
+    if let Some(audit) = audit.as_mut().filter(|_| fifo.is_some()) {
+        audit.record(audit::Event::GetFifo(
+            window.wl_surface().id().protocol_id(),
+        ));
+    }
+    let clock: Rc<dyn clock::Clock> = Rc::new(clock::Monotonic);
+    let planner = planner::Planner::new(args, scenario, connection, clock.clone());
+    let label = match (connection, planner.pacing) {
+        (Some(index), Some(pacing)) => Some(format!("connection {}, {}", index, pacing.name())),
+        (Some(index), None) => Some(format!("connection {}", index)),
+        (None, Some(pacing)) => Some(pacing.name().to_string()),
+        (None, None) => None,
+    };
+    let title = match label.as_ref() {
+        Some(label) => format!("Wayland Fifo Test ({})", label),
+        None => "Wayland Fifo Test".to_string(),
+    };
+    let capture = args
+        .verify_capture
+        .then(|| capture::Capture::bind(&globals, &qh, &shm, title.clone(), "fifo_test".into()))
+        .flatten();
+    let hdr = args
+        .hdr
+        .then(|| hdr::Hdr::bind(&globals, &qh, window.wl_surface()))
+        .flatten();
+    window.set_title(title);
+    window.set_app_id("fifo_test");
+    let buffers::Size { width, height } = args.size;
+    window.set_min_size(Some((width, height)));
+    if args.kiosk || args.promote.is_some_and(promote::Promote::fullscreen) {
+        window.set_fullscreen(None);
+    }
+    if let Some(promote) = args.promote {
+        promote.apply(window.wl_surface(), &compositor);
+        println!("promoting to {}", promote.name());
+    }
+    window.commit();
+    if let Some(audit) = audit.as_mut() {
+        audit.record(audit::Event::Commit);
+    }
+    let commit_timer = globals
+        .bind::<wp_commit_timing_manager_v1::WpCommitTimingManagerV1, _, _>(&qh, 1..=1, ())
+        .ok()
+        .map(|manager| manager.get_timer(window.wl_surface(), &qh, ()));
+    let commit_thread = args.commit_thread.then(|| {
+        commit_thread::CommitThread::spawn(
+            conn.clone(),
+            window.wl_surface().clone(),
+            fifo.clone(),
+            commit_timer.clone(),
+        )
+    });
+
+    let mut pool =
+        SlotPool::new(width as usize * height as usize * 4, &shm).expect("Failed to create pool");
+
+    let mut pattern = registry
+        .pattern(&args.pattern)
+        .expect("pattern was validated");
+    let format = args
+        .promote
+        .map_or(wl_shm::Format::Argb8888, promote::Promote::format);
+    let buffers = buffers::create(
+        &mut pool,
+        width,
+        height,
+        args.buffers,
+        format,
+        pattern.as_mut(),
+    );
+
+    let mut simple_window = SimpleWindow {
+        registry_state: RegistryState::new(&globals),
+        output_state: OutputState::new(&globals, &qh),
+        seat_state: SeatState::new(&globals, &qh),
+        shm,
+        _fifo_manager: fifo_manager,
+        presentation,
+        presentation_clock: None,
+
+        exit: false,
+        first_configure: true,
+        pool,
+        buffers,
+        width,
+        height,
+        growth: args.grow,
+        pool_strategy: args.pool_strategy,
+        format,
+        promote: args.promote,
+        pattern,
+        planner,
+        registry: registry.clone(),
+        stats: plugin::Stats::default(),
+        mlock: args.mlock,
+        madvise_every: args.madvise_every,
+        window,
+        fifo,
+        fifo_enabled: true,
+        paused: args.repl,
+        frozen: false,
+        last_commit: None,
+        last_presented: None,
+        breaker: (!args.break_on.is_empty()).then(|| breakon::Breaker::new(args.break_on.clone())),
+        observers: observers(args),
+        repl: args.repl.then(repl::Repl::default),
+        awaiting_frame_callback: None,
+        qh: qh.clone(),
+        touch: None,
+        taps: Default::default(),
+        keyboard: None,
+        keyboard_seat: None,
+        move_probe: Default::default(),
+        help: false,
+        clock: clock.clone(),
+        last_draw: None,
+        interval_sum: Duration::ZERO,
+        interval_count: 0,
+        interval_square_sum: 0.0,
+        scheduler: args
+            .profile
+            .and_then(profile::Profile::scheduler)
+            .unwrap_or(args.scheduler),
+        pending_draw: None,
+        attached: vec![None; args.buffers],
+        buffer_wait: None,
+        deadline: None,
+        lateness: Default::default(),
+        frame: 0,
+        max_frames: args.frames,
+        log_every,
+        waited_for_buffer: false,
+        hud: args.hud.then(|| hud::Hud::new(args.hud_latency)),
+        frame_counter: args.frame_counter,
+        frame_id: args.frame_id,
+        qr: args.qr,
+        trigger: args.trigger_serial.as_deref().map(|path| {
+            trigger::Trigger::open(path).expect("Failed to open the trigger serial port")
+        }),
+        conn: conn.clone(),
+        event_thread,
+        commit_thread,
+        fill_cost: args.fill_cost,
+        spike: args.spike.map(spike::SpikeInjector::new),
+        loop_handle: event_loop.handle(),
+        placement: ipc::Placement::new(args.ipc, args.place.clone(), args.moves.clone()),
+        startup: startup::Startup::new(start, fifo_version),
+        start,
+        audit,
+        configured_serial: None,
+        acked_serial: None,
+        ack_stale_serial: args.ack_stale_serial,
+        inject: args.inject,
+        inject_at: args.inject_at,
+        disconnected: false,
+        damage_flood: args.damage_flood.map(flood::DamageFlood::new),
+        spanning: Default::default(),
+        flags: Default::default(),
+        partial: args
+            .partial_redraw
+            .then(|| partial::Partial::new(args.buffers)),
+        trace: args
+            .record_trace
+            .as_ref()
+            .map(|path| trace::Recorder::create(path).expect("Failed to create the trace")),
+        thermal: args.thermal.then(thermal::Thermal::start),
+        relock: args.mode_relock.then(relock::Relock::default),
+        gamma: args.gamma_watch.then(|| gamma::Gamma::bind(&globals, &qh)),
+        damage_grid: args
+            .damage_grid
+            .map(|grid| damage_grid::DamageGrid::new(grid, clock.now().as_nanos() as u64)),
+        resize_stress: args
+            .resize_stress
+            .map(|period| resize::ResizeStress::new(period, (width, height))),
+        verdict: args.verdict.then(verdict::Verdict::default),
+        clock_skew: args
+            .clock_skew
+            .map(|skew| skew::ClockSkew::new(skew, start)),
+        output: None,
+        cadence: args
+            .present_divisor
+            .map(|divisor| cadence::Cadence::new(divisor, clock.clone())),
+        fates: args.fate.then(fate::Fates::default),
+        pulldown: None,
+        pipeline: (args.pipeline || args.html_timeline.is_some())
+            .then(|| pipeline::Pipeline::new(args.buffers)),
+        sweep: args
+            .phase_sweep
+            .map(|step| sweep::Sweep::new(Duration::from_micros(step))),
+        commit_timer,
+        capture,
+        hdr,
+        integrity: args
+            .verify_release
+            .then(|| integrity::Integrity::new(args.buffers)),
+        metrics: metrics.map(|metrics| metrics.register(connection.unwrap_or(1))),
+        #[cfg(feature = "sqlite")]
+        db: args.db.as_ref().map(|_| {
+            db::Recorder::new(db::Manifest::current(
+                start,
+                connection,
+                &args.pattern,
+                &args.scenario,
+                fifo_version.is_some(),
+                args.capture_protocol.clone(),
+            ))
+        }),
+        kiosk: args.kiosk.then(kiosk::Kiosk::default),
+        suspend: args.on_suspend.map(|mode| {
+            suspend::Suspend::new(
+                mode,
+                Duration::from_millis(args.suspend_threshold),
+                clock.clone(),
+            )
+        }),
+        log_prefix: label
+            .map(|label| format!("[{}] ", label))
+            .unwrap_or_default(),
+        golden: args
+            .verify_frames
+            .then(|| Arc::new(Mutex::new(golden::Golden::default()))),
+    };
+
+    if simple_window.mlock {
+        simple_window.lock_buffers();
+    }
+    if log_every != 0 {
+        println!(
+            "{}log timestamps are relative to CLOCK_MONOTONIC {:.6}",
+            simple_window.log_prefix,
+            start.as_secs_f64()
+        );
+        println!(
+            "{}presentation clock offset: CLOCK_REALTIME - CLOCK_MONOTONIC = {:.6}",
+            simple_window.log_prefix,
+            clock::realtime_offset().as_secs_f64()
+        );
+    }
+    if let Some(profile) = args.profile {
+        println!(
+            "{}emulating {}, {} scheduler",
+            simple_window.log_prefix,
+            profile.name(),
+            simple_window.scheduler.name()
+        );
+    }
+
+    #[cfg(feature = "gamepad")]
+    gamepad::init(&simple_window.loop_handle);
+    if args.repl {
+        repl::init(&simple_window.loop_handle);
+    }
+    statedump::init(&simple_window.loop_handle);
+    signals::init(&simple_window.loop_handle);
+    if let Some(interval) = args.progress {
+        let interval = Duration::from_secs(interval);
+        let started = Instant::now();
+        simple_window
+            .loop_handle
+            .insert_source(Timer::from_duration(interval), move |_, _, window| {
+                window.print_progress(started.elapsed());
+                TimeoutAction::ToDuration(interval)
+            })
+            .unwrap();
+    }
+
+    if simple_window.thermal.is_some() {
+        simple_window
+            .loop_handle
+            .insert_source(
+                Timer::from_duration(thermal::SAMPLE_INTERVAL),
+                |_, _, window| {
+                    if let Some(thermal) = window.thermal.as_mut() {
+                        thermal.sample(&window.log_prefix);
+                    }
+                    TimeoutAction::ToDuration(thermal::SAMPLE_INTERVAL)
+                },
+            )
+            .unwrap();
+    }
+    let golden = simple_window.golden.clone();
+    let screencast = (args.screencast.is_some() || golden.is_some())
+        .then(|| screencast::Screencast::start(args.screencast.as_deref(), golden.clone()))
+        .flatten();
+
+    if simple_window.gamma.is_some() {
+        simple_window
+            .loop_handle
+            .insert_source(
+                Timer::from_duration(gamma::PROBE_INTERVAL),
+                move |_, _, window| {
+                    let name = window.output_name(window.output.as_ref());
+                    if let Some(gamma) = window.gamma.as_mut() {
+                        gamma.probe(window.output.as_ref(), name, &qh);
+                    }
+                    TimeoutAction::ToDuration(gamma::PROBE_INTERVAL)
+                },
+            )
+            .unwrap();
+    }
+
+    let loop_started = (clock::monotonic(), clock::process_cpu());
+    // We don't draw immediately, the configure will notify us when to first draw.
+    loop {
+        let dispatched = event_loop.dispatch(Duration::from_millis(1), &mut simple_window);
+        if simple_window.disconnected {
+            break;
+        }
+        dispatched.unwrap();
+        simple_window.poll_releases();
+
+        if simple_window.exit {
+            println!("{}exiting example", simple_window.log_prefix);
+            break;
+        }
+    }
+    if args.loop_time {
+        // The CPU time is the one of the whole process, of all connections and the
+        // commit thread.
+        println!(
+            "{}frame loop: frames={} wall={:.6}s cpu={:.6}s",
+            simple_window.log_prefix,
+            simple_window.frame,
+            clock::monotonic()
+                .saturating_sub(loop_started.0)
+                .as_secs_f64(),
+            clock::process_cpu()
+                .saturating_sub(loop_started.1)
+                .as_secs_f64()
+        );
+    }
+
+    if simple_window.fates.is_some() || simple_window.verdict.is_some() {
+        // Unmap the surface so the compositor discards whatever is still queued.
+        if let Some(fates) = simple_window.fates.as_mut() {
+            fates.unmapped();
+        }
+        if let Some(verdict) = simple_window.verdict.as_mut() {
+            verdict.unmapped();
+        }
+        let surface = simple_window.window.wl_surface();
+        surface.attach(None, 0, 0);
+        surface.commit();
+        if conn.roundtrip().is_ok() {
+            let _ = event_loop.dispatch(Duration::ZERO, &mut simple_window);
+        }
+    }
+
+    if let Some(spike) = simple_window.spike.as_ref() {
+        spike.print_report();
+    }
+    for observer in &simple_window.observers {
+        observer.print_report(&simple_window.log_prefix);
+    }
+    match simple_window.kiosk.as_ref() {
+        Some(kiosk) => kiosk.print_report(&simple_window.log_prefix, simple_window.fifo.is_some()),
+        None => simple_window
+            .startup
+            .print_report(&simple_window.log_prefix),
+    }
+    if let Some(cadence) = simple_window.cadence.as_ref() {
+        cadence.print_report(&simple_window.log_prefix);
+    }
+    if let Some(suspend) = simple_window.suspend.as_ref() {
+        suspend.print_report(&simple_window.log_prefix);
+    }
+    if let Some(capture) = simple_window.capture.as_ref() {
+        capture.print_report(&simple_window.log_prefix);
+    }
+    if let Some(integrity) = simple_window.integrity.as_ref() {
+        integrity.print_report(&simple_window.log_prefix);
+    }
+    if let Some(pulldown) = simple_window.pulldown.as_ref() {
+        pulldown.print_report(&simple_window.log_prefix);
+    }
+    if let Some(sweep) = simple_window.sweep.as_ref() {
+        sweep.print_report(&simple_window.log_prefix);
+    }
+    if let Some(pipeline) = simple_window.pipeline.as_ref().filter(|_| args.pipeline) {
+        pipeline.print_report(&simple_window.log_prefix);
+    }
+    simple_window
+        .planner
+        .print_report(&simple_window.log_prefix);
+    if let Some(hdr) = simple_window.hdr.as_ref() {
+        hdr.print_report(&simple_window.log_prefix);
+    }
+    simple_window
+        .spanning
+        .print_report(&simple_window.log_prefix);
+    simple_window.flags.print_report(&simple_window.log_prefix);
+    if let Some(partial) = simple_window.partial.as_ref() {
+        partial.print_report(&simple_window.log_prefix);
+    }
+    if let Some(grid) = simple_window.damage_grid.as_ref() {
+        grid.print_report(&simple_window.log_prefix);
+    }
+    if let Some(thermal) = simple_window.thermal.as_ref() {
+        thermal.print_report(&simple_window.log_prefix);
+    }
+    if let Some(relock) = simple_window.relock.as_ref() {
+        relock.print_report(&simple_window.log_prefix);
+    }
+    if let Some(gamma) = simple_window.gamma.as_ref() {
+        gamma.print_report(&simple_window.log_prefix);
+    }
+    if let Some(flood) = simple_window.damage_flood.as_ref() {
+        flood.print_report(&simple_window.log_prefix);
+    }
+    if let Some(stress) = simple_window.resize_stress.as_ref() {
+        stress.print_report(&simple_window.log_prefix);
+    }
+    if let Some(fates) = simple_window.fates.as_ref() {
+        fates.print_report(&simple_window.log_prefix);
+    }
+    if let Some(verdict) = simple_window.verdict.as_ref() {
+        verdict.print_report(&simple_window.log_prefix);
+    }
+    if let Some(skew) = simple_window.clock_skew.as_ref() {
+        skew.print_report(&simple_window.log_prefix);
+    }
+    if let Some(audit) = simple_window.audit.as_ref() {
+        audit.print_report(&simple_window.log_prefix);
+    }
+    if let Some(screencast) = screencast {
+        screencast.finish();
+    }
+    let mismatched = golden.map_or(0, |golden| {
+        let golden = golden.lock().unwrap();
+        golden.print_report(&simple_window.log_prefix, "verify");
+        golden.mismatched()
+    }) + simple_window
+        .capture
+        .as_ref()
+        .map_or(0, capture::Capture::mismatched);
+    #[cfg(feature = "sqlite")]
+    if let (Some(recorder), Some(path)) = (simple_window.db.as_ref(), args.db.as_ref()) {
+        if let Err(err) = recorder.store(path) {
+            eprintln!(
+                "{}failed to store the run in {}: {}",
+                simple_window.log_prefix,
+                path.display(),
+                err
+            );
+        }
+    }
+
+    Outcome {
+        hook_outputs: simple_window
+            .placement
+            .take()
+            .map_or_else(Vec::new, ipc::Placement::finish),
+        mean_interval: (simple_window.interval_count > 0)
+            .then(|| simple_window.interval_sum / simple_window.interval_count),
+        interval_deviation: (simple_window.interval_count > 0).then(|| {
+            let count = f64::from(simple_window.interval_count);
+            let mean = simple_window.interval_sum.as_secs_f64() / count;
+            let variance = simple_window.interval_square_sum / count - mean * mean;
+            Duration::from_secs_f64(variance.max(0.0).sqrt())
+        }),
+        lateness: simple_window.lateness,
+        mismatched,
+        energy: None,
+        stats: simple_window.stats,
+        pipeline: simple_window.pipeline,
+        verdict: simple_window.verdict.as_ref().map(verdict::Verdict::checks),
+    }
+}
+
+/// The analyzers of `args` only watching the frames, see [`observer`].
+fn observers(args: &Args) -> Vec<Box<dyn observer::FrameObserver>> {
+    let mut observers: Vec<Box<dyn observer::FrameObserver>> = Vec::new();
+    if args.findings {
+        observers.push(Box::new(findings::Findings::default()));
+    }
+    if args.predict {
+        observers.push(Box::new(predict::Predictor::default()));
+    }
+    if args.cadence_string {
+        observers.push(Box::new(cadence_string::CadenceString::default()));
+    }
+    if args.detect_scanout {
+        observers.push(Box::new(scanout::Scanout::default()));
+    }
+    if args.latency {
+        observers.push(Box::new(latency::Latency::default()));
+    }
+    if let Some(condition) = args.notify {
+        observers.push(Box::new(notify::Notifier::new(condition)));
+    }
+    observers
+}
+
+struct SimpleWindow {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    seat_state: SeatState,
+    shm: Shm,
+    _fifo_manager: Option<wp_fifo_manager_v1::WpFifoManagerV1>,
+    presentation: Option<wp_presentation::WpPresentation>,
+    /// Clock the compositor reports presentation timestamps in.
+    presentation_clock: Option<u32>,
+
+    exit: bool,
+    first_configure: bool,
+    pool: SlotPool,
+    buffers: Vec<Buffer>,
+    width: u32,
+    height: u32,
+    growth: Option<buffers::Growth>,
+    pool_strategy: buffers::PoolStrategy,
+    /// Format of the buffers, XRGB8888 with `--promote scanout`.
+    format: wl_shm::Format,
+    promote: Option<promote::Promote>,
+    pattern: Box<dyn plugin::FramePattern>,
+    planner: planner::Planner,
+    /// For patterns switched to by the scenario.
+    registry: Arc<plugin::Registry>,
+    stats: plugin::Stats,
+    mlock: bool,
+    madvise_every: Option<u64>,
+    window: Window,
+    fifo: Option<wp_fifo_v1::WpFifoV1>,
+    fifo_enabled: bool,
+    paused: bool,
+    /// Stopped by --break-on until resumed.
+    frozen: bool,
+    breaker: Option<breakon::Breaker>,
+    /// Analyzers only watching the frames go by.
+    observers: Vec<Box<dyn observer::FrameObserver>>,
+    /// Clock time of the last commit and whether it set a barrier.
+    last_commit: Option<(Duration, bool)>,
+    last_presented: Option<(u64, Duration)>,
+    repl: Option<repl::Repl>,
+    /// Delay to apply once the pending frame callback is done.
+    awaiting_frame_callback: Option<Duration>,
+    qh: QueueHandle<SimpleWindow>,
+    touch: Option<wl_touch::WlTouch>,
+    taps: input::TapDetector,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    keyboard_seat: Option<wl_seat::WlSeat>,
+    move_probe: input::MoveProbe,
+    /// Whether the help overlay is shown, toggled from the keyboard.
+    help: bool,
+    /// Time of the pacing decisions, shared with the planner and the watchdogs.
+    clock: Rc<dyn clock::Clock>,
+    last_draw: Option<Duration>,
+    interval_sum: Duration,
+    interval_count: u32,
+    /// Sum of the squared intervals in seconds, for their deviation.
+    interval_square_sum: f64,
+    scheduler: scheduler::Scheduler,
+    /// Source of the scheduled draw, until it draws.
+    pending_draw: Option<RegistrationToken>,
+    /// Frame each buffer was last committed with, until its release.
+    attached: Vec<Option<u64>>,
+    /// Retry of a draw that found no free buffer, until it draws.
+    buffer_wait: Option<Idle<'static>>,
+    /// When the scheduled draw was due.
+    deadline: Option<Duration>,
+    lateness: scheduler::Lateness,
+    frame: u64,
+    max_frames: Option<u64>,
+    log_every: u64,
+    waited_for_buffer: bool,
+    hud: Option<hud::Hud>,
+    frame_counter: bool,
+    frame_id: bool,
+    qr: bool,
+    trigger: Option<trigger::Trigger>,
+    conn: Connection,
+    event_thread: Option<event_thread::EventThread>,
+    commit_thread: Option<commit_thread::CommitThread>,
+    fill_cost: Option<u32>,
+    spike: Option<spike::SpikeInjector>,
+    loop_handle: LoopHandle<'static, SimpleWindow>,
+    placement: Option<ipc::Placement>,
+    golden: Option<Arc<Mutex<golden::Golden>>>,
+    startup: startup::Startup,
+    audit: Option<audit::Audit>,
+    /// Latest configure serial and the serial acked last.
+    configured_serial: Option<u32>,
+    acked_serial: Option<u32>,
+    ack_stale_serial: bool,
+    inject: Option<inject::Inject>,
+    inject_at: u64,
+    /// Set once the connection was shut down by --inject.
+    disconnected: bool,
+    /// `CLOCK_MONOTONIC` process start, log timestamps are relative to it.
+    start: Duration,
+    damage_flood: Option<flood::DamageFlood>,
+    /// Presentations per output while on several.
+    spanning: spanning::Spanning,
+    /// Kind flags of the presented frames.
+    flags: flags::Flags,
+    partial: Option<partial::Partial>,
+    trace: Option<trace::Recorder>,
+    thermal: Option<thermal::Thermal>,
+    relock: Option<relock::Relock>,
+    gamma: Option<gamma::Gamma>,
+    damage_grid: Option<damage_grid::DamageGrid>,
+    resize_stress: Option<resize::ResizeStress>,
+    verdict: Option<verdict::Verdict>,
+    clock_skew: Option<skew::ClockSkew>,
+    /// Output the surface entered last.
+    output: Option<wl_output::WlOutput>,
+    cadence: Option<cadence::Cadence>,
+    fates: Option<fate::Fates>,
+    /// Cadence analysis, created with the first frame carrying a presentation time.
+    pulldown: Option<pulldown::Pulldown>,
+    pipeline: Option<pipeline::Pipeline>,
+    commit_timer: Option<wp_commit_timer_v1::WpCommitTimerV1>,
+    /// Phase sweep, the next frame is scheduled from the presentation feedback.
+    sweep: Option<sweep::Sweep>,
+    capture: Option<capture::Capture>,
+    hdr: Option<hdr::Hdr>,
+    integrity: Option<integrity::Integrity>,
+    metrics: Option<std::sync::Arc<std::sync::Mutex<metrics::Stats>>>,
+    #[cfg(feature = "sqlite")]
+    db: Option<db::Recorder>,
+    kiosk: Option<kiosk::Kiosk>,
+    suspend: Option<suspend::Suspend>,
+    log_prefix: String,
+}
+
+impl CompositorHandler for SimpleWindow {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_factor: i32,
+    ) {
+        // Not needed for this example.
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+        // Not needed for this example.
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.frame_done(self.clock.now());
+        }
+        if let Some(suspend) = self.suspend.as_mut() {
+            suspend.signal();
+        }
+        if self.repl.is_some() {
+            println!("{} frame callback done", clock::stamp(self.start));
+        }
+        if let Some(pipeline) = self.pipeline.as_mut() {
+            pipeline.callback(self.clock.now());
+        }
+        if let Some(delay) = self.awaiting_frame_callback.take() {
+            if !self.paused {
+                self.schedule_draw(delay);
+            }
+        }
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        self.output = Some(output.clone());
+        self.spanning.enter(output);
+        self.output_mode_changed(output);
+        let name = self.output_name(Some(output));
+        if let Some(placement) = self.placement.as_mut() {
+            placement.place(Some(&name));
+        }
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        let remaining = self.spanning.leave(output);
+        if self.output.as_ref() == Some(output) {
+            if let Some(remaining) = remaining.as_ref() {
+                self.output_mode_changed(remaining);
+            }
+            self.output = remaining;
+        }
+    }
+}
+
+impl OutputHandler for SimpleWindow {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        if self.output.as_ref() == Some(&output) {
+            self.output_mode_changed(&output);
+        }
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl WindowHandler for SimpleWindow {
+    fn request_close(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &Window) {
+        self.exit = true;
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        window: &Window,
+        configure: WindowConfigure,
+        serial: u32,
+    ) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.configure(
+                self.clock.now(),
+                configure
+                    .new_size
+                    .0
+                    .zip(configure.new_size.1)
+                    .map(|(width, height)| (width.get(), height.get())),
+            );
+        }
+        // The configure was already acked by the toolkit when we get here.
+        if let Some(audit) = self.audit.as_mut() {
+            audit.record(audit::Event::Configure(serial));
+            audit.record(audit::Event::AckConfigure(serial));
+        }
+        let previous = self.configured_serial.replace(serial);
+        self.acked_serial = Some(serial);
+        if let Some(stale) = previous.filter(|_| self.ack_stale_serial) {
+            println!(
+                "{}acking stale configure {} after {}",
+                self.log_prefix, stale, serial
+            );
+            window.xdg_surface().ack_configure(stale);
+            self.acked_serial = Some(stale);
+            if let Some(audit) = self.audit.as_mut() {
+                audit.record(audit::Event::AckConfigure(stale));
+            }
+        }
+
+        if let (Some(kiosk), (Some(width), Some(height))) =
+            (self.kiosk.as_mut(), configure.new_size)
+        {
+            if kiosk.configured(width.get(), height.get()) {
+                println!(
+                    "{}kiosk: configured to {}x{}, restarting the measurement",
+                    self.log_prefix, width, height
+                );
+                self.resize(width.get(), height.get());
+            }
+        }
+
+        if let (Some(promote::Promote::Scanout), (Some(width), Some(height))) =
+            (self.promote, configure.new_size)
+        {
+            if (width.get(), height.get()) != (self.width, self.height) {
+                self.resize(width.get(), height.get());
+            }
+        }
+
+        if let (Some(stress), (Some(width), Some(height))) =
+            (self.resize_stress.as_mut(), configure.new_size)
+        {
+            stress.configured(self.frame, (width.get(), height.get()));
+            if (width.get(), height.get()) != (self.width, self.height) {
+                self.resize(width.get(), height.get());
+            }
+        }
+
+        if let (Some(profile), (Some(width), Some(height))) =
+            (self.planner.profile, configure.new_size)
+        {
+            if (width.get(), height.get()) != (self.width, self.height) && !self.first_configure {
+                self.resize(width.get(), height.get());
+                if profile.redraws_on_configure() {
+                    self.redraw_now();
+                }
+            }
+        }
+
+        if let Some(suspend) = self.suspend.as_mut() {
+            let suspended = configure.state.contains(WindowState::SUSPENDED);
+            if suspend.configured(suspended, &self.log_prefix) && !self.first_configure {
+                self.draw();
+            }
+        }
+
+        // Initiate the first draw.
+        if self.first_configure {
+            self.first_configure = false;
+            self.startup.configured();
+            self.draw();
+        }
+    }
+}
+
+impl ShmHandler for SimpleWindow {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+delegate_noop!(SimpleWindow: ignore wp_fifo_manager_v1::WpFifoManagerV1);
+delegate_noop!(SimpleWindow: ignore wp_fifo_v1::WpFifoV1);
+delegate_noop!(SimpleWindow: ignore wp_commit_timing_manager_v1::WpCommitTimingManagerV1);
+delegate_noop!(SimpleWindow: ignore wp_commit_timer_v1::WpCommitTimerV1);
+
+impl SimpleWindow {
+    pub fn draw(&mut self) {
+        self.pending_draw = None;
+        self.buffer_wait = None;
+        if self.frozen {
+            // Drawing restarts with the resume control.
+            return;
+        }
+        if let Some(deadline) = self.deadline.take() {
+            self.lateness.record(self.clock.since(deadline));
+        }
+        self.verify_released_buffers();
+        self.poll_releases();
+
+        let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| self.pool.canvas(buffer).is_some())
+        else {
+            self.waited_for_buffer = true;
+            self.buffer_wait = Some(self.loop_handle.insert_idle(|window| {
+                window.draw();
+            }));
+            return;
+        };
+
+        if self.max_frames.is_some_and(|max| self.frame >= max) {
+            println!("{}frame limit reached", self.log_prefix);
+            self.exit = true;
+            return;
+        }
+        if let Some(suspend) = self.suspend.as_mut() {
+            if suspend.check(&self.log_prefix) {
+                return;
+            }
+        }
+        let now = self.clock.now();
+        let Some(plan) = self
+            .planner
+            .plan(self.frame + 1, &self.stats, &self.log_prefix)
+        else {
+            if let Some(trace) = self.trace.as_mut() {
+                trace.finished(now, self.frame + 1);
+            }
+            println!("{}scenario finished", self.log_prefix);
+            self.exit = true;
+            return;
+        };
+        if let Some(name) = self.planner.scenario.switch_pattern() {
+            self.switch_pattern(&name);
+        }
+        let mut annotations = self.planner.scenario.take_annotations();
+        annotations.extend(
+            self.thermal
+                .as_mut()
+                .and_then(thermal::Thermal::take_annotation),
+        );
+        annotations.extend(
+            self.relock
+                .as_mut()
+                .and_then(relock::Relock::take_annotation),
+        );
+        annotations.extend(self.gamma.as_mut().and_then(gamma::Gamma::take_annotation));
+        annotations.extend(self.flags.take_annotation());
+
+        let elapsed = self
+            .last_draw
+            .replace(self.clock.now())
+            .map(|t| self.clock.since(t));
+        if let Some(elapsed) = elapsed {
+            self.interval_sum += elapsed;
+            self.interval_count += 1;
+            self.interval_square_sum += elapsed.as_secs_f64().powi(2);
+            self.move_probe.interval(elapsed, &self.log_prefix);
+            if let Some(thermal) = self.thermal.as_mut() {
+                thermal.interval(elapsed);
+            }
+        }
+        self.stats.interval = elapsed;
+        if self.log_every != 0 && (self.frame + 1).is_multiple_of(self.log_every) {
+            println!(
+                "{}{} Drawing, elapsed: {:?}",
+                self.log_prefix,
+                clock::stamp(self.start),
+                elapsed
+            );
+        }
+
+        if let Some(spike) = self.spike.as_mut() {
+            if let Some(elapsed) = elapsed {
+                spike.interval(self.frame + 1, elapsed);
+            }
+            spike.render(self.frame + 1);
+        }
+
+        if plan.present_at.is_some() && self.pulldown.is_none() {
+            if self.commit_timer.is_none() {
+                eprintln!(
+                    "{}commit-timing requested, but unavailable",
+                    self.log_prefix
+                );
+            }
+            self.pulldown = Some(pulldown::Pulldown::default());
+        }
+        let decision = planner::Decision::new(
+            &plan,
+            &planner::Surface {
+                fifo: self.fifo.is_some() && self.fifo_enabled,
+                present: self
+                    .cadence
+                    .as_ref()
+                    .is_none_or(|cadence| cadence.presents(self.frame + 1)),
+                frame_callbacks: self.suspend.is_some(),
+                draw_on_presented: self.sweep.is_some() && self.presentation.is_some(),
+                feedback: self.wants_feedback() && self.presentation.is_some(),
+            },
+        );
+        let planner::Decision {
+            present,
+            barrier,
+            commit,
+            present_at,
+            ..
+        } = decision;
+
+        let buffer = &self.buffers[index];
+        // Areas the overlays drew into, for the partial damage.
+        let mut overlays = Vec::new();
+        // Odd frames are white while the trigger is high.
+        let flash = (self.frame + 1) % 2 == 1;
+        {
+            let data = self.pool.canvas(buffer).expect("buffer is free");
+            let mut canvas = canvas::Canvas::new(data, self.width, self.height);
+
+            // Static patterns are pre-rendered into the buffers, they are only
+            // rendered again to add a fill cost.
+            let passes = match self.fill_cost {
+                Some(passes) => passes,
+                None if self.pattern.is_static()
+                    || self.partial.is_some()
+                    || self.damage_grid.is_some() =>
+                {
+                    0
+                }
+                None => 1,
+            };
+            for _ in 0..passes {
+                self.pattern.render(&mut canvas, self.frame + 1);
+            }
+            if let Some(partial) = self.partial.as_mut() {
+                partial.render(&mut canvas, index, self.frame + 1, &self.log_prefix);
+            }
+            if let Some(grid) = self.damage_grid.as_mut() {
+                grid.render(&mut canvas);
+            }
+
+            if self.trigger.is_some() {
+                let color = if flash { 0xFFFF_FFFF } else { 0xFF00_0000 };
+                canvas.fill_rect(0, 0, self.width, self.height, color);
+            }
+
+            if let Some(promote) = self.promote {
+                promote.render(&mut canvas);
+            }
+
+            if let Some(hud) = self.hud.as_mut() {
+                if let Some(elapsed) = elapsed {
+                    hud.push_interval(elapsed);
+                }
+                let state = if self.waited_for_buffer {
+                    hud::State::WaitingForBuffer
+                } else if barrier {
+                    hud::State::Barrier
+                } else {
+                    hud::State::Unthrottled
+                };
+                hud.render(&mut canvas, state);
+            }
+
+            if self.frame_counter {
+                let label = format!("#{}", self.frame + 1);
+                let width = text::width(&label, 2) + 4;
+                let height = text::height(&label, 2) + 2;
+                canvas.fill_rect(0, 0, width, height, 0xFF00_0000);
+                text::draw(&mut canvas, 2, 2, &label, 0xFFFF_FFFF, 2);
+                overlays.push((0, 0, width as i32, height as i32));
+            }
+
+            if self.frame_id {
+                frame_id::stamp(&mut canvas, (self.frame + 1) as u32);
+                let size = frame_id::SIZE as i32;
+                overlays.push((self.width as i32 - size, 0, size, size));
+            }
+
+            if self.qr {
+                qr::stamp(&mut canvas, self.frame + 1, self.clock.now().as_nanos());
+            }
+
+            if self.help {
+                let width = text::width(controls::HELP, 1) + 8;
+                let height = text::height(controls::HELP, 1) + 8;
+                let x = self.width.saturating_sub(width) as i32 / 2;
+                let y = self.height.saturating_sub(height) as i32 / 2;
+                canvas.fill_rect(x, y, width, height, 0xE000_0000);
+                text::draw(&mut canvas, x + 4, y + 4, controls::HELP, 0xFFFF_FFFF, 1);
+                overlays.push((x, y, width as i32, height as i32));
+            }
+        }
+
+        // The timestamp sent to the compositor, present_at stays the real target.
+        let timestamp = match (present_at, self.clock_skew.as_mut()) {
+            (Some(present_at), Some(skew)) => Some(skew.apply(present_at)),
+            (present_at, _) => present_at,
+        };
+
+        if let Some(golden) = self.golden.as_ref().filter(|_| present) {
+            let data = self.pool.canvas(buffer).unwrap();
+            golden
+                .lock()
+                .unwrap()
+                .rendered((self.frame + 1) as u32, self.width, self.height, data);
+        }
+
+        // Frame callbacks and feedback of the commit, with --commit-thread the worker
+        // issues them right before it commits.
+        let mut requests: Vec<commit_thread::Request> = Vec::new();
+        let frame = self.frame + 1;
+        if let Some(thread) = self.event_thread.as_ref().filter(|_| commit) {
+            let handle = thread.handle().clone();
+            requests.push(Box::new(move |surface| {
+                surface.frame(&handle, event_thread::FrameData::new(frame));
+            }));
+        }
+
+        // Frame callbacks also feed the suspend detection.
+        if decision.frame_callback {
+            if let Some(pipeline) = self.pipeline.as_mut() {
+                pipeline.callback_requested(self.frame + 1);
+            }
+            let qh = self.qh.clone();
+            requests.push(Box::new(move |surface| {
+                surface.frame(&qh, surface.clone());
+            }));
+        }
+
+        if let Some(partial) = self.partial.as_mut() {
+            for rect in &overlays {
+                partial.overlay(*rect);
+            }
+        }
+        let mut damage = match (self.partial.as_ref(), self.damage_grid.as_ref()) {
+            (Some(partial), _) => partial.damage(),
+            (None, Some(grid)) => grid.damage().into_iter().chain(overlays).collect(),
+            (None, None) => vec![match plan.damage {
+                plugin::Damage::Full => (0, 0, self.width as i32, self.height as i32),
+                plugin::Damage::Inset(inset) => (
+                    inset as i32,
+                    inset as i32,
+                    self.width.saturating_sub(inset * 2) as i32,
+                    self.height.saturating_sub(inset * 2) as i32,
+                ),
+            }],
+        };
+        if let Some(flood) = self
+            .damage_flood
+            .as_ref()
+            .filter(|flood| flood.floods(self.frame + 1))
+        {
+            damage.extend(flood.rects(self.width, self.height));
+        }
+        if decision.feedback {
+            if let Some(presentation) = self.presentation.clone() {
+                let qh = self.qh.clone();
+                requests.push(Box::new(move |surface| {
+                    presentation.feedback(surface, &qh, presentation::FeedbackData::new(frame));
+                }));
+            }
+        }
+        if let Some(annotation) = self
+            .hdr
+            .as_mut()
+            .filter(|_| commit)
+            .and_then(|hdr| hdr.committing(self.frame + 1))
+        {
+            annotations.push(annotation);
+        }
+
+        if let Some(partial) = self.partial.as_mut().filter(|_| present) {
+            partial.committed();
+        }
+        if let Some(grid) = self.damage_grid.as_mut().filter(|_| present) {
+            grid.committed(self.frame + 1, self.clock.now());
+        }
+        if let Some(integrity) = self.integrity.as_mut().filter(|_| present) {
+            let data = self.pool.canvas(buffer).expect("buffer is free");
+            integrity.committed(index, self.frame + 1, data);
+        }
+        if let Some(capture) = self.capture.as_mut().filter(|_| present) {
+            let data = self.pool.canvas(buffer).expect("buffer is free");
+            capture.rendered((self.frame + 1) as u32, self.width, self.height, data);
+        }
+
+        if commit && !self.ack_stale_serial && self.acked_serial != self.configured_serial {
+            eprintln!(
+                "{}committing frame {} without acking the latest configure {:?}, last acked {:?}",
+                self.log_prefix,
+                self.frame + 1,
+                self.configured_serial,
+                self.acked_serial
+            );
+        }
+        if let Some(size) = self
+            .resize_stress
+            .as_mut()
+            .filter(|_| commit)
+            .and_then(|stress| stress.request(self.frame + 1))
+        {
+            self.window.set_min_size(Some(size));
+            self.window.set_max_size(Some(size));
+        }
+        if let Some(audit) = self.audit.as_mut().filter(|_| commit) {
+            if present {
+                audit.record(audit::Event::Attach);
+            }
+            if barrier {
+                audit.record(audit::Event::WaitBarrier);
+                audit.record(audit::Event::SetBarrier);
+            }
+            audit.record(audit::Event::Commit);
+        }
+        if !commit {
+            // Neither content nor a barrier to commit.
+        } else if let Some(thread) = self.commit_thread.as_ref() {
+            if present {
+                buffer.activate().expect("buffer activate");
+                thread.commit(
+                    buffer.wl_buffer().clone(),
+                    damage,
+                    barrier,
+                    timestamp,
+                    requests,
+                );
+            } else {
+                thread.commit_barrier(requests);
+            }
+        } else {
+            for request in requests {
+                request(self.window.wl_surface());
+            }
+            if present {
+                for (x, y, width, height) in damage {
+                    self.window.wl_surface().damage(x, y, width, height);
+                }
+                buffer
+                    .attach_to(self.window.wl_surface())
+                    .expect("buffer attach");
+            }
+
+            if let Some(fifo) = self.fifo.as_ref().filter(|_| barrier) {
+                fifo.wait_barrier();
+                fifo.set_barrier();
+            }
+            if let (Some(timer), Some(timestamp)) = (self.commit_timer.as_ref(), timestamp) {
+                commit_thread::set_timestamp(timer, timestamp);
+            }
+
+            self.window.commit();
+        }
+        self.frame += 1;
+        if commit {
+            self.stats.frames += 1;
+            self.last_commit = Some((self.clock.now(), barrier));
+            self.planner.committed(self.frame);
+        }
+        if self.trace.is_some() {
+            let drawn = trace::Drawn {
+                plan,
+                interval: elapsed,
+                commit,
+                feedback: decision.feedback,
+            };
+            if let Some(trace) = self.trace.as_mut() {
+                trace.plan(now, self.frame, &drawn);
+            }
+        }
+        if let Some(stress) = self.resize_stress.as_mut().filter(|_| commit && present) {
+            stress.committed(self.frame, (self.width, self.height));
+        }
+        if let Some(fault) = self
+            .inject
+            .filter(|_| commit && barrier && self.frame >= self.inject_at)
+        {
+            self.inject = None;
+            inject::fire(fault, &self.conn, self.frame, &self.log_prefix);
+            if fault == inject::Inject::Disconnect {
+                self.disconnected = true;
+                self.exit = true;
+                return;
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(recorder) = self.db.as_mut().filter(|_| commit) {
+            recorder.push(db::Frame {
+                frame: self.frame,
+                committed: self.clock.now(),
+                interval: elapsed,
+                barrier,
+                content: present,
+                waited_for_buffer: self.waited_for_buffer,
+            });
+        }
+        if commit {
+            let committed = observer::Committed {
+                frame: self.frame,
+                present,
+                barrier,
+                waited_for_buffer: self.waited_for_buffer,
+            };
+            for observer in &mut self.observers {
+                observer.committed(&committed);
+            }
+        }
+        if let Some(kiosk) = self.kiosk.as_mut().filter(|_| commit) {
+            kiosk.frame(elapsed, self.waited_for_buffer);
+        }
+        if let Some(metrics) = self.metrics.as_ref().filter(|_| commit) {
+            metrics
+                .lock()
+                .unwrap()
+                .frame(elapsed, self.waited_for_buffer);
+        }
+        self.waited_for_buffer = false;
+        for annotation in annotations {
+            self.annotate(annotation);
+        }
+        if present {
+            self.attached[index] = Some(self.frame);
+            self.startup.committed(self.frame);
+            if let Some(pipeline) = self.pipeline.as_mut() {
+                pipeline.committed(self.frame, index, self.clock.now());
+            }
+            if let Some(fates) = self.fates.as_mut() {
+                fates.committed(self.frame, barrier);
+            }
+            if let Some(verdict) = self.verdict.as_mut() {
+                verdict.committed(self.frame, barrier, self.clock.now());
+            }
+            if let Some(cadence) = self.cadence.as_mut() {
+                cadence.presented();
+            }
+            if let (Some(pulldown), Some(present_at)) = (self.pulldown.as_mut(), present_at) {
+                pulldown.committed(self.frame, present_at);
+            }
+        }
+
+        if let Some(trigger) = self.trigger.as_mut() {
+            // Make sure the commit is on its way before signalling it.
+            let _ = self.conn.flush();
+            if let Err(err) = trigger.set(self.frame, flash) {
+                eprintln!("{}trigger failed: {}", self.log_prefix, err);
+            }
+        }
+
+        let output = self
+            .output
+            .as_ref()
+            .map(|output| self.output_name(Some(output)));
+        if let Some(placement) = self.placement.as_mut() {
+            placement.frame_committed(self.frame, output.as_deref());
+            if let (Some(mode), Some(relock)) = (placement.take_mode_switch(), self.relock.as_mut())
+            {
+                relock.mark(
+                    relock::Source::Ipc,
+                    self.frame,
+                    self.clock.now(),
+                    Some(mode.refresh()),
+                    &self.log_prefix,
+                );
+            }
+        }
+
+        if let Some(growth) = self.growth {
+            if self.frame.is_multiple_of(growth.every) && self.width < growth.max {
+                self.grow(growth);
+            }
+        }
+
+        if let Some(every) = self.madvise_every {
+            if self.frame.is_multiple_of(every) {
+                self.discard_free_buffers();
+            }
+        }
+
+        if self.paused {
+            return;
+        }
+
+        match decision.next {
+            // Scheduled once the frame is presented.
+            planner::Next::Presented => {}
+            planner::Next::FrameCallback(delay) => {
+                self.awaiting_frame_callback = Some(delay);
+                if let Some(timeout) = self
+                    .planner
+                    .profile
+                    .and_then(profile::Profile::frame_callback_timeout)
+                {
+                    let frame = self.frame;
+                    self.loop_handle
+                        .insert_source(Timer::from_duration(timeout), move |_, _, window| {
+                            if window.frame == frame && !window.paused {
+                                if let Some(delay) = window.awaiting_frame_callback.take() {
+                                    window.schedule_draw(delay);
+                                }
+                            }
+                            TimeoutAction::Drop
+                        })
+                        .unwrap();
+                }
+            }
+            planner::Next::Timer(delay) => self.schedule_draw(delay),
+        }
+    }
+
+    /// Whether the next frame needs presentation feedback.
+    fn wants_feedback(&self) -> bool {
+        self.startup.pending()
+            || self
+                .observers
+                .iter()
+                .any(|observer| observer.wants_feedback())
+            || self.fates.is_some()
+            || self.pulldown.is_some()
+            || self.sweep.is_some()
+            || self.hud.as_ref().is_some_and(hud::Hud::wants_latency)
+            || self.planner.wants_feedback()
+            || self.breaker.is_some()
+            || self.pipeline.is_some()
+            || self.damage_flood.is_some()
+            || self.hdr.is_some()
+            || self.damage_grid.is_some()
+            || self.resize_stress.is_some()
+            || self.verdict.is_some()
+            || self.spanning.is_spanning()
+            || self.relock.is_some()
+            || self.gamma.is_some()
+            || self.metrics.is_some()
+    }
+
+    fn presented(&mut self, frame: u64, presented: Option<presentation::Presented>) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.feedback(self.clock.now(), frame, presented.as_ref());
+        }
+        self.startup
+            .presented(frame, presented.as_ref().map(|presented| presented.time));
+        if let Some(fates) = self.fates.as_mut() {
+            fates.feedback(frame, presented.is_some());
+        }
+        if let Some(verdict) = self.verdict.as_mut() {
+            verdict.presented(frame, presented.as_ref());
+        }
+        let output = self.output_name(
+            presented
+                .as_ref()
+                .and_then(|presented| presented.output.as_ref()),
+        );
+        let log = observer::Log {
+            prefix: &self.log_prefix,
+            every: self.log_every,
+        };
+        for observer in &mut self.observers {
+            observer.presented(frame, presented.as_ref(), &output, &log);
+        }
+        self.planner
+            .feedback(frame, presented.as_ref(), &self.log_prefix);
+        if self.spanning.is_spanning() {
+            self.spanning.presented(presented.as_ref(), output.clone());
+        }
+        if let Some(hdr) = self.hdr.as_mut() {
+            hdr.presented(
+                frame,
+                presented
+                    .as_ref()
+                    .map(|presented| presented.time.saturating_sub(presented.committed)),
+            );
+        }
+        if let Some(pipeline) = self.pipeline.as_mut() {
+            pipeline.presented(frame, presented.as_ref().map(|presented| presented.time));
+        }
+        if let Some(grid) = self.damage_grid.as_mut() {
+            grid.presented(
+                frame,
+                presented
+                    .as_ref()
+                    .map(|presented| presented.time.saturating_sub(presented.committed)),
+            );
+        }
+        let Some(presented) = presented else {
+            self.stats.discarded += 1;
+            if let Some(relock) = self.relock.as_mut() {
+                relock.discarded();
+            }
+            if self.sweep.is_some() && !self.paused {
+                self.schedule_draw(Duration::ZERO);
+            }
+            return;
+        };
+
+        if let Some(anomaly) = self
+            .breaker
+            .as_mut()
+            .and_then(|breaker| breaker.presented(frame, &presented))
+        {
+            self.freeze(&anomaly);
+        }
+        if let Some(relock) = self.relock.as_mut() {
+            relock.presented(frame, &presented, &self.log_prefix);
+        }
+        if let Some(gamma) = self.gamma.as_mut() {
+            gamma.presented(frame, &presented);
+        }
+        self.flags
+            .presented(frame, &presented, self.log_every != 0, &self.log_prefix);
+        let latency = presented.time.saturating_sub(presented.committed);
+        self.stats.presented += 1;
+        self.last_presented = Some((frame, presented.time));
+        self.stats.latency = Some(latency);
+        if let Some(hud) = self.hud.as_mut() {
+            hud.push_latency(latency);
+        }
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.lock().unwrap().presented(latency);
+        }
+        if let Some(flood) = self.damage_flood.as_mut() {
+            flood.presented(frame, latency);
+        }
+        if let Some(stress) = self.resize_stress.as_mut() {
+            stress.presented(frame);
+        }
+        if let Some(sweep) = self.sweep.as_mut() {
+            let delay = sweep.presented(frame, &presented, &self.log_prefix);
+            if !self.paused {
+                self.schedule_draw(delay);
+            }
+        }
+        if let Some(suspend) = self.suspend.as_mut() {
+            suspend.signal();
+        }
+        if let Some(pulldown) = self.pulldown.as_mut() {
+            pulldown.presented(frame, &presented);
+        }
+    }
+
+    /// Name of an output for reports, from wl_output.name if available.
+    fn output_name(&self, output: Option<&wl_output::WlOutput>) -> String {
+        let Some(output) = output else {
+            return "unknown output".to_string();
+        };
+        self.output_state
+            .info(output)
+            .and_then(|info| info.name)
+            .unwrap_or_else(|| format!("wl_output#{}", output.id().protocol_id()))
+    }
+
+    /// Feeds the current mode of `output`, the surface's output, to `--target-fps auto`.
+    fn output_mode_changed(&mut self, output: &wl_output::WlOutput) {
+        let mode = self
+            .output_state
+            .info(output)
+            .and_then(|info| info.modes.into_iter().find(|mode| mode.current));
+        let name = self.output_name(Some(output));
+        if let (Some(target), Some(mode)) = (self.planner.target.as_mut(), mode.as_ref()) {
+            target.output_mode(mode.refresh_rate, &name, &self.log_prefix);
+        }
+        if let (Some(relock), Some(mode)) = (self.relock.as_mut(), mode) {
+            relock.output_mode(
+                mode.refresh_rate,
+                self.frame,
+                self.clock.now(),
+                &self.log_prefix,
+            );
+        }
+    }
+
+    /// Logs and records an annotation of the frame committed last.
+    fn annotate(&mut self, annotation: plugin::Annotation) {
+        if self.log_every != 0 {
+            println!(
+                "{}{} Frame {}: {}",
+                self.log_prefix,
+                clock::stamp(self.start),
+                self.frame,
+                annotation
+            );
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(recorder) = self.db.as_mut() {
+            recorder.annotate(self.frame, annotation);
+        }
+    }
+
+    fn schedule_draw(&mut self, delay: Duration) {
+        self.deadline = (!delay.is_zero()).then(|| self.clock.now() + delay);
+        self.pending_draw = Some(self.scheduler.schedule(&self.loop_handle, delay));
+    }
+
+    /// Leaves the paused state, drawing unless a draw is already on its way.
+    fn resume(&mut self) {
+        self.paused = false;
+        if self.pending_draw.is_none()
+            && self.awaiting_frame_callback.is_none()
+            && self.buffer_wait.is_none()
+        {
+            self.draw();
+        }
+    }
+
+    /// Draws right away instead of with the pending scheduled draw or frame callback.
+    fn redraw_now(&mut self) {
+        if self.paused || (self.pending_draw.is_none() && self.awaiting_frame_callback.is_none()) {
+            // Not waiting for the next frame, e.g. for a buffer to be released.
+            return;
+        }
+        if let Some(token) = self.pending_draw.take() {
+            self.loop_handle.remove(token);
+        }
+        self.deadline = None;
+        self.draw();
+    }
+
+    fn print_progress(&self, elapsed: Duration) {
+        let Some(max_frames) = self.max_frames else {
+            println!(
+                "{}progress: frame {} after {:.0?}",
+                self.log_prefix, self.frame, elapsed
+            );
+            return;
+        };
+        let eta = match self.frame {
+            0 => "unknown".to_string(),
+            frame => format!(
+                "{:.0?}",
+                elapsed.mul_f64(max_frames.saturating_sub(frame) as f64 / frame as f64)
+            ),
+        };
+        println!(
+            "{}progress: frame {}/{} ({:.1}%) after {:.0?}, ETA {}",
+            self.log_prefix,
+            self.frame,
+            max_frames,
+            self.frame as f64 / max_frames as f64 * 100.0,
+            elapsed,
+            eta
+        );
+    }
+
+    fn freeze(&mut self, anomaly: &str) {
+        if self.frozen {
+            return;
+        }
+        self.frozen = true;
+        println!(
+            "{}break on {}, frozen after frame {}, resume to continue",
+            self.log_prefix, anomaly, self.frame
+        );
+        match breakon::compositor_pid(&self.conn) {
+            Some(pid) => println!("{}compositor pid {}", self.log_prefix, pid),
+            None => println!("{}compositor pid unknown", self.log_prefix),
+        }
+    }
+
+    fn switch_pattern(&mut self, name: &str) {
+        let Some(pattern) = self.registry.pattern(name) else {
+            eprintln!("{}unknown pattern `{}`, see --list", self.log_prefix, name);
+            return;
+        };
+        println!("{}Switched to pattern {}", self.log_prefix, name);
+        self.pattern = pattern;
+        if self.pattern.is_static() {
+            // Static patterns are only rendered into new buffers.
+            self.resize(self.width, self.height);
+        }
+    }
+
+    fn grow(&mut self, growth: buffers::Growth) {
+        let width = (self.width + growth.step).min(growth.max);
+        let height = (self.height + growth.step).min(growth.max);
+        self.resize(width, height);
+        println!(
+            "{}Grew buffers to {}x{}, pool size {}",
+            self.log_prefix,
+            width,
+            height,
+            self.pool.len()
+        );
+    }
+
+    /// Replaces the buffers with new ones of the given size.
+    fn resize(&mut self, width: u32, height: u32) {
+        match self.pool_strategy {
+            buffers::PoolStrategy::Recreate => {
+                // Buffers still held by the compositor are destroyed once released.
+                self.pool = SlotPool::new(width as usize * height as usize * 4, &self.shm)
+                    .expect("Failed to create pool");
+                self.buffers = buffers::create(
+                    &mut self.pool,
+                    width,
+                    height,
+                    self.buffers.len(),
+                    self.format,
+                    self.pattern.as_mut(),
+                );
+            }
+            buffers::PoolStrategy::Resize => {
+                // The slot pool grows the wl_shm_pool as needed, slots of in-flight
+                // buffers are only reused once they are released.
+                self.buffers = buffers::create(
+                    &mut self.pool,
+                    width,
+                    height,
+                    self.buffers.len(),
+                    self.format,
+                    self.pattern.as_mut(),
+                );
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        // Releases of the replaced buffers aren't seen anymore.
+        self.attached.fill(None);
+        if let Some(integrity) = self.integrity.as_mut() {
+            integrity.reset();
+        }
+        if let Some(partial) = self.partial.as_mut() {
+            partial.invalidate();
+        }
+        if let Some(grid) = self.damage_grid.as_mut() {
+            grid.invalidate();
+        }
+        if let Some(pipeline) = self.pipeline.as_mut() {
+            pipeline.reset_buffers();
+        }
+
+        if self.mlock {
+            self.lock_buffers();
+        }
+    }
+
+    fn lock_buffers(&mut self) {
+        for buffer in &self.buffers {
+            let data = self.pool.canvas(buffer).expect("new buffer is free");
+            if let Err(err) = memory::lock(data) {
+                eprintln!("{}mlock failed: {}", self.log_prefix, err);
+                return;
+            }
+        }
+    }
+
+    fn verify_released_buffers(&mut self) {
+        let Some(integrity) = self.integrity.as_mut() else {
+            return;
+        };
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            let Some(data) = self.pool.canvas(buffer) else {
+                continue;
+            };
+            if let Some(frame) = integrity.released(index, data) {
+                eprintln!(
+                    "{}buffer of frame {} was modified while held by the compositor",
+                    self.log_prefix, frame
+                );
+            }
+        }
+    }
+
+    fn poll_releases(&mut self) {
+        let free = self
+            .buffers
+            .iter()
+            .map(|buffer| self.pool.canvas(buffer).is_some())
+            .collect::<Vec<_>>();
+        for (attached, free) in self.attached.iter_mut().zip(&free) {
+            let Some(frame) = attached.take_if(|_| *free) else {
+                continue;
+            };
+            if let Some(trace) = self.trace.as_mut() {
+                trace.released(self.clock.now(), frame);
+            }
+            self.planner.released(frame, &self.log_prefix);
+            for observer in &mut self.observers {
+                observer.released(frame, self.clock.now());
+            }
+        }
+        if let Some(pipeline) = self.pipeline.as_mut() {
+            pipeline.poll(|index| free[index], self.clock.now());
+        }
+    }
+
+    fn discard_free_buffers(&mut self) {
+        // Check the released buffers before their pages are dropped.
+        self.verify_released_buffers();
+
+        let mut discarded = 0;
+        for buffer in &self.buffers {
+            let Some(data) = self.pool.canvas(buffer) else {
+                continue;
+            };
+            match memory::discard(data) {
+                Ok(len) => discarded += len,
+                Err(err) => {
+                    eprintln!("{}madvise failed: {}", self.log_prefix, err);
+                    return;
+                }
+            }
+        }
+        println!(
+            "{}Discarded {} KiB of free buffer pages",
+            self.log_prefix,
+            discarded / 1024
+        );
+    }
+}
+
+delegate_compositor!(SimpleWindow);
+delegate_output!(SimpleWindow);
+delegate_shm!(SimpleWindow);
+delegate_seat!(SimpleWindow);
+delegate_keyboard!(SimpleWindow);
+delegate_touch!(SimpleWindow);
+
+delegate_xdg_shell!(SimpleWindow);
+delegate_xdg_window!(SimpleWindow);
+
+delegate_registry!(SimpleWindow);
+
+impl ProvidesRegistryState for SimpleWindow {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState, SeatState,];
+}
@@ -0,0 +1,357 @@
+//! The `matrix` subcommand and the `--repeat`/`--compare` runs.
+//!
+//! A matrix runs the test once for every combination of a parameter grid read from a
+//! file, each parsed as if its options were given on the command line after the
+//! others. Both print a table comparing the runs.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::{CommandFactory, Parser};
+
+use crate::hooks::HookOutput;
+use crate::{plugin, Args};
+
+/// Options of the `matrix` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Matrix {
+    /// Parameter grid, one `option = value, value, ...` line per dimension, e.g. `no-fifo = false, true` or `buffers = 2, 3, 4`
+    config: PathBuf,
+
+    /// Frames every combination commits, unless the grid has a `frames` dimension
+    #[arg(long, default_value_t = 300)]
+    frames: u64,
+}
+
+/// One dimension of the grid: a long option and the values it takes.
+struct Dimension {
+    option: String,
+    values: Vec<String>,
+}
+
+fn parse(path: &Path) -> Result<Vec<Dimension>, String> {
+    let config = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut dimensions = Vec::new();
+    for (number, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (option, values) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `option = value, ...`", number + 1))?;
+        let values = values
+            .split(',')
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return Err(format!("line {}: no values", number + 1));
+        }
+        dimensions.push(Dimension {
+            option: option.trim().trim_start_matches("--").to_string(),
+            values,
+        });
+    }
+    Ok(dimensions)
+}
+
+/// The options given before the subcommand, without those of `dimensions`, which
+/// every combination sets on its own so `false` unsets a flag given here.
+fn base_argv(dimensions: &[Dimension]) -> Vec<String> {
+    let command = Args::command();
+    let mut tokens = std::env::args();
+    let mut argv = tokens.next().into_iter().collect::<Vec<_>>();
+    while let Some(token) = tokens.next() {
+        let (arg, inline) = if let Some(long) = token.strip_prefix("--") {
+            let name = long.split_once('=').map_or(long, |(name, _)| name);
+            (
+                command
+                    .get_arguments()
+                    .find(|arg| arg.get_long() == Some(name)),
+                long.contains('='),
+            )
+        } else if let Some(short) = token
+            .strip_prefix('-')
+            .and_then(|short| short.chars().next())
+        {
+            (
+                command
+                    .get_arguments()
+                    .find(|arg| arg.get_short() == Some(short)),
+                token.len() > 2,
+            )
+        } else {
+            // The subcommand, the matrix and its options.
+            break;
+        };
+        let takes_value = arg.is_some_and(|arg| {
+            arg.get_action().takes_values()
+                && arg
+                    .get_num_args()
+                    .is_none_or(|range| range.min_values() > 0)
+        });
+        let value = if takes_value && !inline {
+            tokens.next()
+        } else {
+            None
+        };
+        let swept = arg
+            .and_then(|arg| arg.get_long())
+            .is_some_and(|long| dimensions.iter().any(|dimension| dimension.option == long));
+        if !swept {
+            argv.push(token);
+            argv.extend(value);
+        }
+    }
+    argv
+}
+
+/// Every combination of the dimension values, as indices into the values.
+fn combinations(dimensions: &[Dimension]) -> Vec<Vec<usize>> {
+    dimensions
+        .iter()
+        .fold(vec![Vec::new()], |combinations, dimension| {
+            combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    (0..dimension.values.len()).map(move |index| {
+                        let mut combination = combination.clone();
+                        combination.push(index);
+                        combination
+                    })
+                })
+                .collect()
+        })
+}
+
+/// Key metrics of one run in the comparison table.
+pub struct Row {
+    label: String,
+    frames: u64,
+    mean_interval: Option<Duration>,
+    presented: u64,
+    discarded: u64,
+    /// Millijoules per frame, with --energy.
+    energy: Option<f64>,
+}
+
+impl Row {
+    /// Sums up the windows of one run.
+    pub fn new(label: String, outcomes: &[crate::Outcome]) -> Self {
+        let intervals = outcomes
+            .iter()
+            .filter_map(|outcome| outcome.mean_interval)
+            .collect::<Vec<_>>();
+        Self {
+            label,
+            frames: outcomes.iter().map(|outcome| outcome.stats.frames).sum(),
+            mean_interval: (!intervals.is_empty())
+                .then(|| intervals.iter().sum::<Duration>() / intervals.len() as u32),
+            presented: outcomes.iter().map(|outcome| outcome.stats.presented).sum(),
+            discarded: outcomes.iter().map(|outcome| outcome.stats.discarded).sum(),
+            energy: outcomes
+                .iter()
+                .find_map(|outcome| outcome.energy)
+                .and_then(|energy| {
+                    energy.per_frame(outcomes.iter().map(|outcome| outcome.stats.frames).sum())
+                }),
+        }
+    }
+}
+
+/// Runs every combination of the grid on top of the other options, one after the
+/// other, and prints a combined report. Returns the hook outputs and the number of
+/// captures that differed from the rendered frames.
+pub fn run(
+    matrix: &Matrix,
+    args: &Args,
+    registry: &Arc<plugin::Registry>,
+    start: Duration,
+) -> (Vec<HookOutput>, u64) {
+    let dimensions = parse(&matrix.config).unwrap_or_else(|err| {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::InvalidValue,
+                format!("invalid matrix `{}`: {}", matrix.config.display(), err),
+            )
+            .exit()
+    });
+
+    // Parse every combination up front so a typo doesn't abort the matrix halfway.
+    let base = base_argv(&dimensions);
+    let sweeps_frames = dimensions
+        .iter()
+        .any(|dimension| dimension.option == "frames");
+    let runs = combinations(&dimensions)
+        .into_iter()
+        .map(|combination| {
+            let mut argv = base.clone();
+            let mut label = Vec::new();
+            for (dimension, &index) in dimensions.iter().zip(&combination) {
+                let value = &dimension.values[index];
+                label.push(format!("{}={}", dimension.option, value));
+                match value.as_str() {
+                    "true" => argv.push(format!("--{}", dimension.option)),
+                    "false" => {}
+                    value => argv.push(format!("--{}={}", dimension.option, value)),
+                }
+            }
+
+            // Parsed anew, as updating `args` would reset every option left out.
+            let base_output_dir = args.output_dir.clone();
+            let mut args = Args::try_parse_from(&argv).unwrap_or_else(|err| err.exit());
+            args.output_dir = args.output_dir.take().or(base_output_dir);
+            crate::resolve_outputs(&mut args);
+            if !sweeps_frames {
+                args.frames = Some(matrix.frames);
+            }
+            args.quiet = true;
+            if !registry.has_pattern(&args.pattern) || !registry.has_scenario(&args.scenario) {
+                Args::command()
+                    .error(
+                        clap::error::ErrorKind::InvalidValue,
+                        format!(
+                            "unknown pattern `{}` or scenario `{}`, see --list",
+                            args.pattern, args.scenario
+                        ),
+                    )
+                    .exit();
+            }
+            crate::check_placement(&args);
+            (label.join(" "), args)
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = Vec::new();
+    let mut hook_outputs = Vec::new();
+    let mut mismatched = 0;
+    let started = Instant::now();
+    for (index, (label, args)) in runs.iter().enumerate() {
+        println!("matrix: run {}/{}: {}", index + 1, runs.len(), label);
+        let mut outcomes = crate::run_connections(args, registry, start, None);
+        for outcome in &mut outcomes {
+            hook_outputs.append(&mut outcome.hook_outputs);
+            mismatched += outcome.mismatched;
+        }
+        results.push(Row::new(label.clone(), &outcomes));
+
+        let done = index as u32 + 1;
+        let elapsed = started.elapsed();
+        println!(
+            "matrix: {}/{} done after {:.0?}, ETA {:.0?}",
+            done,
+            runs.len(),
+            elapsed,
+            elapsed / done * (runs.len() as u32 - done)
+        );
+    }
+
+    println!("matrix: {} combinations", results.len());
+    print_report(&results);
+    (hook_outputs, mismatched)
+}
+
+/// Runs the test `--repeat` times as given and as often with the options of every
+/// `--compare`, then prints the comparison table. Returns the hook outputs and the
+/// number of captures that differed from the rendered frames.
+pub fn run_repeated(
+    args: &Args,
+    registry: &Arc<plugin::Registry>,
+    start: Duration,
+    metrics: Option<&crate::metrics::Metrics>,
+) -> (Vec<HookOutput>, u64) {
+    let mut variants = vec![("base".to_string(), args.clone())];
+    for options in &args.compare {
+        let mut variant = args.clone();
+        variant.compare.clear();
+        let argv = std::iter::once("fifo_test").chain(options.split_whitespace());
+        variant
+            .try_update_from(argv)
+            .unwrap_or_else(|err| err.exit());
+        crate::check_placement(&variant);
+        variants.push((options.clone(), variant));
+    }
+
+    let mut results = Vec::new();
+    let mut hook_outputs = Vec::new();
+    let mut mismatched = 0;
+    let total = variants.len() as u32 * args.repeat;
+    for (options, variant) in &variants {
+        for repetition in 1..=args.repeat {
+            let label = if args.repeat > 1 {
+                format!("{} #{}", options, repetition)
+            } else {
+                options.clone()
+            };
+            println!("run {}/{}: {}", results.len() + 1, total, label);
+            let mut outcomes = crate::run_connections(variant, registry, start, metrics);
+            for outcome in &mut outcomes {
+                hook_outputs.append(&mut outcome.hook_outputs);
+                mismatched += outcome.mismatched;
+            }
+            results.push(Row::new(label, &outcomes));
+        }
+    }
+
+    println!("{} runs", results.len());
+    print_report(&results);
+    (hook_outputs, mismatched)
+}
+
+/// Prints the rows as an aligned table, with the fastest and slowest run.
+pub fn print_report(results: &[Row]) {
+    let width = results
+        .iter()
+        .map(|result| result.label.len())
+        .max()
+        .unwrap_or(0)
+        .max("run".len());
+    let energy = results.iter().any(|result| result.energy.is_some());
+    println!(
+        "  {:<width$}  {:>7}  {:>10}  {:>7}  {:>9}  {:>9}{}",
+        "run",
+        "frames",
+        "interval",
+        "fps",
+        "presented",
+        "discarded",
+        if energy { "   mJ/frame" } else { "" }
+    );
+    for result in results {
+        let (interval, fps) = match result.mean_interval {
+            Some(interval) => (
+                format!("{:.3}ms", interval.as_secs_f64() * 1000.0),
+                format!("{:.1}", 1.0 / interval.as_secs_f64()),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let per_frame = match (energy, result.energy) {
+            (false, _) => String::new(),
+            (true, Some(per_frame)) => format!("  {:>9.3}", per_frame),
+            (true, None) => format!("  {:>9}", "-"),
+        };
+        println!(
+            "  {:<width$}  {:>7}  {:>10}  {:>7}  {:>9}  {:>9}{}",
+            result.label,
+            result.frames,
+            interval,
+            fps,
+            result.presented,
+            result.discarded,
+            per_frame
+        );
+    }
+
+    let timed = results
+        .iter()
+        .filter_map(|result| Some((result, result.mean_interval?)));
+    if let (Some((fastest, _)), Some((slowest, _))) = (
+        timed.clone().min_by_key(|(_, interval)| *interval),
+        timed.max_by_key(|(_, interval)| *interval),
+    ) {
+        println!("  fastest: {}", fastest.label);
+        println!("  slowest: {}", slowest.label);
+    }
+}
@@ -0,0 +1,42 @@
+use std::io;
+
+/// Locks the pages backing `data` into memory.
+pub fn lock(data: &[u8]) -> io::Result<()> {
+    // SAFETY: the range is a valid mapping owned by the slice.
+    let ret = unsafe { libc::mlock(data.as_ptr().cast(), data.len()) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Drops the pages fully contained in `data` from this process' mapping.
+///
+/// The pool is a shared mapping, so the contents survive and are faulted back in from the
+/// shared memory on the next access by either side. Returns the number of bytes dropped.
+pub fn discard(data: &mut [u8]) -> io::Result<usize> {
+    let page_size = page_size();
+    let start = data.as_mut_ptr() as usize;
+    let aligned_start = start.next_multiple_of(page_size);
+    let aligned_end = (start + data.len()) / page_size * page_size;
+    if aligned_end <= aligned_start {
+        return Ok(0);
+    }
+
+    let len = aligned_end - aligned_start;
+    // SAFETY: the page aligned range lies within the mapping borrowed mutably from the
+    // slice, and MADV_DONTNEED on a shared mapping does not lose its contents.
+    let ret = unsafe { libc::madvise(aligned_start as *mut _, len, libc::MADV_DONTNEED) };
+    if ret == 0 {
+        Ok(len)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions.
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    usize::try_from(size).unwrap_or(4096)
+}
@@ -0,0 +1,219 @@
+//! Prometheus text-format endpoint, `--metrics`.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Number of recent intervals the frame rate and quantiles are computed from.
+const WINDOW: usize = 600;
+const QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 1.0];
+/// Time a scraper gets to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+pub struct Stats {
+    frames: u64,
+    stalls: u64,
+    interval_sum: Duration,
+    interval_count: u64,
+    recent: VecDeque<Duration>,
+    latency_sum: Duration,
+    latency_count: u64,
+    /// Commit to present latencies of the recent presented frames.
+    latencies: VecDeque<Duration>,
+}
+
+fn push_recent(recent: &mut VecDeque<Duration>, value: Duration) {
+    if recent.len() == WINDOW {
+        recent.pop_front();
+    }
+    recent.push_back(value);
+}
+
+impl Stats {
+    /// Records a committed frame, `stalled` if it had to wait for a buffer.
+    pub fn frame(&mut self, interval: Option<Duration>, stalled: bool) {
+        self.frames += 1;
+        if stalled {
+            self.stalls += 1;
+        }
+        if let Some(interval) = interval {
+            self.interval_sum += interval;
+            self.interval_count += 1;
+            push_recent(&mut self.recent, interval);
+        }
+    }
+
+    /// Records the commit to present latency of a presented frame.
+    pub fn presented(&mut self, latency: Duration) {
+        self.latency_sum += latency;
+        self.latency_count += 1;
+        push_recent(&mut self.latencies, latency);
+    }
+}
+
+/// Samples of a summary, quantiles over `recent` and the totals.
+fn summary(
+    out: &mut String,
+    name: &str,
+    label: &str,
+    recent: &VecDeque<Duration>,
+    sum: Duration,
+    count: u64,
+) {
+    let mut sorted = recent.iter().copied().collect::<Vec<_>>();
+    sorted.sort();
+    if !sorted.is_empty() {
+        for quantile in QUANTILES {
+            let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+            let _ = writeln!(
+                out,
+                "fifo_test_{}{{{},quantile=\"{}\"}} {}",
+                name,
+                label,
+                quantile,
+                sorted[index].as_secs_f64()
+            );
+        }
+    }
+    let _ = writeln!(
+        out,
+        "fifo_test_{}_sum{{{}}} {}",
+        name,
+        label,
+        sum.as_secs_f64()
+    );
+    let _ = writeln!(out, "fifo_test_{}_count{{{}}} {}", name, label, count);
+}
+
+/// All windows of the process, one set of series per connection.
+#[derive(Default)]
+pub struct Metrics {
+    windows: Mutex<Vec<(u32, Arc<Mutex<Stats>>)>>,
+}
+
+impl Metrics {
+    /// Starts serving the metrics on `addr` from a background thread.
+    pub fn serve(addr: SocketAddr) -> std::io::Result<Arc<Metrics>> {
+        let listener = TcpListener::bind(addr)?;
+        let metrics = Arc::new(Metrics::default());
+
+        let shared = metrics.clone();
+        std::thread::Builder::new()
+            .name("metrics".into())
+            .spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    // A client that never sends its request only holds up its own thread.
+                    let shared = shared.clone();
+                    let _ = std::thread::Builder::new()
+                        .name("metrics-client".into())
+                        .spawn(move || {
+                            // Every request gets the metrics, whatever the path.
+                            let mut request = [0u8; 1024];
+                            let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+                            let _ = stream.set_write_timeout(Some(READ_TIMEOUT));
+                            let _ = stream.read(&mut request);
+                            let body = shared.render();
+                            let _ = write!(
+                                stream,
+                                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                                body.len(),
+                                body
+                            );
+                        });
+                }
+            })?;
+
+        Ok(metrics)
+    }
+
+    pub fn register(&self, connection: u32) -> Arc<Mutex<Stats>> {
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        self.windows
+            .lock()
+            .unwrap()
+            .push((connection, stats.clone()));
+        stats
+    }
+
+    fn render(&self) -> String {
+        let windows = self.windows.lock().unwrap();
+        let mut out = String::new();
+
+        let mut family =
+            |name: &str, kind: &str, help: &str, sample: &dyn Fn(&mut String, &str, &Stats)| {
+                let _ = writeln!(out, "# HELP fifo_test_{} {}", name, help);
+                let _ = writeln!(out, "# TYPE fifo_test_{} {}", name, kind);
+                for (connection, stats) in windows.iter() {
+                    let label = format!("connection=\"{}\"", connection);
+                    sample(&mut out, &label, &stats.lock().unwrap());
+                }
+            };
+
+        family(
+            "frames_total",
+            "counter",
+            "Frames committed.",
+            &|out, label, stats| {
+                let _ = writeln!(out, "fifo_test_frames_total{{{}}} {}", label, stats.frames);
+            },
+        );
+        family(
+            "stalls_total",
+            "counter",
+            "Frames that had to wait for a buffer release.",
+            &|out, label, stats| {
+                let _ = writeln!(out, "fifo_test_stalls_total{{{}}} {}", label, stats.stalls);
+            },
+        );
+        family(
+            "fps",
+            "gauge",
+            "Frame rate over the recent frames.",
+            &|out, label, stats| {
+                let total = stats.recent.iter().sum::<Duration>().as_secs_f64();
+                let fps = if total > 0.0 {
+                    stats.recent.len() as f64 / total
+                } else {
+                    0.0
+                };
+                let _ = writeln!(out, "fifo_test_fps{{{}}} {}", label, fps);
+            },
+        );
+        family(
+            "frame_interval_seconds",
+            "summary",
+            "Time between drawn frames, quantiles over the recent frames.",
+            &|out, label, stats| {
+                summary(
+                    out,
+                    "frame_interval_seconds",
+                    label,
+                    &stats.recent,
+                    stats.interval_sum,
+                    stats.interval_count,
+                );
+            },
+        );
+        family(
+            "latency_seconds",
+            "summary",
+            "Commit to present latency, quantiles over the recent presented frames.",
+            &|out, label, stats| {
+                summary(
+                    out,
+                    "latency_seconds",
+                    label,
+                    &stats.latencies,
+                    stats.latency_sum,
+                    stats.latency_count,
+                );
+            },
+        );
+
+        out
+    }
+}
@@ -0,0 +1,115 @@
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::breakon::{BreakOn, Breaker};
+use crate::observer::{FrameObserver, Log};
+use crate::presentation::Presented;
+
+/// Minimum time between two notifications, anomalies in between are summed up in
+/// the next one.
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sends a desktop notification through org.freedesktop.Notifications on pacing
+/// anomalies, `--notify`.
+pub struct Notifier {
+    breaker: Breaker,
+    last_sent: Option<Instant>,
+    /// Anomalies since the last notification.
+    pending: u64,
+    anomalies: u64,
+    sent: u64,
+}
+
+impl Notifier {
+    pub fn new(condition: BreakOn) -> Self {
+        Self {
+            breaker: Breaker::new(vec![condition]),
+            last_sent: None,
+            pending: 0,
+            anomalies: 0,
+            sent: 0,
+        }
+    }
+}
+
+impl FrameObserver for Notifier {
+    fn presented(&mut self, frame: u64, presented: Option<&Presented>, _: &str, log: &Log) {
+        let Some(anomaly) =
+            presented.and_then(|presented| self.breaker.presented(frame, presented))
+        else {
+            return;
+        };
+        self.anomalies += 1;
+        self.pending += 1;
+        if self
+            .last_sent
+            .is_some_and(|last| last.elapsed() < MIN_INTERVAL)
+        {
+            return;
+        }
+
+        let body = if self.pending > 1 {
+            format!(
+                "frame {}: {}, {} more since the last notification",
+                frame,
+                anomaly,
+                self.pending - 1
+            )
+        } else {
+            format!("frame {}: {}", frame, anomaly)
+        };
+        send(&format!("fifo_test {}pacing anomaly", log.prefix), &body);
+        self.last_sent = Some(Instant::now());
+        self.pending = 0;
+        self.sent += 1;
+    }
+
+    fn print_report(&self, prefix: &str) {
+        println!(
+            "{}notify: {} anomalies, {} notifications sent",
+            prefix, self.anomalies, self.sent
+        );
+    }
+}
+
+/// Calls Notify with gdbus, without waiting for the notification daemon.
+fn send(summary: &str, body: &str) {
+    let child = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.Notifications",
+            "--object-path",
+            "/org/freedesktop/Notifications",
+            "--method",
+            "org.freedesktop.Notifications.Notify",
+            "fifo_test",
+            "0",
+            "",
+            summary,
+            body,
+            "[]",
+            "{}",
+            "-1",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("failed to send a notification, gdbus: {}", err);
+            return;
+        }
+    };
+    std::thread::spawn(move || match child.wait_with_output() {
+        Ok(output) if !output.status.success() => eprintln!(
+            "failed to send a notification: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Ok(_) => {}
+        Err(err) => eprintln!("failed to send a notification: {}", err),
+    });
+}
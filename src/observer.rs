@@ -0,0 +1,51 @@
+//! Per-frame hooks of the analyzers that only watch the frames go by.
+//!
+//! Analyzers steering the window, e.g. `--break-on` freezing it or `--sweep` scheduling
+//! the next draw, stay fields of `SimpleWindow`. The others implement [`FrameObserver`]
+//! and are kept in one list, so adding one does not touch the draw loop.
+
+use std::time::Duration;
+
+use crate::presentation::Presented;
+
+/// A frame that was just committed.
+pub struct Committed {
+    pub frame: u64,
+    /// Whether the commit attached new content, not only state.
+    pub present: bool,
+    pub barrier: bool,
+    /// Whether no buffer was free when the frame was drawn.
+    pub waited_for_buffer: bool,
+}
+
+/// Where and how often the hooks may log.
+pub struct Log<'a> {
+    pub prefix: &'a str,
+    /// `--log-every`, 0 without per-frame logging.
+    pub every: u64,
+}
+
+pub trait FrameObserver {
+    fn committed(&mut self, _committed: &Committed) {}
+
+    /// Feedback of `frame`, `None` if it was discarded. `output` is the name of the
+    /// output it was presented on.
+    fn presented(
+        &mut self,
+        _frame: u64,
+        _presented: Option<&Presented>,
+        _output: &str,
+        _log: &Log,
+    ) {
+    }
+
+    /// The compositor released the buffer of `frame`.
+    fn released(&mut self, _frame: u64, _time: Duration) {}
+
+    /// Whether the observer needs presentation feedback of every frame.
+    fn wants_feedback(&self) -> bool {
+        true
+    }
+
+    fn print_report(&self, prefix: &str);
+}
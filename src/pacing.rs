@@ -0,0 +1,35 @@
+use clap::ValueEnum;
+
+use crate::plugin::FramePlan;
+
+/// Throttling mechanism of one surface in a `--mix` run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Pacing {
+    /// Every frame waits for and sets a fifo barrier
+    Fifo,
+    /// Every frame waits for the frame callback of the previous one
+    FrameCallback,
+    /// Neither barriers nor frame callbacks
+    Unthrottled,
+}
+
+impl Pacing {
+    /// Overrides the throttling of a scenario's plan.
+    pub fn apply(self, plan: &mut FramePlan) {
+        let (barrier, frame_callback) = match self {
+            Pacing::Fifo => (true, false),
+            Pacing::FrameCallback => (false, true),
+            Pacing::Unthrottled => (false, false),
+        };
+        plan.barrier = barrier;
+        plan.frame_callback = frame_callback;
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pacing::Fifo => "fifo",
+            Pacing::FrameCallback => "frame-callback",
+            Pacing::Unthrottled => "unthrottled",
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Partial redraw driven by the implied buffer age, `--partial-redraw`.
+//!
+//! Toolkits keep the content of a reused buffer and only repaint what changed since
+//! it was last drawn, the union of the damage of every frame in between. wl_shm has
+//! no buffer age query, so the age is implied by the order the compositor releases
+//! the buffers in: it is the number of frames rendered since the picked buffer was
+//! last drawn into.
+//!
+//! The scene is a checkerboard with a square bouncing across it, changing its color
+//! every frame, so stale pixels stand out. Every repaint is compared against a full
+//! render of the frame, and the commits only damage the square's old and new
+//! position. With `--verify-capture` that checks that the compositor also composes
+//! the partially damaged, fifo queued commits correctly.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::canvas::Canvas;
+use crate::commit_thread::Rect;
+
+/// Rendered frames remembered, older buffers are repainted in full.
+const HISTORY: usize = 16;
+const SQUARE: u32 = 64;
+const CELL: i32 = 32;
+const BACKGROUND: [u32; 2] = [0xFF40_4040, 0xFF60_6060];
+const COLORS: [u32; 6] = [
+    0xFFFF_0000,
+    0xFFFF_FF00,
+    0xFF00_FF00,
+    0xFF00_FFFF,
+    0xFF00_00FF,
+    0xFFFF_00FF,
+];
+
+#[derive(Default)]
+pub struct Partial {
+    /// Frames rendered so far.
+    renders: u64,
+    /// Squares of the most recently rendered frames.
+    history: VecDeque<Rect>,
+    /// Render count each buffer was last drawn at.
+    drawn: Vec<Option<u64>>,
+    /// Square of the last committed frame.
+    committed: Option<Rect>,
+    /// Damage of the frame rendered last, until it is committed.
+    damage: Vec<Rect>,
+    /// Areas overdrawn by overlays, left out of the comparison.
+    overlays: Vec<Rect>,
+    /// Frames by buffer age, 0 for full repaints.
+    ages: BTreeMap<u64, u64>,
+    repainted_pixels: u64,
+    pixels: u64,
+    mismatches: u64,
+    reference: Vec<u8>,
+}
+
+impl Partial {
+    pub fn new(buffers: usize) -> Self {
+        Self {
+            drawn: vec![None; buffers],
+            ..Self::default()
+        }
+    }
+
+    /// Forgets the content of all buffers, e.g. after they were recreated.
+    pub fn invalidate(&mut self) {
+        self.drawn.fill(None);
+        self.committed = None;
+        self.overlays.clear();
+    }
+
+    /// Repaints the buffer `index` for `frame` and compares it with a full render.
+    pub fn render(&mut self, canvas: &mut Canvas, index: usize, frame: u64, prefix: &str) {
+        let (width, height) = (canvas.width(), canvas.height());
+        let square = square(frame, width, height);
+        let full = (0, 0, width as i32, height as i32);
+
+        let age = self.drawn[index]
+            .map(|drawn| self.renders - drawn + 1)
+            .filter(|age| *age as usize <= self.history.len())
+            .unwrap_or(0);
+        let mut repaint = vec![square];
+        if age == 0 {
+            repaint.push(full);
+        } else {
+            repaint.extend(self.history.iter().rev().take(age as usize).copied());
+        }
+        for rect in &repaint {
+            background(canvas, *rect);
+        }
+        canvas.fill_rect(square.0, square.1, SQUARE, SQUARE, color(frame));
+
+        *self.ages.entry(age).or_default() += 1;
+        // Overlapping rectangles are counted twice.
+        self.repainted_pixels += repaint
+            .iter()
+            .map(|rect| area(*rect))
+            .sum::<u64>()
+            .min(area(full));
+        self.pixels += area(full);
+
+        self.renders += 1;
+        self.drawn[index] = Some(self.renders);
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(square);
+        self.damage = match self.committed {
+            Some(committed) => vec![committed, square],
+            None => vec![full],
+        };
+
+        self.verify(canvas, frame, prefix);
+    }
+
+    fn verify(&mut self, canvas: &Canvas, frame: u64, prefix: &str) {
+        let (width, height) = (canvas.width(), canvas.height());
+        self.reference.resize((width * height * 4) as usize, 0);
+        let mut reference = Canvas::new(&mut self.reference, width, height);
+        background(&mut reference, (0, 0, width as i32, height as i32));
+        let square = square(frame, width, height);
+        reference.fill_rect(square.0, square.1, SQUARE, SQUARE, color(frame));
+
+        // Overlays are drawn after the comparison, their areas hold whatever they
+        // drew last.
+        let data = canvas.data();
+        for (x, y, rect_width, rect_height) in &self.overlays {
+            let x0 = (*x).clamp(0, width as i32) as usize;
+            let x1 = (x + rect_width).clamp(0, width as i32) as usize;
+            for row in (*y).max(0)..(y + rect_height).min(height as i32) {
+                let start = row as usize * width as usize;
+                let range = (start + x0) * 4..(start + x1) * 4;
+                self.reference[range.clone()].copy_from_slice(&data[range]);
+            }
+        }
+        if data != self.reference.as_slice() {
+            self.mismatches += 1;
+            eprintln!(
+                "{}partial redraw of frame {} differs from a full redraw",
+                prefix, frame
+            );
+        }
+    }
+
+    /// Adds an area an overlay draws into every frame to the damage.
+    pub fn overlay(&mut self, rect: Rect) {
+        self.damage.push(rect);
+        if !self.overlays.contains(&rect) {
+            self.overlays.push(rect);
+        }
+    }
+
+    /// The damage of the frame rendered last, relative to the last commit.
+    pub fn damage(&self) -> Vec<Rect> {
+        self.damage.clone()
+    }
+
+    pub fn committed(&mut self) {
+        self.committed = self.history.back().copied();
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let frames = self.ages.values().sum::<u64>();
+        if frames == 0 {
+            println!("{}partial redraw: no frames rendered", prefix);
+            return;
+        }
+        println!(
+            "{}partial redraw: {} frames, {:.1}% of the pixels repainted, {} differed from a full redraw",
+            prefix,
+            frames,
+            self.repainted_pixels as f64 * 100.0 / self.pixels.max(1) as f64,
+            self.mismatches
+        );
+        for (age, count) in &self.ages {
+            let age = match age {
+                0 => "unknown, full repaint".to_string(),
+                age => age.to_string(),
+            };
+            println!("{}  buffer age {}: {} frames", prefix, age, count);
+        }
+    }
+}
+
+/// Top-left corner of the square, bouncing diagonally across the buffer.
+fn square(frame: u64, width: u32, height: u32) -> Rect {
+    let bounce = |position: u64, range: u32| {
+        let range = range.saturating_sub(SQUARE) as u64;
+        if range == 0 {
+            return 0;
+        }
+        let position = position % (range * 2);
+        position.min(range * 2 - position) as i32
+    };
+    (
+        bounce(frame * 7, width),
+        bounce(frame * 5, height),
+        SQUARE as i32,
+        SQUARE as i32,
+    )
+}
+
+fn color(frame: u64) -> u32 {
+    COLORS[(frame % COLORS.len() as u64) as usize]
+}
+
+/// Paints the checkerboard into `rect`.
+fn background(canvas: &mut Canvas, (x, y, width, height): Rect) {
+    let (x1, y1) = (x + width, y + height);
+    for row in y.div_euclid(CELL)..=(y1 - 1).div_euclid(CELL) {
+        for column in x.div_euclid(CELL)..=(x1 - 1).div_euclid(CELL) {
+            let left = (column * CELL).max(x);
+            let top = (row * CELL).max(y);
+            let right = ((column + 1) * CELL).min(x1);
+            let bottom = ((row + 1) * CELL).min(y1);
+            canvas.fill_rect(
+                left,
+                top,
+                (right - left) as u32,
+                (bottom - top) as u32,
+                BACKGROUND[((row + column) & 1) as usize],
+            );
+        }
+    }
+}
+
+fn area((_, _, width, height): Rect) -> u64 {
+    width.max(0) as u64 * height.max(0) as u64
+}
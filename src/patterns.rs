@@ -0,0 +1,56 @@
+use crate::canvas::Canvas;
+use crate::plugin::{FramePattern, Registry};
+
+pub fn register(registry: &mut Registry) {
+    registry.register_pattern("gradient", "static color gradient", || Box::new(Gradient));
+    registry.register_pattern(
+        "bar",
+        "vertical bar moving one step per frame, for spotting judder",
+        || Box::new(Bar),
+    );
+}
+
+struct Gradient;
+
+impl FramePattern for Gradient {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _frame: u64) {
+        let width = canvas.width();
+        let height = canvas.height();
+
+        for y in 0..height {
+            for x in 0..width {
+                let a = 0xFF;
+                let r = u32::min(((width - x) * 0xFF) / width, ((height - y) * 0xFF) / height);
+                let g = u32::min((x * 0xFF) / width, ((height - y) * 0xFF) / height);
+                let b = u32::min(((width - x) * 0xFF) / width, (y * 0xFF) / height);
+                let color = (a << 24) + (r << 16) + (g << 8) + b;
+
+                canvas.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+struct Bar;
+
+impl Bar {
+    const WIDTH: u32 = 16;
+    const STEP: u32 = 8;
+}
+
+impl FramePattern for Bar {
+    fn render(&mut self, canvas: &mut Canvas, frame: u64) {
+        let width = canvas.width();
+        let height = canvas.height();
+        let x = (frame * Self::STEP as u64 % width.max(1) as u64) as i32;
+
+        canvas.fill_rect(0, 0, width, height, 0xFF10_1010);
+        canvas.fill_rect(x, 0, Self::WIDTH, height, 0xFFFF_FFFF);
+        // Wrap around the right edge.
+        canvas.fill_rect(x - width as i32, 0, Self::WIDTH, height, 0xFFFF_FFFF);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+/// Frames shown in the report diagram, the last ones of the run.
+const DIAGRAM_FRAMES: usize = 32;
+/// Time covered by one column of the diagram.
+const DIAGRAM_STEP: Duration = Duration::from_millis(2);
+const DIAGRAM_WIDTH: usize = 50;
+
+/// Times of one frame, in `CLOCK_MONOTONIC`.
+#[derive(Default)]
+pub struct Row {
+    pub buffer: usize,
+    pub committed: Duration,
+    pub callback: Option<Duration>,
+    pub presented: Option<Duration>,
+    pub discarded: bool,
+    pub released: Option<Duration>,
+}
+
+/// Commit, frame callback, presentation and buffer release times of every frame,
+/// `--pipeline`.
+///
+/// Presentation times come from the compositor's feedback, frame callbacks and
+/// releases are timed when the client receives them as wl_buffer.release carries no
+/// timestamp.
+#[derive(Default)]
+pub struct Pipeline {
+    rows: BTreeMap<u64, Row>,
+    /// Frame each buffer was last committed with, until its release.
+    in_flight: Vec<Option<u64>>,
+    callbacks: VecDeque<u64>,
+}
+
+impl Pipeline {
+    pub fn new(buffers: usize) -> Self {
+        Self {
+            in_flight: vec![None; buffers],
+            ..Self::default()
+        }
+    }
+
+    pub fn committed(&mut self, frame: u64, buffer: usize, time: Duration) {
+        self.rows.insert(
+            frame,
+            Row {
+                buffer,
+                committed: time,
+                ..Default::default()
+            },
+        );
+        self.in_flight[buffer] = Some(frame);
+    }
+
+    /// Records a frame callback requested with the commit of `frame`.
+    pub fn callback_requested(&mut self, frame: u64) {
+        self.callbacks.push_back(frame);
+    }
+
+    pub fn callback(&mut self, time: Duration) {
+        if let Some(row) = self
+            .callbacks
+            .pop_front()
+            .and_then(|frame| self.rows.get_mut(&frame))
+        {
+            row.callback = Some(time);
+        }
+    }
+
+    pub fn presented(&mut self, frame: u64, time: Option<Duration>) {
+        if let Some(row) = self.rows.get_mut(&frame) {
+            row.presented = time;
+            row.discarded = time.is_none();
+        }
+    }
+
+    /// Records the release of every in-flight buffer that is free again.
+    pub fn poll(&mut self, free: impl Fn(usize) -> bool, time: Duration) {
+        for (buffer, frame) in self.in_flight.iter_mut().enumerate() {
+            if frame.is_some() && free(buffer) {
+                if let Some(row) = frame.take().and_then(|frame| self.rows.get_mut(&frame)) {
+                    row.released = Some(time);
+                }
+            }
+        }
+    }
+
+    /// Every committed frame in order.
+    pub fn rows(&self) -> impl Iterator<Item = (u64, &Row)> {
+        self.rows.iter().map(|(&frame, row)| (frame, row))
+    }
+
+    /// Forgets the in-flight buffers, after they were replaced.
+    pub fn reset_buffers(&mut self) {
+        self.in_flight.fill(None);
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let after =
+            |row: &Row, time: Option<Duration>| time.map(|time| time.saturating_sub(row.committed));
+        let mean = |values: Vec<Duration>| {
+            (!values.is_empty()).then(|| values.iter().sum::<Duration>() / values.len() as u32)
+        };
+        let format_mean = |mean: Option<Duration>| {
+            mean.map_or("-".to_string(), |mean| format!("{:.3}ms", ms(mean)))
+        };
+
+        let presented = self
+            .rows
+            .values()
+            .filter_map(|row| after(row, row.presented))
+            .collect();
+        let released = self
+            .rows
+            .values()
+            .filter_map(|row| after(row, row.released))
+            .collect();
+        let held = self
+            .rows
+            .values()
+            .filter_map(|row| row.released?.checked_sub(row.presented?))
+            .collect();
+        println!(
+            "{}pipeline: {} frames, commit to present {}, commit to release {}, present to release {}, {} discarded",
+            prefix,
+            self.rows.len(),
+            format_mean(mean(presented)),
+            format_mean(mean(released)),
+            format_mean(mean(held)),
+            self.rows.values().filter(|row| row.discarded).count()
+        );
+
+        println!(
+            "{}  C commit, f frame callback, P presented, D discarded, R released, one column per {}ms",
+            prefix,
+            DIAGRAM_STEP.as_millis()
+        );
+        let skip = self.rows.len().saturating_sub(DIAGRAM_FRAMES);
+        for (frame, row) in self.rows.iter().skip(skip) {
+            let column = |time: Duration| {
+                ((time.saturating_sub(row.committed).as_nanos() / DIAGRAM_STEP.as_nanos()) as usize)
+                    .min(DIAGRAM_WIDTH - 1)
+            };
+            let end = row.released.or(row.presented).map_or(0, column);
+            let mut line = vec![' '; DIAGRAM_WIDTH];
+            for cell in line.iter_mut().take(end + 1) {
+                *cell = '-';
+            }
+            line[0] = 'C';
+            if let Some(callback) = row.callback {
+                line[column(callback)] = 'f';
+            }
+            match row.presented {
+                Some(presented) => line[column(presented)] = 'P',
+                None if row.discarded => line[end] = 'D',
+                None => {}
+            }
+            if let Some(released) = row.released {
+                line[column(released)] = 'R';
+            }
+
+            let offset = |time: Option<Duration>| {
+                after(row, time).map_or("-".to_string(), |time| format!("+{:.1}", ms(time)))
+            };
+            println!(
+                "{}  {:>6} b{} |{}| P {:>7} R {:>7}",
+                prefix,
+                frame,
+                row.buffer,
+                line.into_iter().collect::<String>(),
+                offset(row.presented),
+                offset(row.released)
+            );
+        }
+    }
+}
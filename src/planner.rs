@@ -0,0 +1,669 @@
+//! The frame pacing decisions, free of Wayland I/O and of the clock.
+//!
+//! [`Planner`] plans each frame from the scenario and the options that override it.
+//! [`Decision`] turns a plan into what the window commits and how it waits for the
+//! next frame. The time comes from a [`Clock`], so both a replayed trace and the unit
+//! tests run them on simulated time without a compositor.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::backoff::Backoff;
+use crate::clock::Clock;
+use crate::idle::IdleBurst;
+use crate::pacing::Pacing;
+use crate::plugin::{FramePlan, Scenario, Stats};
+use crate::presentation::Presented;
+use crate::profile::Profile;
+use crate::target::Target;
+use crate::Args;
+
+/// Plans every frame from the scenario and the options overriding it, so a recorded
+/// trace can be replayed against it, see `replay`.
+pub struct Planner {
+    pub scenario: Box<dyn Scenario>,
+    pub pacing: Option<Pacing>,
+    pub profile: Option<Profile>,
+    pub target: Option<Target>,
+    pub idle_burst: Option<IdleBurst>,
+    pub backoff: Option<Backoff>,
+    clock: Rc<dyn Clock>,
+}
+
+impl Planner {
+    /// The planner of the window of `connection`, counted from 1, if there are several.
+    pub fn new(
+        args: &Args,
+        scenario: Box<dyn Scenario>,
+        connection: Option<u32>,
+        clock: Rc<dyn Clock>,
+    ) -> Self {
+        Self {
+            scenario,
+            pacing: (!args.mix.is_empty())
+                .then(|| args.mix[(connection.unwrap_or(1) as usize - 1) % args.mix.len()]),
+            profile: args.profile,
+            target: args.target_fps.map(Target::new),
+            idle_burst: args
+                .idle_burst
+                .map(|idle| IdleBurst::new(Duration::from_millis(idle))),
+            backoff: (!args.no_backoff).then(Backoff::default),
+            clock,
+        }
+    }
+
+    /// Plans `frame` at the current time, `None` once the scenario finished.
+    ///
+    /// Pattern switches and annotations of the scenario are left for the caller.
+    pub fn plan(&mut self, frame: u64, stats: &Stats, prefix: &str) -> Option<FramePlan> {
+        let now = self.clock.now();
+        self.scenario.observe(stats);
+        let mut plan = self.scenario.plan(frame)?;
+        if let Some(pacing) = self.pacing {
+            pacing.apply(&mut plan);
+        }
+        if let Some(profile) = self.profile {
+            profile.apply(&mut plan);
+        }
+        if let Some(delay) = self.target.as_mut().and_then(|target| target.delay(now)) {
+            plan.delay = delay;
+        }
+        if let Some(idle) = self.idle_burst.as_ref() {
+            idle.apply(&mut plan);
+        }
+        if let Some(backoff) = self.backoff.as_mut() {
+            backoff.apply(&mut plan, prefix);
+        }
+        Some(plan)
+    }
+
+    /// Whether planning needs presentation feedback for every frame.
+    pub fn wants_feedback(&self) -> bool {
+        self.scenario.wants_feedback()
+            || self.idle_burst.is_some()
+            || self.target.as_ref().is_some_and(Target::is_auto)
+    }
+
+    /// `frame` was committed.
+    pub fn committed(&mut self, frame: u64) {
+        if let Some(backoff) = self.backoff.as_mut() {
+            backoff.committed(frame);
+        }
+    }
+
+    /// The compositor released the buffer committed with `frame`.
+    pub fn released(&mut self, frame: u64, prefix: &str) {
+        if let Some(backoff) = self.backoff.as_mut() {
+            backoff.consumed(frame, prefix);
+        }
+    }
+
+    /// The feedback of `frame` arrived, `None` if it was discarded.
+    pub fn feedback(&mut self, frame: u64, presented: Option<&Presented>, prefix: &str) {
+        if let Some(backoff) = self.backoff.as_mut() {
+            backoff.consumed(frame, prefix);
+        }
+        let Some(presented) = presented else {
+            if let Some(idle) = self.idle_burst.as_mut() {
+                idle.discarded();
+            }
+            return;
+        };
+        if let Some(idle) = self.idle_burst.as_mut() {
+            idle.presented(
+                presented.time.saturating_sub(presented.committed),
+                presented.refresh,
+            );
+        }
+        if let Some(target) = self.target.as_mut() {
+            target.feedback(presented.refresh, prefix);
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if let Some(idle) = self.idle_burst.as_ref() {
+            idle.print_report(prefix);
+        }
+        if let Some(backoff) = self.backoff.as_ref() {
+            backoff.print_report(prefix);
+        }
+    }
+}
+
+/// What the window offers the frame being drawn, beyond its plan.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Surface {
+    /// wp_fifo_v1 is bound and enabled.
+    pub fifo: bool,
+    /// The content of this frame is presented, `--present-divisor` skips the others.
+    pub present: bool,
+    /// Frame callbacks are requested whatever the plan, for the suspend detection.
+    pub frame_callbacks: bool,
+    /// The next frame is drawn once this one is presented, `--sweep` with
+    /// wp_presentation.
+    pub draw_on_presented: bool,
+    /// Presentation feedback is wanted and wp_presentation is available.
+    pub feedback: bool,
+}
+
+/// When the frame after a commit is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Next {
+    /// Once the presentation feedback of this frame arrives.
+    Presented,
+    /// After the delay once the frame callback of this frame is done.
+    FrameCallback(Duration),
+    /// After the delay.
+    Timer(Duration),
+}
+
+/// How a planned frame is committed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decision {
+    /// Attach and damage the buffer.
+    pub present: bool,
+    /// Wait for and set a fifo barrier.
+    pub barrier: bool,
+    /// Commit at all, frames that aren't presented still commit their barrier so they
+    /// keep occupying a refresh cycle under fifo.
+    pub commit: bool,
+    /// The commit-timing target, only of presented frames.
+    pub present_at: Option<Duration>,
+    pub frame_callback: bool,
+    pub feedback: bool,
+    pub next: Next,
+}
+
+impl Decision {
+    pub fn new(plan: &FramePlan, surface: &Surface) -> Self {
+        let present = surface.present;
+        let barrier = plan.barrier && surface.fifo;
+        let commit = present || barrier;
+        let next = if surface.draw_on_presented && present {
+            Next::Presented
+        } else if plan.frame_callback && commit {
+            Next::FrameCallback(plan.delay)
+        } else {
+            Next::Timer(plan.delay)
+        };
+        Self {
+            present,
+            barrier,
+            commit,
+            present_at: plan.present_at.filter(|_| present),
+            frame_callback: (plan.frame_callback || surface.frame_callbacks) && commit,
+            feedback: present && surface.feedback,
+            next,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::clock::Simulated;
+
+    use clap::Parser;
+    use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::wp_presentation_feedback::Kind;
+
+    /// Plans `plan` for every frame up to `frames`.
+    struct Fixed {
+        plan: FramePlan,
+        frames: u64,
+    }
+
+    impl Scenario for Fixed {
+        fn plan(&mut self, frame: u64) -> Option<FramePlan> {
+            (frame <= self.frames).then_some(self.plan)
+        }
+    }
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("fifo_test").chain(args.iter().copied())).unwrap()
+    }
+
+    fn planner(args: &[&str], plan: FramePlan) -> Planner {
+        planner_on(args, plan, u64::MAX, &Simulated::default())
+    }
+
+    /// A planner of `frames` frames on a simulated clock.
+    fn planner_on(args: &[&str], plan: FramePlan, frames: u64, clock: &Simulated) -> Planner {
+        let scenario = Fixed { plan, frames };
+        Planner::new(
+            &parse(args),
+            Box::new(scenario),
+            None,
+            Rc::new(clock.clone()),
+        )
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn presented(refresh: Duration) -> Presented {
+        Presented {
+            time: ms(20),
+            committed: ms(4),
+            refresh,
+            seq: 1,
+            flags: Kind::empty(),
+            output: None,
+        }
+    }
+
+    fn throttling(plan: FramePlan) -> (bool, bool) {
+        (plan.barrier, plan.frame_callback)
+    }
+
+    #[test]
+    fn scenario_plan_passes_through() {
+        let plan = FramePlan {
+            barrier: false,
+            frame_callback: true,
+            delay: ms(3),
+            present_at: Some(ms(50)),
+            ..FramePlan::default()
+        };
+        let mut planner = planner(&["--no-backoff"], plan);
+        let planned = planner.plan(1, &Stats::default(), "").unwrap();
+        assert_eq!(throttling(planned), (false, true));
+        assert_eq!(planned.delay, ms(3));
+        assert_eq!(planned.present_at, Some(ms(50)));
+    }
+
+    #[test]
+    fn finished_scenario_ends_planning() {
+        let mut planner = planner_on(&[], FramePlan::default(), 100, &Simulated::default());
+        assert!(planner.plan(100, &Stats::default(), "").is_some());
+        assert!(planner.plan(101, &Stats::default(), "").is_none());
+    }
+
+    #[test]
+    fn scenario_observes_the_stats_before_planning() {
+        let stats = Stats {
+            frames: 7,
+            presented: 5,
+            discarded: 1,
+            ..Stats::default()
+        };
+        // A scenario that only plans once it saw the stats.
+        struct Observer(Option<Stats>);
+        impl Scenario for Observer {
+            fn plan(&mut self, _frame: u64) -> Option<FramePlan> {
+                self.0.map(|_| FramePlan::default())
+            }
+            fn observe(&mut self, stats: &Stats) {
+                self.0 = Some(*stats);
+            }
+        }
+        let mut planner = Planner::new(
+            &parse(&[]),
+            Box::new(Observer(None)),
+            None,
+            Rc::new(Simulated::default()),
+        );
+        assert!(planner.plan(1, &stats, "").is_some());
+    }
+
+    #[test]
+    fn mix_pacing_cycles_through_connections() {
+        let args = parse(&["--mix", "fifo,frame-callback,unthrottled"]);
+        let expected = [(true, false), (false, true), (false, false), (true, false)];
+        for (connection, expected) in (1..).zip(expected) {
+            let scenario = Fixed {
+                plan: FramePlan::default(),
+                frames: 1,
+            };
+            let mut planner = Planner::new(
+                &args,
+                Box::new(scenario),
+                Some(connection),
+                Rc::new(Simulated::default()),
+            );
+            let plan = planner.plan(1, &Stats::default(), "").unwrap();
+            assert_eq!(throttling(plan), expected, "connection {}", connection);
+        }
+    }
+
+    #[test]
+    fn profile_overrides_pacing() {
+        for (profile, expected) in [
+            ("gtk", (false, true)),
+            ("qt", (false, true)),
+            ("sdl", (true, false)),
+            ("winit", (true, true)),
+        ] {
+            let mut planner = planner(
+                &["--mix", "unthrottled", "--profile", profile],
+                FramePlan::default(),
+            );
+            let plan = planner.plan(1, &Stats::default(), "").unwrap();
+            assert_eq!(throttling(plan), expected, "profile {}", profile);
+        }
+    }
+
+    #[test]
+    fn target_fps_paces_on_absolute_deadlines() {
+        let clock = Simulated::default();
+        let mut planner = planner_on(
+            &["--target-fps", "100", "--no-backoff"],
+            FramePlan::default(),
+            u64::MAX,
+            &clock,
+        );
+        let stats = Stats::default();
+        let mut delay = |frame, now| {
+            clock.advance_to(now);
+            planner.plan(frame, &stats, "").unwrap().delay
+        };
+        assert_eq!(delay(1, ms(1000)), ms(10));
+        // Drawn 2ms late, the deadline stays.
+        assert_eq!(delay(2, ms(1012)), ms(8));
+        // Deadlines missed entirely are skipped.
+        assert_eq!(delay(3, ms(1100)), ms(10));
+    }
+
+    /// A thousand hours of drawing at 1 fps, mostly a little and sometimes a
+    /// deadline or more late. Deadlines stay a second apart, late frames keep the
+    /// deadline and missed ones restart from the late frame instead of bursting.
+    #[test]
+    fn target_fps_keeps_deadlines_for_a_thousand_hours() {
+        const HOURS: u64 = 1000;
+        let second = Duration::from_secs(1);
+        let clock = Simulated::default();
+        let mut planner = planner_on(
+            &["--target-fps", "1", "--no-backoff"],
+            FramePlan::default(),
+            u64::MAX,
+            &clock,
+        );
+        let stats = Stats::default();
+        let mut random = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut deadline = None;
+        let mut frames = 0;
+        let mut missed = 0;
+        while clock.now() < Duration::from_secs(HOURS * 3600) {
+            frames += 1;
+            let now = clock.now();
+            let next = now + planner.plan(frames, &stats, "").unwrap().delay;
+            match deadline {
+                Some(deadline) if deadline + second > now => {
+                    assert_eq!(next, deadline + second, "frame {}", frames)
+                }
+                _ => assert_eq!(next, now + second, "frame {}", frames),
+            }
+            deadline = Some(next);
+
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            let late = match random % 100 {
+                0 => {
+                    missed += 1;
+                    ms(1000 + random / 100 % 500)
+                }
+                _ => ms(random / 100 % 10),
+            };
+            clock.advance_to(next + late);
+        }
+        assert!(missed > 0);
+        assert!(frames <= HOURS * 3600 - missed);
+    }
+
+    #[test]
+    fn target_fps_auto_waits_for_the_refresh_rate() {
+        let mut planner = planner(&["--target-fps", "refresh/2"], FramePlan::default());
+        assert!(planner.wants_feedback());
+        let plan = FramePlan {
+            delay: ms(5),
+            ..FramePlan::default()
+        };
+        planner.scenario = Box::new(Fixed { plan, frames: 10 });
+        let stats = Stats::default();
+        assert_eq!(planner.plan(1, &stats, "").unwrap().delay, ms(5));
+        planner.feedback(1, Some(&presented(ms(10))), "");
+        assert_eq!(planner.plan(2, &stats, "").unwrap().delay, ms(20));
+    }
+
+    #[test]
+    fn idle_burst_overrides_throttling_and_delay() {
+        let plan = FramePlan {
+            barrier: false,
+            frame_callback: true,
+            delay: ms(1),
+            ..FramePlan::default()
+        };
+        let mut planner = planner(&["--idle-burst", "250", "--target-fps", "100"], plan);
+        assert!(planner.wants_feedback());
+        let plan = planner.plan(1, &Stats::default(), "").unwrap();
+        assert_eq!(throttling(plan), (true, false));
+        assert_eq!(plan.delay, ms(250));
+    }
+
+    #[test]
+    fn backoff_slows_down_while_commits_are_unconsumed() {
+        let plan = FramePlan {
+            delay: ms(1),
+            ..FramePlan::default()
+        };
+        let mut planner = planner(&[], plan);
+        // Buffer releases are enough to tell.
+        assert!(!planner.wants_feedback());
+        let stats = Stats::default();
+        for frame in 1..=30 {
+            assert_eq!(planner.plan(frame, &stats, "").unwrap().delay, ms(1));
+            planner.committed(frame);
+        }
+        planner.committed(31);
+        // Doubling from 16ms with every frame, up to a second.
+        let delays = (32..=41)
+            .map(|frame| planner.plan(frame, &stats, "").unwrap().delay)
+            .collect::<Vec<_>>();
+        assert_eq!(delays[..4], [ms(16), ms(32), ms(64), ms(128)]);
+        assert_eq!(delays[9], Duration::from_secs(1));
+
+        // Releasing the buffer of a commit consumes every earlier one as well.
+        planner.released(15, "");
+        assert_eq!(planner.plan(42, &stats, "").unwrap().delay, ms(1));
+    }
+
+    #[test]
+    fn no_backoff_keeps_full_rate() {
+        let mut planner = planner(&["--no-backoff"], FramePlan::default());
+        assert!(!planner.wants_feedback());
+        let stats = Stats::default();
+        for frame in 1..=100 {
+            assert_eq!(planner.plan(frame, &stats, "").unwrap().delay, ms(0));
+            planner.committed(frame);
+        }
+    }
+
+    /// Plans and surfaces covering every rule the window commits by, with their
+    /// decisions worked out by hand.
+    #[test]
+    fn decisions_for_plans_and_surfaces() {
+        let presenting = Surface {
+            present: true,
+            ..Surface::default()
+        };
+        let fifo = Surface {
+            fifo: true,
+            ..presenting
+        };
+        let presented = Decision {
+            present: true,
+            barrier: false,
+            commit: true,
+            present_at: None,
+            frame_callback: false,
+            feedback: false,
+            next: Next::Timer(Duration::ZERO),
+        };
+        let skipped = Decision {
+            present: false,
+            commit: false,
+            ..presented
+        };
+        let cases = [
+            (
+                "barrier with fifo and feedback",
+                FramePlan::default(),
+                Surface {
+                    feedback: true,
+                    ..fifo
+                },
+                Decision {
+                    barrier: true,
+                    feedback: true,
+                    ..presented
+                },
+            ),
+            (
+                "barrier without fifo",
+                FramePlan::default(),
+                presenting,
+                presented,
+            ),
+            (
+                "frame callback throttled",
+                FramePlan {
+                    barrier: false,
+                    frame_callback: true,
+                    delay: ms(7),
+                    ..FramePlan::default()
+                },
+                fifo,
+                Decision {
+                    frame_callback: true,
+                    next: Next::FrameCallback(ms(7)),
+                    ..presented
+                },
+            ),
+            (
+                "frame callbacks of the surface don't pace",
+                FramePlan::default(),
+                Surface {
+                    frame_callbacks: true,
+                    ..fifo
+                },
+                Decision {
+                    barrier: true,
+                    frame_callback: true,
+                    ..presented
+                },
+            ),
+            (
+                "commit-timing target",
+                FramePlan {
+                    present_at: Some(ms(40)),
+                    ..FramePlan::default()
+                },
+                fifo,
+                Decision {
+                    barrier: true,
+                    present_at: Some(ms(40)),
+                    ..presented
+                },
+            ),
+            (
+                "drawing on presentation beats the frame callback",
+                FramePlan {
+                    frame_callback: true,
+                    delay: ms(7),
+                    ..FramePlan::default()
+                },
+                Surface {
+                    draw_on_presented: true,
+                    ..fifo
+                },
+                Decision {
+                    barrier: true,
+                    frame_callback: true,
+                    next: Next::Presented,
+                    ..presented
+                },
+            ),
+            (
+                "skipped frame keeps its barrier, not its target or feedback",
+                FramePlan {
+                    present_at: Some(ms(40)),
+                    delay: ms(7),
+                    ..FramePlan::default()
+                },
+                Surface {
+                    fifo: true,
+                    draw_on_presented: true,
+                    feedback: true,
+                    ..Surface::default()
+                },
+                Decision {
+                    barrier: true,
+                    commit: true,
+                    next: Next::Timer(ms(7)),
+                    ..skipped
+                },
+            ),
+            (
+                "skipped frame without barrier",
+                FramePlan {
+                    barrier: false,
+                    frame_callback: true,
+                    delay: ms(3),
+                    ..FramePlan::default()
+                },
+                Surface {
+                    fifo: true,
+                    frame_callbacks: true,
+                    ..Surface::default()
+                },
+                Decision {
+                    next: Next::Timer(ms(3)),
+                    ..skipped
+                },
+            ),
+        ];
+        for (name, plan, surface, expected) in cases {
+            assert_eq!(Decision::new(&plan, &surface), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn skipped_frame_commits_only_its_barrier() {
+        let plan = FramePlan {
+            frame_callback: true,
+            present_at: Some(ms(40)),
+            ..FramePlan::default()
+        };
+        let surface = Surface {
+            fifo: true,
+            feedback: true,
+            ..Surface::default()
+        };
+        let decision = Decision::new(&plan, &surface);
+        assert!(decision.commit && decision.barrier && !decision.present);
+        assert!(decision.frame_callback);
+        assert_eq!(decision.present_at, None);
+        assert!(!decision.feedback);
+        assert_eq!(decision.next, Next::FrameCallback(Duration::ZERO));
+    }
+
+    #[test]
+    fn skipped_frame_without_fifo_commits_nothing() {
+        let plan = FramePlan {
+            frame_callback: true,
+            delay: ms(3),
+            ..FramePlan::default()
+        };
+        let surface = Surface {
+            frame_callbacks: true,
+            ..Surface::default()
+        };
+        let decision = Decision::new(&plan, &surface);
+        assert!(!decision.commit);
+        // A frame callback of a frame never committed would never be done.
+        assert!(!decision.frame_callback);
+        assert_eq!(decision.next, Next::Timer(ms(3)));
+    }
+}
@@ -0,0 +1,278 @@
+//! Extension points for custom frame content and commit sequences.
+//!
+//! Patterns and scenarios are registered by name in a [`Registry`] which is handed to
+//! [`main_with`](crate::main_with); `--pattern` and `--scenario` select them and
+//! `--list` prints everything registered.
+//!
+//! Shared objects passed with `--plugin` can register additional entries. They must
+//! export [`ENTRY_POINT`] as
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub fn fifo_test_register(registry: &mut fifo_test::plugin::Registry) { ... }
+//! ```
+//!
+//! and, as the Rust ABI is not stable, be built against the same version of this crate
+//! with the same compiler.
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::canvas::Canvas;
+
+/// Content rendered into every frame, below the overlays.
+pub trait FramePattern {
+    /// Static patterns are rendered once per buffer instead of for every frame.
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    /// Renders the content of `frame` into the canvas.
+    fn render(&mut self, canvas: &mut Canvas, frame: u64);
+}
+
+/// Surface damage posted with a commit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Damage {
+    /// Damage the whole surface.
+    Full,
+    /// Damage a single rectangle, inset by this many pixels from every edge.
+    Inset(u32),
+}
+
+/// How a single frame is committed.
+#[derive(Clone, Copy, Debug)]
+pub struct FramePlan {
+    /// Wait for and set a fifo barrier with this commit, if fifo is in use.
+    pub barrier: bool,
+    /// Request a frame callback with this commit and only draw the next frame once it
+    /// is done.
+    pub frame_callback: bool,
+    pub damage: Damage,
+    /// Time to wait after this commit, or after the frame callback, before the next
+    /// frame is drawn.
+    pub delay: Duration,
+    /// Earliest presentation time in `CLOCK_MONOTONIC` (see [`now`]), passed on with
+    /// commit-timing if the compositor supports it.
+    pub present_at: Option<Duration>,
+}
+
+impl Default for FramePlan {
+    fn default() -> Self {
+        Self {
+            barrier: true,
+            frame_callback: false,
+            damage: Damage::Full,
+            delay: Duration::ZERO,
+            present_at: None,
+        }
+    }
+}
+
+/// Current `CLOCK_MONOTONIC` time, the clock of [`FramePlan::present_at`].
+pub fn now() -> Duration {
+    crate::clock::monotonic()
+}
+
+/// A note attached to the record of a frame, e.g. `occluder shown` or
+/// `size = 1920x1080`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Annotation {
+    Label(String),
+    Value(String, String),
+}
+
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Annotation::Label(label) => f.write_str(label),
+            Annotation::Value(key, value) => write!(f, "{} = {}", key, value),
+        }
+    }
+}
+
+/// Statistics of the run so far, handed to [`Scenario::observe`] before every plan.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    /// Frames committed so far.
+    pub frames: u64,
+    /// Interval between the last two draws.
+    pub interval: Option<Duration>,
+    /// Commit-to-present latency of the last presented frame, only available if
+    /// [`Scenario::wants_feedback`] returns true.
+    pub latency: Option<Duration>,
+    pub presented: u64,
+    pub discarded: u64,
+}
+
+/// A sequence of commits.
+pub trait Scenario {
+    /// Plans `frame`, counted from 1. Returning `None` ends the run.
+    fn plan(&mut self, frame: u64) -> Option<FramePlan>;
+
+    /// Lowest wp_fifo_manager_v1 version the scenario's barriers need, with an older
+    /// one it runs without fifo.
+    fn min_fifo_version(&self) -> u32 {
+        1
+    }
+
+    /// Whether the plans carry [`FramePlan::present_at`], which needs commit-timing.
+    fn uses_commit_timing(&self) -> bool {
+        false
+    }
+
+    /// Whether every frame needs presentation feedback for [`Stats::latency`].
+    fn wants_feedback(&self) -> bool {
+        false
+    }
+
+    /// Called with the current statistics before every [`plan`](Self::plan).
+    fn observe(&mut self, _stats: &Stats) {}
+
+    /// Pattern to switch to from the next frame on, polled after every plan.
+    fn switch_pattern(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Annotations for the frame just planned, polled after every plan. They are
+    /// logged and stored with the frame in --db.
+    fn take_annotations(&mut self) -> Vec<Annotation> {
+        Vec::new()
+    }
+
+    /// Why the last [`plan`](Self::plan) returned `None` if the scenario failed rather
+    /// than finished, e.g. with a script error.
+    fn failure(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Symbol looked up in plugin shared objects.
+pub const ENTRY_POINT: &str = "fifo_test_register";
+
+type Factory<T> = Box<dyn Fn() -> Box<T> + Send + Sync>;
+
+struct Entry<T: ?Sized> {
+    name: String,
+    description: String,
+    factory: Factory<T>,
+}
+
+/// Patterns and scenarios available by name.
+///
+/// Every test window creates its own instances from the registered factories.
+#[derive(Default)]
+pub struct Registry {
+    patterns: Vec<Entry<dyn FramePattern>>,
+    scenarios: Vec<Entry<dyn Scenario>>,
+}
+
+impl Registry {
+    /// A registry with the patterns and scenarios shipped with this crate.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+        crate::patterns::register(&mut registry);
+        crate::scenarios::register(&mut registry);
+        registry
+    }
+
+    /// Registers a pattern, replacing any previous one with the same name.
+    pub fn register_pattern(
+        &mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        factory: impl Fn() -> Box<dyn FramePattern> + Send + Sync + 'static,
+    ) {
+        register(
+            &mut self.patterns,
+            name.into(),
+            description.into(),
+            Box::new(factory),
+        );
+    }
+
+    /// Registers a scenario, replacing any previous one with the same name.
+    pub fn register_scenario(
+        &mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        factory: impl Fn() -> Box<dyn Scenario> + Send + Sync + 'static,
+    ) {
+        register(
+            &mut self.scenarios,
+            name.into(),
+            description.into(),
+            Box::new(factory),
+        );
+    }
+
+    /// Loads a plugin shared object and lets it register its entries.
+    ///
+    /// The library stays loaded for the rest of the process.
+    ///
+    /// # Safety
+    ///
+    /// Loading runs arbitrary code from the library, which must export [`ENTRY_POINT`]
+    /// with the signature documented in the [module docs](self). The entry point uses
+    /// the Rust ABI and gets `Registry` passed by reference, so the library must be
+    /// built with the same compiler and against the same version of this crate as the
+    /// binary loading it. Nothing checks this, a mismatch is undefined behavior.
+    pub unsafe fn load(&mut self, path: &Path) -> Result<(), libloading::Error> {
+        let library = libloading::Library::new(path)?;
+        let register: libloading::Symbol<fn(&mut Registry)> =
+            library.get(ENTRY_POINT.as_bytes())?;
+        register(self);
+        // The registered factories point into the library.
+        std::mem::forget(library);
+        Ok(())
+    }
+
+    pub fn pattern(&self, name: &str) -> Option<Box<dyn FramePattern>> {
+        find(&self.patterns, name).map(|entry| (entry.factory)())
+    }
+
+    pub fn scenario(&self, name: &str) -> Option<Box<dyn Scenario>> {
+        find(&self.scenarios, name).map(|entry| (entry.factory)())
+    }
+
+    pub fn has_pattern(&self, name: &str) -> bool {
+        find(&self.patterns, name).is_some()
+    }
+
+    pub fn has_scenario(&self, name: &str) -> bool {
+        find(&self.scenarios, name).is_some()
+    }
+
+    pub fn scenario_names(&self) -> impl Iterator<Item = &str> {
+        self.scenarios.iter().map(|entry| entry.name.as_str())
+    }
+
+    pub fn print_list(&self) {
+        println!("patterns:");
+        for entry in &self.patterns {
+            println!("  {:<16} {}", entry.name, entry.description);
+        }
+        println!("scenarios:");
+        for entry in &self.scenarios {
+            println!("  {:<16} {}", entry.name, entry.description);
+        }
+    }
+}
+
+fn register<T: ?Sized>(
+    entries: &mut Vec<Entry<T>>,
+    name: String,
+    description: String,
+    factory: Factory<T>,
+) {
+    entries.retain(|entry| entry.name != name);
+    entries.push(Entry {
+        name,
+        description,
+        factory,
+    });
+}
+
+fn find<'a, T: ?Sized>(entries: &'a [Entry<T>], name: &str) -> Option<&'a Entry<T>> {
+    entries.iter().find(|entry| entry.name == name)
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::observer::{FrameObserver, Log};
+use crate::presentation::Presented;
+
+/// Vblank model of one output, from its last presentation.
+struct Model {
+    last: Duration,
+    seq: u64,
+    refresh: Duration,
+}
+
+#[derive(Default)]
+struct Errors {
+    /// Actual minus predicted presentation time, in nanoseconds.
+    errors: Vec<i64>,
+    /// Predictions off by at least half a refresh, i.e. the wrong vblank.
+    missed: u64,
+}
+
+/// Per-output vblank predictor, `--predict`.
+///
+/// A frame is predicted to be presented at the first vblank after its commit,
+/// extrapolated from the previous presentation on the same output.
+#[derive(Default)]
+pub struct Predictor {
+    models: BTreeMap<String, Model>,
+    outputs: BTreeMap<String, Errors>,
+}
+
+impl Predictor {
+    /// Updates the model of `output` and returns the prediction error of the frame,
+    /// `None` while no model exists yet.
+    fn predict(&mut self, output: &str, presented: &Presented) -> Option<i64> {
+        let previous = self.models.get(output);
+
+        let refresh = if !presented.refresh.is_zero() {
+            Some(presented.refresh)
+        } else {
+            // Estimate from the previous presentation, per refresh if the compositor
+            // reports retrace counters.
+            previous.and_then(|previous| {
+                let delta = presented.time.checked_sub(previous.last)?;
+                let cycles = presented.seq.saturating_sub(previous.seq);
+                match cycles {
+                    0 if presented.seq == 0 => Some(delta),
+                    0 => None,
+                    cycles => Some(delta / cycles as u32),
+                }
+            })
+        }
+        .filter(|refresh| !refresh.is_zero())
+        .or(previous.map(|previous| previous.refresh));
+
+        let error = previous.map(|previous| {
+            let refresh = previous.refresh.as_nanos() as i64;
+            let last = previous.last.as_nanos() as i64;
+            let committed = presented.committed.as_nanos() as i64;
+            // The first vblank after the commit, but never the already used one.
+            let cycles = ((committed - last) as f64 / refresh as f64).ceil().max(1.0) as i64;
+            let predicted = last + cycles * refresh;
+            let error = presented.time.as_nanos() as i64 - predicted;
+
+            let errors = self.outputs.entry(output.to_string()).or_default();
+            errors.errors.push(error);
+            if error.abs() >= refresh / 2 {
+                errors.missed += 1;
+            }
+            error
+        });
+
+        if let Some(refresh) = refresh {
+            self.models.insert(
+                output.to_string(),
+                Model {
+                    last: presented.time,
+                    seq: presented.seq,
+                    refresh,
+                },
+            );
+        }
+        error
+    }
+}
+
+impl FrameObserver for Predictor {
+    fn presented(&mut self, frame: u64, presented: Option<&Presented>, output: &str, log: &Log) {
+        let Some(error) = presented.and_then(|presented| self.predict(output, presented)) else {
+            return;
+        };
+        if log.every != 0 && frame.is_multiple_of(log.every) {
+            println!(
+                "{}Frame {} presented on {}, prediction error {:+.3}ms",
+                log.prefix,
+                frame,
+                output,
+                error as f64 / 1e6
+            );
+        }
+    }
+
+    fn print_report(&self, prefix: &str) {
+        println!("{}vblank prediction:", prefix);
+        for (output, errors) in &self.outputs {
+            let mut abs = errors
+                .errors
+                .iter()
+                .map(|e| e.unsigned_abs())
+                .collect::<Vec<_>>();
+            abs.sort();
+            let mean = errors.errors.iter().sum::<i64>() as f64 / errors.errors.len() as f64;
+            let percentile = |p: f64| abs[((abs.len() - 1) as f64 * p).round() as usize] as f64;
+            println!(
+                "{}  {}: {} frames, mean error {:+.3}ms, |error| p50 {:.3}ms p99 {:.3}ms max {:.3}ms, {} on the wrong vblank",
+                prefix,
+                output,
+                errors.errors.len(),
+                mean / 1e6,
+                percentile(0.5) / 1e6,
+                percentile(0.99) / 1e6,
+                percentile(1.0) / 1e6,
+                errors.missed
+            );
+        }
+    }
+}
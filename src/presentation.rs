@@ -0,0 +1,125 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::{
+    protocol::wl_output, Connection, Dispatch, QueueHandle, WEnum,
+};
+use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::{
+    wp_presentation, wp_presentation_feedback,
+};
+
+use crate::{clock, SimpleWindow};
+
+/// Frame a presentation feedback was requested for.
+pub struct FeedbackData {
+    pub frame: u64,
+    /// `CLOCK_MONOTONIC` time of the commit.
+    pub committed: Duration,
+    /// Output announced by `sync_output` before the feedback.
+    output: Mutex<Option<wl_output::WlOutput>>,
+}
+
+impl FeedbackData {
+    pub fn new(frame: u64) -> Self {
+        Self {
+            frame,
+            committed: clock::monotonic(),
+            output: Mutex::new(None),
+        }
+    }
+}
+
+/// Refresh cycles from the presentation at `last` to the one at `next`, both given as
+/// time and retrace counter, from the counters if the compositor sends them.
+pub fn refresh_cycles(last: (Duration, u64), next: (Duration, u64), refresh: Duration) -> u64 {
+    let ((last_time, last_seq), (time, seq)) = (last, next);
+    if last_seq != 0 && seq != 0 {
+        return seq.saturating_sub(last_seq);
+    }
+    if refresh.is_zero() {
+        return 1;
+    }
+    (time.saturating_sub(last_time).as_secs_f64() / refresh.as_secs_f64()).round() as u64
+}
+
+/// A `presented` event.
+#[derive(Clone, Debug)]
+pub struct Presented {
+    /// Presentation time in `CLOCK_MONOTONIC`.
+    pub time: Duration,
+    /// When the frame was committed, in `CLOCK_MONOTONIC`.
+    pub committed: Duration,
+    /// Predicted refresh interval, zero if unknown.
+    pub refresh: Duration,
+    /// Vertical retrace counter of the output, zero if unknown.
+    pub seq: u64,
+    /// How the frame was presented, e.g. zero-copy when scanned out directly.
+    pub flags: wp_presentation_feedback::Kind,
+    pub output: Option<wl_output::WlOutput>,
+}
+
+impl Dispatch<wp_presentation::WpPresentation, ()> for SimpleWindow {
+    fn event(
+        state: &mut Self,
+        _proxy: &wp_presentation::WpPresentation,
+        event: wp_presentation::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wp_presentation::Event::ClockId { clk_id } = event {
+            state.presentation_clock = Some(clk_id);
+        }
+    }
+}
+
+impl Dispatch<wp_presentation_feedback::WpPresentationFeedback, FeedbackData> for SimpleWindow {
+    fn event(
+        state: &mut Self,
+        _proxy: &wp_presentation_feedback::WpPresentationFeedback,
+        event: wp_presentation_feedback::Event,
+        data: &FeedbackData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wp_presentation_feedback::Event::SyncOutput { output } => {
+                *data.output.lock().unwrap() = Some(output);
+            }
+            wp_presentation_feedback::Event::Presented {
+                tv_sec_hi,
+                tv_sec_lo,
+                tv_nsec,
+                refresh,
+                seq_hi,
+                seq_lo,
+                flags,
+            } => {
+                let time = if state.presentation_clock == Some(libc::CLOCK_MONOTONIC as u32) {
+                    let secs = ((tv_sec_hi as u64) << 32) | tv_sec_lo as u64;
+                    Duration::new(secs, tv_nsec)
+                } else {
+                    // Timestamps in any other clock can't be compared to ours, fall
+                    // back to the time the event was received.
+                    state.clock.now()
+                };
+                let presented = Presented {
+                    time,
+                    committed: data.committed,
+                    refresh: Duration::from_nanos(refresh as u64),
+                    seq: ((seq_hi as u64) << 32) | seq_lo as u64,
+                    flags: match flags {
+                        WEnum::Value(flags) => flags,
+                        WEnum::Unknown(bits) => {
+                            wp_presentation_feedback::Kind::from_bits_truncate(bits)
+                        }
+                    },
+                    output: data.output.lock().unwrap().take(),
+                };
+                state.presented(data.frame, Some(presented));
+            }
+            wp_presentation_feedback::Event::Discarded => state.presented(data.frame, None),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use smithay_client_toolkit::reexports::client::globals::{registry_queue_init, GlobalListContents};
+use smithay_client_toolkit::reexports::client::protocol::wl_registry;
+use smithay_client_toolkit::reexports::client::{Connection, Dispatch, QueueHandle};
+
+use crate::plugin::Scenario;
+use crate::{backends, plugin, session};
+
+const CAPABILITIES_HEADER: &str = "# fifo_test capabilities 1";
+
+/// Protocols the report covers, with the highest version this tool implements, zero
+/// for protocols it only reports.
+pub const PROTOCOLS: &[(&str, &str, u32)] = &[
+    ("fifo", "wp_fifo_manager_v1", crate::FIFO_VERSION),
+    ("commit-timing", "wp_commit_timing_manager_v1", 1),
+    ("presentation", "wp_presentation", 1),
+    ("tearing", "wp_tearing_control_manager_v1", 0),
+    ("viewporter", "wp_viewporter", 0),
+    ("fractional-scale", "wp_fractional_scale_manager_v1", 0),
+    ("dmabuf", "zwp_linux_dmabuf_v1", 0),
+    ("explicit-sync", "wp_linux_drm_syncobj_manager_v1", 0),
+];
+
+struct Probe;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Probe {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+/// Highest advertised version of every interface in `PROTOCOLS`, in its order.
+pub fn versions(conn: &Connection) -> Vec<Option<u32>> {
+    let (globals, _) = registry_queue_init::<Probe>(conn).unwrap();
+    let advertised = globals.contents().clone_list();
+    PROTOCOLS
+        .iter()
+        .map(|&(_, interface, _)| {
+            advertised
+                .iter()
+                .filter(|global| global.interface == interface)
+                .map(|global| global.version)
+                .max()
+        })
+        .collect()
+}
+
+/// Writes `versions` to a capability file, one `<interface> <version>` line each and
+/// `-` for the ones not advertised, to be read again with [`load`].
+pub fn save(path: &Path, versions: &[Option<u32>]) -> std::io::Result<()> {
+    let mut contents = format!("{}\n", CAPABILITIES_HEADER);
+    for (&(_, interface, _), version) in PROTOCOLS.iter().zip(versions) {
+        let version = version.map_or("-".to_string(), |version| version.to_string());
+        contents.push_str(&format!("{} {}\n", interface, version));
+    }
+    std::fs::write(path, contents)
+}
+
+/// Reads the versions of a capability file, in the order of `PROTOCOLS`. Interfaces
+/// missing from the file count as not advertised.
+pub fn load(path: &Path) -> Result<Vec<Option<u32>>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut lines = contents.lines();
+    if lines.next() != Some(CAPABILITIES_HEADER) {
+        return Err("not a fifo_test capability file".to_string());
+    }
+    let mut versions = vec![None; PROTOCOLS.len()];
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+        let (interface, version) = line
+            .split_once(' ')
+            .ok_or_else(|| format!("invalid line `{}`", line))?;
+        let version = match version {
+            "-" => None,
+            version => Some(
+                version
+                    .parse()
+                    .map_err(|_| format!("invalid version in `{}`", line))?,
+            ),
+        };
+        if let Some(index) = PROTOCOLS.iter().position(|&(_, name, _)| name == interface) {
+            versions[index] = version;
+        }
+    }
+    Ok(versions)
+}
+
+/// Advertised version of `interface` among `versions`, in the order of `PROTOCOLS`.
+pub fn version(versions: &[Option<u32>], interface: &str) -> Option<u32> {
+    PROTOCOLS
+        .iter()
+        .position(|&(_, name, _)| name == interface)
+        .and_then(|index| versions[index])
+}
+
+/// What `scenario` can't use given the advertised `versions`.
+pub fn degradations(scenario: &dyn Scenario, versions: &[Option<u32>]) -> Vec<String> {
+    let mut missing = Vec::new();
+    match version(versions, "wp_fifo_manager_v1") {
+        None => missing.push("no fifo, barriers are skipped".to_string()),
+        Some(version) if version < scenario.min_fifo_version() => missing.push(format!(
+            "needs fifo v{}, barriers are skipped",
+            scenario.min_fifo_version()
+        )),
+        Some(_) => {}
+    }
+    if scenario.uses_commit_timing() && version(versions, "wp_commit_timing_manager_v1").is_none() {
+        missing.push("no commit-timing, presentation times are ignored".into());
+    }
+    if scenario.wants_feedback() && version(versions, "wp_presentation").is_none() {
+        missing.push("no presentation feedback".into());
+    }
+    missing
+}
+
+/// Prints the protocols the compositor advertises and which scenarios can run on it,
+/// the `probe` subcommand.
+pub fn run(registry: &plugin::Registry) {
+    let conn = session::connect();
+    let versions = versions(&conn);
+
+    println!("probe: protocols");
+    for &(name, interface, supported) in PROTOCOLS {
+        let state = match version(&versions, interface) {
+            None => "-".to_string(),
+            Some(version) if supported != 0 && version > supported => {
+                format!("v{} (using v{})", version, supported)
+            }
+            Some(version) => format!("v{}", version),
+        };
+        println!("  {:<18} {:<34} {}", name, interface, state);
+    }
+
+    println!("probe: buffer backends");
+    backends::print_report(
+        &backends::detect(version(&versions, "zwp_linux_dmabuf_v1")),
+        "",
+    );
+
+    println!("probe: scenarios");
+    for name in registry.scenario_names() {
+        let scenario = registry.scenario(name).expect("scenario is registered");
+        let missing = degradations(scenario.as_ref(), &versions);
+        if missing.is_empty() {
+            println!("  {:<18} runnable", name);
+        } else {
+            println!("  {:<18} degraded: {}", name, missing.join(", "));
+        }
+    }
+}
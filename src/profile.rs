@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::plugin::FramePlan;
+use crate::scheduler::Scheduler;
+
+/// Commit patterns of common toolkits, `--profile`.
+///
+/// Approximations of how their frame clocks drive commits, not exact replicas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// GTK 4: frame callback driven frame clock, configures are applied on its next tick
+    Gtk,
+    /// Qt 6: frame callbacks with a 100ms timeout, resizes are drawn right away
+    Qt,
+    /// SDL 3 with vsync: a fifo barrier per swap, blocking in swap, events between frames
+    Sdl,
+    /// winit with a FIFO present mode: barrier and frame callback per frame, redraws on configure
+    Winit,
+}
+
+impl Profile {
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Gtk => "gtk",
+            Profile::Qt => "qt",
+            Profile::Sdl => "sdl",
+            Profile::Winit => "winit",
+        }
+    }
+
+    /// Overrides the throttling of a scenario's plan.
+    pub fn apply(self, plan: &mut FramePlan) {
+        let (barrier, frame_callback) = match self {
+            Profile::Gtk | Profile::Qt => (false, true),
+            Profile::Sdl => (true, false),
+            Profile::Winit => (true, true),
+        };
+        plan.barrier = barrier;
+        plan.frame_callback = frame_callback;
+    }
+
+    /// Scheduler the toolkit waits with, overriding `--scheduler`.
+    pub fn scheduler(self) -> Option<Scheduler> {
+        match self {
+            Profile::Sdl => Some(Scheduler::Blocking),
+            Profile::Gtk | Profile::Qt | Profile::Winit => None,
+        }
+    }
+
+    /// Whether a configure with a new size is drawn and committed right away instead of
+    /// with the next scheduled frame.
+    pub fn redraws_on_configure(self) -> bool {
+        matches!(self, Profile::Qt | Profile::Winit)
+    }
+
+    /// Time after which the next frame is drawn although the frame callback didn't
+    /// arrive.
+    pub fn frame_callback_timeout(self) -> Option<Duration> {
+        match self {
+            Profile::Qt => Some(Duration::from_millis(100)),
+            Profile::Gtk | Profile::Sdl | Profile::Winit => None,
+        }
+    }
+}
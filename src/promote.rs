@@ -0,0 +1,64 @@
+use clap::ValueEnum;
+
+use smithay_client_toolkit::compositor::{CompositorState, Region};
+use smithay_client_toolkit::reexports::client::protocol::{wl_output, wl_shm, wl_surface};
+
+use crate::canvas::Canvas;
+
+/// Side of the translucent square that keeps composited surfaces off the planes.
+const TRANSLUCENT_SIZE: u32 = 32;
+
+/// Surface setup making direct scanout likely or impossible, `--promote`, to
+/// validate `--detect-scanout` and measure both paths on purpose.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Promote {
+    /// Fullscreen at the configured size, opaque XRGB8888 buffers without transform
+    Scanout,
+    /// Buffers rotated by 90 degrees with translucent pixels and no opaque region
+    Composited,
+}
+
+impl Promote {
+    pub fn name(self) -> &'static str {
+        match self {
+            Promote::Scanout => "scanout",
+            Promote::Composited => "composited",
+        }
+    }
+
+    pub fn format(self) -> wl_shm::Format {
+        match self {
+            Promote::Scanout => wl_shm::Format::Xrgb8888,
+            Promote::Composited => wl_shm::Format::Argb8888,
+        }
+    }
+
+    pub fn fullscreen(self) -> bool {
+        self == Promote::Scanout
+    }
+
+    /// Sets the surface state, which stays in effect for every later commit.
+    pub fn apply(self, surface: &wl_surface::WlSurface, compositor: &CompositorState) {
+        surface.set_buffer_scale(1);
+        match self {
+            Promote::Scanout => {
+                surface.set_buffer_transform(wl_output::Transform::Normal);
+                // Clipped to the surface, so it covers every size.
+                let region = Region::new(compositor).expect("create region");
+                region.add(0, 0, i32::MAX, i32::MAX);
+                surface.set_opaque_region(Some(region.wl_region()));
+            }
+            Promote::Composited => {
+                surface.set_buffer_transform(wl_output::Transform::_90);
+                surface.set_opaque_region(None);
+            }
+        }
+    }
+
+    /// Adds the translucent pixels of composited frames.
+    pub fn render(self, canvas: &mut Canvas) {
+        if self == Promote::Composited {
+            canvas.fill_rect(0, 0, TRANSLUCENT_SIZE, TRANSLUCENT_SIZE, 0x8000_0000);
+        }
+    }
+}
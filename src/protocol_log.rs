@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::path::Path;
+use std::thread::JoinHandle;
+
+use crate::bugreport;
+
+/// One line of a `WAYLAND_DEBUG` log, from libwayland or the rust backend:
+/// `[1234567.890]  -> wl_surface#3.commit()` or `[1234567.890][rs] <- wl_callback@7.done, (5)`.
+pub struct Message<'a> {
+    /// Wall clock milliseconds, wrapping.
+    pub time: f64,
+    pub interface: &'a str,
+    pub id: u32,
+    pub name: &'a str,
+    pub args: &'a str,
+}
+
+impl<'a> Message<'a> {
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let (time, rest) = line.strip_prefix('[')?.split_once(']')?;
+        let time = time.trim().parse().ok()?;
+        let rest = rest.strip_prefix("[rs]").unwrap_or(rest);
+        let rest = rest
+            .strip_prefix("[discarded]")
+            .unwrap_or(rest)
+            .trim_start();
+        let rest = rest
+            .strip_prefix("->")
+            .or_else(|| rest.strip_prefix("<-"))
+            .unwrap_or(rest)
+            .trim_start();
+
+        let (object, rest) = rest.split_once('.')?;
+        let (interface, id) = object.split_once('#').or_else(|| object.split_once('@'))?;
+        let end = rest.find(['(', ','])?;
+        let args = rest[end..]
+            .trim_start_matches(',')
+            .trim()
+            .strip_prefix('(')?
+            .strip_suffix(')')?;
+        Some(Self {
+            time,
+            interface,
+            id: id.parse().ok()?,
+            name: &rest[..end],
+            args,
+        })
+    }
+}
+
+/// Captures the `WAYLAND_DEBUG` protocol log of every connection into a file,
+/// `--capture-protocol`.
+///
+/// The log is written to stderr by the wayland backend, so stderr is redirected into
+/// a pipe and everything but the protocol lines is passed on to the original stderr.
+pub struct ProtocolLog {
+    stderr: OwnedFd,
+    thread: JoinHandle<u64>,
+}
+
+impl ProtocolLog {
+    /// Starts capturing, must be called before the first connection is made and, as
+    /// it sets `WAYLAND_DEBUG` and spawns a thread, after [`crate::session::prepare`].
+    pub fn start(path: &Path) -> std::io::Result<Self> {
+        let mut file = File::create(path)?;
+        let mut fds = [0; 2];
+        // SAFETY: the fds are fresh and owned from here on, fd 2 is replaced by the
+        // write end and restored in `finish`.
+        let (read, stderr) = unsafe {
+            if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let stderr = libc::dup(libc::STDERR_FILENO);
+            if stderr < 0 || libc::dup2(fds[1], libc::STDERR_FILENO) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            libc::close(fds[1]);
+            (File::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(stderr))
+        };
+        let mut forward = File::from(stderr.try_clone()?);
+
+        std::env::set_var("WAYLAND_DEBUG", "client");
+        let thread = std::thread::Builder::new()
+            .name("protocol-log".into())
+            .spawn(move || {
+                let mut messages = 0;
+                for line in BufReader::new(read).lines().map_while(Result::ok) {
+                    let _ = if bugreport::is_protocol_line(&line) {
+                        messages += 1;
+                        writeln!(file, "{}", line)
+                    } else {
+                        writeln!(forward, "{}", line)
+                    };
+                }
+                messages
+            })?;
+        Ok(Self { stderr, thread })
+    }
+
+    /// Restores stderr and waits for the log to be written, returning the number of
+    /// messages.
+    pub fn finish(self) -> u64 {
+        // SAFETY: puts the original stderr back, closing the pipe's last write end.
+        unsafe {
+            libc::dup2(
+                std::os::fd::AsRawFd::as_raw_fd(&self.stderr),
+                libc::STDERR_FILENO,
+            );
+        }
+        self.thread.join().unwrap_or(0)
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::presentation::{self, Presented};
+
+/// Number of holds printed as the cadence pattern.
+const PATTERN_LENGTH: usize = 60;
+
+struct Frame {
+    frame: u64,
+    target: Duration,
+    time: Duration,
+    seq: u64,
+    refresh: Duration,
+}
+
+/// Cadence analysis of frames with a presentation time, e.g. 3:2 pull-down of 24 fps
+/// content on a 60 Hz output.
+///
+/// Every frame is expected to stay on screen for the floor or ceil of the refresh
+/// cycles per content frame, and the accumulated holds must not drift from the
+/// content timeline by more than one refresh.
+#[derive(Default)]
+pub struct Pulldown {
+    targets: HashMap<u64, Duration>,
+    frames: Vec<Frame>,
+}
+
+impl Pulldown {
+    pub fn committed(&mut self, frame: u64, target: Duration) {
+        self.targets.insert(frame, target);
+    }
+
+    pub fn presented(&mut self, frame: u64, presented: &Presented) {
+        let Some(target) = self.targets.remove(&frame) else {
+            return;
+        };
+        self.frames.push(Frame {
+            frame,
+            target,
+            time: presented.time,
+            seq: presented.seq,
+            refresh: presented.refresh,
+        });
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let mut frames = self.frames.iter().collect::<Vec<_>>();
+        frames.sort_by_key(|frame| frame.frame);
+        if frames.len() < 2 {
+            println!("{}timed cadence: not enough presented frames", prefix);
+            return;
+        }
+
+        let median = |mut values: Vec<Duration>| {
+            values.sort();
+            values.get(values.len() / 2).copied()
+        };
+        let refresh = median(
+            frames
+                .iter()
+                .map(|frame| frame.refresh)
+                .filter(|refresh| !refresh.is_zero())
+                .collect(),
+        );
+        let content = median(
+            frames
+                .windows(2)
+                .filter_map(|pair| pair[1].target.checked_sub(pair[0].target))
+                .collect(),
+        );
+        let (Some(refresh), Some(content)) = (refresh, content) else {
+            println!(
+                "{}timed cadence: the compositor reports no refresh rate",
+                prefix
+            );
+            return;
+        };
+
+        let ratio = content.as_secs_f64() / refresh.as_secs_f64();
+        let expected = [ratio.floor() as u64, ratio.ceil() as u64];
+        println!(
+            "{}timed cadence: {:.2}ms content on a {:.2}ms refresh, {:.2} refreshes per frame",
+            prefix,
+            content.as_secs_f64() * 1000.0,
+            refresh.as_secs_f64() * 1000.0,
+            ratio
+        );
+
+        let mut holds = HashMap::<u64, u64>::new();
+        let mut pattern = String::new();
+        let mut breaks = Vec::new();
+        let mut drift = 0.0;
+        for pair in frames.windows(2) {
+            let (frame, next) = (pair[0], pair[1]);
+            let hold = presentation::refresh_cycles(
+                (frame.time, frame.seq),
+                (next.time, next.seq),
+                refresh,
+            );
+            // A frame that was never presented breaks the cadence as well.
+            let skipped = next.frame - frame.frame - 1;
+
+            *holds.entry(hold).or_default() += 1;
+            if pattern.len() < PATTERN_LENGTH {
+                pattern.push(char::from_digit(hold.min(9) as u32, 10).unwrap());
+            }
+
+            drift += hold as f64 - ratio * (skipped + 1) as f64;
+            if skipped > 0 || !expected.contains(&hold) || drift.abs() > 1.0 {
+                breaks.push(frame.frame);
+                drift = 0.0;
+            }
+        }
+
+        let mut holds = holds.into_iter().collect::<Vec<_>>();
+        holds.sort();
+        let holds = holds
+            .iter()
+            .map(|(hold, count)| format!("{}x: {}", hold, count))
+            .collect::<Vec<_>>();
+        println!("{}  holds: {}", prefix, holds.join(", "));
+        println!("{}  pattern: {}", prefix, pattern);
+
+        let late = frames
+            .iter()
+            .map(|frame| frame.time.as_secs_f64() - frame.target.as_secs_f64())
+            .collect::<Vec<_>>();
+        println!(
+            "{}  presented after the target: mean {:.3}ms, max {:.3}ms",
+            prefix,
+            late.iter().sum::<f64>() / late.len() as f64 * 1000.0,
+            late.iter().copied().fold(f64::MIN, f64::max) * 1000.0
+        );
+
+        if breaks.is_empty() {
+            println!("{}  no cadence breaks", prefix);
+        } else {
+            let list = breaks.iter().map(ToString::to_string).collect::<Vec<_>>();
+            println!(
+                "{}  {} cadence breaks after frames {}",
+                prefix,
+                breaks.len(),
+                list.join(" ")
+            );
+        }
+    }
+}
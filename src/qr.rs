@@ -0,0 +1,37 @@
+use qrcode::{Color, EcLevel, QrCode};
+
+use crate::canvas::Canvas;
+
+/// Size of one QR module in pixels.
+const MODULE: u32 = 4;
+/// Light border around the code, in modules, as required by the QR spec.
+const QUIET_ZONE: u32 = 4;
+
+const DARK: u32 = 0xFF00_0000;
+const LIGHT: u32 = 0xFFFF_FFFF;
+
+/// Renders a QR code holding `<frame> <timestamp_ns>` into the center of the canvas.
+///
+/// The timestamp is the `CLOCK_MONOTONIC` time the frame was rendered at, which allows
+/// camera footage of the display to be matched with the client's commit log.
+pub fn stamp(canvas: &mut Canvas, frame: u64, timestamp_ns: u128) {
+    let data = format!("{} {}", frame, timestamp_ns);
+    let Ok(code) = QrCode::with_error_correction_level(data, EcLevel::M) else {
+        return;
+    };
+
+    let modules = code.width() as u32;
+    let size = (modules + QUIET_ZONE * 2) * MODULE;
+    let x0 = (canvas.width() as i32 - size as i32) / 2;
+    let y0 = (canvas.height() as i32 - size as i32) / 2;
+
+    canvas.fill_rect(x0, y0, size, size, LIGHT);
+    for (index, color) in code.to_colors().into_iter().enumerate() {
+        if color != Color::Dark {
+            continue;
+        }
+        let x = (index as u32 % modules + QUIET_ZONE) * MODULE;
+        let y = (index as u32 / modules + QUIET_ZONE) * MODULE;
+        canvas.fill_rect(x0 + x as i32, y0 + y as i32, MODULE, MODULE, DARK);
+    }
+}
@@ -0,0 +1,268 @@
+//! Fifo pacing across output mode changes, `--mode-relock`.
+//!
+//! A mode change mid-run switches the refresh the fifo barriers are released with.
+//! Transitions are taken from the wl_output mode events of the surface's output,
+//! from `--move <frame>:mode=...` switching the mode through the compositor IPC, or
+//! marked by hand with the `o` key or the `mode` command of `--repl` when the mode is
+//! changed some other way. Each transition is annotated with the frame it happened
+//! at, and the presentation feedback after it shows when the frames are presented
+//! on the new refresh again, one per refresh cycle.
+
+use std::time::Duration;
+
+use crate::plugin::Annotation;
+use crate::presentation::Presented;
+
+/// Presentations in a row one refresh apart that count as locked on.
+const LOCKED_FRAMES: u64 = 30;
+/// Relative deviation of an interval from the refresh that still counts as locked.
+const TOLERANCE: f64 = 0.1;
+/// Relative change of the refresh below which it counts as unchanged.
+const REFRESH_TOLERANCE: f64 = 0.001;
+/// Transitions marked this close together are the same mode change, e.g. the mode
+/// event following an IPC switch.
+const MERGE_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// A mode event of the output.
+    Output,
+    /// A mode switch through the compositor IPC.
+    Ipc,
+    /// Marked by hand.
+    Manual,
+}
+
+impl Source {
+    fn name(self) -> &'static str {
+        match self {
+            Source::Output => "output mode event",
+            Source::Ipc => "IPC mode switch",
+            Source::Manual => "manual mark",
+        }
+    }
+}
+
+struct Transition {
+    sources: Vec<Source>,
+    /// Last frame committed before the transition.
+    frame: u64,
+    at: Duration,
+    from: Option<Duration>,
+    /// The new refresh, known from the mode or the first feedback that differs.
+    to: Option<Duration>,
+    /// Time to the first presentation on the new refresh.
+    first: Option<Duration>,
+    /// Time and frames to the first of [`LOCKED_FRAMES`] presentations in a row one
+    /// refresh apart.
+    locked: Option<(Duration, u64)>,
+    discarded: u64,
+    /// Start of the current run of locked presentations, time and frame.
+    run: Option<(Duration, u64)>,
+    run_length: u64,
+}
+
+fn same(a: Duration, b: Duration) -> bool {
+    (a.as_secs_f64() / b.as_secs_f64() - 1.0).abs() < REFRESH_TOLERANCE
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn hz(refresh: Option<Duration>) -> String {
+    match refresh {
+        Some(refresh) => format!("{:.3}Hz", 1.0 / refresh.as_secs_f64()),
+        None => "unknown".to_string(),
+    }
+}
+
+#[derive(Default)]
+pub struct Relock {
+    /// Refresh of the current mode of the surface's output.
+    mode: Option<Duration>,
+    /// Refresh of the latest presentation feedback.
+    refresh: Option<Duration>,
+    /// Presentation time of the latest presented frame.
+    last: Option<Duration>,
+    transitions: Vec<Transition>,
+    annotation: Option<Annotation>,
+}
+
+impl Relock {
+    /// The surface's output has a mode refreshing at `refresh_mhz`, a transition if
+    /// it differs from the previous one.
+    pub fn output_mode(&mut self, refresh_mhz: i32, frame: u64, now: Duration, prefix: &str) {
+        if refresh_mhz <= 0 {
+            return;
+        }
+        let refresh = Duration::from_secs_f64(1000.0 / refresh_mhz as f64);
+        if self.mode.is_some_and(|previous| !same(previous, refresh)) {
+            self.mark(Source::Output, frame, now, Some(refresh), prefix);
+        }
+        self.mode = Some(refresh);
+    }
+
+    /// Marks a mode change after `frame`, to a refresh of `to` if known.
+    pub fn mark(
+        &mut self,
+        source: Source,
+        frame: u64,
+        now: Duration,
+        to: Option<Duration>,
+        prefix: &str,
+    ) {
+        if let Some(transition) = self.transitions.last_mut().filter(|transition| {
+            transition.locked.is_none() && now.saturating_sub(transition.at) < MERGE_WINDOW
+        }) {
+            if !transition.sources.contains(&source) {
+                transition.sources.push(source);
+            }
+            if transition.to.is_none() {
+                transition.to = to;
+            }
+            return;
+        }
+        let from = self.refresh.or(self.mode);
+        let label = format!(
+            "mode change ({}) from {} to {}",
+            source.name(),
+            hz(from),
+            hz(to)
+        );
+        println!("{}{} after frame {}", prefix, label, frame);
+        self.annotation = Some(Annotation::Label(label));
+        self.transitions.push(Transition {
+            sources: vec![source],
+            frame,
+            at: now,
+            from,
+            to,
+            first: None,
+            locked: None,
+            discarded: 0,
+            run: None,
+            run_length: 0,
+        });
+    }
+
+    /// A transition to log with the next frame.
+    pub fn take_annotation(&mut self) -> Option<Annotation> {
+        self.annotation.take()
+    }
+
+    pub fn presented(&mut self, frame: u64, presented: &Presented, prefix: &str) {
+        let interval = self
+            .last
+            .replace(presented.time)
+            .map(|last| presented.time.saturating_sub(last));
+        let refresh = (!presented.refresh.is_zero()).then_some(presented.refresh);
+        if refresh.is_some() {
+            self.refresh = refresh;
+        }
+        let Some(transition) = self
+            .transitions
+            .last_mut()
+            .filter(|transition| transition.locked.is_none())
+        else {
+            return;
+        };
+        if presented.committed < transition.at {
+            // Committed before the transition.
+            return;
+        }
+        if transition.to.is_none() {
+            transition.to =
+                refresh.filter(|refresh| transition.from.is_none_or(|from| !same(from, *refresh)));
+        }
+        let Some(to) = transition.to else {
+            return;
+        };
+        if transition.first.is_none() && refresh.is_some_and(|refresh| same(refresh, to)) {
+            transition.first = Some(presented.time.saturating_sub(transition.at));
+        }
+        if transition.first.is_none() {
+            return;
+        }
+
+        let locked = interval.is_some_and(|interval| {
+            (interval.as_secs_f64() / to.as_secs_f64() - 1.0).abs() < TOLERANCE
+        });
+        if !locked {
+            transition.run = None;
+            transition.run_length = 0;
+            return;
+        }
+        let (start, start_frame) = *transition
+            .run
+            .get_or_insert((presented.time.saturating_sub(transition.at), frame));
+        transition.run_length += 1;
+        if transition.run_length >= LOCKED_FRAMES {
+            let frames = start_frame.saturating_sub(transition.frame);
+            transition.locked = Some((start, frames));
+            println!(
+                "{}mode change: locked on {} again after {:.3}ms, {} frames",
+                prefix,
+                hz(Some(to)),
+                ms(start),
+                frames
+            );
+        }
+    }
+
+    pub fn discarded(&mut self) {
+        if let Some(transition) = self
+            .transitions
+            .last_mut()
+            .filter(|transition| transition.locked.is_none())
+        {
+            transition.discarded += 1;
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if self.transitions.is_empty() {
+            println!("{}mode relock: no mode change seen", prefix);
+            return;
+        }
+        let locked = self
+            .transitions
+            .iter()
+            .filter(|transition| transition.locked.is_some())
+            .count();
+        println!(
+            "{}mode relock: {} mode changes, {} locked on the new refresh again",
+            prefix,
+            self.transitions.len(),
+            locked
+        );
+        for transition in &self.transitions {
+            let sources = transition
+                .sources
+                .iter()
+                .map(|source| source.name())
+                .collect::<Vec<_>>();
+            let first = match transition.first {
+                Some(first) => format!("first presented on it after {:.3}ms", ms(first)),
+                None => "never presented on it".to_string(),
+            };
+            let locked = match transition.locked {
+                Some((time, frames)) => {
+                    format!("locked after {:.3}ms, {} frames", ms(time), frames)
+                }
+                None => "never locked".to_string(),
+            };
+            println!(
+                "{}  after frame {}: {} to {} ({}), {}, {}, {} discarded meanwhile",
+                prefix,
+                transition.frame,
+                hz(transition.from),
+                hz(transition.to),
+                sources.join(", "),
+                first,
+                locked,
+                transition.discarded
+            );
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::calloop::generic::Generic;
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay_client_toolkit::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
+use smithay_client_toolkit::shell::WaylandSurface;
+
+use crate::controls::Control;
+use crate::{canvas, SimpleWindow};
+
+const HELP: &str = "\
+commands, separated by `;`:
+  attach [N]  render the next frame into buffer N (default: a free one), attach and damage it
+  barrier     set a fifo barrier
+  wait        wait for the fifo barrier
+  frame       request a frame callback
+  commit      commit the surface
+  sleep MS    delay the following commands
+  draw        draw one frame like the continuous mode does
+  resume      go back to continuous drawing, `step` pauses again
+  step        pause, or draw a single frame while paused
+  fifo        toggle the fifo barrier of drawn frames
+  mode        mark an output mode change for --mode-relock
+  gamma       mark a gamma or color change for --gamma-watch
+  quit        exit";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Attach(Option<usize>),
+    Barrier,
+    Wait,
+    Frame,
+    Commit,
+    Sleep(Duration),
+    Control(Control),
+    Draw,
+    Help,
+    Quit,
+}
+
+impl std::str::FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let arg = words.next();
+        if words.next().is_some() {
+            return Err(format!("too many arguments to `{}`", command));
+        }
+        let number = |arg: Option<&str>| {
+            arg.map(|arg| {
+                arg.parse::<u64>()
+                    .map_err(|_| format!("invalid number `{}`", arg))
+            })
+            .transpose()
+        };
+
+        let command = match command {
+            "attach" => Command::Attach(number(arg)?.map(|index| index as usize)),
+            "sleep" => Command::Sleep(Duration::from_millis(
+                number(arg)?.ok_or("`sleep` needs a duration in ms")?,
+            )),
+            _ if arg.is_some() => return Err(format!("`{}` takes no argument", command)),
+            "barrier" => Command::Barrier,
+            "wait" => Command::Wait,
+            "frame" => Command::Frame,
+            "commit" => Command::Commit,
+            "draw" => Command::Draw,
+            "resume" => Command::Control(Control::Resume),
+            "step" => Command::Control(Control::Step),
+            "fifo" => Command::Control(Control::ToggleFifo),
+            "mode" => Command::Control(Control::MarkModeChange),
+            "gamma" => Command::Control(Control::MarkGammaChange),
+            "help" => Command::Help,
+            "quit" | "exit" => Command::Quit,
+            command => return Err(format!("unknown command `{}`, see `help`", command)),
+        };
+        Ok(command)
+    }
+}
+
+/// Line-based console on stdin driving the protocol by hand, `--repl`.
+#[derive(Default)]
+pub struct Repl {
+    pending: VecDeque<Command>,
+    sleeping: bool,
+    /// A buffer was attached since the last commit.
+    attached: bool,
+}
+
+/// Starts reading commands from stdin.
+pub fn init(loop_handle: &LoopHandle<'static, SimpleWindow>) {
+    println!("{}", HELP);
+    loop_handle
+        .insert_source(
+            Generic::new(std::io::stdin(), Interest::READ, Mode::Level),
+            |_, stdin, window| {
+                let mut line = String::new();
+                match stdin.lock().read_line(&mut line) {
+                    Ok(0) | Err(_) => {
+                        // Stdin is closed, the window keeps running.
+                        return Ok(PostAction::Remove);
+                    }
+                    Ok(_) => window.repl_line(&line),
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .unwrap();
+}
+
+impl SimpleWindow {
+    fn repl_line(&mut self, line: &str) {
+        let commands = line
+            .split(';')
+            .filter(|command| !command.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Command>, _>>();
+        let commands = match commands {
+            Ok(commands) => commands,
+            Err(err) => {
+                // Nothing of a line runs if any of its commands is invalid.
+                println!("{}", err);
+                return;
+            }
+        };
+
+        let Some(repl) = self.repl.as_mut() else {
+            return;
+        };
+        repl.pending.extend(commands);
+        if !repl.sleeping {
+            self.repl_run();
+        }
+    }
+
+    /// Runs the pending commands up to the next sleep.
+    fn repl_run(&mut self) {
+        while let Some(command) = self.repl.as_mut().and_then(|repl| repl.pending.pop_front()) {
+            match command {
+                Command::Attach(index) => self.repl_attach(index),
+                Command::Barrier => match self.fifo.as_ref() {
+                    Some(fifo) => fifo.set_barrier(),
+                    None => println!("fifo unavailable"),
+                },
+                Command::Wait => match self.fifo.as_ref() {
+                    Some(fifo) => fifo.wait_barrier(),
+                    None => println!("fifo unavailable"),
+                },
+                Command::Frame => {
+                    self.window
+                        .wl_surface()
+                        .frame(&self.qh, self.window.wl_surface().clone());
+                }
+                Command::Commit => {
+                    self.window.commit();
+                    if self
+                        .repl
+                        .as_mut()
+                        .is_some_and(|repl| std::mem::take(&mut repl.attached))
+                    {
+                        self.frame += 1;
+                    }
+                    println!("committed");
+                }
+                Command::Sleep(duration) => {
+                    if let Some(repl) = self.repl.as_mut() {
+                        repl.sleeping = true;
+                    }
+                    self.loop_handle
+                        .insert_source(Timer::from_duration(duration), |_, _, window| {
+                            if let Some(repl) = window.repl.as_mut() {
+                                repl.sleeping = false;
+                            }
+                            window.repl_run();
+                            TimeoutAction::Drop
+                        })
+                        .unwrap();
+                    return;
+                }
+                Command::Control(control) => self.control(control),
+                Command::Draw => {
+                    let paused = std::mem::replace(&mut self.paused, true);
+                    self.draw();
+                    self.paused = paused;
+                }
+                Command::Help => println!("{}", HELP),
+                Command::Quit => self.exit = true,
+            }
+        }
+        let _ = self.conn.flush();
+    }
+
+    fn repl_attach(&mut self, index: Option<usize>) {
+        let index = match index {
+            Some(index) if index >= self.buffers.len() => {
+                println!("there are only {} buffers", self.buffers.len());
+                return;
+            }
+            Some(index) => index,
+            None => match (0..self.buffers.len()).find(|index| {
+                let buffer = &self.buffers[*index];
+                self.pool.canvas(buffer).is_some()
+            }) {
+                Some(index) => index,
+                None => {
+                    println!("no free buffer");
+                    return;
+                }
+            },
+        };
+
+        let buffer = &self.buffers[index];
+        let Some(data) = self.pool.canvas(buffer) else {
+            println!("buffer {} is still held by the compositor", index);
+            return;
+        };
+        let mut canvas = canvas::Canvas::new(data, self.width, self.height);
+        self.pattern.render(&mut canvas, self.frame + 1);
+
+        self.window.wl_surface().damage(0, 0, i32::MAX, i32::MAX);
+        buffer
+            .attach_to(self.window.wl_surface())
+            .expect("buffer attach");
+        if let Some(repl) = self.repl.as_mut() {
+            repl.attached = true;
+        }
+        println!("attached buffer {} with frame {}", index, self.frame + 1);
+    }
+}
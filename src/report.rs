@@ -0,0 +1,247 @@
+//! Compatibility records, `report export-compat`.
+//!
+//! The record is a single JSON object meant to be aggregated into a fifo support
+//! matrix. It carries nothing identifying the user or the machine:
+//!
+//! ```text
+//! {
+//!   "schema": 1,
+//!   "tool": "0.1.0",
+//!   "compositor": { "name": "sway", "version": "sway 1.10-1" },
+//!   "protocols": { "wp_fifo_manager_v1": 1, "wp_commit_timing_manager_v1": null, ... },
+//!   "frames": 600,
+//!   "verdicts": { "queueing": "pass", "latching": "pass", "unmap": "untested" }
+//! }
+//! ```
+//!
+//! `name` is `XDG_CURRENT_DESKTOP`, or the command name of the compositor process
+//! without it. `version` is `--compositor-version`, or the package owning the
+//! compositor binary with its version as the dpkg or pacman database lists it. The
+//! binary itself is never run, so either may be `null`. Protocols map to the highest
+//! advertised version or `null`. `verdicts` is `null` without fifo support, otherwise
+//! every check is `pass`, `fail` or `untested`, see `--verdict`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::Connection;
+
+use crate::{breakon, html, plugin, probe, sandbox, session, timeline, trim, verdict, Args};
+
+/// Version of the record format, bumped on incompatible changes.
+const SCHEMA: u32 = 1;
+
+/// Options of the `report` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Report {
+    #[command(subcommand)]
+    command: ReportCommand,
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum ReportCommand {
+    /// Run a short fifo test and print an anonymized compatibility record of the compositor
+    ExportCompat(ExportCompat),
+    /// Strip a captured protocol log down to the fifo related objects around anomalies
+    Trim(trim::Trim),
+    /// Merge a compositor's log into the timeline of a run's log
+    Timeline(timeline::Timeline),
+    /// Run a short test and write a standalone HTML page with zoomable frame timelines
+    Html(html::Html),
+}
+
+#[derive(clap::Args, Clone, Debug)]
+struct ExportCompat {
+    /// Frames the test commits
+    #[arg(long, default_value_t = 600)]
+    frames: u64,
+
+    /// Write the record to this file instead of stdout
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Version of the compositor to record, instead of looking up its package
+    #[arg(long, value_name = "VERSION")]
+    compositor_version: Option<String>,
+}
+
+pub fn run(report: &Report, args: &Args, registry: &Arc<plugin::Registry>, start: Duration) {
+    match &report.command {
+        ReportCommand::ExportCompat(export) => export_compat(export, args, registry, start),
+        ReportCommand::Trim(trim) => trim::run(trim, args.output_dir.as_deref()),
+        ReportCommand::Timeline(timeline) => timeline::run(timeline, args.output_dir.as_deref()),
+        ReportCommand::Html(html) => html::run(html, args, registry, start),
+    }
+}
+
+fn export_compat(
+    export: &ExportCompat,
+    args: &Args,
+    registry: &Arc<plugin::Registry>,
+    start: Duration,
+) {
+    let conn = session::connect();
+    let versions = probe::versions(&conn);
+    let fifo = probe::PROTOCOLS
+        .iter()
+        .position(|&(_, interface, _)| interface == "wp_fifo_manager_v1")
+        .and_then(|index| versions[index])
+        .is_some();
+
+    let checks = if fifo {
+        let mut args = args.clone();
+        args.connections = 1;
+        args.frames = Some(export.frames);
+        args.quiet = true;
+        args.verdict = true;
+        crate::run_connections(&args, registry, start, None)
+            .pop()
+            .and_then(|outcome| outcome.verdict)
+    } else {
+        eprintln!("report: the compositor has no fifo support, skipping the test");
+        None
+    };
+
+    let record = record(
+        &conn,
+        &versions,
+        export.frames,
+        export.compositor_version.clone(),
+        checks.as_deref(),
+    );
+    match export.output.as_deref() {
+        Some(path) => write(&sandbox::resolve(args.output_dir.as_deref(), path), &record),
+        None => print!("{}", record),
+    }
+}
+
+fn write(path: &Path, record: &str) {
+    std::fs::write(path, record).expect("Failed to write the compatibility record");
+    println!("report: compatibility record written to {}", path.display());
+}
+
+fn record(
+    conn: &Connection,
+    versions: &[Option<u32>],
+    frames: u64,
+    version: Option<String>,
+    checks: Option<&[(verdict::Check, verdict::Status)]>,
+) -> String {
+    let pid = breakon::compositor_pid(conn);
+    let name = std::env::var("XDG_CURRENT_DESKTOP").ok().or_else(|| {
+        let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid?)).ok()?;
+        Some(comm.trim().to_string()).filter(|comm| !comm.is_empty())
+    });
+    let version = version.or_else(|| package_version(pid?));
+    let protocols = probe::PROTOCOLS
+        .iter()
+        .zip(versions)
+        .map(|(&(_, interface, _), version)| {
+            format!("{}: {}", string(interface), optional(*version))
+        })
+        .collect::<Vec<_>>();
+    let verdicts = checks.map_or("null".to_string(), |checks| {
+        let checks = checks
+            .iter()
+            .map(|(check, status)| format!("{}: {}", string(check.name()), string(status.name())))
+            .collect::<Vec<_>>();
+        format!("{{ {} }}", checks.join(", "))
+    });
+
+    format!(
+        "{{\n  \"schema\": {},\n  \"tool\": {},\n  \"compositor\": {{ \"name\": {}, \"version\": {} }},\n  \"protocols\": {{ {} }},\n  \"frames\": {},\n  \"verdicts\": {}\n}}\n",
+        SCHEMA,
+        string(env!("CARGO_PKG_VERSION")),
+        name.as_deref().map_or("null".to_string(), string),
+        version.as_deref().map_or("null".to_string(), string),
+        protocols.join(", "),
+        frames,
+        verdicts
+    )
+}
+
+/// Package owning the compositor binary and its version, e.g. `sway 1.10-1`.
+fn package_version(pid: u32) -> Option<String> {
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    let exe = exe.to_str()?;
+    // With merged /usr, packages may list the binary in /bin.
+    let paths = [exe, exe.strip_prefix("/usr").unwrap_or(exe)];
+    dpkg_version(&paths).or_else(|| pacman_version(&paths))
+}
+
+fn dpkg_version(paths: &[&str]) -> Option<String> {
+    let package = std::fs::read_dir("/var/lib/dpkg/info")
+        .ok()?
+        .flatten()
+        .find_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let package = name.strip_suffix(".list")?;
+            let files = std::fs::read_to_string(entry.path()).ok()?;
+            files
+                .lines()
+                .any(|line| paths.contains(&line))
+                .then(|| package.split(':').next().unwrap_or(package).to_string())
+        })?;
+    let status = std::fs::read_to_string("/var/lib/dpkg/status").ok()?;
+    status.split("\n\n").find_map(|stanza| {
+        let field = |name: &str| {
+            stanza
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+        };
+        if field("Package")? != package {
+            return None;
+        }
+        Some(format!("{} {}", package, field("Version")?))
+    })
+}
+
+fn pacman_version(paths: &[&str]) -> Option<String> {
+    std::fs::read_dir("/var/lib/pacman/local")
+        .ok()?
+        .flatten()
+        .find_map(|entry| {
+            let files = std::fs::read_to_string(entry.path().join("files")).ok()?;
+            // Paths are listed without the leading slash.
+            files
+                .lines()
+                .any(|line| {
+                    paths
+                        .iter()
+                        .any(|path| path.strip_prefix('/') == Some(line))
+                })
+                .then_some(())?;
+            let desc = std::fs::read_to_string(entry.path().join("desc")).ok()?;
+            let mut lines = desc.lines();
+            let mut field = |name: &str| {
+                lines
+                    .by_ref()
+                    .skip_while(|line| *line != name)
+                    .nth(1)
+                    .map(str::to_string)
+            };
+            let name = field("%NAME%")?;
+            Some(format!("{} {}", name, field("%VERSION%")?))
+        })
+}
+
+fn optional(value: Option<u32>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+/// `value` as a JSON string literal.
+pub fn string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
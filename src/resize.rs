@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+/// Size change per step, the size runs up and back down in `STEPS` steps.
+const STEP: u32 = 16;
+const STEPS: u32 = 8;
+/// Mismatches listed in the report, the first ones of the run.
+const LISTED: usize = 10;
+
+/// A frame presented with content of a different size than the configure in effect.
+struct Mismatch {
+    frame: u64,
+    content: (u32, u32),
+    configured: (u32, u32),
+}
+
+/// Oscillates the window size by pinning the min and max size while the fifo loop
+/// runs, `--resize-stress`.
+///
+/// Queued commits still carry the old size when the compositor configures the new
+/// one, which is where compositors glitch.
+pub struct ResizeStress {
+    period: u64,
+    /// Size the window starts out with, the oscillation grows from it.
+    base: (u32, u32),
+    step: u32,
+    /// Size requested last, and the frame it was requested with.
+    requested: Option<((u32, u32), u64)>,
+    configured: Option<(u32, u32)>,
+    /// Content size of every committed frame until it is presented.
+    in_flight: BTreeMap<u64, (u32, u32)>,
+    /// Frames from a request to its configure.
+    configure_delays: Vec<u64>,
+    ignored: u64,
+    presented: u64,
+    mismatches: Vec<Mismatch>,
+    mismatch_count: u64,
+}
+
+impl ResizeStress {
+    pub fn new(period: u64, base: (u32, u32)) -> Self {
+        Self {
+            period: period.max(1),
+            base,
+            step: 0,
+            requested: None,
+            configured: None,
+            in_flight: BTreeMap::new(),
+            configure_delays: Vec::new(),
+            ignored: 0,
+            presented: 0,
+            mismatches: Vec::new(),
+            mismatch_count: 0,
+        }
+    }
+
+    /// Size to pin with the commit of `frame`, every `period` frames.
+    pub fn request(&mut self, frame: u64) -> Option<(u32, u32)> {
+        if !frame.is_multiple_of(self.period) {
+            return None;
+        }
+        self.step = (self.step + 1) % (STEPS * 2);
+        let offset = STEP * self.step.min(STEPS * 2 - self.step);
+        let size = (self.base.0 + offset, self.base.1 + offset);
+        if self
+            .requested
+            .is_some_and(|(requested, _)| self.configured != Some(requested))
+        {
+            // The previous request never got its configure.
+            self.ignored += 1;
+        }
+        self.requested = Some((size, frame));
+        Some(size)
+    }
+
+    pub fn configured(&mut self, frame: u64, size: (u32, u32)) {
+        self.configured = Some(size);
+        if let Some((requested, at)) = self.requested {
+            if requested == size {
+                self.configure_delays.push(frame.saturating_sub(at));
+            }
+        }
+    }
+
+    pub fn committed(&mut self, frame: u64, content: (u32, u32)) {
+        self.in_flight.insert(frame, content);
+    }
+
+    pub fn presented(&mut self, frame: u64) {
+        let Some(content) = self.in_flight.remove(&frame) else {
+            return;
+        };
+        // Frames committed before this one were either presented or discarded.
+        self.in_flight = self.in_flight.split_off(&frame);
+        self.presented += 1;
+        match self.configured {
+            Some(configured) if configured != content => {
+                self.mismatch_count += 1;
+                if self.mismatches.len() < LISTED {
+                    self.mismatches.push(Mismatch {
+                        frame,
+                        content,
+                        configured,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let mean_delay = (!self.configure_delays.is_empty()).then(|| {
+            self.configure_delays.iter().sum::<u64>() as f64 / self.configure_delays.len() as f64
+        });
+        println!(
+            "{}resize stress: {} sizes configured, {:.1} frames from request to configure, {} requests ignored",
+            prefix,
+            self.configure_delays.len(),
+            mean_delay.unwrap_or(0.0),
+            self.ignored
+        );
+        println!(
+            "{}  {} of {} presented frames had content of another size than the configure in effect",
+            prefix, self.mismatch_count, self.presented
+        );
+        for mismatch in &self.mismatches {
+            println!(
+                "{}    frame {}: content {}x{}, configured {}x{}",
+                prefix,
+                mismatch.frame,
+                mismatch.content.0,
+                mismatch.content.1,
+                mismatch.configured.0,
+                mismatch.configured.1
+            );
+        }
+    }
+}
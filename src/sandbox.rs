@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+/// Sandbox the tool runs in, which limits where it can write and may hide the
+/// session's variables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sandbox {
+    Flatpak,
+    /// Podman, Docker, toolbox and the like.
+    Container,
+}
+
+impl Sandbox {
+    pub fn detect() -> Option<Self> {
+        if Path::new("/.flatpak-info").exists() {
+            Some(Sandbox::Flatpak)
+        } else if std::env::var_os("container").is_some()
+            || Path::new("/run/.containerenv").exists()
+            || Path::new("/.dockerenv").exists()
+        {
+            Some(Sandbox::Container)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sandbox::Flatpak => "flatpak",
+            Sandbox::Container => "container",
+        }
+    }
+}
+
+/// Directory relative output paths are resolved in: `explicit`, or inside a sandbox
+/// the data directory, as the working directory may not be writable there.
+pub fn output_dir(explicit: Option<PathBuf>) -> Option<PathBuf> {
+    explicit.or_else(|| {
+        let sandbox = Sandbox::detect()?;
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".local/share")))?;
+        let dir = data_home.join("fifo_test");
+        println!(
+            "running in a {}, relative output paths are written to {}",
+            sandbox.name(),
+            dir.display()
+        );
+        Some(dir)
+    })
+}
+
+/// `path` inside `dir` if it is relative, creating `dir` as needed.
+pub fn resolve(dir: Option<&Path>, path: &Path) -> PathBuf {
+    match dir {
+        Some(dir) if path.is_relative() => {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                eprintln!(
+                    "failed to create the output directory {}: {}",
+                    dir.display(),
+                    err
+                );
+            }
+            dir.join(path)
+        }
+        _ => path.to_path_buf(),
+    }
+}
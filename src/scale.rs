@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{plugin, Args};
+
+/// Options of the `scale-test` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+pub struct ScaleTest {
+    /// Surface counts to step through
+    #[arg(long, value_delimiter = ',', default_value = "10,50,100,200,500")]
+    steps: Vec<u32>,
+
+    /// Frames every surface commits per step
+    #[arg(long, default_value_t = 300)]
+    frames: u64,
+
+    /// Pacing counts as degraded once the median frame interval exceeds the single surface one by this fraction
+    #[arg(long, default_value_t = 0.5)]
+    tolerance: f64,
+}
+
+/// Runs every step with `count` windows, each on its own connection, until the
+/// median frame interval degrades.
+pub fn run(test: &ScaleTest, args: &Args, registry: &Arc<plugin::Registry>, start: Duration) {
+    let step = |count: u32| {
+        let mut args = args.clone();
+        args.connections = count;
+        args.frames = Some(test.frames);
+        args.quiet = true;
+
+        let mut intervals = crate::run_connections(&args, registry, start, None)
+            .into_iter()
+            .filter_map(|outcome| outcome.mean_interval)
+            .collect::<Vec<_>>();
+        intervals.sort();
+        let median = *intervals.get(intervals.len() / 2)?;
+        let worst = *intervals.last()?;
+        Some((median, worst))
+    };
+
+    let Some((baseline, _)) = step(1) else {
+        eprintln!("scale-test: the single surface run drew no frames");
+        return;
+    };
+    println!("scale-test: 1 surface, median interval {:?}", baseline);
+
+    let limit = baseline.mul_f64(1.0 + test.tolerance);
+    let mut capacity = 1;
+    for &count in &test.steps {
+        let Some((median, worst)) = step(count) else {
+            println!("scale-test: {} surfaces drew no frames", count);
+            break;
+        };
+        println!(
+            "scale-test: {} surfaces, median interval {:?}, worst {:?}",
+            count, median, worst
+        );
+        if median > limit {
+            println!(
+                "scale-test: pacing degraded at {} surfaces (median above {:?})",
+                count, limit
+            );
+            break;
+        }
+        capacity = count;
+    }
+    println!("scale-test: capacity {} surfaces", capacity);
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::wp_presentation_feedback::Kind;
+
+use crate::observer::{FrameObserver, Log};
+use crate::presentation::{self, Presented};
+
+/// Regime changes listed in the report, the rest is only counted.
+const MAX_SEGMENTS: usize = 20;
+
+struct Frame {
+    frame: u64,
+    zero_copy: bool,
+    latency: Duration,
+    time: Duration,
+    seq: u64,
+    refresh: Duration,
+}
+
+/// Guesses for every frame whether the surface was scanned out directly or
+/// composited, and compares the pacing of both, `--detect-scanout`.
+///
+/// The zero-copy flag of the presentation feedback decides if the compositor ever
+/// sets it. Otherwise a frame presented within one refresh of its commit counts as
+/// scanned out, as composition usually costs a refresh.
+#[derive(Default)]
+pub struct Scanout {
+    frames: Vec<Frame>,
+}
+
+impl FrameObserver for Scanout {
+    fn presented(&mut self, frame: u64, presented: Option<&Presented>, _: &str, _: &Log) {
+        let Some(presented) = presented else {
+            return;
+        };
+        self.frames.push(Frame {
+            frame,
+            zero_copy: presented.flags.contains(Kind::ZeroCopy),
+            latency: presented.time.saturating_sub(presented.committed),
+            time: presented.time,
+            seq: presented.seq,
+            refresh: presented.refresh,
+        });
+    }
+
+    fn print_report(&self, prefix: &str) {
+        if self.frames.is_empty() {
+            println!("{}scanout: no frames presented", prefix);
+            return;
+        }
+        let mut frames = self.frames.iter().collect::<Vec<_>>();
+        frames.sort_by_key(|frame| frame.frame);
+
+        let flagged = frames.iter().any(|frame| frame.zero_copy);
+        let scanout = |frame: &Frame| {
+            if flagged {
+                frame.zero_copy
+            } else {
+                !frame.refresh.is_zero() && frame.latency < frame.refresh
+            }
+        };
+        let name = |scanout: bool| if scanout { "scanout" } else { "composited" };
+        println!(
+            "{}scanout: {} frames classified by {}",
+            prefix,
+            frames.len(),
+            if flagged {
+                "the zero-copy flag"
+            } else {
+                "latency, the compositor never set zero-copy"
+            }
+        );
+
+        let mut segments = Vec::new();
+        for frame in &frames {
+            match segments.last_mut() {
+                Some((regime, _, last)) if *regime == scanout(frame) => *last = frame.frame,
+                _ => segments.push((scanout(frame), frame.frame, frame.frame)),
+            }
+        }
+        for (regime, first, last) in segments.iter().take(MAX_SEGMENTS) {
+            println!("{}  frames {}-{}: {}", prefix, first, last, name(*regime));
+        }
+        if segments.len() > MAX_SEGMENTS {
+            println!(
+                "{}  ... {} more regime changes",
+                prefix,
+                segments.len() - MAX_SEGMENTS
+            );
+        }
+
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        for regime in [true, false] {
+            let latencies = frames
+                .iter()
+                .filter(|frame| scanout(frame) == regime)
+                .map(|frame| ms(frame.latency))
+                .collect::<Vec<_>>();
+            if latencies.is_empty() {
+                continue;
+            }
+            // Only pairs of consecutive frames in the same regime.
+            let pairs = frames
+                .windows(2)
+                .filter(|pair| {
+                    pair[1].frame == pair[0].frame + 1
+                        && scanout(pair[0]) == regime
+                        && scanout(pair[1]) == regime
+                })
+                .collect::<Vec<_>>();
+            let intervals = pairs
+                .iter()
+                .map(|pair| ms(pair[1].time.saturating_sub(pair[0].time)))
+                .collect::<Vec<_>>();
+            let missed = pairs
+                .iter()
+                .filter(|pair| {
+                    presentation::refresh_cycles(
+                        (pair[0].time, pair[0].seq),
+                        (pair[1].time, pair[1].seq),
+                        pair[1].refresh,
+                    ) > 1
+                })
+                .count();
+            let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len().max(1) as f64;
+            let interval = mean(&intervals);
+            let deviation = mean(
+                &intervals
+                    .iter()
+                    .map(|value| (value - interval).powi(2))
+                    .collect::<Vec<_>>(),
+            )
+            .sqrt();
+            println!(
+                "{}  {}: {} frames, commit to present {:.3}ms, interval {:.3}ms ± {:.3}ms, {} of {} intervals missed a refresh",
+                prefix,
+                name(regime),
+                latencies.len(),
+                mean(&latencies),
+                interval,
+                deviation,
+                missed,
+                pairs.len()
+            );
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use crate::plugin::{self, Damage, FramePlan, Registry, Scenario};
+
+pub fn register(registry: &mut Registry) {
+    registry.register_scenario(
+        "continuous",
+        "commit as fast as possible, with a barrier on every frame",
+        || Box::new(Continuous),
+    );
+    registry.register_scenario(
+        "baseline",
+        "weston-simple-shm: frame callback throttled, no barrier, single inset damage",
+        || Box::new(Baseline),
+    );
+    registry.register_scenario(
+        "24fps",
+        "24 fps content timed with commit-timing and fifo barriers, for 3:2 pull-down on 60 Hz",
+        || Box::new(Timed::new(24)),
+    );
+}
+
+struct Continuous;
+
+impl Scenario for Continuous {
+    fn plan(&mut self, _frame: u64) -> Option<FramePlan> {
+        Some(FramePlan::default())
+    }
+}
+
+/// Mirrors the redraw loop of weston's simple-shm client.
+struct Baseline;
+
+impl Scenario for Baseline {
+    fn plan(&mut self, _frame: u64) -> Option<FramePlan> {
+        Some(FramePlan {
+            barrier: false,
+            frame_callback: true,
+            damage: Damage::Inset(20),
+            ..Default::default()
+        })
+    }
+}
+
+/// Content at a fixed rate, every frame carrying its presentation time.
+struct Timed {
+    interval: Duration,
+    start: Option<Duration>,
+}
+
+impl Timed {
+    /// How long before its presentation time a frame is committed.
+    const LEAD: Duration = Duration::from_millis(50);
+
+    fn new(fps: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / fps,
+            start: None,
+        }
+    }
+}
+
+impl Scenario for Timed {
+    fn plan(&mut self, frame: u64) -> Option<FramePlan> {
+        let now = plugin::now();
+        let start = *self.start.get_or_insert(now + Self::LEAD);
+        let target = |frame: u64| start + self.interval * (frame - 1) as u32;
+
+        Some(FramePlan {
+            present_at: Some(target(frame)),
+            delay: (target(frame + 1).saturating_sub(Self::LEAD)).saturating_sub(now),
+            ..Default::default()
+        })
+    }
+
+    fn uses_commit_timing(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,173 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay_client_toolkit::reexports::calloop::{LoopHandle, RegistrationToken};
+
+use crate::{clock, plugin, Args, SimpleWindow};
+
+/// How the client waits out the delay before the next frame, `--scheduler`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Scheduler {
+    /// An event loop timer
+    #[default]
+    Timer,
+    /// Spin through the event loop until the deadline
+    Busy,
+    /// Sleep on the event loop thread, no events are dispatched meanwhile
+    Blocking,
+}
+
+impl Scheduler {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scheduler::Timer => "timer",
+            Scheduler::Busy => "busy",
+            Scheduler::Blocking => "blocking",
+        }
+    }
+
+    /// Draws the next frame once `delay` has passed.
+    pub fn schedule(
+        self,
+        loop_handle: &LoopHandle<'static, SimpleWindow>,
+        delay: Duration,
+    ) -> RegistrationToken {
+        let deadline = Instant::now() + delay;
+        let timer = match self {
+            Scheduler::Timer if !delay.is_zero() => Timer::from_duration(delay),
+            Scheduler::Blocking => {
+                std::thread::sleep(delay);
+                Timer::immediate()
+            }
+            Scheduler::Timer | Scheduler::Busy => Timer::immediate(),
+        };
+        loop_handle
+            .insert_source(timer, move |_, _, window| {
+                if self == Scheduler::Busy && Instant::now() < deadline {
+                    std::hint::spin_loop();
+                    return TimeoutAction::ToInstant(Instant::now());
+                }
+                window.draw();
+                TimeoutAction::Drop
+            })
+            .unwrap()
+    }
+}
+
+/// How late frames were drawn after their deadline.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lateness {
+    sum: Duration,
+    count: u32,
+    max: Duration,
+}
+
+impl Lateness {
+    pub fn record(&mut self, lateness: Duration) {
+        self.sum += lateness;
+        self.count += 1;
+        self.max = self.max.max(lateness);
+    }
+
+    fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.sum / self.count)
+    }
+}
+
+/// Options of the `scheduler-tradeoff` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Tradeoff {
+    /// Frames every scheduler commits
+    #[arg(long, default_value_t = 600)]
+    frames: u64,
+}
+
+struct Row {
+    scheduler: Scheduler,
+    mean_interval: Option<Duration>,
+    deviation: Option<Duration>,
+    lateness: Lateness,
+    frames: u64,
+    cpu: Duration,
+    wall: Duration,
+    /// Millijoules per frame, with --energy.
+    energy: Option<f64>,
+}
+
+/// Runs the test with every scheduler and compares their pacing and CPU usage, the
+/// `scheduler-tradeoff` subcommand.
+pub fn run(tradeoff: &Tradeoff, args: &Args, registry: &Arc<plugin::Registry>, start: Duration) {
+    let mut rows = Vec::new();
+    for scheduler in Scheduler::value_variants() {
+        let mut args = args.clone();
+        args.connections = 1;
+        args.frames = Some(tradeoff.frames);
+        args.quiet = true;
+        args.scheduler = *scheduler;
+        println!("scheduler-tradeoff: {} scheduler", scheduler.name());
+
+        let (cpu, wall) = (clock::process_cpu(), Instant::now());
+        let outcome = crate::run_connections(&args, registry, start, None)
+            .pop()
+            .expect("one connection was run");
+        rows.push(Row {
+            scheduler: *scheduler,
+            mean_interval: outcome.mean_interval,
+            deviation: outcome.interval_deviation,
+            lateness: outcome.lateness,
+            frames: outcome.stats.frames,
+            cpu: clock::process_cpu() - cpu,
+            wall: wall.elapsed(),
+            energy: outcome
+                .energy
+                .and_then(|energy| energy.per_frame(outcome.stats.frames)),
+        });
+    }
+    print_report(&rows);
+}
+
+fn print_report(rows: &[Row]) {
+    let ms = |duration: Option<Duration>| {
+        duration.map_or("-".to_string(), |duration| {
+            format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+        })
+    };
+    println!(
+        "scheduler-tradeoff: {:<9} {:>10} {:>10} {:>10} {:>10} {:>11} {:>6} {:>9}",
+        "scheduler", "interval", "jitter", "late", "late max", "cpu/frame", "cpu", "mJ/frame"
+    );
+    for row in rows {
+        let per_frame = (row.frames > 0).then(|| row.cpu / row.frames as u32);
+        println!(
+            "scheduler-tradeoff: {:<9} {:>10} {:>10} {:>10} {:>10} {:>11} {:>5.1}% {:>9}",
+            row.scheduler.name(),
+            ms(row.mean_interval),
+            ms(row.deviation),
+            ms(row.lateness.mean()),
+            ms((row.lateness.count > 0).then_some(row.lateness.max)),
+            ms(per_frame),
+            row.cpu.as_secs_f64() / row.wall.as_secs_f64() * 100.0,
+            row.energy
+                .map_or("-".to_string(), |energy| format!("{:.3}", energy))
+        );
+    }
+
+    if rows.iter().all(|row| row.lateness.count == 0) {
+        println!("scheduler-tradeoff: no frame was delayed, the schedulers only differ with delays, e.g. --target-fps");
+        return;
+    }
+    let cheapest = rows.iter().min_by_key(|row| row.cpu);
+    let steadiest = rows
+        .iter()
+        .filter_map(|row| Some((row, row.deviation?)))
+        .min_by_key(|(_, deviation)| *deviation);
+    if let (Some(cheapest), Some((steadiest, _))) = (cheapest, steadiest) {
+        println!(
+            "scheduler-tradeoff: least cpu with the {} scheduler, steadiest pacing with the {} scheduler",
+            cheapest.scheduler.name(),
+            steadiest.scheduler.name()
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! Recording of the test window through the ScreenCast portal, `--screencast`.
+//!
+//! The portal of the desktop, GNOME's or KDE's, hands out a PipeWire stream of a
+//! window the user picks, which `gst-launch-1.0` encodes into a WebM file next to
+//! the report. The portal is driven on a thread of its own while the test runs, as
+//! picking the window waits for the user. Its session is created with
+//! `persist_mode` set to persistent and the restore token the portal returns is kept
+//! in `$XDG_STATE_HOME/fifo_test`, so after the window was picked once later runs
+//! start recording without asking, as far as the portal can match the new window to
+//! the one picked before.
+//!
+//! For `--verify-frames` the stream is also read as raw frames, which
+//! [`crate::golden`] compares with the rendered ones.
+
+use std::io::{self, Read};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::dbus::{self, Connection, Value};
+use crate::golden::Golden;
+
+const PORTAL: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SCREENCAST: &str = "org.freedesktop.portal.ScreenCast";
+/// `SourceType` of a single window.
+const WINDOW: u32 = 2;
+/// `persist_mode` keeping the permission until it is revoked.
+const PERSISTENT: u32 = 2;
+/// Portal version that knows `persist_mode` and restore tokens.
+const RESTORE_VERSION: u32 = 4;
+/// `cursor_mode` leaving the cursor out of the stream.
+const HIDDEN: u32 = 1;
+/// Portal version that knows `cursor_mode`.
+const CURSOR_MODE_VERSION: u32 = 2;
+/// Time the user gets to pick the window, and the portal to answer otherwise.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(120);
+/// Time the encoder gets to finish the file after being interrupted.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A finished recording.
+struct Recording {
+    duration: Duration,
+    restored: bool,
+    /// Exit status of the encoder, if recording.
+    status: Option<String>,
+    /// Exit status of the capture for `--verify-frames`, if verifying.
+    verified: Option<String>,
+}
+
+pub struct Screencast {
+    path: Option<PathBuf>,
+    stop: mpsc::Sender<()>,
+    /// The portal connection, shut down to stop waiting for the portal.
+    stream: UnixStream,
+    worker: JoinHandle<Result<Recording, String>>,
+}
+
+/// Where the restore token of the portal session is kept.
+fn token_path() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".local/state")))?;
+    Some(state_home.join("fifo_test/screencast-token"))
+}
+
+/// A session with the portal, whose requests answer with a `Response` signal on an
+/// object path derived from the unique name and a token of ours.
+struct Portal {
+    connection: Connection,
+    tokens: u32,
+}
+
+impl Portal {
+    fn call(&mut self, member: &str, args: &[Value]) -> io::Result<Vec<Value>> {
+        self.connection
+            .call(PORTAL, PORTAL_PATH, SCREENCAST, member, args)
+    }
+
+    fn property(&mut self, name: &str) -> io::Result<Value> {
+        let reply = self.connection.call(
+            PORTAL,
+            PORTAL_PATH,
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            &[
+                Value::Str(SCREENCAST.to_string()),
+                Value::Str(name.to_string()),
+            ],
+        )?;
+        reply
+            .into_iter()
+            .next()
+            .ok_or_else(|| io::Error::other(format!("no value for the property {}", name)))
+    }
+
+    /// Calls `member` as a request with `options` and waits for its results.
+    fn request(
+        &mut self,
+        member: &str,
+        mut args: Vec<Value>,
+        mut options: Vec<(&str, Value)>,
+    ) -> Result<Value, String> {
+        self.tokens += 1;
+        let token = format!("fifo_test_{}_{}", std::process::id(), self.tokens);
+        let sender = self
+            .connection
+            .unique_name
+            .trim_start_matches(':')
+            .replace('.', "_");
+        let handle = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+        // Subscribe before calling, the response may come before the call returns.
+        self.connection
+            .call(
+                "org.freedesktop.DBus",
+                "/org/freedesktop/DBus",
+                "org.freedesktop.DBus",
+                "AddMatch",
+                &[Value::Str(format!(
+                    "type='signal',interface='org.freedesktop.portal.Request',member='Response',path='{}'",
+                    handle
+                ))],
+            )
+            .map_err(|err| err.to_string())?;
+        options.push(("handle_token", Value::Str(token)));
+        args.push(Value::dict(options));
+        self.call(member, &args).map_err(|err| err.to_string())?;
+        let response = self
+            .connection
+            .signal(&handle, "Response")
+            .map_err(|err| format!("no answer to {}: {}", member, err))?;
+        match response.body.as_slice() {
+            [code, results] => match code.as_u32() {
+                Some(0) => Ok(results.clone()),
+                Some(1) => Err(format!("{} was cancelled", member)),
+                _ => Err(format!("{} failed", member)),
+            },
+            _ => Err(format!("malformed response to {}", member)),
+        }
+    }
+}
+
+/// Runs the portal flow and the consumers of the stream until told to stop.
+fn record(
+    connection: Connection,
+    path: Option<&Path>,
+    golden: Option<Arc<Mutex<Golden>>>,
+    stop: mpsc::Receiver<()>,
+) -> Result<Recording, String> {
+    let mut portal = Portal {
+        connection,
+        tokens: 0,
+    };
+    let version = portal
+        .property("version")
+        .map_err(|err| format!("no ScreenCast portal: {}", err))?
+        .as_u32()
+        .unwrap_or(1);
+    let types = portal
+        .property("AvailableSourceTypes")
+        .map_err(|err| err.to_string())?
+        .as_u32()
+        .unwrap_or(0);
+    if types & WINDOW == 0 {
+        return Err("the portal can't record single windows".to_string());
+    }
+
+    let results = portal.request(
+        "CreateSession",
+        Vec::new(),
+        vec![(
+            "session_handle_token",
+            Value::Str(format!("fifo_test_{}", std::process::id())),
+        )],
+    )?;
+    let session = results
+        .get("session_handle")
+        .and_then(Value::as_str)
+        .ok_or("CreateSession without session handle")?
+        .to_string();
+
+    let token_path = token_path();
+    let restore_token = token_path
+        .as_ref()
+        .filter(|_| version >= RESTORE_VERSION)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty());
+    let mut options = vec![
+        ("types", Value::U32(WINDOW)),
+        ("multiple", Value::Bool(false)),
+    ];
+    if version >= RESTORE_VERSION {
+        options.push(("persist_mode", Value::U32(PERSISTENT)));
+        if let Some(token) = restore_token.clone() {
+            options.push(("restore_token", Value::Str(token)));
+        }
+    }
+    if golden.is_some() && version >= CURSOR_MODE_VERSION {
+        // The captured frames are compared with the rendered ones pixel by pixel.
+        options.push(("cursor_mode", Value::U32(HIDDEN)));
+    }
+    let session_path = Value::Path(session.clone());
+    let result = portal
+        .request("SelectSources", vec![session_path.clone()], options)
+        .and_then(|_| {
+            if restore_token.is_none() {
+                // Whatever is picked is recorded, nothing checks it is the test window.
+                println!(
+                    "screencast: pick the test window in the portal dialog, the recording isn't checked to show it"
+                );
+            }
+            portal.request(
+                "Start",
+                vec![session_path, Value::Str(String::new())],
+                Vec::new(),
+            )
+        })
+        .and_then(|results| {
+            if let (Some(token), Some(path)) = (
+                results.get("restore_token").and_then(Value::as_str),
+                token_path.as_ref(),
+            ) {
+                let saved = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(path, token));
+                if let Err(err) = saved {
+                    eprintln!(
+                        "failed to save the screencast restore token to {}: {}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
+            let (node, size) = match results.get("streams") {
+                Some(Value::Array(_, streams)) => streams.iter().find_map(|stream| match stream {
+                    Value::Struct(fields) => Some((
+                        fields.first()?.as_u32()?,
+                        fields.get(1).and_then(|properties| {
+                            match properties.get("size")? {
+                                Value::Struct(size) => match size.as_slice() {
+                                    [Value::I32(width), Value::I32(height)] => {
+                                        Some((*width as u32, *height as u32))
+                                    }
+                                    _ => None,
+                                },
+                                _ => None,
+                            }
+                        }),
+                    )),
+                    _ => None,
+                }),
+                _ => None,
+            }
+            .ok_or("Start without a stream")?;
+            consume(node, size, path, golden, stop, restore_token.is_some())
+        });
+    if let Err(err) = portal.connection.send(
+        PORTAL,
+        &session,
+        "org.freedesktop.portal.Session",
+        "Close",
+        &[],
+        true,
+    ) {
+        eprintln!("failed to close the screencast session: {}", err);
+    }
+    result
+}
+
+/// Runs `gst-launch-1.0` with the PipeWire stream `node` as source of `elements`.
+fn gst(node: u32, elements: &[&str], stdout: Stdio) -> Result<Child, String> {
+    let mut command = Command::new("gst-launch-1.0");
+    command.args([
+        "-q",
+        "-e",
+        "pipewiresrc",
+        &format!("path={}", node),
+        "do-timestamp=true",
+    ]);
+    for element in elements {
+        command.arg("!").args(element.split(' '));
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .spawn()
+        .map_err(|err| format!("failed to run gst-launch-1.0: {}", err))
+}
+
+/// Interrupts `child`, gst-launch-1.0 -e finishes its output before exiting.
+fn interrupt(mut child: Child) -> String {
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status.to_string(),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break format!(
+                    "killed after not finishing within {}s",
+                    STOP_TIMEOUT.as_secs()
+                );
+            }
+            Err(err) => break err.to_string(),
+        }
+    }
+}
+
+/// Encodes the PipeWire stream `node` into `path` and hands its frames of `size` to
+/// `golden` until told to stop.
+fn consume(
+    node: u32,
+    size: Option<(u32, u32)>,
+    path: Option<&Path>,
+    golden: Option<Arc<Mutex<Golden>>>,
+    stop: mpsc::Receiver<()>,
+    restored: bool,
+) -> Result<Recording, String> {
+    let verify = match golden {
+        Some(golden) => {
+            let (width, height) = size.ok_or("the portal didn't tell the size of the stream")?;
+            let mut reader = gst(
+                node,
+                &["videoconvert", "video/x-raw,format=BGRx", "fdsink fd=1"],
+                Stdio::piped(),
+            )?;
+            let mut frames = reader.stdout.take().unwrap();
+            let thread = std::thread::spawn(move || {
+                let mut frame = vec![0; (width * height * 4) as usize];
+                // Unprefixed, --verify-frames only runs with a single connection.
+                while frames.read_exact(&mut frame).is_ok() {
+                    golden.lock().unwrap().captured(&frame, width, height, "");
+                }
+            });
+            Some((reader, thread))
+        }
+        None => None,
+    };
+    let encoder = path
+        .map(|path| {
+            let location = format!("filesink location={}", path.display());
+            gst(
+                node,
+                &["videoconvert", "vp8enc deadline=1", "webmmux", &location],
+                Stdio::null(),
+            )
+        })
+        .transpose()?;
+    let started = Instant::now();
+    // The sender is dropped rather than used when the run ends.
+    let _ = stop.recv();
+    let duration = started.elapsed();
+
+    let status = encoder.map(interrupt);
+    let verified = verify.map(|(reader, thread)| {
+        let status = interrupt(reader);
+        let _ = thread.join();
+        status
+    });
+    Ok(Recording {
+        duration,
+        restored,
+        status,
+        verified,
+    })
+}
+
+impl Screencast {
+    /// Starts asking the portal for the test window to record to `path` and to hand
+    /// to `golden`, `None` without a session bus.
+    pub fn start(path: Option<&Path>, golden: Option<Arc<Mutex<Golden>>>) -> Option<Self> {
+        let connection = dbus::session_bus().and_then(|stream| {
+            stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+            Connection::open(stream)
+        });
+        let connection = match connection {
+            Ok(connection) => connection,
+            Err(err) => {
+                eprintln!(
+                    "screencast requested, but the session bus is unavailable: {}",
+                    err
+                );
+                return None;
+            }
+        };
+        let stream = connection.stream().try_clone().ok()?;
+        let (stop, stopped) = mpsc::channel();
+        let worker = {
+            let path = path.map(Path::to_path_buf);
+            std::thread::Builder::new()
+                .name("screencast".to_string())
+                .spawn(move || record(connection, path.as_deref(), golden, stopped))
+                .ok()?
+        };
+        Some(Self {
+            path: path.map(Path::to_path_buf),
+            stop,
+            stream,
+            worker,
+        })
+    }
+
+    /// Stops the recording and prints how it went.
+    pub fn finish(self) {
+        drop(self.stop);
+        // Still waiting for the portal, e.g. for the window to be picked.
+        let _ = self.stream.shutdown(Shutdown::Read);
+        match self.worker.join() {
+            Ok(Ok(recording)) => {
+                let asked = if recording.restored {
+                    " without asking"
+                } else {
+                    ""
+                };
+                if let (Some(status), Some(path)) = (recording.status, self.path.as_ref()) {
+                    println!(
+                        "screencast: {:.1}s of the test window recorded to {}{}, encoder {}",
+                        recording.duration.as_secs_f64(),
+                        path.display(),
+                        asked,
+                        status
+                    );
+                }
+                if let Some(status) = recording.verified {
+                    println!(
+                        "screencast: {:.1}s of the test window captured for verification{}, capture {}",
+                        recording.duration.as_secs_f64(),
+                        asked,
+                        status
+                    );
+                }
+            }
+            Ok(Err(err)) => println!("screencast: nothing recorded, {}", err),
+            Err(_) => println!("screencast: nothing recorded, the portal thread panicked"),
+        }
+    }
+}
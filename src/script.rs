@@ -0,0 +1,211 @@
+//! Rhai scenario scripts, `--script`.
+//!
+//! A script defines `fn plan(frame)` returning a map describing the commit of
+//! `frame`, or `()` to end the run:
+//!
+//! ```text
+//! fn plan(frame) {
+//!     let latency = stats().latency;
+//!     if latency != () && latency > 20.0 {
+//!         set_pattern("gradient");
+//!     }
+//!     #{ barrier: frame % 2 == 0, delay: 5 }
+//! }
+//! ```
+//!
+//! Keys, all optional: `barrier` and `frame_callback` (bools), `delay` (ms to sleep
+//! before the next frame), `damage` (`"full"` or an inset in pixels) and `present_at`
+//! (`CLOCK_MONOTONIC` in ms, see `now()`). State kept across frames lives in `this`,
+//! an initially empty map; top-level statements run once when the script is loaded.
+//!
+//! Besides the standard library, scripts can call `now()`, `stats()` returning the
+//! [`Stats`] as a map with times in ms, `set_pattern(name)`, and `annotate(label)` or
+//! `annotate(key, value)` to annotate the planned frame.
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+
+use crate::plugin::{self, Annotation, Damage, FramePlan, Registry, Scenario, Stats};
+
+/// State shared with the functions registered in the engine.
+#[derive(Default)]
+struct Shared {
+    stats: Stats,
+    pattern: Option<String>,
+    annotations: Vec<Annotation>,
+}
+
+struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    this: Dynamic,
+    shared: Rc<RefCell<Shared>>,
+    failure: Option<String>,
+}
+
+/// Compiles the script at `path` and registers it as a scenario named after the file.
+pub fn register(registry: &mut Registry, path: &Path) -> Result<(), String> {
+    let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    // Compile once up front so errors are reported before the run.
+    Script::new(&source)?;
+
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .ok_or("script path has no file name")?;
+    let description = format!("script {}", path.display());
+    registry.register_scenario(name, description, move || {
+        Box::new(Script::new(&source).expect("script was validated"))
+    });
+    Ok(())
+}
+
+impl Script {
+    fn new(source: &str) -> Result<Self, String> {
+        let shared = Rc::new(RefCell::new(Shared::default()));
+        let mut engine = Engine::new();
+        engine.register_fn("now", || millis(plugin::now()));
+        let stats = shared.clone();
+        engine.register_fn("stats", move || stats_map(&stats.borrow().stats));
+        let pattern = shared.clone();
+        engine.register_fn("set_pattern", move |name: &str| {
+            pattern.borrow_mut().pattern = Some(name.to_string());
+        });
+        let annotations = shared.clone();
+        engine.register_fn("annotate", move |label: &str| {
+            let annotation = Annotation::Label(label.to_string());
+            annotations.borrow_mut().annotations.push(annotation);
+        });
+        let annotations = shared.clone();
+        engine.register_fn("annotate", move |key: &str, value: Dynamic| {
+            let annotation = Annotation::Value(key.to_string(), value.to_string());
+            annotations.borrow_mut().annotations.push(annotation);
+        });
+
+        let ast = engine.compile(source).map_err(|err| err.to_string())?;
+        if !ast.iter_functions().any(|function| function.name == "plan") {
+            return Err("the script defines no `fn plan(frame)`".into());
+        }
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            this: Dynamic::from_map(Map::new()),
+            shared,
+            failure: None,
+        })
+    }
+}
+
+impl Scenario for Script {
+    fn plan(&mut self, frame: u64) -> Option<FramePlan> {
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.this);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(
+                options,
+                &mut self.scope,
+                &self.ast,
+                "plan",
+                (frame as rhai::INT,),
+            )
+            .map_err(|err| err.to_string())
+            .and_then(|plan| {
+                if plan.is_unit() {
+                    return Ok(None);
+                }
+                let plan = plan
+                    .try_cast::<Map>()
+                    .ok_or("`plan` must return a map or ()")?;
+                to_plan(plan).map(Some)
+            });
+
+        result.unwrap_or_else(|err| {
+            eprintln!("script failed at frame {}: {}", frame, err);
+            self.failure = Some(err);
+            None
+        })
+    }
+
+    fn wants_feedback(&self) -> bool {
+        true
+    }
+
+    fn observe(&mut self, stats: &Stats) {
+        self.shared.borrow_mut().stats = *stats;
+    }
+
+    fn switch_pattern(&mut self) -> Option<String> {
+        self.shared.borrow_mut().pattern.take()
+    }
+
+    fn take_annotations(&mut self) -> Vec<Annotation> {
+        std::mem::take(&mut self.shared.borrow_mut().annotations)
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.failure.clone()
+    }
+}
+
+fn to_plan(map: Map) -> Result<FramePlan, String> {
+    let mut plan = FramePlan::default();
+    for (key, value) in map {
+        let type_name = value.type_name();
+        let invalid = || format!("invalid `{}`: {}", key, type_name);
+        match key.as_str() {
+            "barrier" => plan.barrier = value.as_bool().map_err(|_| invalid())?,
+            "frame_callback" => plan.frame_callback = value.as_bool().map_err(|_| invalid())?,
+            "delay" => plan.delay = duration(&value).ok_or_else(invalid)?,
+            "present_at" => plan.present_at = Some(duration(&value).ok_or_else(invalid)?),
+            "damage" => {
+                plan.damage = if value.clone().into_string().is_ok_and(|s| s == "full") {
+                    Damage::Full
+                } else {
+                    let inset = value.as_int().map_err(|_| invalid())?;
+                    Damage::Inset(u32::try_from(inset).map_err(|_| invalid())?)
+                }
+            }
+            _ => return Err(format!("unknown key `{}` in the plan", key)),
+        }
+    }
+    Ok(plan)
+}
+
+/// A non-negative number of milliseconds.
+fn duration(value: &Dynamic) -> Option<Duration> {
+    let millis = value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|millis| millis as rhai::FLOAT))?;
+    Duration::try_from_secs_f64(millis / 1000.0).ok()
+}
+
+fn millis(duration: Duration) -> rhai::FLOAT {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn stats_map(stats: &Stats) -> Map {
+    let optional = |duration: Option<Duration>| {
+        duration.map_or(Dynamic::UNIT, |duration| Dynamic::from(millis(duration)))
+    };
+    let mut map = Map::new();
+    map.insert("frames".into(), (stats.frames as rhai::INT).into());
+    map.insert("interval".into(), optional(stats.interval));
+    map.insert("latency".into(), optional(stats.latency));
+    map.insert("presented".into(), (stats.presented as rhai::INT).into());
+    map.insert("discarded".into(), (stats.discarded as rhai::INT).into());
+    map
+}
@@ -0,0 +1,175 @@
+use std::path::{Path, PathBuf};
+
+use smithay_client_toolkit::reexports::client::Connection;
+
+/// Variables pointing at the session the test was started from, which would make
+/// the client or the placement IPC talk to the outer compositor when nested.
+const OUTER_SESSION: &[&str] = &[
+    "WAYLAND_DISPLAY",
+    "WAYLAND_SOCKET",
+    "DISPLAY",
+    "SWAYSOCK",
+    "I3SOCK",
+    "HYPRLAND_INSTANCE_SIGNATURE",
+    "XDG_CURRENT_DESKTOP",
+];
+
+/// Sets up the environment the Wayland connection, the hooks and the IPC commands
+/// are created from.
+///
+/// Must be called before any other thread is spawned.
+pub fn prepare(clean_env: bool, socket: Option<&str>, runtime_dir: Option<&Path>) {
+    if clean_env {
+        for var in OUTER_SESSION {
+            std::env::remove_var(var);
+        }
+    }
+    if let Some(runtime_dir) = runtime_dir {
+        std::env::set_var("XDG_RUNTIME_DIR", runtime_dir);
+    }
+    if let Some(socket) = socket {
+        std::env::set_var("WAYLAND_DISPLAY", socket);
+        // An inherited socket fd takes precedence over WAYLAND_DISPLAY.
+        std::env::remove_var("WAYLAND_SOCKET");
+    }
+
+    let located = socket.is_none() && runtime_dir.is_none() && locate_socket();
+
+    if clean_env || socket.is_some() || runtime_dir.is_some() || located {
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_default();
+        let socket = std::env::var("WAYLAND_DISPLAY").unwrap_or_default();
+        println!(
+            "connecting to {}",
+            Path::new(&runtime_dir).join(socket).display()
+        );
+    }
+}
+
+/// Looks for the socket at the usual places if the environment doesn't lead to one,
+/// e.g. inside a container without XDG_RUNTIME_DIR. Returns whether it was changed.
+fn locate_socket() -> bool {
+    if std::env::var_os("WAYLAND_SOCKET").is_some() {
+        return false;
+    }
+    let display = std::env::var("WAYLAND_DISPLAY").ok();
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+    let socket = |dir: &Path| dir.join(display.as_deref().unwrap_or("wayland-0"));
+    if display
+        .as_deref()
+        .is_some_and(|display| Path::new(display).is_absolute())
+        || runtime_dir
+            .as_deref()
+            .map(socket)
+            .is_some_and(|path| path.exists())
+    {
+        return false;
+    }
+
+    // SAFETY: getuid never fails.
+    let user_dir = PathBuf::from(format!("/run/user/{}", unsafe { libc::getuid() }));
+    let candidates = [runtime_dir.as_deref().map(socket), Some(socket(&user_dir))]
+        .into_iter()
+        .flatten()
+        .chain(runtime_dir.map(|dir| dir.join("wayland-0")))
+        .chain(std::iter::once(user_dir.join("wayland-0")));
+    for candidate in candidates {
+        if candidate.exists() {
+            // An absolute WAYLAND_DISPLAY is used as is.
+            std::env::set_var("WAYLAND_DISPLAY", &candidate);
+            return true;
+        }
+    }
+    false
+}
+
+/// Exit code when no Wayland compositor can be reached.
+pub const EXIT_NO_WAYLAND: i32 = 3;
+
+/// Connects to the compositor from the environment, exiting with guidance for the
+/// usual misconfigurations instead of panicking.
+pub fn connect() -> Connection {
+    Connection::connect_to_env().unwrap_or_else(|err| {
+        eprintln!("error: failed to connect to a Wayland compositor: {}", err);
+        for hint in diagnose() {
+            eprintln!("  {}", hint);
+        }
+        std::process::exit(EXIT_NO_WAYLAND)
+    })
+}
+
+fn diagnose() -> Vec<String> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let runtime_dir = var("XDG_RUNTIME_DIR").map(PathBuf::from);
+    let mut hints = Vec::new();
+
+    if let Some(fd) = var("WAYLAND_SOCKET") {
+        hints.push(format!(
+            "WAYLAND_SOCKET={} is set, the inherited socket may be closed already",
+            fd
+        ));
+    }
+    match (var("WAYLAND_DISPLAY"), var("DISPLAY")) {
+        (None, Some(display)) if xwayland_running() => hints.push(format!(
+            "DISPLAY={} is set but WAYLAND_DISPLAY isn't, this looks like an X11 client environment under Xwayland, run from a Wayland terminal or pass --socket",
+            display
+        )),
+        (None, Some(display)) => hints.push(format!(
+            "DISPLAY={} is set but WAYLAND_DISPLAY isn't, this is an X11 session{}, start a Wayland session or a nested compositor and pass --socket",
+            display,
+            var("XDG_SESSION_TYPE").map_or(String::new(), |session| format!(" (XDG_SESSION_TYPE={})", session))
+        )),
+        (None, None) => hints.push(
+            "neither WAYLAND_DISPLAY nor DISPLAY is set, e.g. on a tty or over ssh, pass the compositor's socket with --socket"
+                .to_string(),
+        ),
+        (Some(display), _) => {
+            let path = match (&runtime_dir, Path::new(&display).is_absolute()) {
+                (_, true) => Some(PathBuf::from(&display)),
+                (Some(dir), false) => Some(dir.join(&display)),
+                (None, false) => None,
+            };
+            match path {
+                Some(path) if !path.exists() => hints.push(format!(
+                    "WAYLAND_DISPLAY={} but {} doesn't exist, the compositor may have exited",
+                    display,
+                    path.display()
+                )),
+                Some(_) => {}
+                None => hints.push(
+                    "XDG_RUNTIME_DIR isn't set, the socket can't be found, pass --runtime-dir"
+                        .to_string(),
+                ),
+            }
+        }
+    }
+
+    if let Some(dir) = runtime_dir {
+        let mut sockets = std::fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("wayland-") && !name.ends_with(".lock"))
+            .collect::<Vec<_>>();
+        sockets.sort();
+        if !sockets.is_empty() {
+            hints.push(format!(
+                "sockets in {}: {}, pick one with --socket",
+                dir.display(),
+                sockets.join(", ")
+            ));
+        }
+    }
+    hints
+}
+
+fn xwayland_running() -> bool {
+    std::fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .any(|entry| {
+            std::fs::read_to_string(entry.path().join("comm"))
+                .is_ok_and(|comm| comm.trim() == "Xwayland")
+        })
+}
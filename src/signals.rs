@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay_client_toolkit::reexports::calloop::LoopHandle;
+
+use crate::plugin::Annotation;
+use crate::SimpleWindow;
+
+/// The handlers only count the signals, every window polls for them, see
+/// `statedump`.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+static PAUSES: AtomicU64 = AtomicU64::new(0);
+static RESUMES: AtomicU64 = AtomicU64::new(0);
+
+extern "C" fn handle_sigusr1(_: libc::c_int) {
+    PAUSES.fetch_add(1, Ordering::Relaxed);
+}
+
+extern "C" fn handle_sigusr2(_: libc::c_int) {
+    RESUMES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Pauses committing on SIGUSR1 and resumes on SIGUSR2, events are dispatched
+/// meanwhile. Both are annotated on the frame committed last.
+pub fn init(loop_handle: &LoopHandle<'static, SimpleWindow>) {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        // SAFETY: the handlers only touch atomics.
+        unsafe {
+            libc::signal(
+                libc::SIGUSR1,
+                handle_sigusr1 as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+            libc::signal(
+                libc::SIGUSR2,
+                handle_sigusr2 as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    });
+
+    let mut seen = (
+        PAUSES.load(Ordering::Relaxed),
+        RESUMES.load(Ordering::Relaxed),
+    );
+    loop_handle
+        .insert_source(Timer::from_duration(POLL_INTERVAL), move |_, _, window| {
+            let (pauses, resumes) = (
+                PAUSES.load(Ordering::Relaxed),
+                RESUMES.load(Ordering::Relaxed),
+            );
+            if pauses != seen.0 {
+                window.signal_pause();
+            }
+            if resumes != seen.1 {
+                window.signal_resume();
+            }
+            seen = (pauses, resumes);
+            TimeoutAction::ToDuration(POLL_INTERVAL)
+        })
+        .unwrap();
+}
+
+impl SimpleWindow {
+    fn signal_pause(&mut self) {
+        if self.paused {
+            return;
+        }
+        println!("{}paused by SIGUSR1", self.log_prefix);
+        self.paused = true;
+        // Whatever would draw next would still commit a frame.
+        if let Some(token) = self.pending_draw.take() {
+            self.loop_handle.remove(token);
+        }
+        if let Some(retry) = self.buffer_wait.take() {
+            retry.cancel();
+        }
+        self.awaiting_frame_callback = None;
+        self.deadline = None;
+        self.annotate(Annotation::Label("paused by SIGUSR1".into()));
+    }
+
+    fn signal_resume(&mut self) {
+        if !self.paused {
+            return;
+        }
+        println!("{}resumed by SIGUSR2", self.log_prefix);
+        self.annotate(Annotation::Label("resumed by SIGUSR2".into()));
+        self.resume();
+    }
+}
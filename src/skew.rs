@@ -0,0 +1,114 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::rng::Rng;
+
+/// Bad client clock, parsed from `offset=<ms>,drift=<ppm>,jitter=<ms>[,seed=<n>]`, every
+/// part is optional.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Skew {
+    /// Constant error in ms, may be negative.
+    offset: f64,
+    /// Error growing with the run time, in parts per million.
+    drift: f64,
+    /// Maximum random error in ms, in both directions.
+    jitter: f64,
+    seed: Option<u64>,
+}
+
+impl FromStr for Skew {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut skew = Skew::default();
+        for part in s.split([',', ' ']).filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected <key>=<value>, got `{}`", part))?;
+            let number = || {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| format!("invalid {} `{}`", key, value))
+            };
+            match key {
+                "offset" => skew.offset = number()?,
+                "drift" => skew.drift = number()?,
+                "jitter" => skew.jitter = number()?.abs(),
+                "seed" => {
+                    skew.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|err| format!("invalid seed `{}`: {}", value, err))?,
+                    )
+                }
+                _ => return Err(format!("unknown clock skew parameter `{}`", key)),
+            }
+        }
+        Ok(skew)
+    }
+}
+
+/// Applies the skew to the commit-timing timestamps, `--clock-skew`.
+///
+/// Only the timestamps sent to the compositor are skewed, the pacing analysis keeps
+/// the real target times.
+pub struct ClockSkew {
+    skew: Skew,
+    start: Duration,
+    rng: Rng,
+    count: u64,
+    error_sum: f64,
+    error_max: f64,
+}
+
+impl ClockSkew {
+    pub fn new(skew: Skew, start: Duration) -> Self {
+        let seed = skew.seed.unwrap_or(start.as_nanos() as u64);
+        Self {
+            skew,
+            start,
+            rng: Rng::new(seed),
+            count: 0,
+            error_sum: 0.0,
+            error_max: 0.0,
+        }
+    }
+
+    /// Uniformly distributed in [-1, 1).
+    fn random(&mut self) -> f64 {
+        (self.rng.next_u64() >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+
+    /// `time` as the bad clock sees it.
+    pub fn apply(&mut self, time: Duration) -> Duration {
+        let elapsed = time.saturating_sub(self.start).as_secs_f64() * 1000.0;
+        let error = self.skew.offset
+            + elapsed * self.skew.drift / 1_000_000.0
+            + self.skew.jitter * self.random();
+        self.count += 1;
+        self.error_sum += error;
+        self.error_max = self.error_max.max(error.abs());
+
+        let nanos = time.as_nanos() as i128 + (error * 1_000_000.0) as i128;
+        Duration::from_nanos(nanos.max(0) as u64)
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if self.count == 0 {
+            println!("{}clock skew: no commit-timing timestamp was sent", prefix);
+            return;
+        }
+        println!(
+            "{}clock skew: {} timestamps, offset {}ms, drift {}ppm, jitter {}ms, mean error {:.3}ms, max {:.3}ms",
+            prefix,
+            self.count,
+            self.skew.offset,
+            self.skew.drift,
+            self.skew.jitter,
+            self.error_sum / self.count as f64,
+            self.error_max
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Presentations per output while the surface spans several outputs.
+//!
+//! A surface overlapping two outputs is presented on whichever one the compositor
+//! picks, and its fifo barriers are released by that output's refresh. While the
+//! surface is on more than one output every frame asks for presentation feedback,
+//! and the output announced with `sync_output` is recorded, so the report shows
+//! which output the compositor latched the frames to and how often it changed.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::client::protocol::wl_output;
+
+use crate::presentation::Presented;
+
+#[derive(Default)]
+struct Output {
+    presented: u64,
+    latency: Duration,
+    refresh: Duration,
+}
+
+#[derive(Default)]
+pub struct Spanning {
+    /// Outputs the surface is on, in the order it entered them.
+    entered: Vec<wl_output::WlOutput>,
+    /// Most outputs the surface was on at once.
+    most: usize,
+    /// Presentations while spanning, by output name.
+    outputs: BTreeMap<String, Output>,
+    /// Output of the previous presentation while spanning.
+    last: Option<String>,
+    /// Presentations on another output than the one before.
+    switches: u64,
+    discarded: u64,
+}
+
+impl Spanning {
+    pub fn enter(&mut self, output: &wl_output::WlOutput) {
+        if !self.entered.contains(output) {
+            self.entered.push(output.clone());
+        }
+        self.most = self.most.max(self.entered.len());
+    }
+
+    /// The surface left `output`, returns the output it entered last of the ones it
+    /// is still on.
+    pub fn leave(&mut self, output: &wl_output::WlOutput) -> Option<wl_output::WlOutput> {
+        self.entered.retain(|entered| entered != output);
+        if !self.is_spanning() {
+            self.last = None;
+        }
+        self.entered.last().cloned()
+    }
+
+    pub fn is_spanning(&self) -> bool {
+        self.entered.len() > 1
+    }
+
+    /// Records the feedback of a frame, `output` names the output it references.
+    pub fn presented(&mut self, presented: Option<&Presented>, output: String) {
+        if !self.is_spanning() {
+            return;
+        }
+        let Some(presented) = presented else {
+            self.discarded += 1;
+            return;
+        };
+        if self.last.as_ref().is_some_and(|last| *last != output) {
+            self.switches += 1;
+        }
+        let entry = self.outputs.entry(output.clone()).or_default();
+        entry.presented += 1;
+        entry.latency += presented.time.saturating_sub(presented.committed);
+        if !presented.refresh.is_zero() {
+            entry.refresh = presented.refresh;
+        }
+        self.last = Some(output);
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if self.most < 2 {
+            return;
+        }
+        let presented = self
+            .outputs
+            .values()
+            .map(|output| output.presented)
+            .sum::<u64>();
+        println!(
+            "{}spanning: on up to {} outputs at once, {} frames presented and {} discarded meanwhile, {} changes of the presenting output",
+            prefix, self.most, presented, self.discarded, self.switches
+        );
+        for (name, output) in &self.outputs {
+            let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+            let refresh = match output.refresh {
+                refresh if refresh.is_zero() => "unknown refresh".to_string(),
+                refresh => format!("refresh {:.3}ms", ms(refresh)),
+            };
+            println!(
+                "{}  {:<16} {} presented ({:.1}%), mean commit to present {:.3}ms, {}",
+                prefix,
+                name,
+                output.presented,
+                output.presented as f64 * 100.0 / presented as f64,
+                ms(output.latency / output.presented as u32),
+                refresh
+            );
+        }
+    }
+}
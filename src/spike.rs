@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Number of recent regular intervals the recovery baseline is computed from.
+const BASELINE_WINDOW: usize = 30;
+/// An interval within this fraction of the baseline counts as recovered.
+const RECOVERY_TOLERANCE: f64 = 0.25;
+
+/// Periodic render-time spike, parsed from `every=<n>,cost=<ms>`.
+#[derive(Clone, Copy, Debug)]
+pub struct Spike {
+    every: u64,
+    cost: Duration,
+}
+
+impl FromStr for Spike {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut every = None;
+        let mut cost = None;
+
+        for part in s.split([',', ' ']).filter(|part| !part.is_empty()) {
+            match part.split_once('=') {
+                Some(("every", value)) => {
+                    every = Some(
+                        value
+                            .parse::<u64>()
+                            .ok()
+                            .filter(|every| *every > 0)
+                            .ok_or_else(|| format!("invalid frame count `{}`", value))?,
+                    )
+                }
+                Some(("cost", value)) => {
+                    cost =
+                        Some(Duration::from_millis(value.parse().map_err(|err| {
+                            format!("invalid cost `{}`: {}", value, err)
+                        })?))
+                }
+                _ => return Err(format!("unknown spike parameter `{}`", part)),
+            }
+        }
+
+        match (every, cost) {
+            (Some(every), Some(cost)) => Ok(Spike { every, cost }),
+            _ => Err("expected every=<n>,cost=<ms>".to_string()),
+        }
+    }
+}
+
+struct Recovery {
+    frame: u64,
+    start: Instant,
+}
+
+/// Injects the spikes and measures how many frames it takes for the frame interval to
+/// return to what it was before.
+pub struct SpikeInjector {
+    spike: Spike,
+    baseline: VecDeque<Duration>,
+    recovering: Option<Recovery>,
+    recoveries: Vec<(u64, Duration)>,
+    unrecovered: u64,
+}
+
+impl SpikeInjector {
+    pub fn new(spike: Spike) -> Self {
+        Self {
+            spike,
+            baseline: VecDeque::new(),
+            recovering: None,
+            recoveries: Vec::new(),
+            unrecovered: 0,
+        }
+    }
+
+    /// Burns CPU for the configured cost if `frame` is a spike frame.
+    pub fn render(&mut self, frame: u64) {
+        if !frame.is_multiple_of(self.spike.every) {
+            return;
+        }
+
+        if self.recovering.take().is_some() {
+            // The previous spike never settled before this one.
+            self.unrecovered += 1;
+        }
+
+        let start = Instant::now();
+        while start.elapsed() < self.spike.cost {
+            std::hint::spin_loop();
+        }
+        self.recovering = Some(Recovery { frame, start });
+    }
+
+    /// Records the interval measured at the start of `frame`.
+    pub fn interval(&mut self, frame: u64, interval: Duration) {
+        let Some(recovery) = self.recovering.as_ref() else {
+            self.baseline.push_back(interval);
+            if self.baseline.len() > BASELINE_WINDOW {
+                self.baseline.pop_front();
+            }
+            return;
+        };
+
+        let Some(baseline) = median(&self.baseline) else {
+            return;
+        };
+
+        // The first interval after the spike always contains the spike itself.
+        let deviation = (interval.as_secs_f64() - baseline.as_secs_f64()).abs();
+        if frame > recovery.frame + 1 && deviation <= baseline.as_secs_f64() * RECOVERY_TOLERANCE {
+            let frames = frame - recovery.frame - 1;
+            let time = recovery.start.elapsed();
+            println!(
+                "Spike at frame {} recovered after {} frames, {:?}",
+                recovery.frame, frames, time
+            );
+            self.recoveries.push((frames, time));
+            self.recovering = None;
+        }
+    }
+
+    pub fn print_report(&self) {
+        println!("spikes:");
+        println!(
+            "  every {} frames, cost {:?}",
+            self.spike.every, self.spike.cost
+        );
+        if self.recoveries.is_empty() {
+            println!("  no recovered spikes");
+        } else {
+            let count = self.recoveries.len() as u64;
+            let frames = self
+                .recoveries
+                .iter()
+                .map(|(frames, _)| frames)
+                .sum::<u64>();
+            let time = self
+                .recoveries
+                .iter()
+                .map(|(_, time)| *time)
+                .sum::<Duration>();
+            let worst = self.recoveries.iter().map(|(frames, _)| frames).max();
+            println!(
+                "  recovered {}: avg {:.1} frames / {:?}, worst {} frames",
+                count,
+                frames as f64 / count as f64,
+                time / count as u32,
+                worst.unwrap()
+            );
+        }
+        let unrecovered = self.unrecovered + u64::from(self.recovering.is_some());
+        if unrecovered > 0 {
+            println!("  unrecovered: {}", unrecovered);
+        }
+    }
+}
+
+fn median(intervals: &VecDeque<Duration>) -> Option<Duration> {
+    if intervals.is_empty() {
+        return None;
+    }
+    let mut sorted = intervals.iter().copied().collect::<Vec<_>>();
+    sorted.sort();
+    Some(sorted[sorted.len() / 2])
+}
@@ -0,0 +1,535 @@
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::reexports::client::globals::{registry_queue_init, GlobalListContents};
+use smithay_client_toolkit::reexports::client::protocol::{
+    wl_buffer, wl_callback, wl_compositor, wl_registry, wl_shm, wl_shm_pool, wl_subcompositor,
+    wl_subsurface, wl_surface,
+};
+use smithay_client_toolkit::reexports::client::{delegate_noop, Connection, Dispatch, QueueHandle};
+use smithay_client_toolkit::reexports::protocols::wp::fifo::v1::client::{
+    wp_fifo_manager_v1, wp_fifo_v1,
+};
+use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::{
+    wp_presentation, wp_presentation_feedback,
+};
+use smithay_client_toolkit::reexports::protocols::xdg::shell::client::{
+    xdg_surface, xdg_toplevel, xdg_wm_base,
+};
+
+use crate::{canvas, clock, session, text};
+
+/// Buffers of every half.
+const BUFFERS: usize = 3;
+/// Commits of the fifo half that may wait for their presentation, like a swapchain
+/// with two images in flight.
+const QUEUE_DEPTH: u32 = 2;
+/// Space between the halves, in pixels.
+const GAP: u32 = 8;
+/// Time the bar needs to cross a half.
+const SWEEP: Duration = Duration::from_secs(2);
+/// An interval longer than this many median intervals counts as a stutter.
+const STUTTER: f64 = 1.5;
+
+/// Options of the `split` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Split {
+    /// Width of each half
+    #[arg(long, default_value_t = 480)]
+    width: u32,
+
+    /// Height of the window
+    #[arg(long, default_value_t = 360)]
+    height: u32,
+
+    /// Frames each half commits before the window closes, runs until closed otherwise
+    #[arg(long)]
+    frames: Option<u64>,
+}
+
+/// Shared memory mapped for drawing.
+struct Memory {
+    fd: OwnedFd,
+    data: *mut u8,
+    size: usize,
+}
+
+impl Memory {
+    fn new(size: usize) -> Self {
+        // SAFETY: plain memfd_create, ftruncate and mmap calls, the fd is owned right
+        // away and the mapping checked before use.
+        unsafe {
+            let fd = libc::memfd_create(c"fifo_test-split".as_ptr(), libc::MFD_CLOEXEC);
+            assert!(fd >= 0, "memfd_create failed");
+            let fd = OwnedFd::from_raw_fd(fd);
+            assert_eq!(
+                libc::ftruncate(fd.as_raw_fd(), size as libc::off_t),
+                0,
+                "ftruncate failed"
+            );
+            let data = libc::mmap(
+                std::ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            );
+            assert!(data != libc::MAP_FAILED, "mmap failed");
+            Self {
+                fd,
+                data: data as *mut u8,
+                size,
+            }
+        }
+    }
+
+    fn slice(&mut self, offset: usize, length: usize) -> &mut [u8] {
+        assert!(offset + length <= self.size);
+        // SAFETY: the range is inside the mapping, which lives as long as self.
+        unsafe { std::slice::from_raw_parts_mut(self.data.add(offset), length) }
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        // SAFETY: data and size are the mapping created in new.
+        unsafe { libc::munmap(self.data as *mut libc::c_void, self.size) };
+    }
+}
+
+/// How a half paces its commits.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pacing {
+    /// A barrier with every commit, the next one is drawn once a buffer is free.
+    Fifo,
+    /// The next frame is drawn when the frame callback of the last one is done.
+    FrameCallback,
+}
+
+impl Pacing {
+    fn name(self) -> &'static str {
+        match self {
+            Pacing::Fifo => "fifo",
+            Pacing::FrameCallback => "frame callback",
+        }
+    }
+}
+
+struct Half {
+    pacing: Pacing,
+    surface: wl_surface::WlSurface,
+    fifo: Option<wp_fifo_v1::WpFifoV1>,
+    memory: Memory,
+    buffers: Vec<wl_buffer::WlBuffer>,
+    busy: [bool; BUFFERS],
+    awaiting_callback: bool,
+    /// Commits whose presentation feedback hasn't arrived yet.
+    outstanding: u32,
+    committed: u64,
+    presented: Vec<Duration>,
+    discarded: u64,
+}
+
+struct State {
+    configured: bool,
+    closed: bool,
+    monotonic: bool,
+    halves: Vec<Half>,
+}
+
+impl Half {
+    fn ready(&self, presentation: bool) -> bool {
+        self.busy.iter().any(|busy| !busy)
+            && match self.pacing {
+                Pacing::Fifo => !presentation || self.outstanding < QUEUE_DEPTH,
+                Pacing::FrameCallback => !self.awaiting_callback,
+            }
+    }
+
+    fn draw(
+        &mut self,
+        index: usize,
+        (width, height): (u32, u32),
+        elapsed: Duration,
+        presentation: Option<&wp_presentation::WpPresentation>,
+        qh: &QueueHandle<State>,
+    ) {
+        let buffer = self.busy.iter().position(|busy| !busy).expect("ready");
+        let size = (width * height * 4) as usize;
+        {
+            let mut canvas =
+                canvas::Canvas::new(self.memory.slice(buffer * size, size), width, height);
+            canvas.fill_rect(0, 0, width, height, 0xFF20_2020);
+            let bar = width / 16;
+            let phase = elapsed.as_secs_f64() / SWEEP.as_secs_f64();
+            let x = (phase.fract() * (width - bar) as f64) as i32;
+            canvas.fill_rect(x, 0, bar, height, 0xFFFF_FFFF);
+            text::draw(&mut canvas, 8, 8, self.pacing.name(), 0xFFFF_C000, 2);
+        }
+
+        match self.pacing {
+            Pacing::Fifo => {
+                if let Some(fifo) = self.fifo.as_ref() {
+                    fifo.wait_barrier();
+                    fifo.set_barrier();
+                }
+            }
+            Pacing::FrameCallback => {
+                self.surface.frame(qh, index);
+                self.awaiting_callback = true;
+            }
+        }
+        if let Some(presentation) = presentation {
+            presentation.feedback(&self.surface, qh, index);
+            self.outstanding += 1;
+        }
+        self.surface.attach(Some(&self.buffers[buffer]), 0, 0);
+        self.surface
+            .damage_buffer(0, 0, width as i32, height as i32);
+        self.surface.commit();
+        self.busy[buffer] = true;
+        self.committed += 1;
+    }
+
+    fn print_report(&self) {
+        let mut intervals = self
+            .presented
+            .windows(2)
+            .map(|times| times[1].saturating_sub(times[0]))
+            .collect::<Vec<_>>();
+        if intervals.is_empty() {
+            println!(
+                "split: {:<14} {} frames committed, no presentation feedback",
+                self.pacing.name(),
+                self.committed
+            );
+            return;
+        }
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let count = intervals.len() as f64;
+        let mean = intervals.iter().map(|interval| ms(*interval)).sum::<f64>() / count;
+        let deviation = (intervals
+            .iter()
+            .map(|interval| (ms(*interval) - mean).powi(2))
+            .sum::<f64>()
+            / count)
+            .sqrt();
+        intervals.sort();
+        let median = ms(intervals[intervals.len() / 2]);
+        let stutters = intervals
+            .iter()
+            .filter(|interval| ms(**interval) > median * STUTTER)
+            .count();
+        println!(
+            "split: {:<14} {} frames committed, {} presented, {} discarded, interval {:.2}ms ± {:.2}ms, {} stutters",
+            self.pacing.name(),
+            self.committed,
+            self.presented.len(),
+            self.discarded,
+            mean,
+            deviation,
+            stutters
+        );
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<xdg_wm_base::XdgWmBase, ()> for State {
+    fn event(
+        _: &mut Self,
+        wm_base: &xdg_wm_base::XdgWmBase,
+        event: xdg_wm_base::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<xdg_surface::XdgSurface, ()> for State {
+    fn event(
+        state: &mut Self,
+        xdg_surface: &xdg_surface::XdgSurface,
+        event: xdg_surface::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            xdg_surface.ack_configure(serial);
+            state.configured = true;
+        }
+    }
+}
+
+impl Dispatch<xdg_toplevel::XdgToplevel, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &xdg_toplevel::XdgToplevel,
+        event: xdg_toplevel::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_toplevel::Event::Close = event {
+            state.closed = true;
+        }
+    }
+}
+
+/// User data of the half's buffers, `None` for the background.
+impl Dispatch<wl_buffer::WlBuffer, Option<(usize, usize)>> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_buffer::WlBuffer,
+        event: wl_buffer::Event,
+        data: &Option<(usize, usize)>,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let (wl_buffer::Event::Release, Some((half, buffer))) = (event, data) {
+            state.halves[*half].busy[*buffer] = false;
+        }
+    }
+}
+
+impl Dispatch<wl_callback::WlCallback, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        half: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            state.halves[*half].awaiting_callback = false;
+        }
+    }
+}
+
+impl Dispatch<wp_presentation::WpPresentation, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &wp_presentation::WpPresentation,
+        event: wp_presentation::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wp_presentation::Event::ClockId { clk_id } = event {
+            state.monotonic = clk_id == libc::CLOCK_MONOTONIC as u32;
+        }
+    }
+}
+
+impl Dispatch<wp_presentation_feedback::WpPresentationFeedback, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &wp_presentation_feedback::WpPresentationFeedback,
+        event: wp_presentation_feedback::Event,
+        half: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let monotonic = state.monotonic;
+        let half = &mut state.halves[*half];
+        match event {
+            wp_presentation_feedback::Event::Presented {
+                tv_sec_hi,
+                tv_sec_lo,
+                tv_nsec,
+                ..
+            } => {
+                half.outstanding = half.outstanding.saturating_sub(1);
+                // Like the test window, fall back to the receive time for other clocks.
+                half.presented.push(if monotonic {
+                    Duration::new(((tv_sec_hi as u64) << 32) | tv_sec_lo as u64, tv_nsec)
+                } else {
+                    clock::monotonic()
+                });
+            }
+            wp_presentation_feedback::Event::Discarded => {
+                half.outstanding = half.outstanding.saturating_sub(1);
+                half.discarded += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(State: wl_compositor::WlCompositor);
+delegate_noop!(State: ignore wl_surface::WlSurface);
+delegate_noop!(State: ignore wl_shm::WlShm);
+delegate_noop!(State: wl_shm_pool::WlShmPool);
+delegate_noop!(State: wl_subcompositor::WlSubcompositor);
+delegate_noop!(State: wl_subsurface::WlSubsurface);
+delegate_noop!(State: wp_fifo_manager_v1::WpFifoManagerV1);
+delegate_noop!(State: wp_fifo_v1::WpFifoV1);
+
+/// Shows the same animation side by side in two subsurfaces of one toplevel, paced
+/// with fifo on the left and with frame callbacks on the right, so the difference can
+/// be seen at once and captured in one recording, the `split` subcommand.
+pub fn run(split: &Split) {
+    let conn = session::connect();
+    let (globals, mut queue) = registry_queue_init::<State>(&conn).unwrap();
+    let qh = queue.handle();
+    let compositor: wl_compositor::WlCompositor = globals
+        .bind(&qh, 1..=6, ())
+        .expect("wl_compositor not available");
+    let subcompositor: wl_subcompositor::WlSubcompositor = globals
+        .bind(&qh, 1..=1, ())
+        .expect("wl_subcompositor not available");
+    let shm: wl_shm::WlShm = globals.bind(&qh, 1..=1, ()).expect("wl_shm not available");
+    let wm_base: xdg_wm_base::XdgWmBase = globals
+        .bind(&qh, 1..=1, ())
+        .expect("xdg shell is not available");
+    let manager: Option<wp_fifo_manager_v1::WpFifoManagerV1> =
+        globals.bind(&qh, 1..=crate::FIFO_VERSION, ()).ok();
+    let presentation: Option<wp_presentation::WpPresentation> = globals.bind(&qh, 1..=1, ()).ok();
+    if manager.is_none() {
+        eprintln!(
+            "split: the compositor has no fifo support, the left half commits without barriers"
+        );
+    }
+    if presentation.is_none() {
+        eprintln!("split: no wp_presentation, the halves can't be compared, only watched");
+    }
+
+    let (width, height) = (split.width.max(1), split.height.max(1));
+    let window_width = width * 2 + GAP;
+    let root = compositor.create_surface(&qh, ());
+    let xdg_surface = wm_base.get_xdg_surface(&root, &qh, ());
+    let toplevel = xdg_surface.get_toplevel(&qh, ());
+    toplevel.set_title("fifo_test split: fifo | frame callback".into());
+    toplevel.set_app_id("fifo_test".into());
+    toplevel.set_min_size(window_width as i32, height as i32);
+    toplevel.set_max_size(window_width as i32, height as i32);
+
+    let size = (width * height * 4) as usize;
+    let mut halves = Vec::new();
+    for (index, pacing) in [Pacing::Fifo, Pacing::FrameCallback]
+        .into_iter()
+        .enumerate()
+    {
+        let surface = compositor.create_surface(&qh, ());
+        let subsurface = subcompositor.get_subsurface(&surface, &root, &qh, ());
+        subsurface.set_position((index as u32 * (width + GAP)) as i32, 0);
+        // Each half commits on its own schedule.
+        subsurface.set_desync();
+        let memory = Memory::new(size * BUFFERS);
+        let pool = shm.create_pool(memory.fd.as_fd(), (size * BUFFERS) as i32, &qh, ());
+        let buffers = (0..BUFFERS)
+            .map(|buffer| {
+                pool.create_buffer(
+                    (buffer * size) as i32,
+                    width as i32,
+                    height as i32,
+                    width as i32 * 4,
+                    wl_shm::Format::Xrgb8888,
+                    &qh,
+                    Some((index, buffer)),
+                )
+            })
+            .collect();
+        pool.destroy();
+        halves.push(Half {
+            pacing,
+            fifo: (pacing == Pacing::Fifo)
+                .then(|| {
+                    manager
+                        .as_ref()
+                        .map(|manager| manager.get_fifo(&surface, &qh, ()))
+                })
+                .flatten(),
+            surface,
+            memory,
+            buffers,
+            busy: [false; BUFFERS],
+            awaiting_callback: false,
+            outstanding: 0,
+            committed: 0,
+            presented: Vec::new(),
+            discarded: 0,
+        });
+    }
+
+    root.commit();
+    let mut state = State {
+        configured: false,
+        closed: false,
+        monotonic: false,
+        halves,
+    };
+    while !state.configured {
+        queue
+            .blocking_dispatch(&mut state)
+            .expect("connection lost before the first configure");
+    }
+
+    // The background, also applying the positions of the subsurfaces.
+    let background_size = (window_width * height * 4) as usize;
+    let mut background = Memory::new(background_size);
+    background.slice(0, background_size).fill(0);
+    let pool = shm.create_pool(background.fd.as_fd(), background_size as i32, &qh, ());
+    let background_buffer = pool.create_buffer(
+        0,
+        window_width as i32,
+        height as i32,
+        window_width as i32 * 4,
+        wl_shm::Format::Xrgb8888,
+        &qh,
+        None,
+    );
+    pool.destroy();
+    root.attach(Some(&background_buffer), 0, 0);
+    root.damage_buffer(0, 0, window_width as i32, height as i32);
+    root.commit();
+
+    println!(
+        "split: fifo on the left, frame callbacks on the right, {}x{} each",
+        width, height
+    );
+    let started = Instant::now();
+    loop {
+        let done = |half: &Half| split.frames.is_some_and(|frames| half.committed >= frames);
+        for (index, half) in state.halves.iter_mut().enumerate() {
+            if !done(half) && half.ready(presentation.is_some()) {
+                half.draw(
+                    index,
+                    (width, height),
+                    started.elapsed(),
+                    presentation.as_ref(),
+                    &qh,
+                );
+            }
+        }
+        if state.closed || state.halves.iter().all(done) {
+            break;
+        }
+        if let Err(err) = queue.blocking_dispatch(&mut state) {
+            eprintln!("split: connection lost: {}", err);
+            break;
+        }
+    }
+    let _ = queue.roundtrip(&mut state);
+
+    for half in &state.halves {
+        half.print_report();
+    }
+}
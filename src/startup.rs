@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use crate::clock;
+
+/// Milestones of a window's first frame, as `CLOCK_MONOTONIC` times.
+pub struct Startup {
+    start: Duration,
+    /// Bound wp_fifo_manager_v1 version, `None` without fifo.
+    fifo: Option<u32>,
+    configure: Option<Duration>,
+    commit: Option<Duration>,
+    /// Number of the first frame committed with content.
+    frame: Option<u64>,
+    presented: Option<Duration>,
+    discarded: bool,
+}
+
+impl Startup {
+    /// `start` is the time the process started, `fifo` the fifo manager version if the
+    /// first frame is committed with a barrier.
+    pub fn new(start: Duration, fifo: Option<u32>) -> Self {
+        Self {
+            start,
+            fifo,
+            configure: None,
+            commit: None,
+            frame: None,
+            presented: None,
+            discarded: false,
+        }
+    }
+
+    pub fn configured(&mut self) {
+        self.configure.get_or_insert_with(clock::monotonic);
+    }
+
+    /// Whether no frame with content was committed yet.
+    pub fn pending(&self) -> bool {
+        self.frame.is_none()
+    }
+
+    pub fn committed(&mut self, frame: u64) {
+        if self.frame.is_none() {
+            self.frame = Some(frame);
+            self.commit = Some(clock::monotonic());
+        }
+    }
+
+    /// Records the presentation of `frame`, `None` if it was discarded.
+    ///
+    /// Only the first frame is of interest, others are ignored.
+    pub fn presented(&mut self, frame: u64, time: Option<Duration>) {
+        if self.frame != Some(frame) {
+            return;
+        }
+        match time {
+            Some(time) => self.presented = Some(time),
+            None => self.discarded = true,
+        }
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let since_start = |time: Option<Duration>| match time {
+            Some(time) => format!("{:?}", time.saturating_sub(self.start)),
+            None => "-".to_string(),
+        };
+        let fifo = match self.fifo {
+            Some(version) => format!("fifo v{}", version),
+            None => "no fifo".to_string(),
+        };
+        println!("{}time to first frame ({}):", prefix, fifo);
+        println!("{}  configure: {}", prefix, since_start(self.configure));
+        println!("{}  commit:    {}", prefix, since_start(self.commit));
+        if self.discarded {
+            println!("{}  presented: discarded", prefix);
+        } else {
+            println!("{}  presented: {}", prefix, since_start(self.presented));
+        }
+    }
+}
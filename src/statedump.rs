@@ -0,0 +1,125 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use std::time::Duration;
+
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay_client_toolkit::reexports::calloop::LoopHandle;
+
+use crate::SimpleWindow;
+
+/// Signal handlers can't reach the event loops, they only count the signals, which
+/// every window polls for.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+extern "C" fn handle_sigquit(_: libc::c_int) {
+    REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Dumps the state of the window to stderr on every SIGQUIT instead of exiting.
+///
+/// The dump is written from the event loop, so a loop blocked in a request doesn't
+/// dump anything.
+pub fn init(loop_handle: &LoopHandle<'static, SimpleWindow>) {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        // SAFETY: the handler only touches an atomic.
+        unsafe {
+            libc::signal(
+                libc::SIGQUIT,
+                handle_sigquit as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    });
+
+    let mut seen = REQUESTS.load(Ordering::Relaxed);
+    loop_handle
+        .insert_source(Timer::from_duration(POLL_INTERVAL), move |_, _, window| {
+            let requests = REQUESTS.load(Ordering::Relaxed);
+            if requests != seen {
+                seen = requests;
+                window.dump_state();
+            }
+            TimeoutAction::ToDuration(POLL_INTERVAL)
+        })
+        .unwrap();
+}
+
+impl SimpleWindow {
+    fn dump_state(&mut self) {
+        let prefix = self.log_prefix.clone();
+        let clock = self.clock.clone();
+        let ago = |time: Option<std::time::Duration>| {
+            time.map_or("never".to_string(), |time| {
+                format!("{:?} ago", clock.since(time))
+            })
+        };
+
+        eprintln!("{}state dump:", prefix);
+        eprintln!(
+            "{}  frame {}, next planned frame {}, {}x{}",
+            prefix,
+            self.frame,
+            self.frame + 1,
+            self.width,
+            self.height
+        );
+        eprintln!(
+            "{}  paused {}, frozen {}, exit {}, first configure pending {}",
+            prefix, self.paused, self.frozen, self.exit, self.first_configure
+        );
+        eprintln!(
+            "{}  fifo {}, barriers {}",
+            prefix,
+            if self.fifo.is_some() {
+                "bound"
+            } else {
+                "unavailable"
+            },
+            if self.fifo_enabled {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+        match self.last_commit {
+            Some((time, barrier)) => eprintln!(
+                "{}  last commit {}, {}",
+                prefix,
+                ago(Some(time)),
+                if barrier {
+                    "with a barrier set"
+                } else {
+                    "without a barrier"
+                }
+            ),
+            None => eprintln!("{}  no commit yet", prefix),
+        }
+        eprintln!("{}  last draw {}", prefix, ago(self.last_draw));
+        eprintln!(
+            "{}  waiting for a frame callback: {}, waited for a buffer: {}",
+            prefix,
+            self.awaiting_frame_callback
+                .map_or("no".to_string(), |delay| format!("yes, then {:?}", delay)),
+            self.waited_for_buffer
+        );
+        match self.last_presented {
+            Some((frame, time)) => eprintln!(
+                "{}  last presented frame {} at {:?}, {} presented, {} discarded",
+                prefix, frame, time, self.stats.presented, self.stats.discarded
+            ),
+            None => eprintln!("{}  no presentation feedback yet", prefix),
+        }
+
+        for index in 0..self.buffers.len() {
+            let state = if self.pool.canvas(&self.buffers[index]).is_some() {
+                "free"
+            } else {
+                "held by the compositor"
+            };
+            eprintln!("{}  buffer {}: {}", prefix, index, state);
+        }
+        eprintln!("{}  pool size {}", prefix, self.pool.len());
+    }
+}
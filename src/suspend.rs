@@ -0,0 +1,201 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::clock::Clock;
+
+/// Reaction to a suspended toplevel that stopped getting frame signals.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OnSuspend {
+    /// Keep committing, only record the suspension
+    Log,
+    /// Stop committing until the toplevel is no longer suspended
+    Pause,
+}
+
+struct Suspension {
+    start: Duration,
+    /// Frames committed while suspended.
+    frames: u64,
+    /// Time the toplevel stayed suspended.
+    duration: Option<Duration>,
+    /// Time from the end of the suspension to the next frame signal.
+    drain: Option<Duration>,
+}
+
+/// Detects suspension from the toplevel state and missing frame callbacks or
+/// presentation feedback, `--on-suspend`.
+pub struct Suspend {
+    mode: OnSuspend,
+    threshold: Duration,
+    suspended: bool,
+    last_signal: Duration,
+    current: Option<Suspension>,
+    resumed: Option<Duration>,
+    suspensions: Vec<Suspension>,
+    clock: Rc<dyn Clock>,
+}
+
+impl Suspend {
+    pub fn new(mode: OnSuspend, threshold: Duration, clock: Rc<dyn Clock>) -> Self {
+        Self {
+            mode,
+            threshold,
+            suspended: false,
+            last_signal: clock.now(),
+            current: None,
+            resumed: None,
+            suspensions: Vec::new(),
+            clock,
+        }
+    }
+
+    /// Records a frame callback or presentation feedback.
+    pub fn signal(&mut self) {
+        self.last_signal = self.clock.now();
+        if let Some(resumed) = self.resumed.take() {
+            if let Some(suspension) = self.suspensions.last_mut() {
+                suspension.drain = Some(self.clock.since(resumed));
+            }
+        }
+    }
+
+    /// Handles the suspended state of a configure, returns true if drawing has to be
+    /// restarted.
+    pub fn configured(&mut self, suspended: bool, log_prefix: &str) -> bool {
+        self.suspended = suspended;
+        if suspended {
+            return false;
+        }
+        let Some(mut suspension) = self.current.take() else {
+            return false;
+        };
+
+        let duration = self.clock.since(suspension.start);
+        println!(
+            "{}toplevel resumed after {:?}, {} frames committed while suspended",
+            log_prefix, duration, suspension.frames
+        );
+        suspension.duration = Some(duration);
+        self.suspensions.push(suspension);
+        self.resumed = Some(self.clock.now());
+        self.mode == OnSuspend::Pause
+    }
+
+    /// Called before every frame, returns true if the frame must not be committed.
+    pub fn check(&mut self, log_prefix: &str) -> bool {
+        if let Some(suspension) = self.current.as_mut() {
+            suspension.frames += 1;
+            return self.mode == OnSuspend::Pause;
+        }
+        let silence = self.clock.since(self.last_signal);
+        if !self.suspended || silence < self.threshold {
+            return false;
+        }
+
+        println!(
+            "{}toplevel suspended, no frame signal for {:?}{}",
+            log_prefix,
+            silence,
+            if self.mode == OnSuspend::Pause {
+                ", pausing"
+            } else {
+                ""
+            }
+        );
+        self.current = Some(Suspension {
+            start: self.clock.now(),
+            frames: 0,
+            duration: None,
+            drain: None,
+        });
+        self.mode == OnSuspend::Pause
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        println!("{}suspensions: {}", prefix, self.suspensions.len());
+        for suspension in &self.suspensions {
+            println!(
+                "{}  {:?} suspended, {} frames committed, first frame signal {} after resume",
+                prefix,
+                suspension.duration.unwrap_or_default(),
+                suspension.frames,
+                suspension
+                    .drain
+                    .map(|drain| format!("{:?}", drain))
+                    .unwrap_or_else(|| "never".to_string())
+            );
+        }
+        if let Some(suspension) = self.current.as_ref() {
+            println!(
+                "{}  still suspended for {:?} at exit",
+                prefix,
+                self.clock.since(suspension.start)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::clock::Simulated;
+
+    fn suspend(mode: OnSuspend, clock: &Simulated) -> Suspend {
+        Suspend::new(mode, Duration::from_secs(1), Rc::new(clock.clone()))
+    }
+
+    #[test]
+    fn suspension_needs_the_state_and_missing_signals() {
+        let clock = Simulated::default();
+        let mut suspend = suspend(OnSuspend::Pause, &clock);
+        clock.advance_to(Duration::from_secs(10));
+        // Missing signals alone, e.g. an occluded toplevel, aren't a suspension.
+        assert!(!suspend.check(""));
+        suspend.configured(true, "");
+        suspend.signal();
+        clock.advance_to(Duration::from_millis(10_900));
+        assert!(!suspend.check(""));
+        clock.advance_to(Duration::from_millis(11_000));
+        assert!(suspend.check(""));
+        assert!(suspend.check(""));
+    }
+
+    /// Fast-forwarding a suspension of two thousand hours.
+    #[test]
+    fn long_suspension_is_measured_on_the_clock() {
+        let hours = |hours: u64| Duration::from_secs(hours * 3600);
+        let clock = Simulated::default();
+        let mut suspend = suspend(OnSuspend::Log, &clock);
+        suspend.configured(true, "");
+        clock.advance_to(hours(1));
+        assert!(!suspend.check(""));
+        clock.advance_to(hours(2001));
+        assert!(!suspend.check(""));
+        assert!(!suspend.configured(false, ""));
+        clock.advance_to(hours(2001) + Duration::from_millis(16));
+        suspend.signal();
+
+        let [suspension] = suspend.suspensions.as_slice() else {
+            panic!("{} suspensions", suspend.suspensions.len());
+        };
+        assert_eq!(suspension.duration, Some(hours(2000)));
+        assert_eq!(suspension.frames, 1);
+        assert_eq!(suspension.drain, Some(Duration::from_millis(16)));
+    }
+
+    #[test]
+    fn pause_restarts_drawing_on_resume() {
+        let clock = Simulated::default();
+        let mut suspend = suspend(OnSuspend::Pause, &clock);
+        suspend.configured(true, "");
+        clock.advance_to(Duration::from_secs(5));
+        assert!(suspend.check(""));
+        assert!(suspend.configured(false, ""));
+        assert!(!suspend.check(""));
+        // Without a frame signal after the resume the drain stays unknown.
+        assert_eq!(suspend.suspensions[0].drain, None);
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use crate::clock;
+use crate::presentation::Presented;
+
+/// Number of phase bins in the report.
+const BINS: u32 = 20;
+
+struct Sample {
+    /// Commit time after the preceding vblank.
+    phase: Duration,
+    /// Refresh cycles from the preceding vblank to the presentation.
+    cycles: u64,
+}
+
+/// Commits every frame a little later after vblank than the previous one,
+/// `--phase-sweep`, and records at which phase presentation flips to the
+/// following refresh, i.e. the compositor's latch deadline.
+///
+/// The next frame is only committed after the previous one was presented, so
+/// frames never queue up.
+pub struct Sweep {
+    step: Duration,
+    phase: Duration,
+    /// Last presentation time and the refresh interval.
+    vblank: Option<(Duration, Duration)>,
+    last_cycles: Option<u64>,
+    samples: Vec<Sample>,
+}
+
+impl Sweep {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step,
+            phase: Duration::ZERO,
+            vblank: None,
+            last_cycles: None,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Records the presentation of `frame` and returns the delay until the next
+    /// commit.
+    pub fn presented(&mut self, frame: u64, presented: &Presented, log_prefix: &str) -> Duration {
+        let refresh = match self.vblank {
+            _ if !presented.refresh.is_zero() => presented.refresh,
+            Some((last, refresh)) => presented
+                .time
+                .checked_sub(last)
+                .filter(|delta| !delta.is_zero() && *delta < refresh * 2)
+                .unwrap_or(refresh),
+            None => Duration::ZERO,
+        };
+
+        if let Some((last, _)) = self.vblank.filter(|_| !refresh.is_zero()) {
+            let refresh_ns = refresh.as_nanos();
+            let since = presented.committed.saturating_sub(last).as_nanos();
+            let phase = Duration::from_nanos((since % refresh_ns) as u64);
+            let preceding = presented.committed - phase;
+            let cycles = (presented.time.saturating_sub(preceding).as_nanos() as f64
+                / refresh_ns as f64)
+                .round() as u64;
+
+            if self.last_cycles.is_some_and(|last| last != cycles) {
+                println!(
+                    "{}frame {} committed {:.3}ms after vblank, presented {} refreshes later",
+                    log_prefix,
+                    frame,
+                    phase.as_secs_f64() * 1000.0,
+                    cycles
+                );
+            }
+            self.last_cycles = Some(cycles);
+            self.samples.push(Sample { phase, cycles });
+        }
+
+        if refresh.is_zero() {
+            // Without a refresh interval there's no phase to sweep.
+            return Duration::ZERO;
+        }
+        self.vblank = Some((presented.time, refresh));
+        self.phase += self.step;
+        if self.phase >= refresh {
+            self.phase -= refresh;
+        }
+
+        let now = clock::monotonic();
+        let mut target = presented.time + self.phase;
+        while target < now {
+            target += refresh;
+        }
+        target - now
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let Some((_, refresh)) = self.vblank.filter(|_| !self.samples.is_empty()) else {
+            println!("{}phase sweep: no presented frames", prefix);
+            return;
+        };
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let on_time = self
+            .samples
+            .iter()
+            .filter(|sample| sample.cycles <= 1)
+            .map(|sample| sample.phase)
+            .max();
+        let late = self
+            .samples
+            .iter()
+            .filter(|sample| sample.cycles > 1)
+            .map(|sample| sample.phase)
+            .min();
+
+        println!(
+            "{}phase sweep: {} frames on a {:.3}ms refresh",
+            prefix,
+            self.samples.len(),
+            ms(refresh)
+        );
+        for bin in 0..BINS {
+            let start = refresh * bin / BINS;
+            let end = refresh * (bin + 1) / BINS;
+            let samples = self
+                .samples
+                .iter()
+                .filter(|sample| sample.phase >= start && sample.phase < end)
+                .collect::<Vec<_>>();
+            if samples.is_empty() {
+                continue;
+            }
+            let late = samples.iter().filter(|sample| sample.cycles > 1).count();
+            println!(
+                "{}  {:7.3}ms - {:7.3}ms: {:4} frames, {:5.1}% presented a refresh later",
+                prefix,
+                ms(start),
+                ms(end),
+                samples.len(),
+                late as f64 / samples.len() as f64 * 100.0
+            );
+        }
+
+        match (on_time, late) {
+            (Some(on_time), Some(late)) if on_time < late => println!(
+                "{}  latch deadline between {:.3}ms and {:.3}ms before vblank",
+                prefix,
+                ms(refresh - late),
+                ms(refresh - on_time)
+            ),
+            (Some(on_time), Some(late)) => println!(
+                "{}  no sharp latch deadline, late from {:.3}ms, on time up to {:.3}ms after vblank",
+                prefix,
+                ms(late),
+                ms(on_time)
+            ),
+            (Some(_), None) => println!("{}  no frame missed the next refresh", prefix),
+            (None, _) => println!("{}  no frame made the next refresh", prefix),
+        }
+    }
+}
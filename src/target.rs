@@ -0,0 +1,117 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Submission rate of `--target-fps`.
+#[derive(Clone, Copy, Debug)]
+pub enum TargetFps {
+    Fixed(f64),
+    /// The refresh rate of the output the surface is on times the factor, `auto` or
+    /// `refresh` for 1, `refresh/2` or `refresh*2` for the usual fifo operating points.
+    Refresh(f64),
+}
+
+impl FromStr for TargetFps {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let positive = |s: &str| s.parse::<f64>().ok().filter(|n| n.is_finite() && *n > 0.0);
+        let target = match s {
+            "auto" | "refresh" => Some(TargetFps::Refresh(1.0)),
+            _ => match (s.strip_prefix("refresh/"), s.strip_prefix("refresh*")) {
+                (Some(divisor), _) => {
+                    positive(divisor).map(|divisor| TargetFps::Refresh(1.0 / divisor))
+                }
+                (_, Some(factor)) => positive(factor).map(TargetFps::Refresh),
+                _ => positive(s).map(TargetFps::Fixed),
+            },
+        };
+        target.ok_or_else(|| {
+            format!(
+                "invalid target `{}`, expected a positive rate, `auto`, `refresh/N` or `refresh*N`",
+                s
+            )
+        })
+    }
+}
+
+/// Relative change of the refresh interval below which it counts as unchanged.
+const REFRESH_TOLERANCE: f64 = 0.001;
+
+/// Paces submissions on absolute deadlines at a target rate.
+pub struct Target {
+    fps: TargetFps,
+    interval: Option<Duration>,
+    /// `CLOCK_MONOTONIC` deadline of the next submission.
+    next: Option<Duration>,
+}
+
+impl Target {
+    pub fn new(fps: TargetFps) -> Self {
+        Self {
+            fps,
+            interval: match fps {
+                TargetFps::Fixed(fps) => Some(Duration::from_secs_f64(1.0 / fps)),
+                TargetFps::Refresh(_) => None,
+            },
+            next: None,
+        }
+    }
+
+    /// Whether the rate follows the output, which needs presentation feedback.
+    pub fn is_auto(&self) -> bool {
+        matches!(self.fps, TargetFps::Refresh(_))
+    }
+
+    /// Delay from `now` until the next submission is due, `None` until the rate is
+    /// known.
+    pub fn delay(&mut self, now: Duration) -> Option<Duration> {
+        let interval = self.interval?;
+        let next = match self.next {
+            // Skip the missed deadlines instead of bursting to catch up.
+            Some(next) if next + interval > now => next + interval,
+            _ => now + interval,
+        };
+        self.next = Some(next);
+        Some(next.saturating_sub(now))
+    }
+
+    /// Updates the refresh interval of the current output from its wl_output mode.
+    pub fn output_mode(&mut self, refresh_mhz: i32, name: &str, prefix: &str) {
+        if refresh_mhz <= 0 {
+            return;
+        }
+        let refresh = Duration::from_secs_f64(1000.0 / refresh_mhz as f64);
+        self.set_refresh(refresh, &format!("the mode of {}", name), prefix);
+    }
+
+    /// Updates the refresh interval from presentation feedback, which also follows the
+    /// surface to other outputs.
+    pub fn feedback(&mut self, refresh: Duration, prefix: &str) {
+        if refresh.is_zero() {
+            return;
+        }
+        self.set_refresh(refresh, "presentation feedback", prefix);
+    }
+
+    fn set_refresh(&mut self, refresh: Duration, source: &str, prefix: &str) {
+        let TargetFps::Refresh(factor) = self.fps else {
+            return;
+        };
+        let refresh = refresh.div_f64(factor);
+        let unchanged = self.interval.is_some_and(|interval| {
+            (interval.as_secs_f64() / refresh.as_secs_f64() - 1.0).abs() < REFRESH_TOLERANCE
+        });
+        if unchanged {
+            return;
+        }
+        println!(
+            "{}target: {:.3} fps from {}, {}x its refresh rate",
+            prefix,
+            1.0 / refresh.as_secs_f64(),
+            source,
+            factor
+        );
+        self.interval = Some(refresh);
+        self.next = None;
+    }
+}
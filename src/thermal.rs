@@ -0,0 +1,307 @@
+//! CPU and GPU frequency sampling during long runs, `--thermal`.
+//!
+//! A machine that throttles halfway through a run makes the compositor look like it
+//! regressed. Every second the frequencies, temperatures and throttle indicators are
+//! read from sysfs and the pacing of that second is attributed to it, so the report
+//! can separate the throttled seconds from the rest.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::plugin::Annotation;
+
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+const CPU: &str = "/sys/devices/system/cpu";
+const THERMAL: &str = "/sys/class/thermal";
+const DRM: &str = "/sys/class/drm";
+
+/// A cap on the maximum CPU frequency this much below the one at the start is
+/// thermal capping rather than noise.
+const CAP_THRESHOLD: f64 = 0.02;
+
+#[derive(Clone, Copy, Default)]
+struct Sample {
+    /// Mean current frequency over all CPUs.
+    cpu_mhz: Option<f64>,
+    /// Lowest `scaling_max_freq` relative to `cpuinfo_max_freq`.
+    cap: Option<f64>,
+    /// Core and package throttle events so far.
+    throttle_count: u64,
+    /// Summed state of the cooling devices that slow down the CPU or GPU.
+    cooling: u64,
+    /// Hottest thermal zone in °C.
+    temperature: Option<f64>,
+    gpu_mhz: Option<f64>,
+    /// i915 reports a throttle reason.
+    gpu_throttled: bool,
+}
+
+fn read(path: &Path) -> Option<String> {
+    Some(std::fs::read_to_string(path).ok()?.trim().to_string())
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    read(path)?.parse().ok()
+}
+
+/// Entries of `dir` whose name starts with `prefix` followed by a number.
+fn numbered(dir: &str, prefix: &str) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(prefix))
+                .is_some_and(|number| {
+                    !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+                })
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+impl Sample {
+    fn read() -> Self {
+        let cpus = numbered(CPU, "cpu");
+        let frequencies = cpus
+            .iter()
+            .filter_map(|cpu| read_u64(&cpu.join("cpufreq/scaling_cur_freq")))
+            .collect::<Vec<_>>();
+        let cap = cpus
+            .iter()
+            .filter_map(|cpu| {
+                let max = read_u64(&cpu.join("cpufreq/cpuinfo_max_freq"))?;
+                let scaling = read_u64(&cpu.join("cpufreq/scaling_max_freq"))?;
+                (max > 0).then(|| scaling as f64 / max as f64)
+            })
+            .reduce(f64::min);
+        let throttle_count = cpus
+            .iter()
+            .flat_map(|cpu| {
+                ["core_throttle_count", "package_throttle_count"]
+                    .map(|name| read_u64(&cpu.join("thermal_throttle").join(name)))
+            })
+            .flatten()
+            .sum();
+        // Fans are cooling devices too, but don't slow anything down.
+        let cooling = numbered(THERMAL, "cooling_device")
+            .iter()
+            .filter(|device| {
+                read(&device.join("type"))
+                    .is_some_and(|kind| !kind.to_ascii_lowercase().contains("fan"))
+            })
+            .filter_map(|device| read_u64(&device.join("cur_state")))
+            .sum();
+        let temperature = numbered(THERMAL, "thermal_zone")
+            .iter()
+            .filter_map(|zone| read(&zone.join("temp"))?.parse::<i64>().ok())
+            .max()
+            .map(|millis| millis as f64 / 1000.0);
+
+        let cards = numbered(DRM, "card");
+        let gpu_mhz = cards.iter().find_map(|card| {
+            read_u64(&card.join("gt_act_freq_mhz"))
+                .map(|mhz| mhz as f64)
+                .or_else(|| amdgpu_sclk(card))
+        });
+        let gpu_throttled = cards.iter().any(|card| {
+            read_u64(&card.join("gt/gt0/throttle_reason_status")).is_some_and(|status| status != 0)
+        });
+
+        Self {
+            cpu_mhz: (!frequencies.is_empty()).then(|| {
+                frequencies.iter().sum::<u64>() as f64 / frequencies.len() as f64 / 1000.0
+            }),
+            cap,
+            throttle_count,
+            cooling,
+            temperature,
+            gpu_mhz,
+            gpu_throttled,
+        }
+    }
+}
+
+/// The active level of `pp_dpm_sclk`, marked with a `*`, e.g. `1: 1800Mhz *`.
+fn amdgpu_sclk(card: &Path) -> Option<f64> {
+    read(&card.join("device/pp_dpm_sclk"))?
+        .lines()
+        .find(|line| line.ends_with('*'))?
+        .split_whitespace()
+        .nth(1)?
+        .trim_end_matches("Mhz")
+        .parse()
+        .ok()
+}
+
+struct Second {
+    sample: Sample,
+    throttled: bool,
+    intervals: Vec<Duration>,
+}
+
+pub struct Thermal {
+    baseline: Sample,
+    last: Sample,
+    intervals: Vec<Duration>,
+    seconds: Vec<Second>,
+    throttling: bool,
+    /// Kinds of reasons seen over the whole run.
+    reasons: Vec<&'static str>,
+    annotation: Option<Annotation>,
+}
+
+impl Thermal {
+    pub fn start() -> Self {
+        let baseline = Sample::read();
+        if baseline.cpu_mhz.is_none() && baseline.gpu_mhz.is_none() {
+            eprintln!(
+                "thermal: no readable CPU or GPU frequencies, only throttle indicators are sampled"
+            );
+        }
+        Self {
+            baseline,
+            last: baseline,
+            intervals: Vec::new(),
+            seconds: Vec::new(),
+            throttling: false,
+            reasons: Vec::new(),
+            annotation: None,
+        }
+    }
+
+    pub fn interval(&mut self, elapsed: Duration) {
+        self.intervals.push(elapsed);
+    }
+
+    /// Takes a sample closing the last second, called every [`SAMPLE_INTERVAL`].
+    pub fn sample(&mut self, prefix: &str) {
+        let sample = Sample::read();
+        // The kind of every reason and its details.
+        let mut reasons = Vec::new();
+        if sample.throttle_count > self.last.throttle_count {
+            reasons.push((
+                "CPU throttle events",
+                format!("({})", sample.throttle_count - self.last.throttle_count),
+            ));
+        }
+        if let (Some(cap), Some(baseline)) = (sample.cap, self.baseline.cap) {
+            if cap < baseline - CAP_THRESHOLD {
+                reasons.push(("CPU frequency capped", format!("to {:.0}%", cap * 100.0)));
+            }
+        }
+        if sample.cooling > self.baseline.cooling {
+            reasons.push((
+                "cooling devices active",
+                format!("state {}", sample.cooling),
+            ));
+        }
+        if sample.gpu_throttled {
+            reasons.push(("GPU throttle reason set", String::new()));
+        }
+        self.last = sample;
+
+        let throttled = !reasons.is_empty();
+        if throttled != self.throttling {
+            self.throttling = throttled;
+            let label = if throttled {
+                let reasons = reasons
+                    .iter()
+                    .map(|(kind, details)| format!("{} {}", kind, details).trim_end().to_string())
+                    .collect::<Vec<_>>();
+                format!("thermal: throttling, {}", reasons.join(", "))
+            } else {
+                "thermal: throttling ended".to_string()
+            };
+            eprintln!("{}{}", prefix, label);
+            self.annotation = Some(Annotation::Label(label));
+        }
+        for (kind, _) in reasons {
+            if !self.reasons.contains(&kind) {
+                self.reasons.push(kind);
+            }
+        }
+        self.seconds.push(Second {
+            sample,
+            throttled,
+            intervals: std::mem::take(&mut self.intervals),
+        });
+    }
+
+    /// A throttling change to log with the next frame.
+    pub fn take_annotation(&mut self) -> Option<Annotation> {
+        self.annotation.take()
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        if self.seconds.is_empty() {
+            println!("{}thermal: no samples taken", prefix);
+            return;
+        }
+        let range = |values: Vec<f64>, unit: &str| {
+            let min = values.iter().copied().reduce(f64::min)?;
+            let max = values.iter().copied().reduce(f64::max)?;
+            Some(format!("{:.0}-{:.0}{}", min, max, unit))
+        };
+        let samples = || self.seconds.iter().map(|second| second.sample);
+        println!(
+            "{}thermal: {} samples, CPU {}, GPU {}, hottest zone {}",
+            prefix,
+            self.seconds.len(),
+            range(samples().filter_map(|s| s.cpu_mhz).collect(), "MHz").unwrap_or("unknown".into()),
+            range(samples().filter_map(|s| s.gpu_mhz).collect(), "MHz").unwrap_or("unknown".into()),
+            range(samples().filter_map(|s| s.temperature).collect(), "°C")
+                .unwrap_or("unknown".into()),
+        );
+
+        let throttled = self
+            .seconds
+            .iter()
+            .filter(|second| second.throttled)
+            .count();
+        if throttled == 0 {
+            println!("{}  no throttling detected", prefix);
+            return;
+        }
+        println!(
+            "{}  WARNING: the machine throttled in {} of {} seconds ({}), pacing changes may not be the compositor's",
+            prefix,
+            throttled,
+            self.seconds.len(),
+            self.reasons.join(", ")
+        );
+        for (name, throttled) in [("unthrottled", false), ("throttled", true)] {
+            let intervals = self
+                .seconds
+                .iter()
+                .filter(|second| second.throttled == throttled)
+                .flat_map(|second| &second.intervals)
+                .map(|interval| interval.as_secs_f64() * 1000.0)
+                .collect::<Vec<_>>();
+            if intervals.is_empty() {
+                println!("{}  {:<11}: no frames", prefix, name);
+                continue;
+            }
+            let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+            let deviation = (intervals
+                .iter()
+                .map(|interval| (interval - mean).powi(2))
+                .sum::<f64>()
+                / intervals.len() as f64)
+                .sqrt();
+            println!(
+                "{}  {:<11}: {} frames, interval {:.3}ms ± {:.3}ms",
+                prefix,
+                name,
+                intervals.len(),
+                mean,
+                deviation
+            );
+        }
+        println!("{}  compare runs by the unthrottled numbers only", prefix);
+    }
+}
@@ -0,0 +1,366 @@
+//! Merged client and compositor timeline, `report timeline`.
+//!
+//! The client log is the output of a run, its `[+seconds]` stamps are relative to the
+//! `CLOCK_MONOTONIC` start and the offset to `CLOCK_REALTIME` it prints next to it.
+//! Compositor lines are stamped by `--format`, a strftime subset:
+//!
+//! ```text
+//! %Y %m %d      date, the run's date if missing
+//! %H %M %S      time of day, or with --clock monotonic hours and minutes since boot
+//! %f            fractional seconds, any number of digits
+//! %s            seconds in --clock, with an optional fraction
+//! %%            a literal %
+//! ```
+//!
+//! e.g. `[%H:%M:%S.%f]` for weston or `%Y-%m-%d %H:%M:%S.%f` for KWin's journal
+//! export. Lines that don't start with the format continue the previous line.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::sandbox;
+
+/// Options of `report timeline`.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Timeline {
+    /// Output of the run, logged with --log-every
+    log: PathBuf,
+
+    /// Log of the compositor during the run
+    #[arg(long, value_name = "PATH")]
+    compositor_log: PathBuf,
+
+    /// Timestamp at the start of every compositor log line
+    #[arg(long, default_value = "[%H:%M:%S.%f]")]
+    format: Format,
+
+    /// Clock the compositor timestamps are in
+    #[arg(long, value_enum, default_value_t = Clock::Local)]
+    clock: Clock,
+
+    /// Compositor lines kept before and after the run, in ms
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    margin: u64,
+
+    /// Write the timeline to this file instead of stdout
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Clock {
+    /// `CLOCK_MONOTONIC`, the presentation clock
+    Monotonic,
+    /// `CLOCK_REALTIME` in UTC
+    Realtime,
+    /// `CLOCK_REALTIME` in the local time zone
+    Local,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Fraction,
+    Seconds,
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Literal(char),
+    Field(Field),
+}
+
+#[derive(Clone, Debug)]
+struct Format(Vec<Token>);
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Vec::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                tokens.push(Token::Literal(c));
+                continue;
+            }
+            let field = match chars.next() {
+                Some('Y') => Field::Year,
+                Some('m') => Field::Month,
+                Some('d') => Field::Day,
+                Some('H') => Field::Hour,
+                Some('M') => Field::Minute,
+                Some('S') => Field::Second,
+                Some('f') => Field::Fraction,
+                Some('s') => Field::Seconds,
+                Some('%') => {
+                    tokens.push(Token::Literal('%'));
+                    continue;
+                }
+                Some(c) => return Err(format!("unsupported field `%{}`", c)),
+                None => return Err("trailing `%`".to_string()),
+            };
+            tokens.push(Token::Field(field));
+        }
+        if !tokens.iter().any(|token| {
+            matches!(
+                token,
+                Token::Field(Field::Second) | Token::Field(Field::Seconds)
+            )
+        }) {
+            return Err("the format needs `%S` or `%s`".to_string());
+        }
+        Ok(Format(tokens))
+    }
+}
+
+/// Timestamp fields of one line.
+struct Stamp {
+    date: Option<(i64, u32, u32)>,
+    seconds: f64,
+}
+
+impl Format {
+    fn has_date(&self) -> bool {
+        self.0
+            .iter()
+            .any(|token| matches!(token, Token::Field(Field::Year)))
+    }
+
+    /// The timestamp at the start of `line` and the rest of the line.
+    fn parse<'a>(&self, line: &'a str) -> Option<(Stamp, &'a str)> {
+        let (mut year, mut month, mut day) = (None, None, None);
+        let mut seconds = 0.0;
+        let mut rest = line;
+        for token in &self.0 {
+            match token {
+                Token::Literal(c) => rest = rest.strip_prefix(*c)?,
+                Token::Field(field) => {
+                    let max = match field {
+                        Field::Year => 4,
+                        Field::Fraction | Field::Seconds => usize::MAX,
+                        _ => 2,
+                    };
+                    // Padding, e.g. the kernel style `[   12.345]`.
+                    let trimmed = if *field == Field::Seconds {
+                        rest.trim_start()
+                    } else {
+                        rest
+                    };
+                    let digits = trimmed
+                        .char_indices()
+                        .take_while(|&(index, c)| index < max && c.is_ascii_digit())
+                        .count();
+                    if digits == 0 {
+                        return None;
+                    }
+                    let (number, after) = trimmed.split_at(digits);
+                    rest = after;
+                    let value = number.parse::<u64>().ok()?;
+                    match field {
+                        Field::Year => year = Some(value as i64),
+                        Field::Month => month = Some(value as u32),
+                        Field::Day => day = Some(value as u32),
+                        Field::Hour => seconds += value as f64 * 3600.0,
+                        Field::Minute => seconds += value as f64 * 60.0,
+                        Field::Second => seconds += value as f64,
+                        Field::Fraction => seconds += fraction(number),
+                        Field::Seconds => {
+                            seconds += value as f64;
+                            if let Some(after) = rest.strip_prefix('.') {
+                                let digits = after.chars().take_while(char::is_ascii_digit).count();
+                                seconds += fraction(&after[..digits]);
+                                rest = &after[digits..];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let date = match (year, month, day) {
+            (Some(year), Some(month), Some(day)) => Some((year, month, day)),
+            _ => None,
+        };
+        Some((Stamp { date, seconds }, rest))
+    }
+}
+
+fn fraction(digits: &str) -> f64 {
+    format!("0.{}", digits).parse().unwrap_or(0.0)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Offset of the local time zone from UTC at `time`, in seconds since the epoch.
+fn utc_offset(time: f64) -> f64 {
+    let time = time as libc::time_t;
+    // SAFETY: tm is plain data and only read after localtime_r filled it.
+    unsafe {
+        let mut tm = std::mem::zeroed::<libc::tm>();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return 0.0;
+        }
+        tm.tm_gmtoff as f64
+    }
+}
+
+/// Clocks of the run, from the lines it logs at the start.
+struct Run {
+    /// `CLOCK_MONOTONIC` the client stamps are relative to.
+    start: f64,
+    /// `CLOCK_REALTIME` minus `CLOCK_MONOTONIC`.
+    offset: Option<f64>,
+}
+
+impl Run {
+    /// Seconds since the run start of a compositor timestamp.
+    fn relative(&self, stamp: &Stamp, clock: Clock) -> Option<f64> {
+        if clock == Clock::Monotonic {
+            return Some(stamp.seconds - self.start);
+        }
+        let offset = self.offset?;
+        let started = self.start + offset;
+        let zone = if clock == Clock::Local {
+            utc_offset(started)
+        } else {
+            0.0
+        };
+        let time = match stamp.date {
+            Some((year, month, day)) => {
+                days_from_civil(year, month, day) as f64 * 86400.0 + stamp.seconds - zone
+            }
+            // Only a time of day, on the day closest to the run.
+            None => {
+                let day = ((started + zone) / 86400.0).floor();
+                [day - 1.0, day, day + 1.0]
+                    .into_iter()
+                    .map(|day| day * 86400.0 + stamp.seconds - zone)
+                    .min_by(|a, b| (a - started).abs().total_cmp(&(b - started).abs()))?
+            }
+        };
+        Some(time - offset - self.start)
+    }
+}
+
+/// Value after `marker` in the first line containing it.
+fn header(log: &str, marker: &str) -> Option<f64> {
+    log.lines()
+        .find_map(|line| line.split_once(marker))
+        .and_then(|(_, value)| value.trim().parse().ok())
+}
+
+/// Client stamp `[+seconds]` in `line` and the line without it.
+fn client_stamp(line: &str) -> Option<(f64, String)> {
+    let start = line.find("[+")?;
+    let end = start + line[start..].find(']')?;
+    let time = line[start + 2..end].parse().ok()?;
+    Some((
+        time,
+        format!("{}{}", &line[..start], line[end + 1..].trim_start()),
+    ))
+}
+
+fn read(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("failed to read {}: {}", path.display(), err);
+        std::process::exit(1)
+    })
+}
+
+/// Merges the compositor log into the client log on the run's timeline.
+pub fn run(timeline: &Timeline, output_dir: Option<&Path>) {
+    let log = read(&timeline.log);
+    let run = Run {
+        start: header(&log, "relative to CLOCK_MONOTONIC").unwrap_or_else(|| {
+            eprintln!(
+                "{} has no log timestamps, run with --log-every",
+                timeline.log.display()
+            );
+            std::process::exit(1)
+        }),
+        offset: header(&log, "CLOCK_REALTIME - CLOCK_MONOTONIC ="),
+    };
+    if run.offset.is_none() && timeline.clock != Clock::Monotonic {
+        eprintln!(
+            "{} has no presentation clock offset, only --clock monotonic can be aligned",
+            timeline.log.display()
+        );
+        std::process::exit(1);
+    }
+
+    // Unstamped lines, e.g. the reports, stay behind the line before them.
+    let mut entries = Vec::new();
+    let mut time = 0.0;
+    for line in log.lines() {
+        let text = match client_stamp(line) {
+            Some((stamp, text)) => {
+                time = stamp;
+                text
+            }
+            None => line.to_string(),
+        };
+        entries.push((time, "client", text));
+    }
+    let end = time;
+    let client = entries.len();
+
+    let margin = timeline.margin as f64 / 1000.0;
+    let compositor_log = read(&timeline.compositor_log);
+    let mut time = None;
+    for line in compositor_log.lines() {
+        if let Some((stamp, _)) = timeline.format.parse(line) {
+            time = run.relative(&stamp, timeline.clock);
+        }
+        if let Some(time) = time.filter(|time| (-margin..=end + margin).contains(time)) {
+            entries.push((time, "compositor", line.to_string()));
+        }
+    }
+    let compositor = entries.len() - client;
+    if compositor == 0 {
+        eprintln!(
+            "timeline: no compositor line falls into the run, check --format{} and --clock",
+            if timeline.format.has_date() {
+                ""
+            } else {
+                ", it has no date,"
+            }
+        );
+    }
+
+    entries.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut merged = String::new();
+    for (time, source, text) in &entries {
+        merged.push_str(&format!("[{:+.6}] {:<10} {}\n", time, source, text));
+    }
+    match timeline.output.as_deref() {
+        Some(path) => {
+            let path = sandbox::resolve(output_dir, path);
+            if let Err(err) = std::fs::write(&path, merged) {
+                eprintln!("failed to write {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+            println!(
+                "timeline: {} client and {} compositor lines written to {}",
+                client,
+                compositor,
+                path.display()
+            );
+        }
+        None => print!("{}", merged),
+    }
+}
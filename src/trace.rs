@@ -0,0 +1,447 @@
+//! Event traces of a run, `--record-trace`, and their replay, `replay`.
+//!
+//! The trace holds the command line and every event the frame planning depends on,
+//! with its `CLOCK_MONOTONIC` time in nanoseconds, one per line:
+//!
+//! ```text
+//! # fifo_test trace 1
+//! args<TAB>fifo_test<TAB>--scenario<TAB>continuous
+//! 1234000000 configure width=256 height=256
+//! 1234100000 frame_done
+//! 1234200000 presented frame=1 time=1250000000 committed=1234050000 refresh=16666666 seq=7 flags=1
+//! 1234300000 discarded frame=2
+//! 1234350000 released frame=1
+//! 1234400000 plan frame=3 interval=16666000 delay=0 barrier=1 frame_callback=0 present_at=- commit=1 feedback=1
+//! 1234500000 finished frame=4
+//! ```
+//!
+//! The arguments are tab separated. `plan` records the plan of a drawn frame, the
+//! interval since the previous draw and whether it was committed with feedback.
+//! `released` is the release of the buffer committed with a frame.
+//!
+//! Replaying feeds the events in order to a fresh [`Planner`] built from the
+//! recorded command line, without any compositor, and compares every plan with the
+//! recorded one. Scenarios without randomness plan the same given the same events, so
+//! a trace recorded once serves as a regression test of the planning logic. Only
+//! presentation targets derived from the current time, see [`crate::plugin::now`],
+//! differ between recording and replay.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use clap::Parser;
+use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client::wp_presentation_feedback::Kind;
+
+use crate::clock::Simulated;
+use crate::planner::Planner;
+use crate::plugin::{FramePlan, Registry, Stats};
+use crate::presentation::Presented;
+use crate::Args;
+
+const HEADER: &str = "# fifo_test trace 1";
+/// Divergences printed in full, the rest is only counted.
+const MAX_DIVERGENCES: usize = 20;
+
+/// Options of the `replay` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Replay {
+    /// Trace written with --record-trace
+    trace: PathBuf,
+}
+
+/// Writes the trace of a run, `--record-trace`.
+pub struct Recorder {
+    file: BufWriter<File>,
+    failed: bool,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{}", HEADER)?;
+        let args = std::env::args().collect::<Vec<_>>();
+        writeln!(file, "args\t{}", args.join("\t"))?;
+        Ok(Self {
+            file,
+            failed: false,
+        })
+    }
+
+    fn event(&mut self, time: Duration, event: std::fmt::Arguments) {
+        if let Err(err) = writeln!(self.file, "{} {}", time.as_nanos(), event) {
+            if !self.failed {
+                eprintln!("trace: failed to write: {}", err);
+            }
+            self.failed = true;
+        }
+    }
+
+    pub fn configure(&mut self, time: Duration, size: Option<(u32, u32)>) {
+        match size {
+            Some((width, height)) => self.event(
+                time,
+                format_args!("configure width={} height={}", width, height),
+            ),
+            None => self.event(time, format_args!("configure")),
+        }
+    }
+
+    pub fn frame_done(&mut self, time: Duration) {
+        self.event(time, format_args!("frame_done"));
+    }
+
+    pub fn feedback(&mut self, time: Duration, frame: u64, presented: Option<&Presented>) {
+        match presented {
+            Some(presented) => self.event(
+                time,
+                format_args!(
+                    "presented frame={} time={} committed={} refresh={} seq={} flags={}",
+                    frame,
+                    presented.time.as_nanos(),
+                    presented.committed.as_nanos(),
+                    presented.refresh.as_nanos(),
+                    presented.seq,
+                    presented.flags.bits()
+                ),
+            ),
+            None => self.event(time, format_args!("discarded frame={}", frame)),
+        }
+    }
+
+    pub fn released(&mut self, time: Duration, frame: u64) {
+        self.event(time, format_args!("released frame={}", frame));
+    }
+
+    pub fn plan(&mut self, time: Duration, frame: u64, drawn: &Drawn) {
+        let plan = &drawn.plan;
+        self.event(
+            time,
+            format_args!(
+                "plan frame={} interval={} delay={} barrier={} frame_callback={} present_at={} commit={} feedback={}",
+                frame,
+                drawn
+                    .interval
+                    .map_or("-".to_string(), |interval| interval.as_nanos().to_string()),
+                plan.delay.as_nanos(),
+                u8::from(plan.barrier),
+                u8::from(plan.frame_callback),
+                plan.present_at
+                    .map_or("-".to_string(), |at| at.as_nanos().to_string()),
+                u8::from(drawn.commit),
+                u8::from(drawn.feedback)
+            ),
+        );
+    }
+
+    pub fn finished(&mut self, time: Duration, frame: u64) {
+        self.event(time, format_args!("finished frame={}", frame));
+    }
+}
+
+/// What happened to a planned frame.
+pub struct Drawn {
+    pub plan: FramePlan,
+    /// Since the previous draw.
+    pub interval: Option<Duration>,
+    pub commit: bool,
+    pub feedback: bool,
+}
+
+/// One parsed line of a trace.
+struct Line<'a> {
+    time: Duration,
+    event: &'a str,
+    fields: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Line<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let time = Duration::from_nanos(words.next()?.parse().ok()?);
+        let event = words.next()?;
+        let fields = words
+            .map(|word| word.split_once('='))
+            .collect::<Option<_>>()?;
+        Some(Self {
+            time,
+            event,
+            fields,
+        })
+    }
+
+    fn field(&self, name: &str) -> Result<&'a str, String> {
+        self.fields
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| format!("`{}` without `{}`", self.event, name))
+    }
+
+    fn number(&self, name: &str) -> Result<u64, String> {
+        let value = self.field(name)?;
+        value
+            .parse()
+            .map_err(|_| format!("invalid `{}` value `{}`", name, value))
+    }
+
+    fn nanos(&self, name: &str) -> Result<Option<Duration>, String> {
+        match self.field(name)? {
+            "-" => Ok(None),
+            _ => self
+                .number(name)
+                .map(|nanos| Some(Duration::from_nanos(nanos))),
+        }
+    }
+
+    fn flag(&self, name: &str) -> Result<bool, String> {
+        self.number(name).map(|value| value != 0)
+    }
+}
+
+/// Outcome of a replay.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub events: u64,
+    pub plans: u64,
+    pub divergences: Vec<String>,
+}
+
+/// Replays a trace against a fresh planner, with the scenarios of `registry`.
+pub fn replay(trace: &str, registry: &Registry) -> Result<Summary, String> {
+    let mut lines = trace.lines().enumerate();
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err("not a fifo_test trace".to_string());
+    }
+    let argv = lines
+        .next()
+        .and_then(|(_, line)| line.strip_prefix("args\t"))
+        .ok_or("trace without command line")?
+        .split('\t')
+        .collect::<Vec<_>>();
+    let args = Args::try_parse_from(&argv).map_err(|err| format!("recorded arguments: {}", err))?;
+    let scenario = registry.scenario(&args.scenario).ok_or_else(|| {
+        format!(
+            "scenario `{}` isn't registered, load its --plugin or --script",
+            args.scenario
+        )
+    })?;
+    let clock = Simulated::default();
+    let mut planner = Planner::new(&args, scenario, None, Rc::new(clock.clone()));
+    let mut stats = Stats::default();
+    let mut summary = Summary::default();
+
+    for (index, text) in lines {
+        let diverged = |message: String| format!("line {}: {}", index + 1, message);
+        let line = Line::parse(text).ok_or_else(|| diverged("unreadable event".to_string()))?;
+        summary.events += 1;
+        clock.advance_to(line.time);
+        match line.event {
+            "configure" | "frame_done" => {}
+            "presented" => {
+                let presented = Presented {
+                    time: Duration::from_nanos(line.number("time")?),
+                    committed: Duration::from_nanos(line.number("committed")?),
+                    refresh: Duration::from_nanos(line.number("refresh")?),
+                    seq: line.number("seq")?,
+                    flags: Kind::from_bits_truncate(line.number("flags")? as u32),
+                    output: None,
+                };
+                stats.presented += 1;
+                stats.latency = Some(presented.time.saturating_sub(presented.committed));
+                planner.feedback(line.number("frame")?, Some(&presented), "");
+            }
+            "discarded" => {
+                stats.discarded += 1;
+                planner.feedback(line.number("frame")?, None, "");
+            }
+            "released" => planner.released(line.number("frame")?, ""),
+            "plan" => {
+                summary.plans += 1;
+                let frame = line.number("frame")?;
+                let recorded = FramePlan {
+                    barrier: line.flag("barrier")?,
+                    frame_callback: line.flag("frame_callback")?,
+                    delay: Duration::from_nanos(line.number("delay")?),
+                    present_at: line.nanos("present_at")?,
+                    ..FramePlan::default()
+                };
+                match planner.plan(frame, &stats, "") {
+                    None => summary.divergences.push(diverged(format!(
+                        "frame {}: the scenario finished, recorded {}",
+                        frame,
+                        describe(&recorded)
+                    ))),
+                    Some(plan) => {
+                        if describe(&plan) != describe(&recorded) {
+                            summary.divergences.push(diverged(format!(
+                                "frame {}: planned {}, recorded {}",
+                                frame,
+                                describe(&plan),
+                                describe(&recorded)
+                            )));
+                        }
+                    }
+                }
+                // The rest of the draw as recorded.
+                stats.interval = line.nanos("interval")?;
+                if line.flag("commit")? {
+                    stats.frames += 1;
+                    planner.committed(frame);
+                }
+            }
+            "finished" => {
+                let frame = line.number("frame")?;
+                if let Some(plan) = planner.plan(frame, &stats, "") {
+                    summary.divergences.push(diverged(format!(
+                        "frame {}: planned {}, the recorded scenario finished",
+                        frame,
+                        describe(&plan)
+                    )));
+                }
+            }
+            event => return Err(diverged(format!("unknown event `{}`", event))),
+        }
+    }
+    Ok(summary)
+}
+
+/// The compared part of a plan, the damage isn't part of the trace.
+fn describe(plan: &FramePlan) -> String {
+    format!(
+        "delay {:?}, barrier {}, frame callback {}, present at {:?}",
+        plan.delay, plan.barrier, plan.frame_callback, plan.present_at
+    )
+}
+
+/// Replays a trace and reports the divergences, `replay`.
+pub fn run(options: &Replay, registry: &Registry) {
+    let trace = std::fs::read_to_string(&options.trace).unwrap_or_else(|err| {
+        eprintln!("failed to read {}: {}", options.trace.display(), err);
+        std::process::exit(1)
+    });
+    let summary = replay(&trace, registry).unwrap_or_else(|err| {
+        eprintln!("replay: {}", err);
+        std::process::exit(1)
+    });
+    for divergence in summary.divergences.iter().take(MAX_DIVERGENCES) {
+        println!("replay: {}", divergence);
+    }
+    if summary.divergences.len() > MAX_DIVERGENCES {
+        println!(
+            "replay: ... {} more divergences",
+            summary.divergences.len() - MAX_DIVERGENCES
+        );
+    }
+    println!(
+        "replay: {} events, {} plans, {} diverged",
+        summary.events,
+        summary.plans,
+        summary.divergences.len()
+    );
+    if !summary.divergences.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::plugin::Scenario;
+
+    /// Commits with a frame callback and without a barrier for two frames.
+    struct Short;
+
+    impl Scenario for Short {
+        fn plan(&mut self, frame: u64) -> Option<FramePlan> {
+            (frame <= 2).then_some(FramePlan {
+                barrier: false,
+                frame_callback: true,
+                ..FramePlan::default()
+            })
+        }
+    }
+
+    fn registry() -> Registry {
+        let mut registry = Registry::with_builtins();
+        registry.register_scenario("short", "two frames", || Box::new(Short));
+        registry
+    }
+
+    /// A trace of `scenario` with `events`, at 1ms apart.
+    fn trace(scenario: &str, events: &[&str]) -> String {
+        let mut trace = format!("{}\nargs\tfifo_test\t--scenario\t{}\n", HEADER, scenario);
+        for (index, event) in events.iter().enumerate() {
+            trace.push_str(&format!("{} {}\n", (index as u64 + 1) * 1_000_000, event));
+        }
+        trace
+    }
+
+    const CONTINUOUS: [&str; 6] = [
+        "configure width=256 height=256",
+        "plan frame=1 interval=- delay=0 barrier=1 frame_callback=0 present_at=- commit=1 feedback=1",
+        "plan frame=2 interval=1000000 delay=0 barrier=1 frame_callback=0 present_at=- commit=1 feedback=1",
+        "presented frame=1 time=4000000 committed=2000000 refresh=16666666 seq=1 flags=1",
+        "discarded frame=2",
+        "plan frame=3 interval=4000000 delay=0 barrier=1 frame_callback=0 present_at=- commit=1 feedback=1",
+    ];
+
+    #[test]
+    fn faithful_trace_replays_without_divergence() {
+        let summary = replay(&trace("continuous", &CONTINUOUS), &registry()).unwrap();
+        assert_eq!(summary.events, 6);
+        assert_eq!(summary.plans, 3);
+        assert!(summary.divergences.is_empty(), "{:?}", summary.divergences);
+    }
+
+    #[test]
+    fn altered_plan_diverges() {
+        let mut events = CONTINUOUS;
+        events[2] = "plan frame=2 interval=1000000 delay=0 barrier=0 frame_callback=0 present_at=- commit=1 feedback=1";
+        let summary = replay(&trace("continuous", &events), &registry()).unwrap();
+        assert_eq!(summary.divergences.len(), 1);
+        assert!(
+            summary.divergences[0].starts_with("line 5: frame 2: planned"),
+            "{}",
+            summary.divergences[0]
+        );
+    }
+
+    #[test]
+    fn finishing_at_another_frame_diverges() {
+        let plan = "delay=0 barrier=0 frame_callback=1 present_at=- commit=1 feedback=0";
+        let faithful = [
+            format!("plan frame=1 interval=- {}", plan),
+            "frame_done".to_string(),
+            format!("plan frame=2 interval=1000000 {}", plan),
+            "finished frame=3".to_string(),
+        ];
+        let events = faithful.iter().map(String::as_str).collect::<Vec<_>>();
+        let summary = replay(&trace("short", &events), &registry()).unwrap();
+        assert!(summary.divergences.is_empty(), "{:?}", summary.divergences);
+
+        let summary = replay(&trace("short", &["finished frame=2"]), &registry()).unwrap();
+        assert_eq!(summary.divergences.len(), 1);
+        assert!(summary.divergences[0].contains("the recorded scenario finished"));
+
+        let events = [
+            events[0],
+            events[2],
+            &format!("plan frame=3 interval=1000000 {}", plan),
+        ];
+        let summary = replay(&trace("short", &events), &registry()).unwrap();
+        assert_eq!(summary.divergences.len(), 1);
+        assert!(summary.divergences[0].contains("the scenario finished, recorded"));
+    }
+
+    #[test]
+    fn rejects_unknown_events() {
+        let err = replay(&trace("continuous", &["frame_done soon"]), &registry()).unwrap_err();
+        assert_eq!(err, "line 3: unreadable event");
+        let err = replay(&trace("continuous", &["resized width=1"]), &registry()).unwrap_err();
+        assert_eq!(err, "line 3: unknown event `resized`");
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use crate::clock;
+
+/// Output toggled at commit time, for photodiode latency rigs.
+pub enum Trigger {
+    /// RTS and DTR of a serial port are raised for white frames and lowered for black.
+    Serial(File),
+    /// A trigger line is printed to stdout instead.
+    Stdout,
+}
+
+impl Trigger {
+    /// Opens the serial port at `path`, `-` selects printing to stdout.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if path.as_os_str() == "-" {
+            return Ok(Trigger::Stdout);
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(path)?;
+        Ok(Trigger::Serial(file))
+    }
+
+    pub fn set(&mut self, frame: u64, high: bool) -> io::Result<()> {
+        match self {
+            Trigger::Serial(file) => {
+                let lines: libc::c_int = libc::TIOCM_RTS | libc::TIOCM_DTR;
+                let request = if high { libc::TIOCMBIS } else { libc::TIOCMBIC };
+                // SAFETY: the fd is open for the lifetime of the file, and the
+                // request takes a pointer to an int.
+                let ret = unsafe { libc::ioctl(file.as_raw_fd(), request, &lines) };
+                if ret == 0 {
+                    Ok(())
+                } else {
+                    Err(io::Error::last_os_error())
+                }
+            }
+            Trigger::Stdout => {
+                println!(
+                    "TRIGGER frame={} level={} time={}",
+                    frame,
+                    if high { "high" } else { "low" },
+                    clock::monotonic().as_nanos()
+                );
+                Ok(())
+            }
+        }
+    }
+}
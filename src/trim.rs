@@ -0,0 +1,151 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::protocol_log::Message;
+use crate::sandbox;
+
+/// Interfaces whose messages matter for pacing, everything else is dropped.
+const INTERFACES: &[&str] = &[
+    "wl_surface",
+    "wl_buffer",
+    "wl_callback",
+    "xdg_surface",
+    "xdg_toplevel",
+    "wp_fifo_manager_v1",
+    "wp_fifo_v1",
+    "wp_commit_timing_manager_v1",
+    "wp_commit_timer_v1",
+    "wp_presentation",
+    "wp_presentation_feedback",
+    "wp_viewport",
+];
+
+/// Options of `report trim`.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Trim {
+    /// Protocol log from --capture-protocol or a bug report
+    input: PathBuf,
+
+    /// File to write the trimmed log to
+    output: PathBuf,
+
+    /// Time kept before and after every anomaly, in ms
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    context: u64,
+
+    /// Time between two commits of a surface that counts as an anomaly, in ms
+    #[arg(long, value_name = "MS", default_value_t = 100)]
+    gap: u64,
+}
+
+/// Whether the message is about one of the `INTERFACES`.
+fn relevant(message: &Message) -> bool {
+    match message.interface {
+        "wl_display" => message.name == "error",
+        "wl_registry" => INTERFACES
+            .iter()
+            .any(|interface| message.args.contains(&format!("\"{}\"", interface))),
+        interface => INTERFACES.contains(&interface),
+    }
+}
+
+/// The line with string arguments replaced, they may carry titles or names.
+fn anonymize(line: &str, message: &Message) -> String {
+    if message.interface == "wl_registry" {
+        return line.to_string();
+    }
+    let mut anonymized = String::with_capacity(line.len());
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' if quoted => {
+                anonymized.push_str("…\"");
+                quoted = false;
+            }
+            '"' => {
+                anonymized.push(c);
+                quoted = true;
+            }
+            _ if quoted => {}
+            c => anonymized.push(c),
+        }
+    }
+    anonymized
+}
+
+/// Strips a protocol log to the fifo related objects and the time around anomalies,
+/// `report trim`.
+pub fn run(trim: &Trim, output_dir: Option<&Path>) {
+    let output = sandbox::resolve(output_dir, &trim.output);
+    let log = std::fs::read_to_string(&trim.input).unwrap_or_else(|err| {
+        eprintln!("failed to read {}: {}", trim.input.display(), err);
+        std::process::exit(1)
+    });
+    let messages = log
+        .lines()
+        .filter_map(|line| Some((line, Message::parse(line)?)))
+        .filter(|(_, message)| relevant(message))
+        .collect::<Vec<_>>();
+    let total = log.lines().count();
+
+    let mut anomalies = Vec::new();
+    let mut last_commit = std::collections::HashMap::new();
+    for (line, message) in &messages {
+        let anomaly = match (message.interface, message.name) {
+            ("wl_display", "error") => Some("protocol error"),
+            ("wp_presentation_feedback", "discarded") => Some("discarded frame"),
+            ("wl_surface", "commit") => last_commit
+                .insert(message.id, message.time)
+                .filter(|last| message.time - last > trim.gap as f64)
+                .map(|_| "commit gap"),
+            _ => None,
+        };
+        if let Some(anomaly) = anomaly {
+            anomalies.push((message.time, anomaly, *line));
+        }
+    }
+
+    // The globals are kept for their versions, the rest around the anomalies, or at
+    // the end of the run if nothing stands out.
+    let context = trim.context as f64;
+    let end = messages.last().map_or(0.0, |(_, message)| message.time);
+    let kept = messages
+        .iter()
+        .filter(|(_, message)| {
+            message.interface == "wl_registry"
+                || if anomalies.is_empty() {
+                    message.time >= end - context
+                } else {
+                    anomalies
+                        .iter()
+                        .any(|(time, _, _)| (message.time - time).abs() <= context)
+                }
+        })
+        .collect::<Vec<_>>();
+
+    let mut trimmed = String::new();
+    let mut objects = BTreeSet::new();
+    for (line, message) in &kept {
+        objects.insert((message.interface, message.id));
+        trimmed.push_str(&anonymize(line, message));
+        trimmed.push('\n');
+    }
+    if let Err(err) = std::fs::write(&output, trimmed) {
+        eprintln!("failed to write {}: {}", output.display(), err);
+        std::process::exit(1);
+    }
+
+    println!(
+        "trim: kept {} of {} lines, {} objects, written to {}",
+        kept.len(),
+        total,
+        objects.len(),
+        output.display()
+    );
+    if anomalies.is_empty() {
+        println!("trim: no anomalies, kept the last {}ms", trim.context);
+    }
+    for (_, anomaly, line) in &anomalies {
+        println!("trim: {}: {}", anomaly, line);
+    }
+}
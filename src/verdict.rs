@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::presentation::Presented;
+
+/// A fifo property the verdict is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Commits with a barrier wait in a queue instead of replacing each other.
+    Queueing,
+    /// At most one barrier is cleared per refresh.
+    Latching,
+    /// Unmapping the surface clears the queue.
+    Unmap,
+}
+
+impl Check {
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Queueing => "queueing",
+            Check::Latching => "latching",
+            Check::Unmap => "unmap",
+        }
+    }
+
+    fn violation(self) -> &'static str {
+        match self {
+            Check::Queueing => "commits are not queued",
+            Check::Latching => "barriers don't hold frames for a refresh",
+            Check::Unmap => "the queue is not cleared on unmap",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Fail,
+    /// The run didn't produce the evidence, e.g. commits never outpaced the refresh.
+    Untested,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Fail => "fail",
+            Status::Untested => "untested",
+        }
+    }
+}
+
+struct Finding {
+    check: Check,
+    status: Status,
+    sentence: String,
+}
+
+impl Finding {
+    fn new(check: Check, status: Status, sentence: String) -> Self {
+        Self {
+            check,
+            status,
+            sentence,
+        }
+    }
+}
+
+struct Committed {
+    barrier: bool,
+    /// `CLOCK_MONOTONIC` commit time.
+    time: Duration,
+    /// `Some(true)` if presented, `Some(false)` if discarded.
+    fate: Option<bool>,
+}
+
+#[derive(Clone, Copy)]
+struct Last {
+    seq: u64,
+    time: Duration,
+    refresh: Duration,
+}
+
+/// Evidence for the fifo semantics the compositor implements, summed up in a
+/// paragraph for bug reports, `--verdict`.
+#[derive(Default)]
+pub struct Verdict {
+    frames: BTreeMap<u64, Committed>,
+    last: Option<Last>,
+    refresh_sum: Duration,
+    refresh_count: u32,
+    /// Barrier frames presented in the same refresh as the frame before them.
+    same_refresh: u64,
+    /// Barrier frames discarded while a newer frame was already committed.
+    replaced: u64,
+    /// Frames without feedback when the surface was unmapped.
+    pending_at_unmap: Option<Vec<u64>>,
+}
+
+impl Verdict {
+    pub fn committed(&mut self, frame: u64, barrier: bool, time: Duration) {
+        self.frames.insert(
+            frame,
+            Committed {
+                barrier,
+                time,
+                fate: None,
+            },
+        );
+    }
+
+    pub fn presented(&mut self, frame: u64, presented: Option<&Presented>) {
+        let newest = self.frames.keys().next_back().copied().unwrap_or(0);
+        let Some(committed) = self.frames.get_mut(&frame) else {
+            return;
+        };
+        committed.fate = Some(presented.is_some());
+        let Some(presented) = presented else {
+            if committed.barrier && frame < newest && self.pending_at_unmap.is_none() {
+                self.replaced += 1;
+            }
+            return;
+        };
+
+        if !presented.refresh.is_zero() {
+            self.refresh_sum += presented.refresh;
+            self.refresh_count += 1;
+        }
+        let refresh = match (presented.refresh.is_zero(), self.last) {
+            (true, Some(last)) => last.refresh,
+            _ => presented.refresh,
+        };
+        if let (Some(last), true) = (self.last, committed.barrier) {
+            let same = if last.seq != 0 && presented.seq != 0 {
+                presented.seq == last.seq
+            } else {
+                presented.time.saturating_sub(last.time) < refresh / 2
+            };
+            if same {
+                self.same_refresh += 1;
+            }
+        }
+        self.last = Some(Last {
+            seq: presented.seq,
+            time: presented.time,
+            refresh,
+        });
+    }
+
+    /// Marks the surface as unmapped, the frames still queued should be discarded.
+    pub fn unmapped(&mut self) {
+        self.pending_at_unmap = Some(
+            self.frames
+                .iter()
+                .filter(|(_, committed)| committed.fate.is_none())
+                .map(|(frame, _)| *frame)
+                .collect(),
+        );
+    }
+
+    /// The status of every check, with a sentence describing the evidence.
+    fn findings(&self) -> Vec<Finding> {
+        let barrier_frames = self.frames.values().filter(|committed| committed.barrier);
+        let barriers = barrier_frames.clone().count();
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        let mut findings = Vec::new();
+        let refresh = (self.refresh_count > 0).then(|| self.refresh_sum / self.refresh_count);
+        let commit_interval = {
+            let times = barrier_frames
+                .map(|committed| committed.time)
+                .collect::<Vec<_>>();
+            (times.len() > 1)
+                .then(|| (times[times.len() - 1] - times[0]) / (times.len() as u32 - 1))
+        };
+
+        findings.push(match (commit_interval, refresh) {
+            (Some(interval), Some(refresh)) if interval < refresh => {
+                if self.replaced == 0 {
+                    Finding::new(Check::Queueing, Status::Pass, format!(
+                        "Barrier commits arrived every {:.2}ms against a {:.2}ms refresh and none of them was replaced, so the compositor queues commits behind barriers.",
+                        ms(interval),
+                        ms(refresh)
+                    ))
+                } else {
+                    Finding::new(Check::Queueing, Status::Fail, format!(
+                        "Barrier commits arrived every {:.2}ms against a {:.2}ms refresh and {} of {} were replaced by newer commits instead of queued.",
+                        ms(interval),
+                        ms(refresh),
+                        self.replaced,
+                        barriers
+                    ))
+                }
+            }
+            (Some(interval), Some(refresh)) => Finding::new(Check::Queueing, Status::Untested, format!(
+                "Barrier commits arrived every {:.2}ms, not faster than the {:.2}ms refresh, so queueing was not exercised.",
+                ms(interval),
+                ms(refresh)
+            )),
+            _ => Finding::new(
+                Check::Queueing,
+                Status::Untested,
+                "Without barrier commits and presentation feedback queueing could not be checked."
+                    .to_string(),
+            ),
+        });
+
+        findings.push(if self.last.is_none() {
+            Finding::new(
+                Check::Latching,
+                Status::Untested,
+                "No frame was presented, latching could not be checked.".to_string(),
+            )
+        } else if self.same_refresh == 0 {
+            Finding::new(
+                Check::Latching,
+                Status::Pass,
+                "No two frames were presented in the same refresh with a barrier between them, so at most one barrier is latched per refresh."
+                    .to_string(),
+            )
+        } else {
+            Finding::new(
+                Check::Latching,
+                Status::Fail,
+                format!(
+                    "{} barrier frames were presented in the same refresh as the frame before them.",
+                    self.same_refresh
+                ),
+            )
+        });
+
+        findings.push(match self.pending_at_unmap.as_ref() {
+            None => Finding::new(
+                Check::Unmap,
+                Status::Untested,
+                "The surface was not unmapped, clearing on unmap was not checked.".to_string(),
+            ),
+            Some(pending) if pending.is_empty() => Finding::new(
+                Check::Unmap,
+                Status::Untested,
+                "Nothing was queued when the surface was unmapped, clearing on unmap was not exercised."
+                    .to_string(),
+            ),
+            Some(pending) => {
+                let stuck = pending
+                    .iter()
+                    .filter(|frame| self.frames[frame].fate.is_none())
+                    .count();
+                if stuck == 0 {
+                    Finding::new(Check::Unmap, Status::Pass, format!(
+                        "The {} frames queued at unmap were all released, so the queue is cleared on unmap.",
+                        pending.len()
+                    ))
+                } else {
+                    Finding::new(Check::Unmap, Status::Fail, format!(
+                        "{} of the {} frames queued at unmap got no feedback afterwards, the queue was not cleared.",
+                        stuck,
+                        pending.len()
+                    ))
+                }
+            }
+        });
+
+        findings
+    }
+
+    /// The status of every check, for `report export-compat`.
+    pub fn checks(&self) -> Vec<(Check, Status)> {
+        self.findings()
+            .into_iter()
+            .map(|finding| (finding.check, finding.status))
+            .collect()
+    }
+
+    pub fn print_report(&self, prefix: &str) {
+        let barriers = self
+            .frames
+            .values()
+            .filter(|committed| committed.barrier)
+            .count();
+        let compositor =
+            std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_else(|_| "unknown".to_string());
+        let findings = self.findings();
+        let violations = findings
+            .iter()
+            .filter(|finding| finding.status == Status::Fail)
+            .map(|finding| finding.check.violation())
+            .collect::<Vec<_>>();
+
+        let summary = if violations.is_empty() {
+            "No violation of the fifo semantics was observed.".to_string()
+        } else {
+            format!(
+                "The fifo semantics are violated: {}.",
+                violations.join(", ")
+            )
+        };
+        println!("{}verdict:", prefix);
+        println!(
+            "{}  fifo on {}, {} frames committed, {} with a barrier. {} {}",
+            prefix,
+            compositor,
+            self.frames.len(),
+            barriers,
+            findings
+                .iter()
+                .map(|finding| finding.sentence.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            summary
+        );
+    }
+}